@@ -0,0 +1,57 @@
+//! Compares the CPU usage of an executor running many tasks, with and without the timer wheel.
+//! Run with `cargo run --release --example timer_wheel [tasks] [interval in ms] [seconds]`.
+extern crate scheduled_executor;
+
+use scheduled_executor::ExecutorBuilder;
+use scheduled_executor::schedule::FixedRate;
+
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Returns the CPU time used by the process, in clock ticks, on Linux.
+fn cpu_ticks() -> Option<u64> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    // The fields after the command, which is in parentheses
+    let fields = stat.rsplit_once(')')?.1.split_whitespace().collect::<Vec<_>>();
+    Some(fields[11].parse::<u64>().ok()? + fields[12].parse::<u64>().ok()?)
+}
+
+fn run(timer_wheel: bool, tasks: usize, interval: Duration, duration: Duration) {
+    let executor = ExecutorBuilder::new().timer_wheel(timer_wheel).build().unwrap();
+    let executions = Arc::new(AtomicUsize::new(0));
+    let cpu_start = cpu_ticks();
+    let handles = (0..tasks)
+        .map(|i| {
+            let executions = Arc::clone(&executions);
+            // Spread the tasks over the interval
+            let initial = interval * i as u32 / tasks as u32;
+            executor.schedule_with(FixedRate::new(initial, interval), move |_handle| {
+                executions.fetch_add(1, Ordering::Relaxed);
+            })
+        })
+        .collect::<Vec<_>>();
+    thread::sleep(duration);
+    let max_lag = handles.iter().filter_map(|handle| handle.stats().max_lag).max();
+    let cpu = cpu_start.and_then(|start| cpu_ticks().map(|end| end - start));
+    executor.stop_with_timeout(Duration::from_secs(10));
+    println!("timer wheel: {:5}, executions: {:8}, CPU time: {:>8}, max lag: {:?}",
+             timer_wheel,
+             executions.load(Ordering::Relaxed),
+             cpu.map_or("n/a".to_owned(), |ticks| format!("{} ticks", ticks)),
+             max_lag.unwrap_or_default());
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let tasks = args.next().map_or(20_000, |tasks| tasks.parse().expect("Invalid number of tasks"));
+    let interval = args.next().map_or(1000, |interval| interval.parse().expect("Invalid interval"));
+    let seconds = args.next().map_or(10, |seconds| seconds.parse().expect("Invalid duration"));
+    println!("{} tasks running every {}ms, for {}s", tasks, interval, seconds);
+    for &timer_wheel in &[false, true] {
+        run(timer_wheel, tasks, Duration::from_millis(interval), Duration::from_secs(seconds));
+    }
+}
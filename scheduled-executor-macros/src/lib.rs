@@ -0,0 +1,173 @@
+//! Procedural macros for the scheduled-executor crate. They are re-exported by
+//! `scheduled_executor`, see the documentation of its `annotated` module.
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+/// The schedule and the name of a function, as given in the arguments of `#[scheduled]`.
+struct Arguments {
+    schedule: String,
+    name: Option<String>,
+}
+
+fn parse_arguments(args: TokenStream) -> Result<Arguments, String> {
+    let mut schedule = None;
+    let mut name = None;
+    let mut tokens = args.into_iter().peekable();
+    while tokens.peek().is_some() {
+        let key = match tokens.next() {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            other => return Err(format!("expected an argument name, found {:?}", other.map(|t| t.to_string()))),
+        };
+        match tokens.next() {
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == '=' => {},
+            _ => return Err(format!("expected '=' after '{}'", key)),
+        }
+        let value = match tokens.next() {
+            Some(TokenTree::Literal(literal)) if literal.to_string().starts_with('"') => literal.to_string(),
+            _ => return Err(format!("the value of '{}' should be a string", key)),
+        };
+        match key.as_str() {
+            "name" => name = Some(value),
+            "every" | "cron" | "schedule" if schedule.is_some() => {
+                return Err("only one of 'every', 'cron' and 'schedule' can be used".to_owned());
+            }
+            "every" => schedule = Some(format!("concat!(\"every \", {})", value)),
+            "cron" | "schedule" => schedule = Some(value),
+            _ => return Err(format!("unknown argument '{}', expected 'every', 'cron', 'schedule' or 'name'", key)),
+        }
+        match tokens.next() {
+            None => break,
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == ',' => {},
+            Some(other) => return Err(format!("expected ',', found '{}'", other)),
+        }
+    }
+    let schedule = schedule.ok_or_else(|| "missing schedule, use 'every', 'cron' or 'schedule'".to_owned())?;
+    Ok(Arguments { schedule, name })
+}
+
+/// Returns the visibility and the name of a function.
+fn function_signature(item: &TokenStream) -> Result<(String, String), String> {
+    let mut visibility = String::new();
+    let mut tokens = item.clone().into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ref ident) if ident.to_string() == "pub" => visibility.push_str("pub"),
+            TokenTree::Group(ref group) if group.delimiter() == Delimiter::Parenthesis && visibility == "pub" => {
+                visibility.push_str(&group.to_string());
+            }
+            TokenTree::Ident(ref ident) if ident.to_string() == "fn" => {
+                return match tokens.next() {
+                    Some(TokenTree::Ident(name)) => Ok((visibility, name.to_string())),
+                    _ => Err("expected the name of the function".to_owned()),
+                };
+            }
+            _ => {},
+        }
+    }
+    Err("#[scheduled] can only be applied to functions".to_owned())
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("compile_error!({:?});", message).parse().unwrap()
+}
+
+/// Marks a function taking a `&Handle` for scheduling, for example with
+/// `#[scheduled(every = "30s", name = "heartbeat")]`. The schedule is given with one of `every`
+/// (a human readable interval), `cron` (a cron expression) or `schedule` (any of the formats of
+/// the configuration files), and `name` defaults to the name of the function.
+///
+/// Next to the function, a module with the same name is generated, whose `scheduled` function
+/// returns its `scheduled_executor::annotated::Scheduled` description.
+#[proc_macro_attribute]
+pub fn scheduled(args: TokenStream, item: TokenStream) -> TokenStream {
+    let (arguments, (visibility, function)) = match parse_arguments(args).and_then(|args| {
+        function_signature(&item).map(|signature| (args, signature))
+    }) {
+        Ok(result) => result,
+        Err(message) => {
+            let mut output = compile_error(&format!("#[scheduled]: {}", message));
+            output.extend(item);
+            return output;
+        }
+    };
+    let name = arguments.name.unwrap_or_else(|| format!("{:?}", function));
+    let registration = format!(
+        "#[doc(hidden)]
+        #[allow(non_snake_case)]
+        {visibility} mod {function} {{
+            pub fn scheduled() -> ::scheduled_executor::annotated::Scheduled {{
+                ::scheduled_executor::annotated::Scheduled::new({name}, {schedule}, super::{function})
+            }}
+        }}",
+        visibility = visibility, function = function, name = name, schedule = arguments.schedule);
+    let mut output = item;
+    output.extend(registration.parse::<TokenStream>().unwrap());
+    output
+}
+
+/// Returns true if the attribute, given as the content of its brackets, is `#[scheduled(...)]`.
+fn is_scheduled_attribute(attribute: &Group) -> bool {
+    let path = attribute.stream().into_iter()
+        .take_while(|token| !matches!(*token, TokenTree::Group(_)))
+        .filter_map(|token| match token {
+            TokenTree::Ident(ident) => Some(ident.to_string()),
+            _ => None,
+        })
+        .last();
+    path.as_deref() == Some("scheduled")
+}
+
+/// Returns the names of the functions annotated with `#[scheduled]` in the body of a module.
+fn scheduled_functions(body: &TokenStream) -> Vec<String> {
+    let mut functions = Vec::new();
+    let mut annotated = false;
+    let mut tokens = body.clone().into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(ref group) if group.delimiter() == Delimiter::Bracket => {
+                annotated |= is_scheduled_attribute(group);
+            }
+            TokenTree::Ident(ref ident) if ident.to_string() == "fn" => {
+                if let (true, Some(TokenTree::Ident(name))) = (annotated, tokens.next()) {
+                    functions.push(name.to_string());
+                }
+                annotated = false;
+            }
+            TokenTree::Group(ref group) if group.delimiter() == Delimiter::Brace => annotated = false,
+            TokenTree::Punct(ref punct) if punct.as_char() == ';' => annotated = false,
+            _ => {},
+        }
+    }
+    functions
+}
+
+/// Applied to an inline module, generates a `scheduled_tasks` function in it, returning all the
+/// functions of the module annotated with `#[scheduled]`.
+#[proc_macro_attribute]
+pub fn scheduled_tasks(_args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut tokens = item.into_iter().collect::<Vec<_>>();
+    let body = match tokens.last() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.clone(),
+        _ => {
+            let mut output = compile_error("#[scheduled_tasks] can only be applied to inline modules");
+            output.extend(tokens);
+            return output;
+        }
+    };
+    let functions = scheduled_functions(&body.stream()).iter()
+        .map(|function| format!("{}::scheduled()", function))
+        .collect::<Vec<_>>();
+    let registration = format!(
+        "pub fn scheduled_tasks() -> Vec<::scheduled_executor::annotated::Scheduled> {{
+            vec![{}]
+        }}",
+        functions.join(", "));
+    let mut stream = body.stream();
+    stream.extend(registration.parse::<TokenStream>().unwrap());
+    let mut group = Group::new(Delimiter::Brace, stream);
+    group.set_span(body.span());
+    let last = tokens.len() - 1;
+    tokens[last] = TokenTree::Group(group);
+    tokens.into_iter().collect()
+}
@@ -0,0 +1,79 @@
+//! Pinning the executor threads to specific CPU cores, so that latency sensitive tasks, like
+//! periodic sampling, don't migrate between cores. Requires the `affinity` feature, and is only
+//! available on Linux.
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::{ExecutorBuilder, ThreadPoolExecutor};
+//! // The executor thread runs on core 2 only
+//! let executor = ExecutorBuilder::new().core_affinity(&[2]).build().unwrap();
+//! // Each worker runs on one of the cores 4 to 7, and the scheduling thread on core 3
+//! let scheduling = ExecutorBuilder::new().core_affinity(&[3]).build().unwrap();
+//! let pool = ThreadPoolExecutor::with_core_affinity(4, "sampling_", scheduling, &[4, 5, 6, 7]);
+//! ```
+use libc;
+
+use std::io;
+use std::mem;
+
+/// Restricts the current thread to run on the given cores only.
+pub fn pin_current_thread(cores: &[usize]) -> io::Result<()> {
+    if cores.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no cores to pin the thread to"));
+    }
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    for &core in cores {
+        if core >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid core {}", core)));
+        }
+        unsafe { libc::CPU_SET(core, &mut set) };
+    }
+    // A pid of 0 is the calling thread
+    if unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the cores the current thread is allowed to run on.
+pub fn current_affinity() -> io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    if unsafe { libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize).filter(|&core| unsafe { libc::CPU_ISSET(core, &set) }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+
+    use std::thread;
+
+    use executor::{ExecutorBuilder, ThreadPoolExecutor};
+    use super::{current_affinity, pin_current_thread};
+
+    #[test]
+    fn pin_current_thread_test() {
+        let core = current_affinity().unwrap()[0];
+        thread::spawn(move || {
+            pin_current_thread(&[core]).unwrap();
+            assert_eq!(current_affinity().unwrap(), vec![core]);
+            assert!(pin_current_thread(&[]).is_err());
+            assert!(pin_current_thread(&[1 << 20]).is_err());
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn core_affinity_test() {
+        let core = current_affinity().unwrap()[0];
+        let executor = ExecutorBuilder::new().core_affinity(&[core]).build().unwrap();
+        assert_eq!(executor.execute(|_handle| current_affinity().unwrap()).wait().unwrap(), vec![core]);
+
+        let pool = ThreadPoolExecutor::with_core_affinity(2, "core_affinity_test_", executor, &[core]);
+        let affinity = pool.pool().spawn_fn(current_affinity).wait().unwrap();
+        assert_eq!(affinity, vec![core]);
+
+        // Cores that don't exist
+        assert!(ExecutorBuilder::new().core_affinity(&[1 << 20]).build().is_err());
+    }
+}
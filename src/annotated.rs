@@ -0,0 +1,126 @@
+//! Functions scheduled through attributes, instead of being wired to an executor one by one.
+//! The `#[scheduled]` attribute marks a function taking a `&Handle` with its schedule, and
+//! `#[scheduled_tasks]` collects all the marked functions of a module, so that they can be
+//! registered at once with `CoreExecutor::register_annotated`:
+//!
+//! ```rust
+//! extern crate scheduled_executor;
+//! extern crate tokio_core;
+//!
+//! use scheduled_executor::{CoreExecutor, scheduled_tasks};
+//!
+//! #[scheduled_tasks]
+//! mod maintenance {
+//!     use tokio_core::reactor::Handle;
+//!     use scheduled_executor::scheduled;
+//!
+//!     #[scheduled(every = "30s", name = "heartbeat")]
+//!     fn heartbeat(_handle: &Handle) {
+//!         println!("Still alive");
+//!     }
+//!
+//!     #[scheduled(cron = "0 0 3 * * *")]
+//!     pub fn cleanup(_handle: &Handle) {
+//!         println!("Cleaning up");
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let executor = CoreExecutor::new().unwrap();
+//!     let handles = executor.register_annotated(maintenance::scheduled_tasks()).unwrap();
+//!     assert_eq!(handles[0].name(), Some("heartbeat"));
+//!     assert_eq!(handles[1].name(), Some("cleanup"));
+//! }
+//! ```
+//!
+//! The schedule is given with one of the `every` (see [`human`]), `cron` (see [`cron`]) or
+//! `schedule` (any of the formats of the [`config`] files) arguments, and the name of a task
+//! defaults to the name of its function. The schedules are only parsed on registration: an
+//! invalid one is reported as an error by `register_annotated`.
+//!
+//! Rust doesn't provide a way to discover items across a whole crate, so the annotated
+//! functions are collected per module. Functions outside of a `#[scheduled_tasks]` module can be
+//! registered through the module generated next to them by `#[scheduled]`, as in
+//! `executor.register_annotated(vec![heartbeat::scheduled()])`.
+//!
+//! [`human`]: ../schedule/human/index.html
+//! [`cron`]: ../schedule/cron/index.html
+//! [`config`]: ../config/index.html
+use tokio_core::reactor::Handle;
+
+use config::{Config, Jobs, TaskConfig};
+
+/// A function marked with `#[scheduled]`, with its schedule.
+#[derive(Debug, Clone, Copy)]
+pub struct Scheduled {
+    name: &'static str,
+    schedule: &'static str,
+    function: fn(&Handle),
+}
+
+impl Scheduled {
+    /// Describes a scheduled function. Usually called by the code generated by `#[scheduled]`.
+    pub fn new(name: &'static str, schedule: &'static str, function: fn(&Handle)) -> Scheduled {
+        Scheduled { name, schedule, function }
+    }
+
+    /// Returns the name of the task.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the schedule of the task, in any of the formats of the configuration files.
+    pub fn schedule(&self) -> &'static str {
+        self.schedule
+    }
+}
+
+/// Returns the configuration and the jobs running the given functions.
+pub(crate) fn to_config<I: IntoIterator<Item=Scheduled>>(functions: I) -> (Config, Jobs) {
+    let mut config = Config::default();
+    let mut jobs = Jobs::new();
+    for scheduled in functions {
+        config.tasks.push(TaskConfig {
+            name: scheduled.name.to_owned(),
+            schedule: scheduled.schedule.to_owned(),
+            enabled: true,
+        });
+        jobs = jobs.add(scheduled.name, scheduled.function);
+    }
+    (config, jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use tokio_core::reactor::Handle;
+
+    use executor::CoreExecutor;
+    use config::ConfigError;
+    use super::Scheduled;
+
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count(_handle: &Handle) {
+        RUNS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn register_annotated_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let invalid = vec![Scheduled::new("count", "every 1s", count), Scheduled::new("other", "never", count)];
+        match executor.register_annotated(invalid) {
+            Err(ConfigError::Schedule { task, .. }) => assert_eq!(task, "other"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(executor.tasks().is_empty());
+
+        let handles = executor.register_annotated(vec![Scheduled::new("count", "every 50ms", count)]).unwrap();
+        assert_eq!(handles[0].name(), Some("count"));
+        thread::sleep(Duration::from_millis(120));
+        assert!(RUNS.load(Ordering::Relaxed) >= 2);
+    }
+}
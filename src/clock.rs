@@ -0,0 +1,142 @@
+//! Time sources for the executors. By default, the executors use the [`SystemClock`], based on
+//! `Instant::now()` and on the timers of the event loop. A custom [`Clock`] can be provided with
+//! `ExecutorBuilder::clock`, for example to apply a correction from an external time source, or
+//! to run the schedules faster or slower than real time.
+//!
+//! The clock is used to compute when the tasks should run, to wait until then, and to measure
+//! the statistics of the tasks. The watchdog and the shutdown timeouts always use real time.
+//! The system time of the clock is used by the schedules anchored to the wall clock, see
+//! `schedule::Anchor`, and by the calendar schedules, such as cron expressions, to convert
+//! between instants and system times.
+//!
+//! [`SystemClock`]: struct.SystemClock.html
+//! [`Clock`]: trait.Clock.html
+use futures::future::{self, Future};
+use tokio_core::reactor::{Handle, Timeout};
+
+use error::ScheduleError;
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+/// A source of time for the executors.
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+
+    /// Returns a future that completes once `deadline` has been reached, according to this
+    /// clock. The future is driven by the event loop of `handle`, and it fails if the timer
+    /// can't be created.
+    fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>>;
+
+    /// Returns the current system time. The executors check the system time at least every
+    /// second while waiting for the executions anchored to the wall clock, so that they follow
+    /// the adjustments of the system clock. Defaults to `SystemTime::now()`.
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+thread_local!(static CURRENT_CLOCK: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) });
+
+/// Runs `f` with `clock` as the clock of the current thread, used by the schedules and the
+/// scheduled functions to read the time of the executor running them.
+pub(crate) fn with_clock<T, F: FnOnce() -> T>(clock: &Arc<dyn Clock>, f: F) -> T {
+    struct Restore(Option<Arc<dyn Clock>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT_CLOCK.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(CURRENT_CLOCK.with(|current| current.replace(Some(Arc::clone(clock)))));
+    f()
+}
+
+/// Returns the clock of the executor running on the current thread, or the `SystemClock`
+/// outside of the executors.
+pub(crate) fn current() -> Arc<dyn Clock> {
+    CURRENT_CLOCK.with(|current| current.borrow().clone()).unwrap_or_else(|| Arc::new(SystemClock))
+}
+
+/// The default clock, using the monotonic system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        match Timeout::new_at(deadline, handle) {
+            Ok(timeout) => Box::new(timeout.map_err(ScheduleError::Timer)),
+            Err(e) => Box::new(future::err(ScheduleError::Timer(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use tokio_core::reactor::Handle;
+
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use error::ScheduleError;
+    use executor::ExecutorBuilder;
+    use super::{Clock, SystemClock};
+
+    /// A clock running ten times faster than real time.
+    struct FastClock {
+        start: Instant,
+    }
+
+    impl FastClock {
+        fn to_real(&self, instant: Instant) -> Instant {
+            if instant > self.start {
+                self.start + (instant - self.start) / 10
+            } else {
+                instant
+            }
+        }
+    }
+
+    impl Clock for FastClock {
+        fn now(&self) -> Instant {
+            self.start + self.start.elapsed() * 10
+        }
+
+        fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            SystemClock.sleep_until(self.to_real(deadline), handle)
+        }
+    }
+
+    #[test]
+    fn custom_clock_test() {
+        let executor = ExecutorBuilder::new()
+            .clock(FastClock { start: Instant::now() })
+            .build()
+            .unwrap();
+        let timings = Arc::new(Mutex::new(Vec::new()));
+        let timings_clone = Arc::clone(&timings);
+        let executor_clone = executor.clone();
+        let task = executor.schedule_fixed_rate(Duration::from_secs(1), Duration::from_secs(1), move |_handle| {
+            timings_clone.lock().unwrap().push(executor_clone.now());
+        });
+        thread::sleep(Duration::from_millis(550));
+        task.stop();
+
+        let timings = timings.lock().unwrap();
+        // Executions every 100ms of real time
+        assert!(timings.len() >= 4 && timings.len() <= 5);
+        for pair in timings.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(900));
+        }
+    }
+}
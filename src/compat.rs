@@ -0,0 +1,120 @@
+//! Interoperability with `std::future`. The [`Compat`] wrapper turns any `std::future::Future`
+//! (including `async` blocks) into a `futures` 0.1 future, so that it can be spawned on the event
+//! loop of the executors, for example from inside a scheduled function:
+//!
+//! ```rust,ignore
+//! executor.schedule_fixed_rate(initial, interval, |handle| {
+//!     compat::spawn(handle, async {
+//!         // Async code
+//!     });
+//! });
+//! ```
+//!
+//! The futures are driven by the executor's event loop, so they can't depend on the timers or
+//! the IO resources of a different runtime (for example tokio 1.x).
+//!
+//! [`Compat`]: struct.Compat.html
+use futures::{Async, Future, Poll};
+use futures::task::{self, Task};
+use tokio_core::reactor::Handle;
+
+use std::future::Future as StdFuture;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll as StdPoll, Wake, Waker};
+
+/// Wakes up a `futures` 0.1 task when the wrapped `std::future::Future` is woken.
+struct TaskWaker(Task);
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.notify();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.notify();
+    }
+}
+
+/// A `futures` 0.1 future wrapping a `std::future::Future`. The resulting future never fails.
+pub struct Compat<F: StdFuture> {
+    future: Pin<Box<F>>,
+}
+
+impl<F: StdFuture> Compat<F> {
+    /// Wraps the given `std::future::Future`.
+    pub fn new(future: F) -> Compat<F> {
+        Compat { future: Box::pin(future) }
+    }
+}
+
+impl<F: StdFuture> Future for Compat<F> {
+    type Item = F::Output;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<F::Output, ()> {
+        let waker = Waker::from(Arc::new(TaskWaker(task::current())));
+        let mut context = Context::from_waker(&waker);
+        match self.future.as_mut().poll(&mut context) {
+            StdPoll::Ready(output) => Ok(Async::Ready(output)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Spawns a `std::future::Future` on the event loop.
+pub fn spawn<F>(handle: &Handle, future: F)
+    where F: StdFuture<Output = ()> + 'static
+{
+    handle.spawn(Compat::new(future));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::{Arc, RwLock};
+    use std::task::{Context, Poll};
+    use std::thread;
+    use std::time::Duration;
+
+    use executor::CoreExecutor;
+    use super::spawn;
+
+    /// Counts the number of times it's polled, returning pending a number of times before
+    /// completing and waking itself up every time.
+    struct Counting {
+        remaining: u32,
+        polls: Arc<RwLock<u32>>,
+    }
+
+    impl Future for Counting {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+            *self.polls.write().unwrap() += 1;
+            if self.remaining == 0 {
+                Poll::Ready(())
+            } else {
+                self.remaining -= 1;
+                context.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn spawn_std_future_test() {
+        let polls = Arc::new(RwLock::new(0));
+        let polls_clone = Arc::clone(&polls);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            executor.schedule_once(
+                Duration::from_millis(0),
+                move |handle| spawn(handle, Counting { remaining: 2, polls: polls_clone })
+            );
+            thread::sleep(Duration::from_millis(200));
+        }
+        assert_eq!(*polls.read().unwrap(), 3);
+    }
+}
@@ -0,0 +1,62 @@
+//! Runs scheduled tasks on external thread pools, for periodic heavy computations, like
+//! rebuilding an index, that should run on the pool already used by the application for CPU
+//! bound work. The executor thread only keeps the schedule: each execution is sent to the pool,
+//! and the next one is computed once it completes, so the statistics of the task include the
+//! time spent on the pool.
+//!
+//! Any function spawning a job can be used as a [`ComputePool`], for instance with a rayon
+//! `ThreadPool`:
+//!
+//! ```rust,ignore
+//! let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap());
+//! let every_hour = FixedRate::new(Duration::from_secs(0), Duration::from_secs(3600));
+//! executor.schedule_on_pool(every_hour, move |job| pool.spawn(job), |_remote| {
+//!     // Reindex, possibly using rayon::join and parallel iterators
+//! });
+//! ```
+//!
+//! [`ComputePool`]: trait.ComputePool.html
+
+/// A job to run on a `ComputePool`.
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of threads that can run jobs.
+pub trait ComputePool: Send + Sync + 'static {
+    /// Runs the job on one of the threads of the pool. The job must eventually run, otherwise
+    /// the task waits forever for its execution to complete.
+    fn spawn(&self, job: Job);
+}
+
+impl<F: Fn(Job) + Send + Sync + 'static> ComputePool for F {
+    fn spawn(&self, job: Job) {
+        self(job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use executor::CoreExecutor;
+    use schedule::FixedRate;
+
+    #[test]
+    fn schedule_on_pool_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        let threads_clone = Arc::clone(&threads);
+        let start = Instant::now();
+        // Spawns a new thread for each job
+        let spawn = |job| { thread::Builder::new().name("compute".to_owned()).spawn(job).unwrap(); };
+        let schedule = FixedRate::new(Duration::from_millis(0), Duration::from_millis(100));
+        let task = executor.schedule_on_pool(schedule, spawn, move |_remote| {
+            threads_clone.lock().unwrap().push(thread::current().name().map(|name| name.to_owned()));
+            thread::sleep(Duration::from_millis(30));
+        });
+        thread::sleep(Duration::from_millis(150).checked_sub(start.elapsed()).unwrap_or_default());
+        assert_eq!(*threads.lock().unwrap(), vec![Some("compute".to_owned()); 2]);
+        assert!(task.stats().mean_duration.is_some_and(|duration| duration >= Duration::from_millis(30)));
+    }
+}
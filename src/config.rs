@@ -0,0 +1,570 @@
+//! Tasks defined in a configuration file. The file uses a subset of TOML, with a `[[task]]`
+//! table for each task:
+//!
+//! ```toml
+//! [[task]]
+//! name = "heartbeat"
+//! schedule = "every 30s"
+//!
+//! [[task]]
+//! name = "cleanup"
+//! schedule = "0 0 3 * * *"
+//! enabled = false
+//! ```
+//!
+//! The `schedule` of a task can be written in any of the formats supported by the crate: human
+//! readable (`"every 5m"`, see [`human`]), cron (`"0 */5 * * * *"`, see [`cron`]), ISO 8601
+//! repeating intervals (`"R/PT1H"`, see [`iso8601`]) or systemd calendar events (`"Mon..Fri
+//! 09:00"`, see [`systemd`]). Tasks are disabled by setting `enabled` to false.
+//!
+//! The tasks are bound by name to the functions registered in a [`Jobs`] map:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::config::{Config, Jobs};
+//! let config = Config::load("tasks.toml").unwrap();
+//! let jobs = Jobs::new()
+//!     .add("heartbeat", |_handle| println!("Still alive"))
+//!     .add("cleanup", |_handle| println!("Cleaning up"));
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_config(&config, &jobs).unwrap();
+//! ```
+//!
+//! When the configuration changes, `CoreExecutor::apply_config` updates the running tasks to
+//! match it, without restarting the process or the tasks that didn't change.
+//!
+//! [`human`]: ../schedule/human/index.html
+//! [`cron`]: ../schedule/cron/index.html
+//! [`iso8601`]: ../schedule/iso8601/index.html
+//! [`systemd`]: ../schedule/systemd/index.html
+//! [`Jobs`]: struct.Jobs.html
+use tokio_core::reactor::Handle;
+
+use executor::TaskHandle;
+use schedule::{Anchor, Schedule};
+use schedule::cron::CronSchedule;
+use schedule::human;
+use schedule::iso8601::RepeatingInterval;
+use schedule::systemd::CalendarEvent;
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// The error returned when a configuration can't be loaded or scheduled.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file couldn't be read.
+    Io(io::Error),
+    /// The configuration file is not valid.
+    Parse { line: usize, reason: String },
+    /// The schedule of a task is not valid.
+    Schedule { task: String, reason: String },
+    /// No job is registered with the name of an enabled task.
+    UnknownJob(String),
+    /// Multiple enabled tasks have the same name.
+    DuplicateTask(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref e) => write!(f, "failed to read the configuration: {}", e),
+            ConfigError::Parse { line, ref reason } => write!(f, "invalid configuration at line {}: {}", line, reason),
+            ConfigError::Schedule { ref task, ref reason } => write!(f, "invalid schedule for task '{}': {}", task, reason),
+            ConfigError::UnknownJob(ref name) => write!(f, "no job registered for task '{}'", name),
+            ConfigError::DuplicateTask(ref name) => write!(f, "task '{}' is defined multiple times", name),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ConfigError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> ConfigError {
+        ConfigError::Io(error)
+    }
+}
+
+/// The configuration of a single task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskConfig {
+    /// The name of the task, used to find its job and as the name of the scheduled task.
+    pub name: String,
+    /// The schedule of the task, in any of the supported formats.
+    pub schedule: String,
+    /// Whether the task should be scheduled.
+    pub enabled: bool,
+}
+
+/// A set of tasks, usually loaded from a file. See the [module documentation].
+///
+/// [module documentation]: index.html
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Config {
+    /// The configured tasks, in the order they appear in the file.
+    pub tasks: Vec<TaskConfig>,
+}
+
+impl Config {
+    /// Reads and parses the configuration file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a configuration.
+    pub fn parse(input: &str) -> Result<Config, ConfigError> {
+        let mut tasks = Vec::new();
+        let mut current: Option<(usize, HashMap<String, Value>)> = None;
+        for (index, line) in input.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |reason: String| ConfigError::Parse { line: line_number, reason };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[task]]" {
+                if let Some((start, values)) = current.take() {
+                    tasks.push(task_config(start, values)?);
+                }
+                current = Some((line_number, HashMap::new()));
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(error(format!("unexpected table '{}'", line)));
+            }
+            let values = match current {
+                Some((_, ref mut values)) => values,
+                None => return Err(error("expected a [[task]] table".to_owned())),
+            };
+            let pos = line.find('=').ok_or_else(|| error("expected 'key = value'".to_owned()))?;
+            let key = line[..pos].trim();
+            let value = parse_value(line[pos + 1..].trim()).map_err(&error)?;
+            if values.insert(key.to_owned(), value).is_some() {
+                return Err(error(format!("duplicate key '{}'", key)));
+            }
+        }
+        if let Some((start, values)) = current {
+            tasks.push(task_config(start, values)?);
+        }
+        Ok(Config { tasks })
+    }
+
+    /// Returns the enabled tasks.
+    pub fn enabled_tasks(&self) -> impl Iterator<Item=&TaskConfig> {
+        self.tasks.iter().filter(|task| task.enabled)
+    }
+}
+
+/// Writes the configuration in the format of the configuration files.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, task) in self.tasks.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[[task]]")?;
+            writeln!(f, "name = {}", quote(&task.name))?;
+            writeln!(f, "schedule = {}", quote(&task.schedule))?;
+            if !task.enabled {
+                writeln!(f, "enabled = false")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Bool(bool),
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (pos, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            // Only the basic strings have escapes
+            (Some('"'), '\\') => escaped = true,
+            (None, '#') => return &line[..pos],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {},
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    match value {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {},
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return Ok(Value::String(value[1..value.len() - 1].to_owned()));
+    }
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(format!("unsupported value '{}'", value));
+    }
+    let mut result = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            _ => return Err(format!("invalid escape in {}", value)),
+        }
+    }
+    Ok(Value::String(result))
+}
+
+fn task_config(line: usize, mut values: HashMap<String, Value>) -> Result<TaskConfig, ConfigError> {
+    let error = |reason: String| ConfigError::Parse { line, reason };
+    let mut string = |key: &str| match values.remove(key) {
+        Some(Value::String(value)) => Ok(value),
+        Some(_) => Err(error(format!("'{}' should be a string", key))),
+        None => Err(error(format!("missing '{}'", key))),
+    };
+    let name = string("name")?;
+    let schedule = string("schedule")?;
+    let enabled = match values.remove("enabled") {
+        Some(Value::Bool(enabled)) => enabled,
+        Some(_) => return Err(error("'enabled' should be a boolean".to_owned())),
+        None => true,
+    };
+    if let Some(key) = values.keys().next() {
+        return Err(error(format!("unknown key '{}'", key)));
+    }
+    Ok(TaskConfig { name, schedule, enabled })
+}
+
+/// Parses a schedule in any of the formats supported in the configuration files.
+pub fn parse_schedule(schedule: &str) -> Result<Box<dyn Schedule>, String> {
+    let schedule = schedule.trim();
+    if schedule.starts_with("every ") || schedule.starts_with("once ") {
+        human::parse_schedule(schedule).map(|s| s as Box<dyn Schedule>).map_err(|e| e.to_string())
+    } else if schedule.starts_with('R') && schedule.contains('/') {
+        RepeatingInterval::parse(schedule).map(|s| Box::new(s) as Box<dyn Schedule>).map_err(|e| e.to_string())
+    } else if schedule.split_whitespace().count() == 6 {
+        CronSchedule::parse(schedule).map(|s| Box::new(s) as Box<dyn Schedule>).map_err(|e| e.to_string())
+    } else {
+        CalendarEvent::parse(schedule).map(|s| Box::new(s) as Box<dyn Schedule>).map_err(|e| e.to_string())
+    }
+}
+
+pub(crate) type Job = Arc<dyn Fn(&Handle) + Send + Sync>;
+
+/// The functions that can be bound to the tasks of a configuration, by name.
+#[derive(Clone, Default)]
+pub struct Jobs {
+    jobs: HashMap<String, Job>,
+}
+
+impl Jobs {
+    /// Creates an empty set of jobs.
+    pub fn new() -> Jobs {
+        Jobs::default()
+    }
+
+    /// Registers a function with the given name, replacing any previous one.
+    pub fn add<F>(mut self, name: &str, job: F) -> Jobs
+        where F: Fn(&Handle) + Send + Sync + 'static
+    {
+        self.jobs.insert(name.to_owned(), Arc::new(job));
+        self
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Job> {
+        self.jobs.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for Jobs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.jobs.keys()).finish()
+    }
+}
+
+/// An enabled task of a configuration, with its parsed schedule and its job.
+pub(crate) struct ResolvedTask {
+    pub(crate) name: String,
+    pub(crate) spec: String,
+    pub(crate) schedule: Box<dyn Schedule>,
+    pub(crate) job: Job,
+}
+
+/// Checks that all enabled tasks have a valid schedule, a job and a unique name, returning them.
+pub(crate) fn resolve(config: &Config, jobs: &Jobs) -> Result<Vec<ResolvedTask>, ConfigError> {
+    let mut names = HashSet::new();
+    config.enabled_tasks()
+        .map(|task| {
+            if !names.insert(task.name.as_str()) {
+                return Err(ConfigError::DuplicateTask(task.name.clone()));
+            }
+            let schedule = parse_schedule(&task.schedule)
+                .map_err(|reason| ConfigError::Schedule { task: task.name.clone(), reason })?;
+            let job = jobs.get(&task.name).ok_or_else(|| ConfigError::UnknownJob(task.name.clone()))?;
+            Ok(ResolvedTask { name: task.name.clone(), spec: task.schedule.clone(), schedule, job })
+        })
+        .collect()
+}
+
+/// The changes made to the running tasks by `CoreExecutor::apply_config`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConfigChanges {
+    /// The tasks that have been scheduled.
+    pub added: Vec<String>,
+    /// The tasks that have been stopped, because they were removed or disabled.
+    pub removed: Vec<String>,
+    /// The tasks whose schedule has changed.
+    pub updated: Vec<String>,
+}
+
+impl ConfigChanges {
+    /// Returns true if no task has changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+}
+
+type Replacement = Arc<Mutex<Option<Box<dyn Schedule>>>>;
+
+/// A schedule that can be replaced while the task is running.
+pub(crate) struct ReloadableSchedule {
+    current: Box<dyn Schedule>,
+    replacement: Replacement,
+}
+
+impl Schedule for ReloadableSchedule {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        if let Some(schedule) = self.replacement.lock().unwrap().take() {
+            self.current = schedule;
+        }
+        self.current.next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.current.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.current.interval()
+    }
+
+    fn executed(&mut self) {
+        self.current.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.current.anchor()
+    }
+}
+
+/// A task scheduled from a configuration, whose schedule and job can be replaced in place.
+pub(crate) struct RunningTask {
+    pub(crate) handle: TaskHandle,
+    spec: String,
+    replacement: Replacement,
+    job: Arc<RwLock<Job>>,
+}
+
+impl RunningTask {
+    /// Creates a running task, using `spawn` to schedule its function with its schedule.
+    pub(crate) fn new<F>(task: ResolvedTask, spawn: F) -> RunningTask
+        where F: FnOnce(ReloadableSchedule, Box<dyn FnMut(&Handle) + Send>) -> TaskHandle
+    {
+        let replacement = Arc::new(Mutex::new(None));
+        let job = Arc::new(RwLock::new(task.job));
+        let schedule = ReloadableSchedule { current: task.schedule, replacement: Arc::clone(&replacement) };
+        let job_clone = Arc::clone(&job);
+        let handle = spawn(schedule, Box::new(move |handle| {
+            let job = job_clone.read().unwrap().clone();
+            job(handle)
+        }));
+        RunningTask { handle, spec: task.spec, replacement, job }
+    }
+
+    /// Replaces the job of the task, and its schedule if it has changed. Returns true if the
+    /// schedule was replaced.
+    pub(crate) fn update(&mut self, task: ResolvedTask) -> bool {
+        *self.job.write().unwrap() = task.job;
+        if task.spec == self.spec {
+            return false;
+        }
+        debug!("Changing schedule of {} from '{}' to '{}'", self.handle, self.spec, task.spec);
+        *self.replacement.lock().unwrap() = Some(task.schedule);
+        self.spec = task.spec;
+        self.handle.reschedule();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use executor::CoreExecutor;
+    use super::{Config, ConfigChanges, ConfigError, Jobs, TaskConfig, parse_schedule};
+
+    const CONFIG: &str = r#"
+        # Tasks of the service
+        [[task]]
+        name = "heartbeat"
+        schedule = "every 100ms"  # Fast
+
+        [[task]]
+        name = 'cleanup'
+        schedule = "0 0 3 * * *"
+        enabled = false
+    "#;
+
+    #[test]
+    fn parse_test() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(config.tasks, vec![
+            TaskConfig { name: "heartbeat".to_owned(), schedule: "every 100ms".to_owned(), enabled: true },
+            TaskConfig { name: "cleanup".to_owned(), schedule: "0 0 3 * * *".to_owned(), enabled: false },
+        ]);
+        assert_eq!(Config::parse("").unwrap().tasks, vec![]);
+        assert!(Config::parse("name = \"a\"").is_err());
+        assert!(Config::parse("[[task]]\nname = \"a\"").is_err());
+        assert!(Config::parse("[[task]]\nname = \"a\"\nschedule = \"every 1s\"\nenabled = 1").is_err());
+        assert!(Config::parse("[[task]]\nname = \"a\"\nschedule = \"every 1s\"\ncolor = \"red\"").is_err());
+        match Config::parse("[[task]]\nname = \"a\"\nname = \"b\"") {
+            Err(ConfigError::Parse { line, .. }) => assert_eq!(line, 3),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn display_test() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+        let task = TaskConfig { name: "say \"hi\"\n".to_owned(), schedule: "every 1s".to_owned(), enabled: true };
+        let config = Config { tasks: vec![task] };
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+        let task = TaskConfig { name: "a\"#b\\".to_owned(), schedule: "every 1s".to_owned(), enabled: true };
+        let config = Config { tasks: vec![task] };
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+        let config = Config::parse("[[task]]\nname = \"a\\\"#b\" # comment\nschedule = 'every 1s' # comment").unwrap();
+        assert_eq!(config.tasks[0].name, "a\"#b");
+    }
+
+    #[test]
+    fn parse_schedule_test() {
+        let now = Instant::now();
+        assert_eq!(parse_schedule("every 1s").unwrap().next_after(now), Some(now));
+        assert!(parse_schedule("0 */5 * * * *").is_ok());
+        assert!(parse_schedule("R5/PT1H").is_ok());
+        assert!(parse_schedule("Mon..Fri 09:00").is_ok());
+        assert!(parse_schedule("daily").is_ok());
+        assert!(parse_schedule("sometimes").is_err());
+    }
+
+    #[test]
+    fn schedule_config_test() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let jobs = Jobs::new()
+            .add("heartbeat", move |_handle| { counter_clone.fetch_add(1, Ordering::Relaxed); })
+            .add("cleanup", |_handle| panic!("disabled"));
+        let executor = CoreExecutor::new().unwrap();
+
+        let missing = Config::parse("[[task]]\nname = \"unknown\"\nschedule = \"every 1s\"").unwrap();
+        assert!(executor.schedule_config(&missing, &jobs).is_err());
+        assert!(executor.tasks().is_empty());
+
+        let handles = executor.schedule_config(&Config::parse(CONFIG).unwrap(), &jobs).unwrap();
+        assert_eq!(handles.len(), 1);
+        assert_eq!(handles[0].name(), Some("heartbeat"));
+        thread::sleep(Duration::from_millis(250));
+        assert!(counter.load(Ordering::Relaxed) >= 2);
+    }
+
+    #[test]
+    fn apply_config_test() {
+        let config = |tasks: &[(&str, &str)]| Config {
+            tasks: tasks.iter()
+                .map(|&(name, schedule)| TaskConfig { name: name.to_owned(), schedule: schedule.to_owned(), enabled: true })
+                .collect(),
+        };
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let jobs = Jobs::new()
+            .add("a", move |_handle| { counter_clone.fetch_add(1, Ordering::Relaxed); })
+            .add("b", |_handle| {})
+            .add("c", |_handle| {});
+        let executor = CoreExecutor::new().unwrap();
+
+        let changes = executor.apply_config(&config(&[("a", "every 1h"), ("b", "every 1h")]), &jobs).unwrap();
+        assert_eq!(changes, ConfigChanges { added: names(&["a", "b"]), ..ConfigChanges::default() });
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(counter.load(Ordering::Relaxed), 1);
+        let a = executor.task("a").unwrap();
+
+        // Re-timed in place: the task is moved to the new schedule without waiting an hour
+        let changes = executor.apply_config(&config(&[("a", "every 50ms"), ("c", "every 1h")]), &jobs).unwrap();
+        assert_eq!(changes, ConfigChanges {
+            added: names(&["c"]),
+            removed: names(&["b"]),
+            updated: names(&["a"]),
+        });
+        thread::sleep(Duration::from_millis(180));
+        assert!(counter.load(Ordering::Relaxed) >= 3);
+        assert_eq!(executor.task("a").unwrap().id(), a.id());
+        assert_eq!(a.interval(), Some(Duration::from_millis(50)));
+        assert!(executor.task("b").is_none());
+
+        let unchanged = config(&[("a", "every 50ms"), ("c", "every 1h")]);
+        assert!(executor.apply_config(&unchanged, &jobs).unwrap().is_empty());
+
+        let duplicate = config(&[("a", "every 1s"), ("a", "every 2s")]);
+        match executor.apply_config(&duplicate, &jobs) {
+            Err(ConfigError::DuplicateTask(name)) => assert_eq!(name, "a"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let invalid = config(&[("c", "sometimes")]);
+        assert!(executor.apply_config(&invalid, &jobs).is_err());
+        assert_eq!(executor.tasks().len(), 2);
+    }
+}
@@ -0,0 +1,388 @@
+//! Coordination of the executions of the same jobs across multiple processes, so that when
+//! several instances of a service run the same schedules, each execution of a named job happens
+//! on exactly one of them. Before each execution, the instances race to claim it from a shared
+//! [`Coordinator`], such as the `RedisCoordinator` of the `redis` feature, and only the winner
+//! runs the job:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::coordination::{Coordination, MemoryCoordinator};
+//! # use scheduled_executor::schedule::cron::CronSchedule;
+//! // A coordinator shared by all the instances, such as a RedisCoordinator
+//! let coordination = Coordination::new(MemoryCoordinator::new());
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_coordinated("report", CronSchedule::parse("0 0 8 * * *").unwrap(), &coordination, |_handle| {
+//!     // Runs on a single instance every day
+//! });
+//! ```
+//!
+//! An execution is identified by the name of its job and by the instant at which it was
+//! planned, rounded to the `granularity` of the coordination: the clocks of the instances
+//! should be synchronized to well within the granularity, one second by default. Schedules
+//! that depend on their previous executions, such as `FixedInterval`, don't plan the same
+//! instants on all the instances, and shouldn't be coordinated this way.
+//!
+//! Claiming an execution doesn't prevent it from overlapping with the previous execution of the
+//! same job on another instance, for instance when an instance is slow or during a failover.
+//! A coordination made `exclusive` also holds a lock on the job while it runs, and skips the
+//! executions that can't take it. The lock expires after the `ttl`, in case the instance holding
+//! it dies, so the `ttl` should be longer than the longest execution of the jobs.
+//!
+//! Large sets of jobs can also be split between the instances without any coordinator, by
+//! sharding the executors with `ExecutorBuilder::shard`: each instance only runs the named
+//! tasks whose name hashes to its shard, see [`shard_of`].
+//!
+//! [`Coordinator`]: trait.Coordinator.html
+//! [`shard_of`]: fn.shard_of.html
+use rand;
+
+use clock;
+use executor::CancellationToken;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Grants the executions of the jobs to a single instance.
+pub trait Coordinator: Send + Sync + 'static {
+    /// Tries to claim the execution of `job` planned at `tick`, a number of milliseconds since
+    /// the Unix epoch. Returns true for only one of the callers, until `ttl` has elapsed.
+    fn claim(&self, job: &str, tick: u64, ttl: Duration) -> io::Result<bool>;
+
+    /// Tries to lock `job` on behalf of `owner`, a value unique to each execution. Returns false
+    /// if the job is locked by another owner. The lock expires after `ttl`, unless released
+    /// before with `unlock`.
+    fn lock(&self, job: &str, owner: &str, ttl: Duration) -> io::Result<bool>;
+
+    /// Releases the lock of `job`, if it is still held by `owner`.
+    fn unlock(&self, job: &str, owner: &str) -> io::Result<()>;
+}
+
+/// A coordinator for the executors of a single process, mostly useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCoordinator {
+    claims: Arc<Mutex<HashMap<(String, u64), Instant>>>,
+    locks: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+}
+
+impl MemoryCoordinator {
+    /// Creates a new coordinator, without any claim.
+    pub fn new() -> MemoryCoordinator {
+        MemoryCoordinator::default()
+    }
+}
+
+impl Coordinator for MemoryCoordinator {
+    fn claim(&self, job: &str, tick: u64, ttl: Duration) -> io::Result<bool> {
+        let now = Instant::now();
+        let mut claims = self.claims.lock().unwrap();
+        claims.retain(|_, expiry| *expiry > now);
+        let key = (job.to_owned(), tick);
+        if claims.contains_key(&key) {
+            return Ok(false);
+        }
+        claims.insert(key, now + ttl);
+        Ok(true)
+    }
+
+    fn lock(&self, job: &str, owner: &str, ttl: Duration) -> io::Result<bool> {
+        let now = Instant::now();
+        let mut locks = self.locks.lock().unwrap();
+        if locks.get(job).is_some_and(|&(_, expiry)| expiry > now) {
+            return Ok(false);
+        }
+        locks.insert(job.to_owned(), (owner.to_owned(), now + ttl));
+        Ok(true)
+    }
+
+    fn unlock(&self, job: &str, owner: &str) -> io::Result<()> {
+        let mut locks = self.locks.lock().unwrap();
+        if locks.get(job).is_some_and(|(holder, _)| holder == owner) {
+            locks.remove(job);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the shard of `key` among `count` shards, between 0 and `count - 1`. The hash is
+/// stable across processes, platforms and versions of the crate, so that all the instances
+/// agree on the shard of each task.
+///
+/// Panics if `count` is zero.
+pub fn shard_of(key: &str, count: usize) -> usize {
+    assert!(count > 0, "The number of shards should be positive");
+    // 64-bit FNV-1a
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % count as u64) as usize
+}
+
+/// Returns a value identifying this process across all the instances: the host name, the
+/// process id and a random part, since processes in different containers often share the same
+/// process id.
+pub(crate) fn instance_id() -> &'static str {
+    static INSTANCE: OnceLock<String> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let hostname = env::var("HOSTNAME").ok()
+            .or_else(|| fs::read_to_string("/etc/hostname").ok())
+            .map(|hostname| hostname.trim().to_owned())
+            .filter(|hostname| !hostname.is_empty())
+            .unwrap_or_else(|| "unknown".to_owned());
+        format!("{}-{}-{:016x}", hostname, process::id(), rand::random::<u64>())
+    })
+}
+
+/// Returns a value identifying an execution across all the instances.
+fn owner() -> String {
+    static EXECUTIONS: AtomicUsize = AtomicUsize::new(0);
+    let since_epoch = clock::current().system_time().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}-{}-{}", instance_id(), since_epoch.as_nanos(), EXECUTIONS.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Claims the executions of the jobs from a `Coordinator`. See the [module documentation].
+/// Cloning it returns a new reference to the same coordinator.
+///
+/// [module documentation]: index.html
+#[derive(Clone)]
+pub struct Coordination {
+    coordinator: Arc<dyn Coordinator>,
+    granularity: Duration,
+    ttl: Duration,
+    exclusive: bool,
+}
+
+impl Coordination {
+    /// Creates a new `Coordination`, with a granularity of one second, and keeping the claims
+    /// for one minute.
+    pub fn new<C: Coordinator>(coordinator: C) -> Coordination {
+        Coordination {
+            coordinator: Arc::new(coordinator),
+            granularity: Duration::from_secs(1),
+            ttl: Duration::from_secs(60),
+            exclusive: false,
+        }
+    }
+
+    /// Sets the precision of the planned instants used to identify the executions.
+    ///
+    /// Panics if `granularity` is less than one millisecond.
+    pub fn granularity(mut self, granularity: Duration) -> Coordination {
+        assert!(granularity >= Duration::from_millis(1), "The granularity should be at least one millisecond");
+        self.granularity = granularity;
+        self
+    }
+
+    /// Sets for how long the claims and the locks are kept. It should be longer than the
+    /// difference between the clocks of the instances, than the lag of their executions, and
+    /// for exclusive jobs than their longest execution.
+    pub fn ttl(mut self, ttl: Duration) -> Coordination {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Makes the jobs exclusive: an execution only runs if it can lock the job, so that the
+    /// same job never runs on two instances at the same time. Disabled by default.
+    pub fn exclusive(mut self, exclusive: bool) -> Coordination {
+        self.exclusive = exclusive;
+        self
+    }
+
+    /// Returns the identifier of the execution planned at `planned`: the number of milliseconds
+    /// since the Unix epoch, rounded to the granularity, according to the clock of the current
+    /// executor.
+    fn tick(&self, planned: Instant) -> u64 {
+        let clock = clock::current();
+        let now = clock.now();
+        let system_now = clock.system_time();
+        let planned = if planned <= now {
+            system_now - (now - planned)
+        } else {
+            system_now + (planned - now)
+        };
+        let millis = planned.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let granularity = self.granularity.as_millis() as u64;
+        (millis + granularity / 2) / granularity * granularity
+    }
+
+    /// Returns true if this instance should run the execution of `job` planned at `planned`.
+    /// Errors of the coordinator are logged, and the execution is not run.
+    pub fn claim(&self, job: &str, planned: Instant) -> bool {
+        let tick = self.tick(planned);
+        match self.coordinator.claim(job, tick, self.ttl) {
+            Ok(true) => true,
+            Ok(false) => {
+                debug!("Execution of {} at {} claimed by another instance", job, tick);
+                false
+            },
+            Err(e) => {
+                error!("Failed to claim the execution of {} at {}: {}", job, tick, e);
+                false
+            },
+        }
+    }
+
+    /// Runs `f` while holding the lock of `job`, returning false without running it if the job
+    /// is locked by another execution. Errors of the coordinator are logged, and `f` is not run.
+    pub fn run_exclusive<F: FnOnce()>(&self, job: &str, f: F) -> bool {
+        let owner = owner();
+        match self.coordinator.lock(job, &owner, self.ttl) {
+            Ok(true) => {},
+            Ok(false) => {
+                debug!("Skipping the execution of {}, already running on another instance", job);
+                return false;
+            },
+            Err(e) => {
+                error!("Failed to lock {}: {}", job, e);
+                return false;
+            },
+        }
+        struct Unlock<'a>(&'a Coordination, &'a str, &'a str);
+        impl<'a> Drop for Unlock<'a> {
+            fn drop(&mut self) {
+                if let Err(e) = self.0.coordinator.unlock(self.1, self.2) {
+                    error!("Failed to unlock {}: {}", self.1, e);
+                }
+            }
+        }
+        let _unlock = Unlock(self, job, &owner);
+        f();
+        true
+    }
+
+    /// Wraps the function, so that it only runs when this instance claims the execution. The
+    /// execution is identified by the planned instant of the current execution of the task. If
+    /// the coordination is exclusive, the function also runs while holding the lock of the job.
+    pub fn wrap<A, F>(&self, job: &str, mut scheduled_fn: F) -> impl FnMut(&A) + Send + 'static
+        where A: ?Sized,
+              F: FnMut(&A) + Send + 'static
+    {
+        let coordination = self.clone();
+        let job = job.to_owned();
+        move |arg| {
+            let planned = CancellationToken::current()
+                .and_then(|token| token.task().next_run())
+                .unwrap_or_else(Instant::now);
+            if !coordination.claim(&job, planned) {
+                return;
+            }
+            if coordination.exclusive {
+                coordination.run_exclusive(&job, || scheduled_fn(arg));
+            } else {
+                scheduled_fn(arg);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use executor::CoreExecutor;
+    use schedule::FixedRate;
+    use super::{Coordination, Coordinator, MemoryCoordinator, shard_of};
+
+    #[test]
+    fn claim_test() {
+        let coordinator = MemoryCoordinator::new();
+        let ttl = Duration::from_millis(50);
+        assert!(coordinator.claim("report", 1000, ttl).unwrap());
+        assert!(!coordinator.claim("report", 1000, ttl).unwrap());
+        assert!(coordinator.claim("cleanup", 1000, ttl).unwrap());
+        assert!(coordinator.claim("report", 2000, ttl).unwrap());
+        thread::sleep(Duration::from_millis(60));
+        assert!(coordinator.claim("report", 1000, ttl).unwrap());
+
+        let coordination = Coordination::new(coordinator);
+        let planned = Instant::now();
+        assert!(coordination.claim("report", planned));
+        assert!(!coordination.claim("report", planned));
+    }
+
+    #[test]
+    fn shard_of_test() {
+        assert_eq!(shard_of("report", 1), 0);
+        // Changing these would move the tasks between the instances during an upgrade
+        assert_eq!(shard_of("", 7), (0xcbf2_9ce4_8422_2325u64 % 7) as usize);
+        assert_eq!(shard_of("a", 1 << 32), 0x8601ec8c);
+        let counts = (0..1000).fold([0; 4], |mut counts, i| {
+            counts[shard_of(&format!("task {}", i), 4)] += 1;
+            counts
+        });
+        assert!(counts.iter().all(|&count| count > 200), "{:?}", counts);
+    }
+
+    #[test]
+    fn lock_test() {
+        let coordinator = MemoryCoordinator::new();
+        let ttl = Duration::from_millis(50);
+        assert!(coordinator.lock("report", "a", ttl).unwrap());
+        assert!(!coordinator.lock("report", "b", ttl).unwrap());
+        coordinator.unlock("report", "b").unwrap();
+        assert!(!coordinator.lock("report", "b", ttl).unwrap());
+        coordinator.unlock("report", "a").unwrap();
+        assert!(coordinator.lock("report", "b", ttl).unwrap());
+        // Expired, as if the instance holding it died
+        thread::sleep(Duration::from_millis(60));
+        assert!(coordinator.lock("report", "a", ttl).unwrap());
+
+        let coordination = Coordination::new(MemoryCoordinator::new()).exclusive(true);
+        let mut nested = None;
+        assert!(coordination.run_exclusive("report", || {
+            nested = Some(coordination.run_exclusive("report", || {}));
+        }));
+        assert_eq!(nested, Some(false));
+        assert!(coordination.run_exclusive("report", || {}));
+    }
+
+    #[test]
+    fn exclusive_test() {
+        // Granularity of the claims finer than the executions, so that only the lock prevents
+        // the overlaps
+        let coordination = Coordination::new(MemoryCoordinator::new())
+            .granularity(Duration::from_millis(1))
+            .exclusive(true);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let executors = (0..2).map(|_| CoreExecutor::new().unwrap()).collect::<Vec<_>>();
+        let start = Instant::now();
+        for (i, executor) in executors.iter().enumerate() {
+            let runs = Arc::clone(&runs);
+            // The second instance is 100ms late, while the first is still running
+            let schedule = FixedRate::new(Duration::from_millis(100 * i as u64), Duration::from_millis(1000));
+            executor.schedule_coordinated("slow", schedule, &coordination, move |_handle| {
+                runs.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(200));
+            });
+        }
+        thread::sleep(Duration::from_millis(400).checked_sub(start.elapsed()).unwrap_or_default());
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn schedule_coordinated_test() {
+        // Two instances of the same service
+        let coordination = Coordination::new(MemoryCoordinator::new()).granularity(Duration::from_millis(100));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let executors = (0..2).map(|_| CoreExecutor::new().unwrap()).collect::<Vec<_>>();
+        let start = Instant::now();
+        for executor in &executors {
+            let runs = Arc::clone(&runs);
+            let schedule = FixedRate::new(Duration::from_millis(0), Duration::from_millis(200));
+            executor.schedule_coordinated("count", schedule, &coordination, move |_handle| {
+                runs.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        thread::sleep(Duration::from_millis(500).checked_sub(start.elapsed()).unwrap_or_default());
+        // At 0, 200 and 400ms, on one of the instances
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
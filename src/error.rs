@@ -0,0 +1,62 @@
+//! The errors of the executors.
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// The error returned when an executor can't be created, or can't schedule a task.
+#[derive(Debug)]
+pub enum ScheduleError {
+    /// A thread of the executor couldn't be started.
+    Thread(io::Error),
+    /// The event loop of the executor couldn't be created.
+    Reactor(io::Error),
+    /// A timer couldn't be created on the event loop.
+    Timer(io::Error),
+    /// The thread running the event loop of the executor has terminated unexpectedly.
+    Disconnected,
+    /// The signal handlers couldn't be installed.
+    Signal(io::Error),
+    /// The executor thread couldn't be configured, for example pinned to a CPU core, or given a
+    /// scheduling priority.
+    ThreadConfig(io::Error),
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScheduleError::Thread(ref e) => write!(f, "failed to start the executor thread: {}", e),
+            ScheduleError::Reactor(ref e) => write!(f, "failed to create the event loop: {}", e),
+            ScheduleError::Timer(ref e) => write!(f, "failed to create a timer: {}", e),
+            ScheduleError::Disconnected => write!(f, "the executor thread has terminated"),
+            ScheduleError::Signal(ref e) => write!(f, "failed to install the signal handlers: {}", e),
+            ScheduleError::ThreadConfig(ref e) => write!(f, "failed to configure the executor thread: {}", e),
+        }
+    }
+}
+
+impl Error for ScheduleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ScheduleError::Thread(ref e) | ScheduleError::Reactor(ref e) | ScheduleError::Timer(ref e)
+                | ScheduleError::Signal(ref e) | ScheduleError::ThreadConfig(ref e) => Some(e),
+            ScheduleError::Disconnected => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io;
+
+    use super::ScheduleError;
+
+    #[test]
+    fn display_test() {
+        let error = ScheduleError::Reactor(io::Error::other("no epoll"));
+        assert_eq!(error.to_string(), "failed to create the event loop: no epoll");
+        assert!(error.source().is_some());
+        assert_eq!(ScheduleError::Disconnected.to_string(), "the executor thread has terminated");
+        assert!(ScheduleError::Disconnected.source().is_none());
+    }
+}
@@ -5,471 +5,6060 @@
 //! the scheduling of the functions (and for the `CoreExecutor`, also their execution). A reference
 //! to the event loop is passed to every function when executed, allowing it to register additional
 //! events if needed.
-use futures::future::Future;
-use futures::sync::oneshot::{channel, Sender};
+use futures::{Async, Poll};
+use futures::future::{self, Either, Future, IntoFuture, Shared};
+use futures::sync::oneshot::{channel, Canceled, Receiver, Sender};
+use futures::task::{self as future_task, Task as FutureTask};
 use futures_cpupool::{Builder, CpuPool};
-use tokio_core::reactor::Timeout;
 use tokio_core::reactor::{Core, Handle, Remote};
 
+#[cfg(all(target_os = "linux", feature = "affinity"))]
+use affinity;
+use annotated::{self, Scheduled};
+use clock::{self, Clock, SystemClock};
+use compute::ComputePool;
+use coordination::{self, Coordination};
+use config::{self, Config, ConfigChanges, ConfigError, Jobs, ResolvedTask, RunningTask};
+use error::ScheduleError;
+use leadership::Leadership;
+use limit::ConcurrencyLimit;
+use persist::Persistence;
+#[cfg(all(target_os = "linux", feature = "priority"))]
+use priority::{self, ThreadPriority};
+use steal::{self, StealingPool};
+use store::{JobStore, StoreListener};
+use wheel::TimerWheel;
+use schedule::{Anchor, Delays, FixedInterval, FixedRate, Schedule};
+use schedule::cron::{CronSchedule, ParseError};
+use stream::{self, BufferPolicy, ChannelSchedule, TaskStream};
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::cmp::{self, Reverse};
+use std::fmt;
 use std::io;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::iter;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, RwLock, Weak};
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
 use std::thread::{self, JoinHandle};
-use std::time::{Instant, Duration};
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
+
+
+static NEXT_TASK_ID: AtomicUsize = AtomicUsize::new(0);
+static NEXT_EXECUTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+type CancelHandler = Arc<dyn Fn(&TaskHandle) + Send + Sync>;
+
+struct TaskState {
+    id: usize,
+    name: Option<String>,
+    should_stop: AtomicBool,
+    paused: AtomicBool,
+    panics: AtomicUsize,
+    consecutive_panics: AtomicUsize,
+    resume_at: Mutex<Option<Instant>>,
+    new_interval: Mutex<Option<Duration>>,
+    reschedule: AtomicBool,
+    waiting: Mutex<Option<FutureTask>>,
+    runs: AtomicUsize,
+    next_run: Mutex<Option<Instant>>,
+    interval: Mutex<Option<Duration>>,
+    metrics: Mutex<Metrics>,
+    running_since: Mutex<Option<Instant>>,
+    in_flight: AtomicUsize,
+    shutdown_phase: AtomicUsize,
+    draining: AtomicBool,
+    cancel_waiters: Mutex<HashMap<usize, FutureTask>>,
+    cancel_handler: Mutex<Option<CancelHandler>>,
+    skip_missed: AtomicBool,
+    skipped: AtomicUsize,
+    slot_waiter: Mutex<Option<FutureTask>>,
+    priority: AtomicI32,
+    deadline: Mutex<Option<Duration>>,
+    current_deadline: Mutex<Option<Instant>>,
+    deadline_misses: AtomicUsize,
+    tags: Mutex<Vec<String>>,
+    /// The system time of the next execution, if the schedule is anchored to the wall clock.
+    next_run_system_time: Mutex<Option<SystemTime>>,
+    gap_recovery: Mutex<Option<GapRecovery>>,
+    clock_jumped: AtomicBool,
+}
+
+/// The recovery of a task from a clock gap, see `GapPolicy`.
+#[derive(Debug, Clone, Copy)]
+struct GapRecovery {
+    /// The number of executions still to run right away.
+    runs: usize,
+    /// The execution that was planned when the gap was detected.
+    pending: Option<Instant>,
+}
 
+/// The execution statistics of a task, as stored in its state.
+#[derive(Default)]
+struct Metrics {
+    executions: usize,
+    total_duration: Duration,
+    last_duration: Option<Duration>,
+    max_duration: Option<Duration>,
+    last_lag: Option<Duration>,
+    max_lag: Option<Duration>,
+    total_lag: Duration,
+    lagged: usize,
+}
+
+/// The execution statistics of a task. See `TaskHandle::stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskStats {
+    /// The number of completed executions.
+    pub executions: usize,
+    /// The total duration of the completed executions.
+    pub total_duration: Duration,
+    /// The duration of the last completed execution.
+    pub last_duration: Option<Duration>,
+    /// The mean duration of the completed executions.
+    pub mean_duration: Option<Duration>,
+    /// The duration of the longest execution.
+    pub max_duration: Option<Duration>,
+    /// How late the last execution started, compared to its planned start.
+    pub last_lag: Option<Duration>,
+    /// The maximum lag of all the executions.
+    pub max_lag: Option<Duration>,
+    /// The mean lag of all the executions, that measures the accuracy of the timers of the
+    /// executor. See also `ExecutorBuilder::high_resolution`.
+    pub mean_lag: Option<Duration>,
+}
+
+/// The health of an executor, as returned by `CoreExecutor::health`, to be exposed for example in
+/// the readiness or liveness checks of an application.
+#[derive(Debug, Clone)]
+pub struct Health {
+    /// True if the executor is running. See `CoreExecutor::is_alive`.
+    pub alive: bool,
+    /// The number of tasks currently scheduled.
+    pub tasks: usize,
+    /// The last time an execution of a task started, if any.
+    pub last_tick: Option<Instant>,
+    /// The tasks whose next execution is overdue by more than the threshold given to `health`.
+    /// Tasks that are currently running are not included, see `CoreExecutor::set_watchdog`.
+    pub overdue: Vec<TaskHandle>,
+    /// The number of times the event loop has been restarted. See `ExecutorBuilder::supervised`.
+    pub restarts: usize,
+}
+
+impl Health {
+    /// Returns true if the executor is running, and none of its tasks is overdue.
+    pub fn is_healthy(&self) -> bool {
+        self.alive && self.overdue.is_empty()
+    }
+}
 
-/// A handle that allows a task to be stopped. A new handle is returned every time a new task is
-/// scheduled. Note that stopping a task will prevent it from running the next time it's scheduled
-/// to run, but it won't interrupt a task that is currently being executed.
+/// A handle that allows a task to be stopped, and its state to be inspected. A new handle is
+/// returned every time a new task is scheduled. Note that stopping a task will prevent it from
+/// running the next time it's scheduled to run, but it won't interrupt a task that is currently
+/// being executed.
 #[derive(Clone)]
 pub struct TaskHandle {
-    should_stop: Arc<AtomicBool>,
+    state: Arc<TaskState>,
 }
 
 impl TaskHandle {
-    fn new() -> TaskHandle {
-        TaskHandle { should_stop: Arc::new(AtomicBool::new(false)) }
+    pub(crate) fn new(name: Option<&str>) -> TaskHandle {
+        let state = TaskState {
+            id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
+            name: name.map(|name| name.to_owned()),
+            should_stop: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            panics: AtomicUsize::new(0),
+            consecutive_panics: AtomicUsize::new(0),
+            resume_at: Mutex::new(None),
+            new_interval: Mutex::new(None),
+            reschedule: AtomicBool::new(false),
+            waiting: Mutex::new(None),
+            runs: AtomicUsize::new(0),
+            next_run: Mutex::new(None),
+            interval: Mutex::new(None),
+            metrics: Mutex::new(Metrics::default()),
+            running_since: Mutex::new(None),
+            in_flight: AtomicUsize::new(0),
+            shutdown_phase: AtomicUsize::new(0),
+            draining: AtomicBool::new(false),
+            cancel_waiters: Mutex::new(HashMap::new()),
+            cancel_handler: Mutex::new(None),
+            skip_missed: AtomicBool::new(false),
+            skipped: AtomicUsize::new(0),
+            slot_waiter: Mutex::new(None),
+            priority: AtomicI32::new(0),
+            deadline: Mutex::new(None),
+            current_deadline: Mutex::new(None),
+            deadline_misses: AtomicUsize::new(0),
+            tags: Mutex::new(Vec::new()),
+            next_run_system_time: Mutex::new(None),
+            gap_recovery: Mutex::new(None),
+            clock_jumped: AtomicBool::new(false),
+        };
+        TaskHandle { state: Arc::new(state) }
     }
 
     /// Stops the correspondent task. Not that a running task won't be interrupted, but
     /// future tasks executions will be prevented.
     pub fn stop(&self) {
-        self.should_stop.store(true, Ordering::Relaxed);
+        self.state.should_stop.store(true, Ordering::Relaxed);
+        for (_, waiting) in self.state.cancel_waiters.lock().unwrap().drain() {
+            waiting.notify();
+        }
     }
 
-    /// Returns true if the task is stopped.
+    /// Returns true if the task is stopped, or if its schedule has completed.
     pub fn stopped(&self) -> bool {
-        self.should_stop.load(Ordering::Relaxed)
+        self.state.should_stop.load(Ordering::Relaxed)
     }
-}
 
-fn fixed_interval_loop<F>(scheduled_fn: F, interval: Duration, handle: &Handle, task_handle: TaskHandle)
-    where F: Fn(&Handle) + Send + 'static
-{
-    if task_handle.stopped() {
-        return;
-    }
-    let start_time = Instant::now();
-    scheduled_fn(handle);
-    let execution = start_time.elapsed();
-    let next_iter_wait = if execution >= interval {
-        Duration::from_secs(0)
-    } else {
-        interval - execution
-    };
-    let handle_clone = handle.clone();
-    let t = Timeout::new(next_iter_wait, handle).unwrap()
-        .then(move |_| {
-            fixed_interval_loop(scheduled_fn, interval, &handle_clone, task_handle);
-            Ok::<(), ()>(())
-        });
-    handle.spawn(t);
-}
+    /// Cancels the task, from any thread. Equivalent to `stop`.
+    pub fn cancel(&self) {
+        self.stop()
+    }
 
-fn calculate_delay(interval: Duration, execution: Duration, delay: Duration) -> (Duration, Duration) {
-    if execution >= interval {
-        (Duration::from_secs(0), delay + execution - interval)
-    } else {
-        let wait_gap = interval - execution;
-        if delay == Duration::from_secs(0) {
-            (wait_gap, Duration::from_secs(0))
-        } else if delay < wait_gap {
-            (wait_gap - delay, Duration::from_secs(0))
+    /// Returns true if the task has been cancelled or stopped, or if its schedule has completed.
+    /// Equivalent to `stopped`.
+    pub fn is_cancelled(&self) -> bool {
+        self.stopped()
+    }
+
+    /// Pauses the task: the executions that are due while the task is paused are skipped, but the
+    /// task keeps following its schedule, and it will run again once resumed. Like for `stop`, a
+    /// running task won't be interrupted.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes a paused task.
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns true if the task is paused.
+    pub fn paused(&self) -> bool {
+        self.state.paused.load(Ordering::Relaxed)
+    }
+
+    /// Changes the interval between the executions of the task. The new interval is used starting
+    /// from the next execution, without interrupting the task. This has no effect on tasks whose
+    /// schedule doesn't have an interval (for example cron schedules).
+    pub fn set_interval(&self, interval: Duration) {
+        *self.state.new_interval.lock().unwrap() = Some(interval);
+    }
+
+    /// Interrupts the wait for the next execution, so that the schedule is asked again for the
+    /// next execution instant. Used when the schedule of the task is replaced.
+    pub(crate) fn reschedule(&self) {
+        self.state.reschedule.store(true, Ordering::SeqCst);
+        if let Some(waiting) = self.state.waiting.lock().unwrap().take() {
+            waiting.notify();
+        }
+    }
+
+    /// Returns true if the next execution of the task is anchored to the wall clock.
+    fn anchored_to_wall_clock(&self) -> bool {
+        self.state.next_run_system_time.lock().unwrap().is_some()
+    }
+
+    /// Interrupts the check of the system time of an execution anchored to the wall clock, so
+    /// that it's recomputed right away.
+    fn clock_jumped(&self) {
+        self.state.clock_jumped.store(true, Ordering::SeqCst);
+        if let Some(waiting) = self.state.waiting.lock().unwrap().take() {
+            waiting.notify();
+        }
+    }
+
+    /// Runs the task `runs` times right away, and then resumes its schedule, skipping the
+    /// executions missed during a clock gap.
+    fn recover_gap(&self, runs: usize) {
+        let pending = self.next_run();
+        *self.state.gap_recovery.lock().unwrap() = Some(GapRecovery { runs, pending });
+        self.reschedule();
+    }
+
+    /// Sets the shutdown phase of the task, 0 by default. When the executor is drained, the tasks
+    /// are stopped one phase at a time, starting from the lowest one: the tasks of a phase are
+    /// stopped only once the executions of the tasks of the previous phases have completed. For
+    /// example, tasks flushing the data produced by other tasks should have a higher phase.
+    /// See `Shutdown::drain`.
+    pub fn set_shutdown_phase(&self, phase: usize) {
+        self.state.shutdown_phase.store(phase, Ordering::Relaxed);
+    }
+
+    /// Returns the shutdown phase of the task.
+    pub fn shutdown_phase(&self) -> usize {
+        self.state.shutdown_phase.load(Ordering::Relaxed)
+    }
+
+    /// Sets the priority of the task, 0 by default. When several tasks of an executor are due at
+    /// the same time, the ones with the highest priority run first. Tasks with the same
+    /// priority run in the order of their planned instants, and then in the order in which they
+    /// were scheduled. Priorities only order the executions that are due together: a running
+    /// execution is never interrupted by a task with a higher priority.
+    pub fn set_priority(&self, priority: i32) {
+        self.state.priority.store(priority, Ordering::Relaxed);
+    }
+
+    /// Returns the priority of the task.
+    pub fn priority(&self) -> i32 {
+        self.state.priority.load(Ordering::Relaxed)
+    }
+
+    /// Sets the deadline of the executions of the task, relative to their planned instant. An
+    /// execution that completes after its deadline is counted as a deadline miss, and reported
+    /// to the listeners of the executor. With `ExecutorBuilder::earliest_deadline_first`, the
+    /// tasks due at the same time also run in the order of their deadlines. Overlapping
+    /// executions of the same task are only checked against the deadline of the latest one.
+    pub fn set_deadline(&self, deadline: Option<Duration>) {
+        *self.state.deadline.lock().unwrap() = deadline;
+    }
+
+    /// Returns the deadline of the executions of the task, if any.
+    pub fn deadline(&self) -> Option<Duration> {
+        *self.state.deadline.lock().unwrap()
+    }
+
+    /// Returns the number of executions that completed after their deadline. See
+    /// `set_deadline`.
+    pub fn deadline_misses(&self) -> usize {
+        self.state.deadline_misses.load(Ordering::Relaxed)
+    }
+
+    /// Tags the task, so that it can be found, paused, resumed and stopped together with the
+    /// other tasks with the same tag, for instance with `CoreExecutor::cancel_tagged`. The tags
+    /// are also exported as a label of the metrics. A task can have any number of tags.
+    pub fn add_tag(&self, tag: &str) {
+        let mut tags = self.state.tags.lock().unwrap();
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+
+    /// Returns the tags of the task, in the order in which they were added.
+    pub fn tags(&self) -> Vec<String> {
+        self.state.tags.lock().unwrap().clone()
+    }
+
+    /// Returns true if the task has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.state.tags.lock().unwrap().iter().any(|t| t == tag)
+    }
+
+    /// Returns the number of executions that were skipped because the previous execution was
+    /// still running when they were due. See `OverlapPolicy::Skip`.
+    pub fn skipped(&self) -> usize {
+        self.state.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times the task panicked.
+    pub fn panics(&self) -> usize {
+        self.state.panics.load(Ordering::Relaxed)
+    }
+
+    /// Returns the identifier of the task, unique within the process.
+    pub fn id(&self) -> usize {
+        self.state.id
+    }
+
+    /// Returns the name of the task, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.state.name.as_deref()
+    }
+
+    /// Returns the number of times the task has been executed.
+    pub fn runs(&self) -> usize {
+        self.state.runs.load(Ordering::Relaxed)
+    }
+
+    /// Returns the instant of the next execution, or `None` if no execution is planned.
+    pub fn next_run(&self) -> Option<Instant> {
+        *self.state.next_run.lock().unwrap()
+    }
+
+    /// Returns the interval between executions, if the schedule of the task has one.
+    pub fn interval(&self) -> Option<Duration> {
+        *self.state.interval.lock().unwrap()
+    }
+
+    /// Returns for how long the current execution of the task has been running, or `None` if the
+    /// task is not running.
+    pub fn running_for(&self) -> Option<Duration> {
+        self.state.running_since.lock().unwrap().map(|start| start.elapsed())
+    }
+
+    /// Returns the execution statistics of the task. For functions returning a future, the
+    /// duration of an execution includes the time needed for the future to complete.
+    pub fn stats(&self) -> TaskStats {
+        let metrics = self.state.metrics.lock().unwrap();
+        let mean_duration = if metrics.executions > 0 {
+            Some(metrics.total_duration / metrics.executions as u32)
         } else {
-            (Duration::from_secs(0), delay - wait_gap)
+            None
+        };
+        let mean_lag = if metrics.lagged > 0 {
+            Some(metrics.total_lag / metrics.lagged as u32)
+        } else {
+            None
+        };
+        TaskStats {
+            executions: metrics.executions,
+            total_duration: metrics.total_duration,
+            last_duration: metrics.last_duration,
+            mean_duration,
+            max_duration: metrics.max_duration,
+            last_lag: metrics.last_lag,
+            max_lag: metrics.max_lag,
+            mean_lag,
         }
     }
 }
 
-fn fixed_rate_loop<F>(scheduled_fn: F, interval: Duration, handle: &Handle, delay: Duration, task_handle: TaskHandle)
-    where F: Fn(&Handle) + Send + 'static
-{
-    if task_handle.stopped() {
-        return;
-    }
-    let start_time = Instant::now();
-    scheduled_fn(handle);
-    let execution = start_time.elapsed();
-    let (next_iter_wait, updated_delay) = calculate_delay(interval, execution, delay);
-    let handle_clone = handle.clone();
-    let t = Timeout::new(next_iter_wait, handle).unwrap()
-        .then(move |_| {
-            fixed_rate_loop(scheduled_fn, interval, &handle_clone, updated_delay, task_handle);
-            Ok::<(), ()>(())
-        });
-    handle.spawn(t);
+impl fmt::Display for TaskHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "task \"{}\"", name),
+            None => write!(f, "task #{}", self.id()),
+        }
+    }
 }
 
+impl fmt::Debug for TaskHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskHandle")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .field("tags", &self.tags())
+            .field("stopped", &self.stopped())
+            .field("paused", &self.paused())
+            .field("interval", &self.interval())
+            .field("next_run", &self.next_run())
+            .field("runs", &self.runs())
+            .field("skipped", &self.skipped())
+            .field("panics", &self.panics())
+            .finish()
+    }
+}
 
-struct CoreExecutorInner {
-    remote: Remote,
-    termination_sender: Option<Sender<()>>,
-    thread_handle: Option<JoinHandle<()>>,
+/// Defines what happens when a function returning a future is due, but the future returned by the
+/// previous execution hasn't completed yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Skip the executions that were due while the previous one was running. The skipped
+    /// executions are counted by `TaskHandle::skipped`.
+    Skip,
+    /// Wait for the previous execution to complete, and then run the task. Like for synchronous
+    /// tasks, the schedule decides how to recover the delay.
+    Queue,
+    /// Run the task anyway: multiple executions of the same task can run concurrently.
+    Concurrent,
+    /// Like `Concurrent`, but at most `max` executions of the task run at the same time. When
+    /// `max` executions are running, the executions that are due are skipped if `skip` is true,
+    /// and otherwise they wait for one of the running executions to complete.
+    Limited { max: usize, skip: bool },
 }
 
-impl Drop for CoreExecutorInner {
-    fn drop(&mut self) {
-        let _ = self.termination_sender.take().unwrap().send(());
-        let _ = self.thread_handle.take().unwrap().join();
+impl OverlapPolicy {
+    /// Returns true if the policy allows multiple executions to run at the same time.
+    fn concurrent(&self) -> bool {
+        matches!(*self, OverlapPolicy::Concurrent | OverlapPolicy::Limited { .. })
     }
 }
 
-/// A `CoreExecutor` is the most simple executor provided. It runs a single thread, which is
-/// responsible for both scheduling the function (registering the timer for the wakeup),
-/// and the actual execution. The executor will stop once dropped. The `CoreExecutor`
-/// can be cloned to generate a new reference to the same underlying executor.
-/// Given the single threaded nature of this executor, tasks are executed sequentially, and a long
-/// running task will cause delay in other subsequent executions.
-pub struct CoreExecutor {
-    inner: Arc<CoreExecutorInner>
+/// Defines what happens to a fallible task after it returns an error, once its error handler has
+/// been called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Keep running the task according to its schedule.
+    Continue,
+    /// Stop the task.
+    Stop,
+    /// Handle the error as a panic of the task, according to the `PanicPolicy` of the executor.
+    Escalate,
 }
 
-impl Clone for CoreExecutor {
-    fn clone(&self) -> Self {
-        CoreExecutor { inner: Arc::clone(&self.inner) }
-    }
+/// Defines what happens to a task after it panics. Panics never affect the executor or the other
+/// tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Keep running the task according to its schedule. This is the default.
+    Restart,
+    /// Stop the task once it panicked the given number of times.
+    StopAfter(usize),
+    /// Delay the next execution after a panic by `initial`, doubling the delay after every
+    /// consecutive panic, up to `max`. The delay is reset after a successful execution.
+    Backoff { initial: Duration, max: Duration },
 }
 
-impl CoreExecutor {
-    /// Creates a new `CoreExecutor`.
-    pub fn new() -> Result<CoreExecutor, io::Error> {
-        CoreExecutor::with_name("core_executor")
+impl PanicPolicy {
+    fn backoff(&self, consecutive_panics: usize) -> Option<Duration> {
+        match *self {
+            PanicPolicy::Backoff { initial, max } => {
+                let factor = 1u32 << cmp::min(consecutive_panics.saturating_sub(1), 31);
+                Some(initial.checked_mul(factor).map_or(max, |delay| cmp::min(delay, max)))
+            },
+            _ => None,
+        }
     }
+}
 
-    /// Creates a new `CoreExecutor` with the specified thread name.
-    pub fn with_name(thread_name: &str) -> Result<CoreExecutor, io::Error> {
-        let (termination_tx, termination_rx) = channel();
-        let (core_tx, core_rx) = channel();
-        let thread_handle = thread::Builder::new()
-            .name(thread_name.to_owned())
-            .spawn(move || {
-                debug!("Core starting");
-                let mut core = Core::new().expect("Failed to start core");
-                let _ = core_tx.send(core.remote());
-                match core.run(termination_rx) {
-                    Ok(v) => debug!("Core terminated correctly {:?}", v),
-                    Err(e) => debug!("Core terminated with error: {:?}", e),
-                }
-            })?;
-        let inner = CoreExecutorInner {
-            remote: core_rx.wait().expect("Failed to receive remote"),
-            termination_sender: Some(termination_tx),
-            thread_handle: Some(thread_handle),
-        };
-        let executor = CoreExecutor {
-            inner: Arc::new(inner)
-        };
-        debug!("Executor created");
-        Ok(executor)
-    }
+/// Defines what happens to the tasks anchored to the monotonic clock after a clock gap, when the
+/// executor didn't run for a while, for example because the system was suspended. See
+/// `CoreExecutor::on_clock_gap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapPolicy {
+    /// Drops the executions that were due during the gap: the tasks keep their next execution
+    /// if it's still ahead, and otherwise continue with the first execution planned by their
+    /// schedule after the wake up. The tasks whose only remaining execution was missed, like
+    /// the tasks scheduled once, are stopped.
+    Reanchor,
+    /// Runs the tasks that missed executions once, right away, and then continues like
+    /// `Reanchor`.
+    RunOnce,
+    /// Runs up to the given number of the missed executions of each task, right away, and then
+    /// continues like `Reanchor`.
+    CatchUp(usize),
+}
 
-    /// Schedule a function for running at fixed intervals. The executor will try to run the
-    /// function every `interval`, but if one execution takes longer than `interval` it will delay
-    /// all the subsequent calls.
-    pub fn schedule_fixed_interval<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
-        where F: Fn(&Handle) + Send + 'static
-    {
-        let task_handle = TaskHandle::new();
-        let task_handle_clone = task_handle.clone();
-        self.inner.remote.spawn(move |handle| {
-            let handle_clone = handle.clone();
-            let t = Timeout::new(initial, handle).unwrap()
-                .then(move |_| {
-                    fixed_interval_loop(scheduled_fn, interval, &handle_clone, task_handle_clone);
-                    Ok::<(), ()>(())
-                });
-            handle.spawn(t);
-            Ok::<(), ()>(())
-        });
-        task_handle
+impl GapPolicy {
+    /// Returns the number of executions to run right away for `missed` missed executions.
+    fn runs(&self, missed: usize) -> usize {
+        match *self {
+            GapPolicy::Reanchor => 0,
+            GapPolicy::RunOnce => cmp::min(missed, 1),
+            GapPolicy::CatchUp(max) => cmp::min(missed, max),
+        }
     }
+}
 
-    /// Schedule a function for running at fixed rate. The executor will try to run the function
-    /// every `interval`, and if a task execution takes longer than `interval`, the wait time
-    /// between task will be reduced to decrease the overall delay.
-    pub fn schedule_fixed_rate<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
-        where F: Fn(&Handle) + Send + 'static
-    {
-        let task_handle = TaskHandle::new();
-        let task_handle_clone = task_handle.clone();
-        self.inner.remote.spawn(move |handle| {
-            let handle_clone = handle.clone();
-            let t = Timeout::new(initial, handle).unwrap()
-                .then(move |_| {
-                    fixed_rate_loop(scheduled_fn, interval, &handle_clone, Duration::from_secs(0), task_handle_clone);
-                    Ok::<(), ()>(())
-                });
-            handle.spawn(t);
-            Ok::<(), ()>(())
-        });
-        task_handle
-    }
+/// A jump of the system clock, compared to the monotonic clock. See `CoreExecutor::on_clock_jump`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockJump {
+    /// The system clock moved forwards by the given amount.
+    Forward(Duration),
+    /// The system clock moved backwards by the given amount.
+    Backward(Duration),
 }
 
+/// Defines how a `ThreadPoolExecutor` with multiple scheduling threads chooses the thread that
+/// schedules a new task. See `ThreadPoolExecutor::set_load_balancing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadBalancing {
+    /// Use the scheduling threads in turn. This is the default.
+    RoundRobin,
+    /// Use the scheduling thread with the fewest active tasks.
+    LeastTasks,
+    /// Use the scheduling thread whose active tasks have the lowest total mean execution time.
+    /// Tasks that never ran count as taking no time.
+    LeastBusy,
+}
 
-/// A `ThreadPoolExecutor` will use one thread for the task scheduling and a thread pool for
-/// task execution, allowing multiple tasks to run in parallel.
-#[derive(Clone)]
-pub struct ThreadPoolExecutor {
-    executor: CoreExecutor,
-    pool: CpuPool
+/// Receives notifications about the executions of all the tasks of an executor. See
+/// `CoreExecutor::add_listener`. All the methods do nothing by default.
+pub trait TaskListener: Send + Sync + 'static {
+    /// Called before every execution.
+    fn on_start(&self, _task: &TaskHandle) {}
+
+    /// Called after every successful execution, with the duration of the execution.
+    fn on_complete(&self, _task: &TaskHandle, _duration: Duration) {}
+
+    /// Called every time an execution panics, with the panic message.
+    fn on_panic(&self, _task: &TaskHandle, _message: &str) {}
+
+    /// Called every time an execution completes after its deadline, with how late it completed.
+    /// See `TaskHandle::set_deadline`.
+    fn on_deadline_miss(&self, _task: &TaskHandle, _lateness: Duration) {}
 }
 
-impl ThreadPoolExecutor {
-    /// Creates a new `ThreadPoolExecutor` with the specified number of threads. Threads will
-    /// be named "pool_thread_0", "pool_thread_1" and so on.
-    pub fn new(threads: usize) -> Result<ThreadPoolExecutor, io::Error> {
-        ThreadPoolExecutor::with_prefix(threads, "pool_thread_")
-    }
+/// Forwards the notifications about the tasks with a tag to a listener. See
+/// `CoreExecutor::add_tagged_listener`.
+struct TagFilter<L> {
+    tag: String,
+    listener: L,
+}
 
-    /// Creates a new `ThreadPoolExecutor` with the specified number of threads and prefix for
-    /// the thread names.
-    pub fn with_prefix(threads: usize, prefix: &str) -> Result<ThreadPoolExecutor, io::Error> {
-        let new_executor = CoreExecutor::with_name(&format!("{}executor", prefix))?;
-        Ok(ThreadPoolExecutor::with_executor(threads, prefix, new_executor))
+impl<L: TaskListener> TaskListener for TagFilter<L> {
+    fn on_start(&self, task: &TaskHandle) {
+        if task.has_tag(&self.tag) {
+            self.listener.on_start(task);
+        }
     }
 
-    /// Creates a new `ThreadPoolExecutor` with the specified number of threads, prefix and
-    /// using the given `CoreExecutor` for scheduling.
-    pub fn with_executor(threads: usize, prefix: &str, executor: CoreExecutor) -> ThreadPoolExecutor {
-        let pool = Builder::new()
-            .pool_size(threads)
-            .name_prefix(prefix)
-            .create();
-        ThreadPoolExecutor { pool, executor }
+    fn on_complete(&self, task: &TaskHandle, duration: Duration) {
+        if task.has_tag(&self.tag) {
+            self.listener.on_complete(task, duration);
+        }
     }
 
-    /// Schedules the given function to be executed every `interval`. The function will be
-    /// scheduled on one of the threads in the thread pool.
-    pub fn schedule_fixed_rate<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
-        where F: Fn(&Remote) + Send + Sync + 'static
-    {
-        let pool_clone = self.pool.clone();
-        let arc_fn = Arc::new(scheduled_fn);
-        self.executor.schedule_fixed_interval(  // Fixed interval is enough
-            initial,
-            interval,
-            move |handle| {
-                let arc_fn_clone = arc_fn.clone();
-                let remote = handle.remote().clone();
-                let t = pool_clone.spawn_fn(move || {
-                    arc_fn_clone(&remote);
-                    Ok::<(),()>(())
-                });
-                handle.spawn(t);
-            }
-        )
+    fn on_panic(&self, task: &TaskHandle, message: &str) {
+        if task.has_tag(&self.tag) {
+            self.listener.on_panic(task, message);
+        }
     }
 
-    // TODO: make pub(crate)
-    /// Returns the thread pool used internally.
-    pub fn pool(&self) -> &CpuPool {
-        &self.pool
+    fn on_deadline_miss(&self, task: &TaskHandle, lateness: Duration) {
+        if task.has_tag(&self.tag) {
+            self.listener.on_deadline_miss(task, lateness);
+        }
     }
 }
 
+type PanicHandler = Arc<dyn Fn(&str) + Send + Sync>;
+type ErrorHandler = Arc<dyn Fn(&TaskHandle, &ScheduleError) + Send + Sync>;
 
-#[cfg(test)]
-mod tests {
-    use std::sync::{Arc, RwLock};
-    use std::thread;
-    use std::time::{Duration, Instant};
+/// The number of consecutive timer failures after which a task is stopped.
+const MAX_TIMER_FAILURES: usize = 8;
 
-    use super::{CoreExecutor, ThreadPoolExecutor, calculate_delay};
+/// How often the system time is checked while waiting for an execution anchored to the wall
+/// clock.
+const WALL_CLOCK_CHECK: Duration = Duration::from_secs(1);
 
-    #[test]
-    fn fixed_interval_test() {
-        let timings = Arc::new(RwLock::new(Vec::new()));
-        {
-            let executor = CoreExecutor::new().unwrap();
-            let timings_clone = Arc::clone(&timings);
-            executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    timings_clone.write().unwrap().push(Instant::now());
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
+/// Settings shared by all the tasks of an executor.
+pub(crate) struct Settings {
+    pub(crate) panic_policy: RwLock<PanicPolicy>,
+    panic_handler: RwLock<Option<PanicHandler>>,
+    error_handler: RwLock<Option<ErrorHandler>>,
+    listeners: RwLock<Vec<Arc<dyn TaskListener>>>,
+    pub(crate) clock: Arc<dyn Clock>,
+    terminated: AtomicBool,
+    draining: AtomicBool,
+    in_flight: (Mutex<usize>, Condvar),
+    last_tick: Mutex<Option<Instant>>,
+    ready: Mutex<BinaryHeap<Ready>>,
+    earliest_deadline_first: AtomicBool,
+    wheel: Option<Arc<TimerWheel>>,
+    /// The executions are delayed to the next multiple of the slack since `origin`.
+    timer_slack: Duration,
+    spin: Duration,
+    origin: Instant,
+    abort_sender: Mutex<Option<Sender<()>>>,
+    abort: Shared<Receiver<()>>,
+}
+
+/// Waits for `deadline` in a busy loop, for up to `spin` of real time.
+fn spin_until(clock: &dyn Clock, deadline: Instant, spin: Duration) {
+    let give_up = Instant::now() + spin;
+    while clock.now() < deadline && Instant::now() < give_up {
+        std::hint::spin_loop();
+    }
+}
+
+impl Settings {
+    /// Returns the first multiple of the timer slack since the origin not before `deadline`.
+    fn apply_slack(&self, deadline: Instant) -> Instant {
+        let slack = self.timer_slack.as_nanos();
+        if slack == 0 || deadline <= self.origin {
+            return deadline;
         }
+        let since_origin = (deadline - self.origin).as_nanos();
+        let slots = since_origin.div_ceil(slack);
+        self.origin + Duration::from_nanos((slots * slack) as u64)
+    }
 
-        let timings = timings.read().unwrap();
-        assert_eq!(timings.len(), 6);
-        for i in 1..6 {
-            let execution_interval = timings[i] - timings[i-1];
-            assert!(execution_interval < Duration::from_millis(1020));
-            assert!(execution_interval > Duration::from_millis(980));
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Settings {
+        let (abort_sender, abort) = channel();
+        let origin = clock.now();
+        Settings {
+            panic_policy: RwLock::new(PanicPolicy::Restart),
+            panic_handler: RwLock::new(None),
+            error_handler: RwLock::new(None),
+            listeners: RwLock::new(Vec::new()),
+            clock,
+            terminated: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            in_flight: (Mutex::new(0), Condvar::new()),
+            last_tick: Mutex::new(None),
+            ready: Mutex::new(BinaryHeap::new()),
+            earliest_deadline_first: AtomicBool::new(false),
+            wheel: None,
+            timer_slack: Duration::from_secs(0),
+            spin: Duration::from_secs(0),
+            origin,
+            abort_sender: Mutex::new(Some(abort_sender)),
+            abort: abort.shared(),
         }
     }
 
-    #[test]
-    fn fixed_interval_slow_task_test() {
-        let counter = Arc::new(RwLock::new(0));
-        let counter_clone = Arc::clone(&counter);
-        {
-            let executor = CoreExecutor::new().unwrap();
-            executor.schedule_fixed_interval(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    // TODO: use atomic int when available
-                    let counter = {
-                        let mut counter = counter_clone.write().unwrap();
-                        (*counter) += 1;
-                        *counter
-                    };
-                    if counter == 1 {
-                        thread::sleep(Duration::from_secs(3));
-                    }
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
+    /// Cancels the futures returned by the executions of the asynchronous tasks.
+    fn abort(&self) {
+        if let Some(sender) = self.abort_sender.lock().unwrap().take() {
+            let _ = sender.send(());
         }
-        assert_eq!(*counter.read().unwrap(), 4);
     }
 
-    #[test]
-    fn calculate_delay_test() {
-        fn s(n: u64) -> Duration { Duration::from_secs(n) };
-        assert_eq!(calculate_delay(s(10), s(3), s(0)), (s(7), s(0)));
-        assert_eq!(calculate_delay(s(10), s(11), s(0)), (s(0), s(1)));
-        assert_eq!(calculate_delay(s(10), s(3), s(3)), (s(4), s(0)));
-        assert_eq!(calculate_delay(s(10), s(3), s(9)), (s(0), s(2)));
-        assert_eq!(calculate_delay(s(10), s(12), s(15)), (s(0), s(17)));
+    /// Waits until no execution is in progress, returns false if the deadline expired.
+    fn wait_idle(&self, deadline: Option<Instant>) -> bool {
+        self.wait_executions(deadline, |in_flight| in_flight == 0)
     }
 
-    #[test]
-    fn fixed_rate_test() {
-        let counter = Arc::new(RwLock::new(0));
-        let counter_clone = Arc::clone(&counter);
-        {
-            let executor = CoreExecutor::new().unwrap();
-            executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    let mut counter = counter_clone.write().unwrap();
-                    (*counter) += 1;
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
+    /// Waits until `done`, called with the number of executions in progress every time an
+    /// execution completes, returns true. Returns false if the deadline expired.
+    fn wait_executions<F: Fn(usize) -> bool>(&self, deadline: Option<Instant>, done: F) -> bool {
+        let (ref in_flight, ref condvar) = self.in_flight;
+        let mut in_flight = in_flight.lock().unwrap();
+        while !done(*in_flight) {
+            in_flight = match deadline {
+                Some(deadline) => {
+                    let remaining = remaining(deadline);
+                    if remaining == Duration::from_secs(0) {
+                        return false;
+                    }
+                    condvar.wait_timeout(in_flight, remaining).unwrap().0
+                },
+                None => condvar.wait(in_flight).unwrap(),
+            };
         }
-        assert_eq!(*counter.read().unwrap(), 6);
+        true
     }
 
-    #[test]
-    fn fixed_rate_slow_task_test() {
-        let counter = Arc::new(RwLock::new(0));
+    fn listeners(&self) -> Vec<Arc<dyn TaskListener>> {
+        self.listeners.read().unwrap().clone()
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// The internal representation of a scheduled task.
+#[derive(Clone)]
+pub(crate) struct Task {
+    pub(crate) handle: TaskHandle,
+    pub(crate) settings: Arc<Settings>,
+}
+
+impl Task {
+    pub(crate) fn stopped(&self) -> bool {
+        self.cancelled() || self.handle.state.draining.load(Ordering::Relaxed)
+            || self.settings.draining.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if the task is stopped, or if the executor has been stopped. Unlike
+    /// `stopped`, this is false while the executor is drained.
+    fn cancelled(&self) -> bool {
+        self.handle.stopped() || self.settings.terminated.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn paused(&self) -> bool {
+        self.handle.paused()
+    }
+
+    /// Returns the current instant, according to the clock of the executor.
+    pub(crate) fn now(&self) -> Instant {
+        self.settings.clock.now()
+    }
+
+    /// Returns the system time corresponding to `instant`, according to the clock of the
+    /// executor.
+    fn system_time_at(&self, instant: Instant) -> SystemTime {
+        let now = self.now();
+        let system_now = self.settings.clock.system_time();
+        if instant > now {
+            system_now + (instant - now)
+        } else {
+            system_now.checked_sub(now - instant).unwrap_or(system_now)
+        }
+    }
+
+    /// Returns a future completing at `deadline`, or, if the next execution is anchored to the
+    /// wall clock, once the system clock reaches its planned system time.
+    fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        let system_time = *self.handle.state.next_run_system_time.lock().unwrap();
+        match system_time {
+            Some(system_time) => self.sleep_until_system_time(deadline, system_time, handle),
+            None => self.sleep_until_instant(deadline, handle),
+        }
+    }
+
+    /// Waits until `deadline`, for at most `WALL_CLOCK_CHECK`, and then again until the system
+    /// clock reaches `system_time`, updating the planned instant of the execution if the system
+    /// clock was adjusted.
+    fn sleep_until_system_time(&self, deadline: Instant, system_time: SystemTime, handle: &Handle)
+        -> Box<dyn Future<Item=(), Error=ScheduleError>>
+    {
+        let check = cmp::min(deadline, self.now() + WALL_CLOCK_CHECK);
+        let task = self.clone();
+        let handle = handle.clone();
+        let sleep = ClockJumped { sleep: self.sleep_until_instant(check, &handle), task: self.clone() };
+        Box::new(sleep.and_then(move |()| -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            match system_time.duration_since(task.settings.clock.system_time()) {
+                Ok(remaining) if remaining > Duration::from_secs(0) => {
+                    let deadline = task.now() + remaining;
+                    *task.handle.state.next_run.lock().unwrap() = Some(deadline);
+                    task.sleep_until_system_time(deadline, system_time, &handle)
+                },
+                _ => Box::new(future::ok(())),
+            }
+        }))
+    }
+
+    /// Returns a future completing at `deadline`, according to the clock of the executor. In
+    /// high resolution mode, the future completes `spin` before the deadline, and then waits
+    /// for the deadline in a busy loop.
+    fn sleep_until_instant(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        let deadline = self.settings.apply_slack(deadline);
+        let spin = self.settings.spin;
+        if spin > Duration::from_secs(0) {
+            let clock = Arc::clone(&self.settings.clock);
+            let wakeup = deadline.checked_sub(spin).unwrap_or(deadline);
+            return Box::new(self.timer_until(wakeup, handle).map(move |()| spin_until(&*clock, deadline, spin)));
+        }
+        self.timer_until(deadline, handle)
+    }
+
+    /// Returns a future completing at `deadline`, according to the timers of the executor.
+    fn timer_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        match self.settings.wheel {
+            Some(ref wheel) => TimerWheel::sleep_until(wheel, deadline, handle),
+            None => self.settings.clock.sleep_until(deadline, handle),
+        }
+    }
+
+    /// Records the start of an execution, returning the start instant.
+    pub(crate) fn started(&self) -> Instant {
+        let start = self.now();
+        *self.settings.last_tick.lock().unwrap() = Some(start);
+        {
+            let mut in_flight = self.settings.in_flight.0.lock().unwrap();
+            *in_flight += 1;
+            self.handle.state.in_flight.fetch_add(1, Ordering::Relaxed);
+        }
+        let run = self.handle.state.runs.fetch_add(1, Ordering::Relaxed) + 1;
+        // The watchdog always uses the real clock
+        *self.handle.state.running_since.lock().unwrap() = Some(Instant::now());
+        let planned = *self.handle.state.next_run.lock().unwrap();
+        *self.handle.state.current_deadline.lock().unwrap() = planned.and_then(|planned| {
+            self.handle.deadline().map(|deadline| planned + deadline)
+        });
+        if let Some(planned) = planned {
+            let lag = if start > planned { start - planned } else { Duration::from_secs(0) };
+            debug!("Starting {} (run {}), planned at {:?}, lag {:?}", self.handle, run, planned, lag);
+            let mut metrics = self.handle.state.metrics.lock().unwrap();
+            metrics.last_lag = Some(lag);
+            metrics.max_lag = cmp::max(metrics.max_lag, Some(lag));
+            metrics.total_lag += lag;
+            metrics.lagged += 1;
+        }
+        for listener in self.settings.listeners() {
+            listener.on_start(&self.handle);
+        }
+        start
+    }
+
+    /// Records the end of an execution that started at `start`, returning its duration.
+    pub(crate) fn ended(&self, start: Instant) -> Duration {
+        let now = self.now();
+        let duration = if now > start { now - start } else { Duration::from_secs(0) };
+        *self.handle.state.running_since.lock().unwrap() = None;
+        let (ref in_flight, ref condvar) = self.settings.in_flight;
+        let mut in_flight = in_flight.lock().unwrap();
+        *in_flight -= 1;
+        self.handle.state.in_flight.fetch_sub(1, Ordering::Relaxed);
+        condvar.notify_all();
+        drop(in_flight);
+        if let Some(waiting) = self.handle.state.slot_waiter.lock().unwrap().take() {
+            waiting.notify();
+        }
+        debug!("Execution of {} completed in {:?}", self.handle, duration);
+        let mut metrics = self.handle.state.metrics.lock().unwrap();
+        metrics.executions += 1;
+        metrics.total_duration += duration;
+        metrics.last_duration = Some(duration);
+        metrics.max_duration = cmp::max(metrics.max_duration, Some(duration));
+        drop(metrics);
+        let deadline = self.handle.state.current_deadline.lock().unwrap().take();
+        if let Some(deadline) = deadline.filter(|&deadline| now > deadline) {
+            self.deadline_missed(now - deadline);
+        }
+        duration
+    }
+
+    fn deadline_missed(&self, lateness: Duration) {
+        warn!("{} missed its deadline by {:?}", self.handle, lateness);
+        self.handle.state.deadline_misses.fetch_add(1, Ordering::Relaxed);
+        for listener in self.settings.listeners() {
+            listener.on_deadline_miss(&self.handle, lateness);
+        }
+    }
+
+    /// Executes the given function, catching any panic.
+    pub(crate) fn call<T, F: FnOnce() -> T>(&self, f: F) -> Option<T> {
+        let token = CancellationToken { task: self.clone() };
+        match panic::catch_unwind(AssertUnwindSafe(|| with_cancellation_token(Some(token), f))) {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                self.panicked(&*payload);
+                None
+            }
+        }
+    }
+
+    /// Applies any interval change requested through the handle to the schedule.
+    pub(crate) fn update_schedule<S: Schedule>(&self, schedule: &mut S) {
+        self.handle.state.reschedule.store(false, Ordering::SeqCst);
+        if let Some(interval) = self.handle.state.new_interval.lock().unwrap().take() {
+            debug!("Changing interval of {} to {:?}", self.handle, interval);
+            schedule.set_interval(interval);
+        }
+    }
+
+    /// Returns true if the wait for the current execution was interrupted by `reschedule`.
+    fn rescheduled(&self) -> bool {
+        self.handle.state.reschedule.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future completing at `deadline`, or earlier if the task is rescheduled, once the
+    /// other tasks that are due at the same time and that run before this one have been
+    /// executed (see `TaskHandle::set_priority`). When the timer fails, the failure is reported
+    /// and the wait is retried with an exponential backoff. The future fails after
+    /// `MAX_TIMER_FAILURES` consecutive failures.
+    fn wait_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        let task = self.clone();
+        Box::new(self.retry_wait_until(deadline, handle, 0)
+            .and_then(move |()| Turn { task, planned: deadline, queued: false }.map_err(|()| ScheduleError::Disconnected)))
+    }
+
+    fn retry_wait_until(&self, deadline: Instant, handle: &Handle, failures: usize) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        let rescheduled = Rescheduled(self.handle.clone());
+        let sleep = self.sleep_until(deadline, handle).select2(rescheduled);
+        let task = self.clone();
+        let handle = handle.clone();
+        Box::new(sleep.then(move |result| -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            let error = match result {
+                Err(Either::A((error, _))) => error,
+                _ => return Box::new(future::ok(())),
+            };
+            let failures = failures + 1;
+            task.timer_failed(&error, failures);
+            if failures >= MAX_TIMER_FAILURES {
+                return Box::new(future::err(error));
+            }
+            let backoff = Duration::from_millis(10) * (1 << (failures - 1));
+            let retry_at = cmp::max(deadline, task.now() + backoff);
+            task.retry_wait_until(retry_at, &handle, failures)
+        }))
+    }
+
+    /// Reports a timer failure to the error handler of the executor.
+    fn timer_failed(&self, error: &ScheduleError, failures: usize) {
+        error!("Timer of {} failed ({} consecutive failures): {}", self.handle, failures, error);
+        let handler = self.settings.error_handler.read().unwrap().clone();
+        if let Some(handler) = handler {
+            handler(&self.handle, error);
+        }
+    }
+
+    /// Stops the task after its timer failed too many times.
+    fn abandoned(&self, error: &ScheduleError) {
+        error!("Stopping {} after {} timer failures: {}", self.handle, MAX_TIMER_FAILURES, error);
+        *self.handle.state.next_run.lock().unwrap() = None;
+        self.handle.stop();
+    }
+
+    /// Returns the next execution of the schedule after `after`. If `skip` is true, the
+    /// executions that were due before `after`, while the previous execution was running, are
+    /// skipped.
+    fn next_tick<S: Schedule>(&self, schedule: &mut S, after: Instant, skip: bool) -> Option<Instant> {
+        let mut skip = skip;
+        let recovery = self.handle.state.gap_recovery.lock().unwrap().take();
+        if let Some(recovery) = recovery {
+            if recovery.runs > 0 {
+                let runs = recovery.runs - 1;
+                *self.handle.state.gap_recovery.lock().unwrap() = Some(GapRecovery { runs, ..recovery });
+                return Some(after);
+            }
+            match recovery.pending {
+                Some(pending) if pending > after => return Some(pending),
+                _ => skip = true,
+            }
+        }
+        clock::with_clock(&self.settings.clock, || {
+            let mut next = schedule.next_after(after)?;
+            while skip && next < after {
+                debug!("Skipping execution of {}, the previous one was still running", self.handle);
+                self.handle.state.skipped.fetch_add(1, Ordering::Relaxed);
+                next = schedule.next_after(after)?;
+            }
+            Some(next)
+        })
+    }
+
+    /// Marks the task as stopped once its schedule doesn't have any more executions.
+    pub(crate) fn finished(&self) {
+        debug!("Schedule of {} completed, stopping it", self.handle);
+        *self.handle.state.next_run.lock().unwrap() = None;
+        self.handle.stop();
+    }
+
+    /// Records the successful completion of an execution.
+    pub(crate) fn completed(&self, duration: Duration) {
+        self.handle.state.consecutive_panics.store(0, Ordering::Relaxed);
+        for listener in self.settings.listeners() {
+            listener.on_complete(&self.handle, duration);
+        }
+    }
+
+    /// Records a panic, and applies the panic policy.
+    pub(crate) fn panicked(&self, payload: &(dyn Any + Send)) {
+        let message = panic_message(payload);
+        error!("{} panicked: {}", self.handle, message);
+        let state = &self.handle.state;
+        let panics = state.panics.fetch_add(1, Ordering::Relaxed) + 1;
+        let consecutive_panics = state.consecutive_panics.fetch_add(1, Ordering::Relaxed) + 1;
+        let policy = *self.settings.panic_policy.read().unwrap();
+        match policy {
+            PanicPolicy::StopAfter(max_panics) if panics >= max_panics => self.handle.stop(),
+            _ => {},
+        }
+        if let Some(delay) = policy.backoff(consecutive_panics) {
+            *state.resume_at.lock().unwrap() = Some(self.now() + delay);
+        }
+        let handler = self.settings.panic_handler.read().unwrap().clone();
+        if let Some(handler) = handler {
+            handler(message);
+        }
+        for listener in self.settings.listeners() {
+            listener.on_panic(&self.handle, message);
+        }
+    }
+
+    /// Returns the instant of the next execution, taking into account any panic backoff, and
+    /// records it in the task state.
+    pub(crate) fn next_execution<S: Schedule>(&self, schedule: &S, next: Instant) -> Instant {
+        let next = match *self.handle.state.resume_at.lock().unwrap() {
+            Some(resume_at) if resume_at > next => resume_at,
+            _ => next,
+        };
+        *self.handle.state.next_run.lock().unwrap() = Some(next);
+        *self.handle.state.next_run_system_time.lock().unwrap() = match schedule.anchor() {
+            Anchor::WallClock => Some(self.system_time_at(next)),
+            Anchor::Monotonic => None,
+        };
+        *self.handle.state.interval.lock().unwrap() = schedule.interval();
+        next
+    }
+}
+
+thread_local!(static CURRENT_TOKEN: RefCell<Option<CancellationToken>> = const { RefCell::new(None) });
+
+/// Allows a running execution to find out that its task has been stopped, or that its executor
+/// has been stopped, so that long running functions can bail out early. The token of the current
+/// execution is returned by `CancellationToken::current`, from within the scheduled function.
+///
+/// ```rust,no_run
+/// # use scheduled_executor::{CancellationToken, CoreExecutor};
+/// # use std::time::Duration;
+/// # let executor = CoreExecutor::new().unwrap();
+/// executor.schedule_fixed_interval(Duration::from_secs(0), Duration::from_secs(60), |_handle| {
+///     let token = CancellationToken::current().unwrap();
+///     for _batch in 0..1000 {
+///         if token.is_cancelled() {
+///             return;
+///         }
+///         // Process the batch
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct CancellationToken {
+    task: Task,
+}
+
+impl CancellationToken {
+    /// Returns the token of the execution running on the current thread, or `None` if called
+    /// outside of a scheduled function.
+    pub fn current() -> Option<CancellationToken> {
+        CURRENT_TOKEN.with(|current| current.borrow().clone())
+    }
+
+    /// Returns true if the task has been stopped, or if the executor has been stopped. Draining
+    /// the executor doesn't cancel the executions in progress.
+    pub fn is_cancelled(&self) -> bool {
+        self.task.cancelled()
+    }
+
+    /// Returns the handle of the task.
+    pub fn task(&self) -> &TaskHandle {
+        &self.task.handle
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("task", &self.task.handle)
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Runs `f` with `token` as the current cancellation token of the thread.
+pub(crate) fn with_cancellation_token<T, F: FnOnce() -> T>(token: Option<CancellationToken>, f: F) -> T {
+    struct Restore(Option<CancellationToken>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT_TOKEN.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let clock = token.as_ref().map(|token| Arc::clone(&token.task.settings.clock));
+    let _restore = Restore(CURRENT_TOKEN.with(|current| current.replace(token)));
+    match clock {
+        Some(clock) => clock::with_clock(&clock, f),
+        None => f(),
+    }
+}
+
+/// An execution of an asynchronous task, completing early with `None` once the task is stopped.
+/// If the execution is dropped before completing, because the task was stopped or because the
+/// executor is gone, the cancellation handler of the task is called.
+struct Cancellable<F> {
+    execution: F,
+    task: Task,
+    id: usize,
+    finished: bool,
+}
+
+impl<F> Cancellable<F> {
+    fn new(execution: F, task: Task) -> Cancellable<F> {
+        let id = NEXT_EXECUTION_ID.fetch_add(1, Ordering::Relaxed);
+        Cancellable { execution, task, id, finished: false }
+    }
+}
+
+impl<F: Future<Item=(), Error=()>> Future for Cancellable<F> {
+    type Item = Option<()>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<()>, ()> {
+        self.task.handle.state.cancel_waiters.lock().unwrap().insert(self.id, future_task::current());
+        if self.task.cancelled() {
+            return Ok(Async::Ready(None));
+        }
+        // A panic of the execution is not a cancellation
+        self.finished = true;
+        let result = self.execution.poll();
+        self.finished = !matches!(result, Ok(Async::NotReady));
+        result.map(|ready| ready.map(Some))
+    }
+}
+
+impl<F> Drop for Cancellable<F> {
+    fn drop(&mut self) {
+        self.task.handle.state.cancel_waiters.lock().unwrap().remove(&self.id);
+        if self.finished {
+            return;
+        }
+        debug!("Execution of {} cancelled", self.task.handle);
+        let handler = self.task.handle.state.cancel_handler.lock().unwrap().clone();
+        if let Some(handler) = handler {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| handler(&self.task.handle))) {
+                error!("Cancellation handler of {} panicked: {}", self.task.handle, panic_message(&*payload));
+            }
+        }
+    }
+}
+
+/// Completes when the task is rescheduled.
+struct Rescheduled(TaskHandle);
+
+impl Future for Rescheduled {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let state = &self.0.state;
+        if state.reschedule.load(Ordering::SeqCst) {
+            return Ok(Async::Ready(()));
+        }
+        *state.waiting.lock().unwrap() = Some(future_task::current());
+        // Check again, in case the task was rescheduled before the poll was registered
+        if state.reschedule.load(Ordering::SeqCst) {
+            return Ok(Async::Ready(()));
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+/// Completes with the inner sleep, or earlier once a jump of the system clock is detected. The
+/// waiting task is notified through the `Rescheduled` future polled alongside.
+struct ClockJumped {
+    sleep: Box<dyn Future<Item=(), Error=ScheduleError>>,
+    task: Task,
+}
+
+impl Future for ClockJumped {
+    type Item = ();
+    type Error = ScheduleError;
+
+    fn poll(&mut self) -> Poll<(), ScheduleError> {
+        if self.task.handle.state.clock_jumped.swap(false, Ordering::SeqCst) {
+            return Ok(Async::Ready(()));
+        }
+        self.sleep.poll()
+    }
+}
+
+/// Returns the time left until `deadline`, or zero if it's in the past.
+fn remaining(deadline: Instant) -> Duration {
+    let now = Instant::now();
+    if deadline > now { deadline - now } else { Duration::from_secs(0) }
+}
+
+/// Returns the time left until the given system time of the clock, or zero if it's in the past.
+fn delay_until(clock: &dyn Clock, time: SystemTime) -> Duration {
+    time.duration_since(clock.system_time()).unwrap_or_else(|_| Duration::from_secs(0))
+}
+
+/// Runs a task once, at the given instant.
+pub(crate) struct Once(pub(crate) Option<Instant>);
+
+impl Schedule for Once {
+    fn next_after(&mut self, _after: Instant) -> Option<Instant> {
+        self.0.take()
+    }
+}
+
+/// A task to schedule with `CoreExecutor::schedule_all`.
+pub struct TaskSpec {
+    name: Option<String>,
+    tags: Vec<String>,
+    schedule: Box<dyn Schedule>,
+    scheduled_fn: Box<dyn FnMut(&Handle) + Send>,
+}
+
+impl TaskSpec {
+    /// Creates the specification of a task running `scheduled_fn` according to `schedule`, as
+    /// for `CoreExecutor::schedule_with`.
+    pub fn new<S, F>(schedule: S, scheduled_fn: F) -> TaskSpec
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        TaskSpec { name: None, tags: Vec::new(), schedule: Box::new(schedule), scheduled_fn: Box::new(scheduled_fn) }
+    }
+
+    /// Gives a name to the task, as for `CoreExecutor::schedule_with_named`.
+    pub fn named(mut self, name: &str) -> TaskSpec {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Tags the task, as for `TaskHandle::add_tag`. The tags are added before the task is
+    /// scheduled.
+    pub fn tagged(mut self, tag: &str) -> TaskSpec {
+        self.tags.push(tag.to_owned());
+        self
+    }
+}
+
+impl fmt::Debug for TaskSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskSpec").field("name", &self.name).field("tags", &self.tags).finish()
+    }
+}
+
+/// A task ready to be spawned on the event loop. See `CoreExecutor::spawn_batch`.
+type PendingTask = (Task, Box<dyn Schedule>, Box<dyn FnMut(&Handle) + Send>);
+
+/// Spawns the loop running the task according to its schedule on the event loop of `handle`.
+/// The first execution is planned right away.
+fn schedule_loop<S, F>(schedule: S, scheduled_fn: F, after: Instant, handle: &Handle, task: Task)
+    where S: Schedule,
+          F: FnMut(&Handle) + 'static
+{
+    let mut schedule_loop = ScheduleLoop { schedule, scheduled_fn, handle: handle.clone(), task, wait: None };
+    if schedule_loop.plan(after) {
+        handle.spawn(schedule_loop);
+    }
+}
+
+/// The future running a task according to its schedule, for its whole life: it waits for the
+/// next execution, runs it, and plans the following one, until the task is stopped or its
+/// schedule completes.
+struct ScheduleLoop<S, F> {
+    schedule: S,
+    scheduled_fn: F,
+    handle: Handle,
+    task: Task,
+    /// The wait for the next execution.
+    wait: Option<Box<dyn Future<Item=(), Error=ScheduleError>>>,
+}
+
+impl<S: Schedule, F: FnMut(&Handle)> ScheduleLoop<S, F> {
+    /// Plans the next execution after `after`. Returns false if the schedule has completed.
+    fn plan(&mut self, after: Instant) -> bool {
+        let task = &self.task;
+        task.update_schedule(&mut self.schedule);
+        let skip = task.handle.state.skip_missed.load(Ordering::Relaxed);
+        match task.next_tick(&mut self.schedule, after, skip) {
+            Some(next) => {
+                let next = task.next_execution(&self.schedule, next);
+                self.wait = Some(task.wait_until(next, &self.handle));
+                true
+            },
+            None => {
+                task.finished();
+                false
+            },
+        }
+    }
+}
+
+impl<S: Schedule, F: FnMut(&Handle)> Future for ScheduleLoop<S, F> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            let result = match self.wait.as_mut().map(|wait| wait.poll()) {
+                Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+                Some(result) => result.map(|_| ()),
+                None => return Ok(Async::Ready(())),
+            };
+            self.wait = None;
+            let task = &self.task;
+            if task.stopped() {
+                return Ok(Async::Ready(()));
+            }
+            if let Err(error) = result {
+                task.abandoned(&error);
+                return Ok(Async::Ready(()));
+            }
+            if task.rescheduled() {
+                debug!("{} rescheduled", task.handle);
+            } else if task.paused() {
+                debug!("{} paused, skipping execution", task.handle);
+            } else {
+                let start = task.started();
+                let handle = &self.handle;
+                let scheduled_fn = &mut self.scheduled_fn;
+                let result = task.call(|| scheduled_fn(handle));
+                let duration = task.ended(start);
+                if result.is_some() {
+                    task.completed(duration);
+                }
+                self.schedule.executed();
+            }
+            let now = self.task.now();
+            if !self.plan(now) {
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
+
+/// Spawns the loop running an asynchronous task according to its schedule on the event loop of
+/// `handle`. The first execution is planned right away.
+fn async_schedule_loop<S, F, R>(schedule: S, scheduled_fn: F, policy: OverlapPolicy, after: Instant, handle: &Handle, task: Task)
+    where S: Schedule,
+          F: FnMut(&Handle) -> R + Send + 'static,
+          R: Future<Item=(), Error=()> + 'static
+{
+    let mut schedule_loop = AsyncScheduleLoop {
+        schedule,
+        scheduled_fn,
+        policy,
+        handle: handle.clone(),
+        task,
+        state: AsyncLoopState::Idle,
+    };
+    if schedule_loop.plan(after) {
+        handle.spawn(schedule_loop);
+    }
+}
+
+enum AsyncLoopState {
+    /// Between two executions, or once the loop has completed.
+    Idle,
+    /// Waiting for the next execution.
+    Waiting(Box<dyn Future<Item=(), Error=ScheduleError>>),
+    /// Waiting for one of the running executions to complete. See `OverlapPolicy::Limited`.
+    Slot(SlotAvailable),
+    /// Running an execution that mustn't overlap with the next one.
+    Running(Box<dyn Future<Item=(), Error=()>>),
+}
+
+/// Like `ScheduleLoop`, for the tasks returning a future, whose executions can overlap
+/// according to the overlap policy.
+struct AsyncScheduleLoop<S, F> {
+    schedule: S,
+    scheduled_fn: F,
+    policy: OverlapPolicy,
+    handle: Handle,
+    task: Task,
+    state: AsyncLoopState,
+}
+
+impl<S, F, R> AsyncScheduleLoop<S, F>
+    where S: Schedule,
+          F: FnMut(&Handle) -> R,
+          R: Future<Item=(), Error=()> + 'static
+{
+    /// Plans the next execution after `after`. Returns false if the schedule has completed.
+    fn plan(&mut self, after: Instant) -> bool {
+        let task = &self.task;
+        task.update_schedule(&mut self.schedule);
+        match task.next_tick(&mut self.schedule, after, self.policy == OverlapPolicy::Skip) {
+            Some(next) => {
+                let next = task.next_execution(&self.schedule, next);
+                self.state = AsyncLoopState::Waiting(task.wait_until(next, &self.handle));
+                true
+            },
+            None => {
+                task.finished();
+                self.state = AsyncLoopState::Idle;
+                false
+            },
+        }
+    }
+
+    /// Starts an execution. Returns false if the loop has completed.
+    fn execute(&mut self) -> bool {
+        let task = self.task.clone();
+        if task.stopped() {
+            self.state = AsyncLoopState::Idle;
+            return false;
+        }
+        let start = task.started();
+        let handle = &self.handle;
+        let scheduled_fn = &mut self.scheduled_fn;
+        let execution = task.call(|| scheduled_fn(handle));
+        self.schedule.executed();
+        let execution = match execution {
+            Some(execution) => execution,
+            None => {
+                task.ended(start);
+                return self.plan(task.now());
+            }
+        };
+        let task_clone = task.clone();
+        let execution = AssertUnwindSafe(Cancellable::new(execution, task.clone())).catch_unwind()
+            .select2(task.settings.abort.clone())
+            .then(move |result| {
+                let duration = task_clone.ended(start);
+                match result {
+                    Ok(Either::A((Ok(None), _))) => {},
+                    Ok(Either::A(_)) => task_clone.completed(duration),
+                    Err(Either::A((payload, _))) => task_clone.panicked(&*payload),
+                    Ok(Either::B(_)) | Err(Either::B(_)) => debug!("Execution of {} aborted", task_clone.handle),
+                };
+                Ok::<(), ()>(())
+            });
+        if self.policy.concurrent() {
+            self.handle.spawn(execution);
+            return self.plan(task.now());
+        }
+        self.state = AsyncLoopState::Running(Box::new(execution));
+        true
+    }
+}
+
+impl<S, F, R> Future for AsyncScheduleLoop<S, F>
+    where S: Schedule,
+          F: FnMut(&Handle) -> R,
+          R: Future<Item=(), Error=()> + 'static
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            let result = match self.state {
+                AsyncLoopState::Idle => return Ok(Async::Ready(())),
+                AsyncLoopState::Waiting(ref mut wait) => match wait.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    result => result.map(|_| ()),
+                },
+                AsyncLoopState::Slot(ref mut slot) => {
+                    if let Ok(Async::NotReady) = slot.poll() {
+                        return Ok(Async::NotReady);
+                    }
+                    if !self.execute() {
+                        return Ok(Async::Ready(()));
+                    }
+                    continue;
+                },
+                AsyncLoopState::Running(ref mut execution) => {
+                    if let Ok(Async::NotReady) = execution.poll() {
+                        return Ok(Async::NotReady);
+                    }
+                    let now = self.task.now();
+                    if !self.plan(now) {
+                        return Ok(Async::Ready(()));
+                    }
+                    continue;
+                },
+            };
+            // The wait for the next execution has completed
+            self.state = AsyncLoopState::Idle;
+            let task = self.task.clone();
+            if task.stopped() {
+                return Ok(Async::Ready(()));
+            }
+            if let Err(error) = result {
+                task.abandoned(&error);
+                return Ok(Async::Ready(()));
+            }
+            if task.rescheduled() || task.paused() {
+                if task.rescheduled() {
+                    debug!("{} rescheduled", task.handle);
+                } else {
+                    debug!("{} paused, skipping execution", task.handle);
+                }
+                if !self.plan(task.now()) {
+                    return Ok(Async::Ready(()));
+                }
+                continue;
+            }
+            if let OverlapPolicy::Limited { max, skip } = self.policy {
+                if task.handle.state.in_flight.load(Ordering::Relaxed) >= max {
+                    if skip {
+                        debug!("Skipping execution of {}, {} executions are running", task.handle, max);
+                        task.handle.state.skipped.fetch_add(1, Ordering::Relaxed);
+                        if !self.plan(task.now()) {
+                            return Ok(Async::Ready(()));
+                        }
+                        continue;
+                    }
+                    debug!("Delaying execution of {}, {} executions are running", task.handle, max);
+                    self.state = AsyncLoopState::Slot(SlotAvailable { task, max });
+                    continue;
+                }
+            }
+            if !self.execute() {
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
+
+/// Completes when fewer than `max` executions of the task are running.
+struct SlotAvailable {
+    task: Task,
+    max: usize,
+}
+
+impl Future for SlotAvailable {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let state = &self.task.handle.state;
+        *state.slot_waiter.lock().unwrap() = Some(future_task::current());
+        if state.in_flight.load(Ordering::Relaxed) < self.max || self.task.stopped() {
+            return Ok(Async::Ready(()));
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+/// An execution that is due, waiting for its turn in the ready queue of the executor. The
+/// greatest entry runs first.
+struct Ready {
+    /// The deadline of the execution, only set for executors running the earliest deadline
+    /// first, so that the executions without a deadline run last.
+    deadline: Option<Reverse<Instant>>,
+    priority: i32,
+    planned: Reverse<Instant>,
+    id: Reverse<usize>,
+    waiter: FutureTask,
+}
+
+impl Ready {
+    fn key(&self) -> (Option<Reverse<Instant>>, i32, Reverse<Instant>, Reverse<usize>) {
+        (self.deadline, self.priority, self.planned, self.id)
+    }
+}
+
+impl PartialEq for Ready {
+    fn eq(&self, other: &Ready) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Ready {}
+
+impl PartialOrd for Ready {
+    fn partial_cmp(&self, other: &Ready) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ready {
+    fn cmp(&self, other: &Ready) -> cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Completes when it's the turn of the task to run. The first poll adds the task to the ready
+/// queue and yields, so that all the tasks woken up by the same turn of the event loop are
+/// queued before the first one runs.
+struct Turn {
+    task: Task,
+    planned: Instant,
+    queued: bool,
+}
+
+impl Future for Turn {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let mut ready = self.task.settings.ready.lock().unwrap();
+        if !self.queued {
+            self.queued = true;
+            let deadline = if self.task.settings.earliest_deadline_first.load(Ordering::Relaxed) {
+                self.task.handle.deadline().map(|deadline| Reverse(self.planned + deadline))
+            } else {
+                None
+            };
+            ready.push(Ready {
+                deadline,
+                priority: self.task.handle.priority(),
+                planned: Reverse(self.planned),
+                id: Reverse(self.task.handle.id()),
+                waiter: future_task::current(),
+            });
+            future_task::current().notify();
+            return Ok(Async::NotReady);
+        }
+        if ready.peek().is_some_and(|first| first.id.0 == self.task.handle.id()) {
+            ready.pop();
+            self.queued = false;
+            // The next one runs once the event loop is done with this one
+            if let Some(next) = ready.peek() {
+                next.waiter.notify();
+            }
+            return Ok(Async::Ready(()));
+        }
+        if let Some(first) = ready.peek() {
+            first.waiter.notify();
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        if !self.queued {
+            return;
+        }
+        let mut ready = self.task.settings.ready.lock().unwrap();
+        let id = self.task.handle.id();
+        ready.retain(|entry| entry.id.0 != id);
+        if let Some(next) = ready.peek() {
+            next.waiter.notify();
+        }
+    }
+}
+
+/// Runs the event loop until the executor is terminated. If `supervised`, the panics of the
+/// event loop are caught and returned.
+fn run_core(core: &mut Core, termination: &mut Receiver<()>, supervised: bool) -> Option<Box<dyn Any + Send>> {
+    let result = if supervised {
+        match panic::catch_unwind(AssertUnwindSafe(|| core.run(termination))) {
+            Ok(result) => result,
+            Err(payload) => return Some(payload),
+        }
+    } else {
+        core.run(termination)
+    };
+    match result {
+        Ok(v) => debug!("Core terminated correctly {:?}", v),
+        Err(e) => debug!("Core terminated with error: {:?}", e),
+    }
+    None
+}
+
+/// Signals the termination of the executor thread when dropped, even if the thread panicked.
+struct ExitGuard {
+    sender: Option<Sender<()>>,
+    exited: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(());
+        }
+        let (ref exited, ref condvar) = *self.exited;
+        *exited.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+}
+
+/// Locks a mutex, ignoring poisoning: the state of a supervised task has to survive the panics
+/// that took down the event loop.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The schedule of a supervised task, shared between the event loops that run it.
+struct SharedSchedule<S>(Arc<Mutex<S>>);
+
+impl<S: Schedule> Schedule for SharedSchedule<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        lock(&self.0).next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        lock(&self.0).set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        lock(&self.0).interval()
+    }
+
+    fn executed(&mut self) {
+        lock(&self.0).executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        lock(&self.0).anchor()
+    }
+}
+
+type Respawn = Arc<dyn Fn(&Handle) + Send + Sync>;
+
+/// A task of a supervised executor, with the function spawning its loop on an event loop.
+struct Restartable {
+    task: Task,
+    respawn: Respawn,
+}
+
+/// Keeps track of the tasks of a supervised executor, to schedule them again when the event loop
+/// is restarted. See `ExecutorBuilder::supervised`.
+struct Supervisor {
+    tasks: Mutex<Vec<Restartable>>,
+    restarts: AtomicUsize,
+}
+
+impl Supervisor {
+    /// Registers a task, and spawns it on the current event loop.
+    fn register(&self, remote: &RwLock<Remote>, task: Task, respawn: Respawn) {
+        // Holding the lock prevents a concurrent restart from spawning the task twice
+        let mut tasks = lock(&self.tasks);
+        tasks.retain(|restartable| !restartable.task.stopped());
+        tasks.push(Restartable { task, respawn: Arc::clone(&respawn) });
+        remote.read().unwrap().spawn(move |handle| {
+            respawn(handle);
+            Ok::<(), ()>(())
+        });
+    }
+
+    /// Replaces the event loop of the executor, and spawns the tasks on it.
+    fn restart(&self, remote: &RwLock<Remote>, handle: &Handle) {
+        let mut tasks = lock(&self.tasks);
+        *remote.write().unwrap() = handle.remote().clone();
+        tasks.retain(|restartable| !restartable.task.stopped());
+        for restartable in tasks.iter() {
+            debug!("Restarting {}", restartable.task.handle);
+            (restartable.respawn)(handle);
+        }
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The right to stop an executor. The executors can be cloned and shared by many components to
+/// schedule tasks, while the shutdown token is kept by the component that owns the executor and
+/// decides when it should stop. See `CoreExecutor::shutdown` and `ThreadPoolExecutor::shutdown`.
+///
+/// The tokens of different executors can be combined, for applications running one executor per
+/// subsystem, so that all of them are stopped at once:
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate scheduled_executor;
+/// # use futures::Future;
+/// # use scheduled_executor::{CoreExecutor, Shutdown, ThreadPoolExecutor};
+/// # fn main() {
+/// let ingestion = CoreExecutor::with_name("ingestion").unwrap();
+/// let reporting = ThreadPoolExecutor::new(4).unwrap();
+/// let shutdown = vec![ingestion.shutdown(), reporting.shutdown()].into_iter().collect::<Shutdown>();
+/// // ...
+/// shutdown.stop().wait().unwrap();
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Shutdown {
+    executors: Vec<CoreExecutor>,
+}
+
+impl Shutdown {
+    /// Creates a token that doesn't stop any executor, until other tokens are added to it.
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    /// Adds the executors of another token to this one.
+    pub fn add(&mut self, shutdown: Shutdown) {
+        for executor in shutdown.executors {
+            if !self.executors.iter().any(|e| Arc::ptr_eq(&e.inner, &executor.inner)) {
+                self.executors.push(executor);
+            }
+        }
+    }
+
+    /// Stops the executor. See `CoreExecutor::stop`.
+    pub fn stop(self) -> StopFuture {
+        let exits = self.executors.iter()
+            .flat_map(|executor| executor.stop().exits)
+            .collect();
+        StopFuture { exits }
+    }
+
+    /// Stops the executor right away, cancelling the futures returned by the executions of the
+    /// asynchronous tasks instead of waiting for them. See `CoreExecutor::stop_now`.
+    pub fn stop_now(self) -> StopFuture {
+        for executor in &self.executors {
+            executor.inner.settings.abort();
+        }
+        self.stop()
+    }
+
+    /// Stops the executor, and waits up to `timeout` for its threads to terminate. See
+    /// `CoreExecutor::stop_with_timeout`.
+    pub fn stop_with_timeout(self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        for executor in &self.executors {
+            executor.inner.terminate();
+        }
+        self.executors.iter().all(|executor| executor.stop_with_timeout(remaining(deadline)))
+    }
+
+    /// Stops the executor gracefully: no new execution is started, and the executions in
+    /// progress, including the futures returned by asynchronous tasks and the functions running
+    /// on a thread pool, are allowed to complete before the executor threads are terminated.
+    /// Waits up to `timeout`, if given, for the executions to complete and for the threads to
+    /// terminate. Once the timeout expires the executor is stopped anyway, and false is returned.
+    /// Like `stop_with_timeout`, this shouldn't be called from a task.
+    ///
+    /// If some tasks have a shutdown phase (see `TaskHandle::set_shutdown_phase`), the phases are
+    /// drained in order, and the tasks of the last phase keep running according to their
+    /// schedules until the executions of the previous phases have completed.
+    pub fn drain(self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let drained = self.drain_phases(deadline) && {
+            for executor in &self.executors {
+                executor.inner.settings.draining.store(true, Ordering::Relaxed);
+            }
+            self.executors.iter().all(|executor| executor.inner.settings.wait_idle(deadline))
+        };
+        if !drained {
+            warn!("Executions still in progress after {:?}, stopping the executor", timeout);
+        }
+        match deadline {
+            Some(deadline) => self.stop_with_timeout(remaining(deadline)) && drained,
+            None => {
+                let _ = self.stop().wait();
+                drained
+            }
+        }
+    }
+}
+
+impl Shutdown {
+    /// Stops the tasks one shutdown phase at a time, except the last phase, waiting for the
+    /// executions of each phase to complete. Returns false if the deadline expired.
+    fn drain_phases(&self, deadline: Option<Instant>) -> bool {
+        let tasks = self.executors.iter()
+            .map(|executor| (executor, executor.tasks()))
+            .collect::<Vec<_>>();
+        let mut phases = tasks.iter()
+            .flat_map(|(_, tasks)| tasks.iter().map(|task| task.shutdown_phase()))
+            .collect::<Vec<_>>();
+        phases.sort();
+        phases.dedup();
+        phases.pop();
+        for phase in phases {
+            debug!("Draining the tasks of shutdown phase {}", phase);
+            for &(executor, ref tasks) in &tasks {
+                let tasks = tasks.iter()
+                    .filter(|task| task.shutdown_phase() == phase)
+                    .collect::<Vec<_>>();
+                for task in &tasks {
+                    task.state.draining.store(true, Ordering::Relaxed);
+                }
+                let drained = executor.inner.settings.wait_executions(deadline, |_| {
+                    tasks.iter().all(|task| task.state.in_flight.load(Ordering::Relaxed) == 0)
+                });
+                if !drained {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Extend<Shutdown> for Shutdown {
+    fn extend<I: IntoIterator<Item=Shutdown>>(&mut self, shutdowns: I) {
+        for shutdown in shutdowns {
+            self.add(shutdown);
+        }
+    }
+}
+
+impl iter::FromIterator<Shutdown> for Shutdown {
+    fn from_iter<I: IntoIterator<Item=Shutdown>>(shutdowns: I) -> Shutdown {
+        let mut shutdown = Shutdown::new();
+        shutdown.extend(shutdowns);
+        shutdown
+    }
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("threads", &self.executors.iter().map(|e| &e.inner.thread_name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// A future resolving to the result of a function run on the executor thread. See
+/// `CoreExecutor::execute`.
+#[derive(Debug)]
+pub struct ExecuteFuture<R> {
+    receiver: Receiver<R>,
+}
+
+impl<R> Future for ExecuteFuture<R> {
+    type Item = R;
+    type Error = Canceled;
+
+    fn poll(&mut self) -> Poll<R, Canceled> {
+        self.receiver.poll()
+    }
+}
+
+/// A future that completes once the executor threads have terminated. See
+/// `CoreExecutor::stop`.
+pub struct StopFuture {
+    exits: Vec<Shared<Receiver<()>>>,
+}
+
+impl Future for StopFuture {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        // The receiver fails only if the thread is gone without sending, which is also fine
+        let mut pending = Vec::new();
+        for mut exit in self.exits.drain(..) {
+            if let Ok(Async::NotReady) = exit.poll() {
+                pending.push(exit);
+            }
+        }
+        self.exits = pending;
+        if self.exits.is_empty() {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+/// Defines what happens to the executor thread once the last reference to a `CoreExecutor` is
+/// dropped, without the executor being explicitly stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropBehavior {
+    /// Leave the executor thread running: the scheduled tasks will keep running until the end of
+    /// the process.
+    Detach,
+    /// Stop the executor, without waiting for the currently running task to complete.
+    Stop,
+    /// Stop the executor, and wait for the executor thread to terminate. This is the default.
+    StopAndWait,
+}
+
+type WatchdogHandler = Arc<dyn Fn(&TaskHandle, Duration) + Send + Sync>;
+
+/// The configuration of the watchdog of an executor. See `CoreExecutor::set_watchdog`.
+struct Watchdog {
+    threshold: Duration,
+    handler: WatchdogHandler,
+}
+
+/// Periodically checks the tasks of the executor, reporting the executions running for longer
+/// than the watchdog threshold. Every execution is reported only once.
+fn watchdog_loop(executor: Weak<CoreExecutorInner>) {
+    let mut reported = HashMap::new();
+    loop {
+        let (threshold, handler, thread_name, tasks) = match executor.upgrade() {
+            Some(ref executor) if !*executor.exited.0.lock().unwrap() => {
+                let watchdog = executor.watchdog.lock().unwrap();
+                let watchdog = watchdog.as_ref().expect("Watchdog not configured");
+                let tasks = executor.tasks.lock().unwrap().clone();
+                (watchdog.threshold, Arc::clone(&watchdog.handler), executor.thread_name.clone(), tasks)
+            },
+            _ => return,
+        };
+        reported.retain(|id, _| tasks.iter().any(|task| task.id() == *id));
+        for task in &tasks {
+            let start = match *task.state.running_since.lock().unwrap() {
+                Some(start) => start,
+                None => continue,
+            };
+            let running_for = start.elapsed();
+            if running_for >= threshold && reported.get(&task.id()) != Some(&start) {
+                warn!("{} has been running for {:?} on executor thread {}", task, running_for, thread_name);
+                reported.insert(task.id(), start);
+                handler(task, running_for);
+            }
+        }
+        thread::sleep(cmp::max(threshold / 4, Duration::from_millis(1)));
+    }
+}
+
+type GapHandler = Arc<dyn Fn(Duration) + Send + Sync>;
+type JumpHandler = Arc<dyn Fn(ClockJump) + Send + Sync>;
+
+/// The configuration of the clock gap detection of an executor. See
+/// `CoreExecutor::on_clock_gap`.
+#[derive(Clone)]
+struct GapMonitor {
+    threshold: Duration,
+    policy: GapPolicy,
+    handler: GapHandler,
+}
+
+/// The configuration of the clock jump detection of an executor. See
+/// `CoreExecutor::on_clock_jump`.
+#[derive(Clone)]
+struct JumpMonitor {
+    threshold: Duration,
+    handler: JumpHandler,
+}
+
+/// The clock monitoring of an executor, running on its own thread once any detection is
+/// configured.
+#[derive(Clone, Default)]
+struct ClockMonitor {
+    gap: Option<GapMonitor>,
+    jump: Option<JumpMonitor>,
+}
+
+impl ClockMonitor {
+    /// Returns how long to sleep between two checks of the clock.
+    fn check_interval(&self) -> Duration {
+        let gap = self.gap.as_ref().map(|gap| gap.threshold);
+        let jump = self.jump.as_ref().map(|jump| jump.threshold);
+        let threshold = gap.into_iter().chain(jump).min().unwrap_or(WALL_CLOCK_CHECK);
+        cmp::max(threshold / 4, Duration::from_millis(1))
+    }
+}
+
+/// Returns the number of executions of the task planned at most `late` before the wake up.
+fn missed_executions(task: &TaskHandle, late: i128) -> usize {
+    if late < 0 {
+        return 0;
+    }
+    match task.interval() {
+        Some(interval) if interval > Duration::from_secs(0) => 1 + (late / interval.as_nanos() as i128) as usize,
+        _ => 1,
+    }
+}
+
+/// Applies the gap policy to the tasks anchored to the monotonic clock, after a gap that started
+/// at `instant`, and lasted `system_elapsed` nanoseconds of system time.
+fn recover_from_gap(tasks: &[TaskHandle], policy: GapPolicy, instant: Instant, system_elapsed: i128) {
+    for task in tasks.iter().filter(|task| !task.stopped() && !task.anchored_to_wall_clock()) {
+        // How long before the wake up the next execution was due, in system time
+        let late = task.next_run().map_or(-1, |next_run| {
+            let ahead = if next_run > instant {
+                (next_run - instant).as_nanos() as i128
+            } else {
+                -((instant - next_run).as_nanos() as i128)
+            };
+            system_elapsed - ahead
+        });
+        task.recover_gap(policy.runs(missed_executions(task, late)));
+    }
+}
+
+/// Periodically compares the clock of the executor with the time actually slept, and with the
+/// system time. Reports the gaps longer than the threshold, applying the gap policy to the tasks
+/// anchored to the monotonic clock, and the jumps of the system time, waking up the tasks
+/// anchored to the wall clock.
+fn clock_monitor_loop(executor: Weak<CoreExecutorInner>) {
+    let mut previous: Option<(Instant, SystemTime, Duration)> = None;
+    loop {
+        let (monitor, clock, tasks) = match executor.upgrade() {
+            Some(ref executor) if !*executor.exited.0.lock().unwrap() => {
+                let monitor = executor.clock_monitor.lock().unwrap().clone();
+                let clock = Arc::clone(&executor.settings.clock);
+                let tasks = executor.tasks.lock().unwrap().clone();
+                (monitor, clock, tasks)
+            },
+            _ => return,
+        };
+        let now = clock.now();
+        let system_now = clock.system_time();
+        if let Some((instant, system_time, slept)) = previous {
+            let elapsed = now.saturating_duration_since(instant);
+            let system_elapsed = match system_now.duration_since(system_time) {
+                Ok(system_elapsed) => system_elapsed.as_nanos() as i128,
+                Err(e) => -(e.duration().as_nanos() as i128),
+            };
+            // Positive if the system time moved faster than the monotonic clock
+            let drift = system_elapsed - elapsed.as_nanos() as i128;
+            if let Some(jump) = monitor.jump.as_ref().filter(|jump| drift.unsigned_abs() >= jump.threshold.as_nanos()) {
+                let jump_by = Duration::from_nanos(drift.unsigned_abs() as u64);
+                let jump_by = if drift > 0 { ClockJump::Forward(jump_by) } else { ClockJump::Backward(jump_by) };
+                warn!("System clock jump detected: {:?}", jump_by);
+                for task in tasks.iter().filter(|task| task.anchored_to_wall_clock()) {
+                    task.clock_jumped();
+                }
+                (jump.handler)(jump_by);
+            }
+            // The monotonic clock either kept running while the thread wasn't, or stopped while
+            // the system time kept going
+            let gap = cmp::max(elapsed.saturating_sub(slept), Duration::from_nanos(cmp::max(drift, 0) as u64));
+            if let Some(gap_monitor) = monitor.gap.as_ref().filter(|gap_monitor| gap >= gap_monitor.threshold) {
+                warn!("Clock gap of {:?} detected, applying {:?}", gap, gap_monitor.policy);
+                recover_from_gap(&tasks, gap_monitor.policy, instant, system_elapsed);
+                (gap_monitor.handler)(gap);
+            }
+        }
+        let check = monitor.check_interval();
+        previous = Some((now, system_now, check));
+        thread::sleep(check);
+    }
+}
+
+struct CoreExecutorInner {
+    remote: Arc<RwLock<Remote>>,
+    supervisor: Option<Arc<Supervisor>>,
+    thread_name: String,
+    drop_behavior: DropBehavior,
+    shard: Option<(usize, usize)>,
+    settings: Arc<Settings>,
+    termination_sender: Mutex<Option<Sender<()>>>,
+    thread_handle: Mutex<Option<JoinHandle<()>>>,
+    exit_receiver: Shared<Receiver<()>>,
+    exited: Arc<(Mutex<bool>, Condvar)>,
+    tasks: Mutex<Vec<TaskHandle>>,
+    config_tasks: Mutex<HashMap<String, RunningTask>>,
+    watchdog: Mutex<Option<Watchdog>>,
+    clock_monitor: Mutex<ClockMonitor>,
+    blocking_threads: usize,
+    stack_size: Option<usize>,
+    blocking_pool: OnceLock<CpuPool>,
+}
+
+impl CoreExecutorInner {
+    fn terminate(&self) {
+        // Needed when running on an external event loop, that will keep running the tasks
+        self.settings.terminated.store(true, Ordering::Relaxed);
+        if let Some(sender) = self.termination_sender.lock().unwrap().take() {
+            let _ = sender.send(());
+        }
+    }
+
+    /// Waits for the executor thread to terminate, returns false if the timeout expired.
+    fn wait_exit(&self, timeout: Duration) -> bool {
+        let (ref exited, ref condvar) = *self.exited;
+        let exited = condvar.wait_timeout_while(exited.lock().unwrap(), timeout, |exited| !*exited)
+            .unwrap().0;
+        *exited
+    }
+}
+
+impl Drop for CoreExecutorInner {
+    fn drop(&mut self) {
+        match self.drop_behavior {
+            DropBehavior::Detach => {
+                debug!("Executor dropped, detaching thread");
+                // Keep the termination sender alive, otherwise the event loop would stop
+                if let Some(sender) = self.termination_sender.lock().unwrap().take() {
+                    mem::forget(sender);
+                }
+            },
+            DropBehavior::Stop => self.terminate(),
+            DropBehavior::StopAndWait => {
+                self.terminate();
+                if let Some(thread_handle) = self.thread_handle.lock().unwrap().take() {
+                    let _ = thread_handle.join();
+                }
+            },
+        }
+    }
+}
+
+/// Configures and creates a `CoreExecutor`.
+///
+/// ```rust,no_run
+/// # use scheduled_executor::executor::{DropBehavior, ExecutorBuilder};
+/// let executor = ExecutorBuilder::new()
+///     .name("my_executor")
+///     .drop_behavior(DropBehavior::Detach)
+///     .build()
+///     .expect("Failed to create executor");
+/// ```
+#[derive(Clone)]
+pub struct ExecutorBuilder {
+    name: String,
+    drop_behavior: DropBehavior,
+    clock: Arc<dyn Clock>,
+    supervised: bool,
+    shard: Option<(usize, usize)>,
+    earliest_deadline_first: bool,
+    timer_wheel: bool,
+    timer_slack: Duration,
+    coarse: bool,
+    spin: Duration,
+    blocking_threads: usize,
+    stack_size: Option<usize>,
+    configure_thread: Option<ConfigureThread>,
+    thread_setup: Vec<ThreadSetup>,
+}
+
+/// A function customizing the builder of the executor thread.
+type ConfigureThread = Arc<dyn Fn(thread::Builder) -> thread::Builder + Send + Sync>;
+
+/// A function configuring the executor thread once started, for example pinning it to a core.
+type ThreadSetup = Arc<dyn Fn() -> io::Result<()> + Send + Sync>;
+
+impl Default for ExecutorBuilder {
+    fn default() -> ExecutorBuilder {
+        ExecutorBuilder {
+            name: "core_executor".to_owned(),
+            drop_behavior: DropBehavior::StopAndWait,
+            clock: Arc::new(SystemClock),
+            supervised: false,
+            shard: None,
+            earliest_deadline_first: false,
+            timer_wheel: true,
+            timer_slack: Duration::from_secs(0),
+            coarse: false,
+            spin: Duration::from_secs(0),
+            blocking_threads: 4,
+            stack_size: None,
+            configure_thread: None,
+            thread_setup: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for ExecutorBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExecutorBuilder")
+            .field("name", &self.name)
+            .field("drop_behavior", &self.drop_behavior)
+            .field("supervised", &self.supervised)
+            .field("shard", &self.shard)
+            .field("earliest_deadline_first", &self.earliest_deadline_first)
+            .field("timer_wheel", &self.timer_wheel)
+            .field("timer_slack", &self.timer_slack)
+            .field("coarse", &self.coarse)
+            .field("spin", &self.spin)
+            .field("blocking_threads", &self.blocking_threads)
+            .field("stack_size", &self.stack_size)
+            .field("configure_thread", &self.configure_thread.is_some())
+            .field("thread_setup", &self.thread_setup.len())
+            .finish()
+    }
+}
+
+impl ExecutorBuilder {
+    /// Creates a new builder, with the default configuration.
+    pub fn new() -> ExecutorBuilder {
+        ExecutorBuilder::default()
+    }
+
+    /// Sets the name of the executor thread.
+    pub fn name(mut self, name: &str) -> ExecutorBuilder {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Sets what happens when the executor is dropped without being stopped.
+    pub fn drop_behavior(mut self, drop_behavior: DropBehavior) -> ExecutorBuilder {
+        self.drop_behavior = drop_behavior;
+        self
+    }
+
+    /// Sets the clock used to schedule the tasks and to measure their executions. Defaults to
+    /// the `SystemClock`. Disables the timer wheel, see `timer_wheel`.
+    pub fn clock<C: Clock>(mut self, clock: C) -> ExecutorBuilder {
+        self.clock = Arc::new(clock);
+        self.timer_wheel = false;
+        self
+    }
+
+    /// Sets whether the timers of the tasks are kept in a timing wheel, driven by a single timer
+    /// of the event loop, instead of creating a timer of the event loop for every execution of
+    /// every task. Enabled by default, which is lighter for executors with many tasks. Custom
+    /// clocks always create their own timers, so with `clock` the wheel is disabled, and
+    /// enabling it again ignores the `sleep_until` of the clock.
+    pub fn timer_wheel(mut self, timer_wheel: bool) -> ExecutorBuilder {
+        self.timer_wheel = timer_wheel;
+        self
+    }
+
+    /// Enables the supervision of the executor thread. If the event loop terminates unexpectedly,
+    /// for example because a future spawned on it panicked, a new event loop is started on the
+    /// same thread and all the tasks that are still scheduled are spawned on it, resuming from
+    /// their schedules. The futures spawned with `spawn` or `execute`, and the tasks scheduled
+    /// with `schedule_local`, are lost. Only applies to executors started with `build`.
+    pub fn supervised(mut self, supervised: bool) -> ExecutorBuilder {
+        self.supervised = supervised;
+        self
+    }
+
+    /// Makes the executor the shard `index` of `count`: the named tasks whose name doesn't hash
+    /// to `index` are never run by this executor, their handles being stopped right away, so
+    /// that `count` instances scheduling the same tasks split them between each other. Tasks
+    /// without a name always run. See `coordination::shard_of`.
+    ///
+    /// Panics if `index` is not less than `count`.
+    pub fn shard(mut self, index: usize, count: usize) -> ExecutorBuilder {
+        assert!(index < count, "The shard index should be less than the number of shards");
+        self.shard = Some((index, count));
+        self
+    }
+
+    /// Makes the executor run the tasks that are due at the same time in the order of their
+    /// deadlines, earliest first, instead of their priorities, which only order the tasks with
+    /// the same deadline. The tasks without a deadline run after the others. See
+    /// `TaskHandle::set_deadline`.
+    pub fn earliest_deadline_first(mut self, earliest_deadline_first: bool) -> ExecutorBuilder {
+        self.earliest_deadline_first = earliest_deadline_first;
+        self
+    }
+
+    /// Sets the timer slack of the executor, zero by default. The executions are delayed by up
+    /// to `slack`, to the next multiple of `slack` since the creation of the executor, so that
+    /// the tasks due within the same window of `slack` run together, after a single wakeup of
+    /// the executor thread. Useful to save wakeups and power when many tasks don't need a
+    /// precise timing. The delay is included in the lag of the executions. See also `coarse`.
+    pub fn timer_slack(mut self, slack: Duration) -> ExecutorBuilder {
+        self.timer_slack = slack;
+        self.coarse = false;
+        self
+    }
+
+    /// Enables the coarse mode, for devices running many background schedules on battery. Like
+    /// with `timer_slack`, the executions are delayed to the next multiple of `granularity`,
+    /// but the multiples are aligned to the system clock, since the Unix epoch: with a
+    /// granularity of one second, the tasks run at the start of each second of the system
+    /// clock, so that the executors of all the processes using the same granularity wake up
+    /// together. The alignment is computed when the executor is created, and is not updated
+    /// when the system clock is adjusted afterwards.
+    pub fn coarse(mut self, granularity: Duration) -> ExecutorBuilder {
+        self.timer_slack = granularity;
+        self.coarse = true;
+        self
+    }
+
+    /// Enables the high resolution mode, for schedules that need a precision below the
+    /// millisecond resolution of the timers. Before each execution, the executor thread wakes
+    /// up `spin` earlier than planned, and waits for the rest of the time in a busy loop. The
+    /// busy loop blocks the executor thread and keeps a core busy, for up to `spin` before
+    /// each execution, so `spin` should be as short as possible, while longer than the
+    /// inaccuracy of the timers: a couple of milliseconds is usually enough. The accuracy of
+    /// the executions can be checked with the `mean_lag` of `TaskHandle::stats`.
+    pub fn high_resolution(mut self, spin: Duration) -> ExecutorBuilder {
+        self.spin = spin;
+        self
+    }
+
+    /// Sets the number of threads running the functions scheduled with `schedule_blocking_with`,
+    /// 4 by default. The threads are started the first time a blocking function is scheduled.
+    ///
+    /// Panics if `threads` is zero.
+    pub fn blocking_threads(mut self, threads: usize) -> ExecutorBuilder {
+        assert!(threads > 0, "at least one blocking thread is required");
+        self.blocking_threads = threads;
+        self
+    }
+
+    /// Sets the size of the stack of the executor thread, and of the blocking threads, in bytes.
+    /// Defaults to the default of the standard library, see `std::thread::Builder::stack_size`.
+    pub fn stack_size(mut self, size: usize) -> ExecutorBuilder {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Customizes the executor thread: `configure` is called with the `thread::Builder` of the
+    /// thread, with the name and stack size already set, before the thread is started by
+    /// `build`.
+    ///
+    /// ```rust,no_run
+    /// # use scheduled_executor::ExecutorBuilder;
+    /// let executor = ExecutorBuilder::new()
+    ///     .thread_builder(|builder| builder.stack_size(32 * 1024 * 1024))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn thread_builder<F>(mut self, configure: F) -> ExecutorBuilder
+        where F: Fn(thread::Builder) -> thread::Builder + Send + Sync + 'static
+    {
+        self.configure_thread = Some(Arc::new(configure));
+        self
+    }
+
+    /// Pins the executor thread to the given CPU cores: the thread only runs on them. `build`
+    /// fails with `ScheduleError::ThreadConfig` if the thread can't be pinned, for example
+    /// because a core doesn't exist. Only applies to executors started with `build`. Requires
+    /// the `affinity` feature, and is only available on Linux. See the
+    /// [`affinity`](../affinity/index.html) module.
+    #[cfg(all(target_os = "linux", feature = "affinity"))]
+    pub fn core_affinity(mut self, cores: &[usize]) -> ExecutorBuilder {
+        let cores = cores.to_vec();
+        self.thread_setup.push(Arc::new(move || affinity::pin_current_thread(&cores)));
+        self
+    }
+
+    /// Sets the scheduling priority of the executor thread, for example the `SCHED_FIFO`
+    /// real-time policy. `build` fails with `ScheduleError::ThreadConfig` if the priority can't
+    /// be set, for example without the required capabilities. Only applies to executors started
+    /// with `build`. Requires the `priority` feature, and is only available on Linux. See the
+    /// [`priority`](../priority/index.html) module.
+    #[cfg(all(target_os = "linux", feature = "priority"))]
+    pub fn thread_priority(mut self, priority: ThreadPriority) -> ExecutorBuilder {
+        self.thread_setup.push(Arc::new(move || priority::set_current_thread_priority(priority)));
+        self
+    }
+
+    /// Creates the executor, starting its thread.
+    pub fn build(self) -> Result<CoreExecutor, ScheduleError> {
+        let (termination_tx, mut termination_rx) = channel();
+        let (core_tx, core_rx) = channel();
+        let (exit_tx, exit_rx) = channel();
+        let exited = Arc::new((Mutex::new(false), Condvar::new()));
+        let exit_guard = ExitGuard { sender: Some(exit_tx), exited: Arc::clone(&exited) };
+        let supervisor = if self.supervised {
+            Some(Arc::new(Supervisor { tasks: Mutex::new(Vec::new()), restarts: AtomicUsize::new(0) }))
+        } else {
+            None
+        };
+        let thread_supervisor = supervisor.clone();
+        let thread_setup = self.thread_setup.clone();
+        let mut builder = thread::Builder::new().name(self.name.clone());
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        if let Some(ref configure) = self.configure_thread {
+            builder = configure(builder);
+        }
+        let thread_handle = builder
+            .spawn(move || {
+                let _exit_guard = exit_guard;
+                debug!("Core starting");
+                if let Err(e) = thread_setup.iter().try_for_each(|setup| setup()) {
+                    let _ = core_tx.send(Err(ScheduleError::ThreadConfig(e)));
+                    return;
+                }
+                let mut core = match Core::new() {
+                    Ok(core) => core,
+                    Err(e) => {
+                        let _ = core_tx.send(Err(ScheduleError::Reactor(e)));
+                        return;
+                    }
+                };
+                let remote = Arc::new(RwLock::new(core.remote()));
+                let _ = core_tx.send(Ok(Arc::clone(&remote)));
+                while let Some(payload) = run_core(&mut core, &mut termination_rx, thread_supervisor.is_some()) {
+                    error!("Event loop panicked: {}, restarting it", panic_message(&*payload));
+                    core = match Core::new() {
+                        Ok(core) => core,
+                        Err(e) => {
+                            error!("Failed to restart the event loop: {}", e);
+                            return;
+                        }
+                    };
+                    if let Some(ref supervisor) = thread_supervisor {
+                        supervisor.restart(&remote, &core.handle());
+                    }
+                }
+            })
+            .map_err(ScheduleError::Thread)?;
+        let remote = match core_rx.wait() {
+            Ok(Ok(remote)) => remote,
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err(ScheduleError::Disconnected),
+        };
+        Ok(self.executor(remote, supervisor, termination_tx, Some(thread_handle), exit_rx, exited))
+    }
+
+    /// Creates the executor on an existing event loop, instead of starting a new thread. The
+    /// tasks are executed by the thread running the event loop, and they stop running when the
+    /// executor is stopped or dropped (depending on the drop behavior), or when the event loop
+    /// terminates. Note that waiting for the executor to stop from the thread running the event
+    /// loop, for example with `stop_with_timeout`, will block the event loop.
+    pub fn build_on(self, remote: Remote) -> CoreExecutor {
+        let (termination_tx, termination_rx) = channel::<()>();
+        let (exit_tx, exit_rx) = channel();
+        let exited = Arc::new((Mutex::new(false), Condvar::new()));
+        let exit_guard = ExitGuard { sender: Some(exit_tx), exited: Arc::clone(&exited) };
+        remote.spawn(move |_handle| {
+            termination_rx.then(move |_| {
+                debug!("Executor terminated");
+                drop(exit_guard);
+                Ok(())
+            })
+        });
+        self.executor(Arc::new(RwLock::new(remote)), None, termination_tx, None, exit_rx, exited)
+    }
+
+    /// Runs the executor on the calling thread, blocking until the executor is stopped. The tasks
+    /// are scheduled by `setup`, which also receives the handle of the event loop, to schedule
+    /// tasks that are not `Send` with `CoreExecutor::schedule_local`. The executor can be stopped
+    /// by one of its tasks, or from another thread through a clone or a `Shutdown` token.
+    pub fn run_on_current_thread<F>(self, setup: F) -> Result<(), ScheduleError>
+        where F: FnOnce(&CoreExecutor, &Handle)
+    {
+        let mut core = Core::new().map_err(ScheduleError::Reactor)?;
+        let executor = self.build_on(core.remote());
+        setup(&executor, &core.handle());
+        let exit = executor.inner.exit_receiver.clone();
+        let _ = core.run(exit);
+        debug!("Executor on current thread terminated");
+        Ok(())
+    }
+
+    fn executor(self, remote: Arc<RwLock<Remote>>, supervisor: Option<Arc<Supervisor>>, termination_sender: Sender<()>,
+                thread_handle: Option<JoinHandle<()>>, exit_receiver: Receiver<()>,
+                exited: Arc<(Mutex<bool>, Condvar)>) -> CoreExecutor {
+        let mut settings = Settings::new(self.clock);
+        if self.timer_wheel {
+            settings.wheel = Some(Arc::new(TimerWheel::new()));
+        }
+        settings.timer_slack = self.timer_slack;
+        if self.coarse && self.timer_slack > Duration::from_secs(0) {
+            // Moves the origin back to the last multiple of the granularity since the epoch
+            let since_epoch = settings.clock.system_time().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let offset = (since_epoch.as_nanos() % self.timer_slack.as_nanos()) as u64;
+            settings.origin = settings.origin.checked_sub(Duration::from_nanos(offset)).unwrap_or(settings.origin);
+        }
+        settings.spin = self.spin;
+        settings.earliest_deadline_first.store(self.earliest_deadline_first, Ordering::Relaxed);
+        let inner = CoreExecutorInner {
+            remote,
+            supervisor,
+            thread_name: self.name,
+            drop_behavior: self.drop_behavior,
+            shard: self.shard,
+            settings: Arc::new(settings),
+            termination_sender: Mutex::new(Some(termination_sender)),
+            thread_handle: Mutex::new(thread_handle),
+            exit_receiver: exit_receiver.shared(),
+            exited,
+            tasks: Mutex::new(Vec::new()),
+            config_tasks: Mutex::new(HashMap::new()),
+            watchdog: Mutex::new(None),
+            clock_monitor: Mutex::new(ClockMonitor::default()),
+            blocking_threads: self.blocking_threads,
+            stack_size: self.stack_size,
+            blocking_pool: OnceLock::new(),
+        };
+        let executor = CoreExecutor {
+            inner: Arc::new(inner)
+        };
+        debug!("Executor created");
+        executor
+    }
+}
+
+/// A `CoreExecutor` is the most simple executor provided. It runs a single thread, which is
+/// responsible for both scheduling the function (registering the timer for the wakeup),
+/// and the actual execution. By default, the executor will stop once dropped (see
+/// `DropBehavior`). The `CoreExecutor` can be cloned to generate a new reference to the same underlying executor.
+/// Given the single threaded nature of this executor, tasks are executed sequentially, and a long
+/// running task will cause delay in other subsequent executions. The tasks that are due at the
+/// same time run in the order of their priorities (see `TaskHandle::set_priority`). Instead of
+/// starting its own thread, the executor can also run on an existing event loop (see
+/// `with_handle`).
+///
+/// Panics in the scheduled functions are caught, and handled according to the executor's
+/// `PanicPolicy`.
+pub struct CoreExecutor {
+    inner: Arc<CoreExecutorInner>
+}
+
+impl Clone for CoreExecutor {
+    fn clone(&self) -> Self {
+        CoreExecutor { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl CoreExecutor {
+    /// Creates a new `CoreExecutor`.
+    pub fn new() -> Result<CoreExecutor, ScheduleError> {
+        CoreExecutor::with_name("core_executor")
+    }
+
+    /// Creates a new `CoreExecutor` with the specified thread name. Use `ExecutorBuilder` for
+    /// more configuration options.
+    pub fn with_name(thread_name: &str) -> Result<CoreExecutor, ScheduleError> {
+        ExecutorBuilder::new().name(thread_name).build()
+    }
+
+    /// Creates a new `CoreExecutor` running on an existing event loop, instead of starting a new
+    /// thread. See `ExecutorBuilder::build_on`.
+    pub fn with_remote(remote: Remote) -> CoreExecutor {
+        ExecutorBuilder::new().build_on(remote)
+    }
+
+    /// Runs a new `CoreExecutor` on the calling thread, blocking until it's stopped. See
+    /// `ExecutorBuilder::run_on_current_thread`.
+    ///
+    /// ```rust,no_run
+    /// # use scheduled_executor::CoreExecutor;
+    /// # use std::time::Duration;
+    /// CoreExecutor::run_on_current_thread(|executor, _handle| {
+    ///     executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_secs(60), |_handle| {
+    ///         println!("Running");
+    ///     });
+    /// }).expect("Failed to start the executor");
+    /// ```
+    pub fn run_on_current_thread<F>(setup: F) -> Result<(), ScheduleError>
+        where F: FnOnce(&CoreExecutor, &Handle)
+    {
+        ExecutorBuilder::new().run_on_current_thread(setup)
+    }
+
+    /// Like `with_remote`, for the event loop of `handle`.
+    pub fn with_handle(handle: &Handle) -> CoreExecutor {
+        CoreExecutor::with_remote(handle.remote().clone())
+    }
+
+    /// Stops the executor, without waiting for the currently running task (if any) to complete.
+    /// No other task will be executed, and the returned future will complete once the executor
+    /// thread has terminated. Stopping affects all the clones of the executor. All the tasks are
+    /// stopped at once: use `drain` to stop them in the order of their shutdown phases.
+    pub fn stop(&self) -> StopFuture {
+        self.inner.terminate();
+        StopFuture { exits: vec![self.inner.exit_receiver.clone()] }
+    }
+
+    /// Stops the executor and waits up to `timeout` for the executor thread to terminate.
+    /// Returns false if the thread didn't terminate in time, for example because a task is stuck.
+    /// In that case the thread is detached, and dropping the executor won't wait for it.
+    pub fn stop_with_timeout(&self, timeout: Duration) -> bool {
+        self.inner.terminate();
+        let exited = self.inner.wait_exit(timeout);
+        if !exited {
+            warn!("Executor thread didn't terminate in {:?}, detaching it", timeout);
+            self.inner.thread_handle.lock().unwrap().take();
+        }
+        exited
+    }
+
+    /// Stops the executor like `stop`, but also cancels the futures returned by the executions of
+    /// the asynchronous tasks that are still in progress, which are dropped without being polled
+    /// again. This matters for executors running on an existing event loop, where the futures
+    /// would otherwise keep running, and for thread pools, where the functions that haven't
+    /// started yet won't be executed. A synchronous function that is currently running is never
+    /// interrupted.
+    pub fn stop_now(&self) -> StopFuture {
+        self.shutdown().stop_now()
+    }
+
+    /// Stops the executor once the executions in progress have completed. See `Shutdown::drain`.
+    pub fn drain(&self, timeout: Option<Duration>) -> bool {
+        self.shutdown().drain(timeout)
+    }
+
+    /// Runs `f` once on the executor thread, returning a future that resolves to its result. Since
+    /// the functions are executed by a single thread, this can be used to serialize the access to
+    /// resources owned by the executor thread. The future fails with `Canceled` if `f` panics, or
+    /// if the executor is stopped before `f` is executed.
+    pub fn execute<F, R>(&self, f: F) -> ExecuteFuture<R>
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: Send + 'static
+    {
+        let (sender, receiver) = channel();
+        self.remote().spawn(move |handle| {
+            match panic::catch_unwind(AssertUnwindSafe(|| f(handle))) {
+                Ok(result) => { let _ = sender.send(result); },
+                Err(payload) => error!("Executed function panicked: {}", panic_message(&*payload)),
+            }
+            Ok(())
+        });
+        ExecuteFuture { receiver }
+    }
+
+    /// Returns a token that can be used to stop the executor. Components that only schedule
+    /// tasks can be given a clone of the executor, while the shutdown token is kept by its owner.
+    /// The executor keeps running while the token exists, even if all the clones of the executor
+    /// are dropped.
+    pub fn shutdown(&self) -> Shutdown {
+        Shutdown { executors: vec![self.clone()] }
+    }
+
+    /// Sets the policy applied to tasks that panic. The policy applies to all the tasks of the
+    /// executor, including the ones that are already scheduled.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        *self.inner.settings.panic_policy.write().unwrap() = policy;
+    }
+
+    /// Sets a function that will be called with the panic message every time a task panics.
+    pub fn on_panic<F>(&self, handler: F)
+        where F: Fn(&str) + Send + Sync + 'static
+    {
+        *self.inner.settings.panic_handler.write().unwrap() = Some(Arc::new(handler));
+    }
+
+    /// Sets a function that will be called every time the timer of a task fails. The wait for
+    /// the next execution is retried with an exponential backoff, and the task is stopped after
+    /// too many consecutive failures. The other tasks are not affected.
+    pub fn on_schedule_error<F>(&self, handler: F)
+        where F: Fn(&TaskHandle, &ScheduleError) + Send + Sync + 'static
+    {
+        *self.inner.settings.error_handler.write().unwrap() = Some(Arc::new(handler));
+    }
+
+    /// Starts a watchdog, that will call `handler` every time an execution of a task has been
+    /// running for longer than `threshold`, for example because it's stuck or deadlocked. The
+    /// handler is called only once per execution, from the watchdog thread, and a warning
+    /// containing the name of the executor thread is logged. Calling this method again replaces
+    /// the threshold and the handler.
+    pub fn set_watchdog<F>(&self, threshold: Duration, handler: F) -> Result<(), ScheduleError>
+        where F: Fn(&TaskHandle, Duration) + Send + Sync + 'static
+    {
+        let mut watchdog = self.inner.watchdog.lock().unwrap();
+        let start = watchdog.is_none();
+        *watchdog = Some(Watchdog { threshold, handler: Arc::new(handler) });
+        if start {
+            let executor = Arc::downgrade(&self.inner);
+            thread::Builder::new()
+                .name(format!("{}_watchdog", self.inner.thread_name))
+                .spawn(move || watchdog_loop(executor))
+                .map_err(ScheduleError::Thread)?;
+        }
+        Ok(())
+    }
+
+    /// Starts monitoring the clock for gaps longer than `threshold`, when the executor couldn't
+    /// run, for example because the system was suspended. Depending on the platform, the
+    /// monotonic clock either stops during a suspend, silently shifting the schedules, or keeps
+    /// going, making the tasks catch up all the missed executions on resume: a gap is detected
+    /// in both cases, and `policy` defines how the tasks anchored to the monotonic clock recover
+    /// from it. The tasks anchored to the wall clock just follow the system clock. `handler` is
+    /// called with the duration of each gap, from the monitoring thread, after the policy has
+    /// been applied. Calling this method again replaces the threshold, the policy and the
+    /// handler.
+    ///
+    /// Since the system time keeps going during a suspend, a forward adjustment of the system
+    /// clock is also detected as a gap.
+    pub fn on_clock_gap<F>(&self, threshold: Duration, policy: GapPolicy, handler: F) -> Result<(), ScheduleError>
+        where F: Fn(Duration) + Send + Sync + 'static
+    {
+        self.monitor_clock(|monitor| monitor.gap = Some(GapMonitor { threshold, policy, handler: Arc::new(handler) }))
+    }
+
+    /// Starts monitoring the system clock for jumps of at least `threshold`, compared to the
+    /// monotonic clock, for example when the system clock is stepped by NTP or changed
+    /// manually. `handler` is called with the direction and the size of each jump, from the
+    /// monitoring thread, once the tasks anchored to the wall clock have been woken up to
+    /// recompute their next execution. Calling this method again replaces the threshold and
+    /// the handler.
+    ///
+    /// On the platforms where the monotonic clock stops during a suspend, resuming the system is
+    /// also detected as a forward jump. See `on_clock_gap`.
+    pub fn on_clock_jump<F>(&self, threshold: Duration, handler: F) -> Result<(), ScheduleError>
+        where F: Fn(ClockJump) + Send + Sync + 'static
+    {
+        self.monitor_clock(|monitor| monitor.jump = Some(JumpMonitor { threshold, handler: Arc::new(handler) }))
+    }
+
+    /// Updates the clock monitoring of the executor, starting its thread if needed.
+    fn monitor_clock<F: FnOnce(&mut ClockMonitor)>(&self, update: F) -> Result<(), ScheduleError> {
+        let mut monitor = self.inner.clock_monitor.lock().unwrap();
+        let start = monitor.gap.is_none() && monitor.jump.is_none();
+        update(&mut monitor);
+        if start {
+            let executor = Arc::downgrade(&self.inner);
+            thread::Builder::new()
+                .name(format!("{}_clock", self.inner.thread_name))
+                .spawn(move || clock_monitor_loop(executor))
+                .map_err(ScheduleError::Thread)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a listener, that will be notified about the executions of all the tasks of the
+    /// executor. Listeners are called on the thread running the task, so they should be fast.
+    pub fn add_listener<L: TaskListener>(&self, listener: L) {
+        self.inner.settings.listeners.write().unwrap().push(Arc::new(listener));
+    }
+
+    /// Adds a listener, like `add_listener`, that is only notified about the tasks with the given
+    /// tag.
+    pub fn add_tagged_listener<L: TaskListener>(&self, tag: &str, listener: L) {
+        self.add_listener(TagFilter { tag: tag.to_owned(), listener });
+    }
+
+    fn new_task(&self, name: Option<&str>) -> Task {
+        self.new_tasks(iter::once(name)).pop().unwrap()
+    }
+
+    /// Creates the tasks with the given names, registering them at once.
+    fn new_tasks<'a, I: IntoIterator<Item=Option<&'a str>>>(&self, names: I) -> Vec<Task> {
+        let alive = self.is_alive();
+        let mut tasks = self.inner.tasks.lock().unwrap();
+        tasks.retain(|task| !task.stopped());
+        names.into_iter()
+            .map(|name| {
+                let handle = TaskHandle::new(name);
+                if name.is_some_and(|name| !self.in_shard(name)) {
+                    debug!("{} belongs to another shard, it won't be scheduled", handle);
+                    handle.stop();
+                } else if alive {
+                    tasks.push(handle.clone());
+                } else {
+                    error!("Executor {} is not running, {} won't be scheduled", self.inner.thread_name, handle);
+                    handle.stop();
+                }
+                Task {
+                    handle,
+                    settings: Arc::clone(&self.inner.settings),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns true if the task with the given name belongs to the shard of the executor, and
+    /// will run when scheduled. Always true for executors that are not sharded. See
+    /// `ExecutorBuilder::shard`.
+    pub fn in_shard(&self, name: &str) -> bool {
+        self.inner.shard.is_none_or(|(index, count)| coordination::shard_of(name, count) == index)
+    }
+
+    /// Returns true if the executor is running. An executor stops running when it's stopped, or
+    /// if its thread terminates unexpectedly, for example because a future spawned on its event
+    /// loop panicked. The tasks scheduled on an executor that is not running are never executed:
+    /// their handles are stopped right away, and `try_schedule_with` returns an error.
+    pub fn is_alive(&self) -> bool {
+        !*self.inner.exited.0.lock().unwrap() && !self.inner.settings.terminated.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times the event loop of a supervised executor has been restarted.
+    /// See `ExecutorBuilder::supervised`.
+    pub fn restarts(&self) -> usize {
+        self.inner.supervisor.as_ref().map_or(0, |supervisor| supervisor.restarts.load(Ordering::Relaxed))
+    }
+
+    /// Returns the health of the executor. A task is reported as overdue when its next execution
+    /// was planned more than `threshold` ago, for example because the executor thread is blocked
+    /// by another task.
+    pub fn health(&self, threshold: Duration) -> Health {
+        let now = self.now();
+        let tasks = self.tasks();
+        let overdue = tasks.iter()
+            .filter(|task| task.running_for().is_none())
+            .filter(|task| task.next_run().is_some_and(|next_run| next_run + threshold < now))
+            .cloned()
+            .collect();
+        Health {
+            alive: self.is_alive(),
+            tasks: tasks.len(),
+            last_tick: *self.inner.settings.last_tick.lock().unwrap(),
+            overdue,
+            restarts: self.restarts(),
+        }
+    }
+
+    /// Returns the current instant, according to the clock of the executor.
+    pub fn now(&self) -> Instant {
+        self.inner.settings.clock.now()
+    }
+
+    /// Returns the remote handle of the event loop of the executor, that can be used to spawn
+    /// arbitrary futures on the executor thread. For supervised executors, the remote handle is
+    /// replaced every time the event loop is restarted.
+    pub fn remote(&self) -> Remote {
+        self.inner.remote.read().unwrap().clone()
+    }
+
+    /// Runs `f` once on the executor thread, spawning the future it returns on the event loop.
+    /// Unlike the scheduled tasks, the future is not tracked by the executor.
+    pub fn spawn<F, R>(&self, f: F)
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: IntoFuture<Item=(), Error=()>,
+              R::Future: 'static
+    {
+        self.remote().spawn(f);
+    }
+
+    /// Returns the handles of all the tasks that are currently scheduled on the executor, that
+    /// is, that haven't been stopped and whose schedule hasn't completed.
+    pub fn tasks(&self) -> Vec<TaskHandle> {
+        let mut tasks = self.inner.tasks.lock().unwrap();
+        tasks.retain(|task| !task.stopped());
+        tasks.clone()
+    }
+
+    /// Returns the handle of the active task with the given name, if any. If multiple tasks have
+    /// the same name, the one scheduled first is returned.
+    pub fn task(&self, name: &str) -> Option<TaskHandle> {
+        self.tasks().into_iter().find(|task| task.name() == Some(name))
+    }
+
+    /// Stops all the active tasks with the given tag, for instance to remove all the schedules
+    /// of a tenant at once, and returns their handles. As with `TaskHandle::stop`, running
+    /// executions are not interrupted.
+    ///
+    /// ```rust,no_run
+    /// # use scheduled_executor::CoreExecutor;
+    /// # use scheduled_executor::schedule::FixedRate;
+    /// # use std::time::Duration;
+    /// let executor = CoreExecutor::new().unwrap();
+    /// let every_minute = FixedRate::new(Duration::from_secs(0), Duration::from_secs(60));
+    /// executor.schedule_with(every_minute, |_handle| println!("Report")).add_tag("tenant-42");
+    /// // Later, when the tenant is removed
+    /// executor.cancel_tagged("tenant-42");
+    /// ```
+    pub fn cancel_tagged(&self, tag: &str) -> Vec<TaskHandle> {
+        let tagged = self.tasks_with_tag(tag);
+        for task in &tagged {
+            task.stop();
+        }
+        tagged
+    }
+
+    /// Returns the handles of the active tasks with the given tag, in the order in which they
+    /// were scheduled.
+    pub fn tasks_with_tag(&self, tag: &str) -> Vec<TaskHandle> {
+        self.tasks().into_iter().filter(|task| task.has_tag(tag)).collect()
+    }
+
+    /// Pauses all the active tasks with the given tag, and returns their handles. See
+    /// `TaskHandle::pause`.
+    pub fn pause_tagged(&self, tag: &str) -> Vec<TaskHandle> {
+        let tagged = self.tasks_with_tag(tag);
+        for task in &tagged {
+            task.pause();
+        }
+        tagged
+    }
+
+    /// Resumes all the active tasks with the given tag, and returns their handles.
+    pub fn resume_tagged(&self, tag: &str) -> Vec<TaskHandle> {
+        let tagged = self.tasks_with_tag(tag);
+        for task in &tagged {
+            task.resume();
+        }
+        tagged
+    }
+
+    fn spawn_task<S, F>(&self, task: Task, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        let task_handle = task.handle.clone();
+        if let Some(ref supervisor) = self.inner.supervisor {
+            let schedule = Arc::new(Mutex::new(schedule));
+            let scheduled_fn = Arc::new(Mutex::new(scheduled_fn));
+            let restartable = task.clone();
+            supervisor.register(&self.inner.remote, task, Arc::new(move |handle| {
+                let scheduled_fn = Arc::clone(&scheduled_fn);
+                let now = restartable.now();
+                schedule_loop(SharedSchedule(Arc::clone(&schedule)), move |handle| lock(&scheduled_fn)(handle),
+                              now, handle, restartable.clone());
+            }));
+            return task_handle;
+        }
+        self.remote().spawn(move |handle| {
+            let now = task.now();
+            schedule_loop(schedule, scheduled_fn, now, handle, task);
+            Ok::<(), ()>(())
+        });
+        task_handle
+    }
+
+    /// Spawns the tasks on the event loop at once, instead of one at a time. Supervised
+    /// executors still register them one at a time.
+    fn spawn_batch(&self, batch: Vec<PendingTask>) {
+        if batch.is_empty() {
+            return;
+        }
+        if self.inner.supervisor.is_some() {
+            for (task, schedule, scheduled_fn) in batch {
+                self.spawn_task(task, schedule, scheduled_fn);
+            }
+            return;
+        }
+        self.remote().spawn(move |handle| {
+            for (task, schedule, scheduled_fn) in batch {
+                let now = task.now();
+                schedule_loop(schedule, scheduled_fn, now, handle, task);
+            }
+            Ok::<(), ()>(())
+        });
+    }
+
+    /// Schedules all the given tasks. Unlike calling `schedule_with` for each of them, the tasks
+    /// are sent to the executor thread at once, which is faster when scheduling many tasks. The
+    /// handles are returned in the same order as the tasks.
+    ///
+    /// ```rust,no_run
+    /// # use scheduled_executor::{CoreExecutor, TaskSpec};
+    /// # use scheduled_executor::schedule::FixedRate;
+    /// # use std::time::Duration;
+    /// let executor = CoreExecutor::new().unwrap();
+    /// let handles = executor.schedule_all((0..100).map(|i| {
+    ///     let schedule = FixedRate::new(Duration::from_secs(0), Duration::from_secs(10));
+    ///     TaskSpec::new(schedule, move |_handle| println!("Task {}", i)).named(&format!("task {}", i))
+    /// }));
+    /// ```
+    pub fn schedule_all<I: IntoIterator<Item=TaskSpec>>(&self, specs: I) -> Vec<TaskHandle> {
+        let specs = specs.into_iter().collect::<Vec<_>>();
+        let tasks = self.new_tasks(specs.iter().map(|spec| spec.name.as_deref()));
+        for (task, spec) in tasks.iter().zip(&specs) {
+            for tag in &spec.tags {
+                task.handle.add_tag(tag);
+            }
+        }
+        let handles = tasks.iter().map(|task| task.handle.clone()).collect();
+        let batch = tasks.into_iter().zip(specs)
+            .filter(|(task, _)| !task.handle.stopped())
+            .map(|(task, spec)| (task, spec.schedule, spec.scheduled_fn))
+            .collect();
+        self.spawn_batch(batch);
+        handles
+    }
+
+    /// Schedule a function for running according to the given `schedule`, like `schedule_with`,
+    /// but the function doesn't have to be `Send`, for example when it captures an `Rc`. The
+    /// `handle` is used to spawn the task directly on the event loop of the executor, so this
+    /// method can only be called from the thread running it: from a task, or, for executors
+    /// running on an existing event loop, from the thread running that event loop.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `handle` doesn't belong to the event loop of the executor.
+    pub fn schedule_local<S, F>(&self, handle: &Handle, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + 'static
+    {
+        assert!(handle.id() == self.remote().id(), "The handle doesn't belong to the event loop of the executor");
+        let task = self.new_task(None);
+        let task_handle = task.handle.clone();
+        let now = task.now();
+        schedule_loop(schedule, scheduled_fn, now, handle, task);
+        task_handle
+    }
+
+    fn spawn_async_task<S, F, R>(&self, task: Task, schedule: S, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) -> R + Send + 'static,
+              R: Future<Item=(), Error=()> + 'static
+    {
+        let task_handle = task.handle.clone();
+        if let Some(ref supervisor) = self.inner.supervisor {
+            let schedule = Arc::new(Mutex::new(schedule));
+            let scheduled_fn = Arc::new(Mutex::new(scheduled_fn));
+            let restartable = task.clone();
+            supervisor.register(&self.inner.remote, task, Arc::new(move |handle| {
+                let scheduled_fn = Arc::clone(&scheduled_fn);
+                let now = restartable.now();
+                async_schedule_loop(SharedSchedule(Arc::clone(&schedule)), move |handle| lock(&scheduled_fn)(handle),
+                                    policy, now, handle, restartable.clone());
+            }));
+            return task_handle;
+        }
+        self.remote().spawn(move |handle| {
+            let now = task.now();
+            async_schedule_loop(schedule, scheduled_fn, policy, now, handle, task);
+            Ok::<(), ()>(())
+        });
+        task_handle
+    }
+
+    /// Schedule a function for running according to the given `schedule`. See the
+    /// [`schedule`](../schedule/index.html) module for the available schedules.
+    /// The function is only ever called by the executor thread, so it can mutate its own state
+    /// without any synchronization.
+    pub fn schedule_with<S, F>(&self, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        self.spawn_task(self.new_task(None), schedule, scheduled_fn)
+    }
+
+    /// Like `schedule_with`, but the executions that are due while the previous execution is
+    /// still running are skipped, instead of being run late: a slow execution doesn't cause a
+    /// burst of delayed executions with schedules like `FixedRate`. The skipped executions are
+    /// counted by `TaskHandle::skipped`.
+    pub fn schedule_skipping_with<S, F>(&self, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        let task = self.new_task(None);
+        task.handle.state.skip_missed.store(true, Ordering::Relaxed);
+        self.spawn_task(task, schedule, scheduled_fn)
+    }
+
+    /// Like `schedule_with`, but returns an error if the executor is not running, instead of a
+    /// stopped handle. See `is_alive`.
+    pub fn try_schedule_with<S, F>(&self, schedule: S, scheduled_fn: F) -> Result<TaskHandle, ScheduleError>
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        if !self.is_alive() {
+            return Err(ScheduleError::Disconnected);
+        }
+        Ok(self.schedule_with(schedule, scheduled_fn))
+    }
+
+    /// Like `schedule_with`, but the task is given a `name`, that can be used to look it up
+    /// with `task`.
+    pub fn schedule_with_named<S, F>(&self, name: &str, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        self.spawn_task(self.new_task(Some(name)), schedule, scheduled_fn)
+    }
+
+    /// Like `schedule_with_named`, but the successful executions of the task are recorded with
+    /// `persistence`, and the task runs immediately if it missed a run while the process wasn't
+    /// running. See the [`persist`] module.
+    ///
+    /// [`persist`]: ../persist/index.html
+    pub fn schedule_persistent<S, F>(&self, name: &str, schedule: S, persistence: &Persistence, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with_named(name, persistence.schedule(name, schedule), persistence.record(name, scheduled_fn))
+    }
+
+    /// Like `schedule_with_named`, but when multiple instances of the service run the task, each
+    /// execution happens only on the instance claiming it from `coordination`. See the
+    /// [`coordination`] module.
+    ///
+    /// [`coordination`]: ../coordination/index.html
+    pub fn schedule_coordinated<S, F>(&self, name: &str, schedule: S, coordination: &Coordination, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with_named(name, schedule, coordination.wrap(name, scheduled_fn))
+    }
+
+    /// Like `schedule_with`, but the task is cluster-wide: its executions only happen while
+    /// this instance is the leader according to `leadership`, and are skipped otherwise. See
+    /// the [`leadership`] module.
+    ///
+    /// [`leadership`]: ../leadership/index.html
+    pub fn schedule_cluster_wide<S, F>(&self, schedule: S, leadership: &Leadership, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with(schedule, leadership.wrap(scheduled_fn))
+    }
+
+    /// Schedule a fallible function for running according to the given `schedule`. When the
+    /// function returns an error, `on_error` is called with the error, and the `policy` defines
+    /// whether the task keeps running.
+    pub fn schedule_fallible<S, F, E, H>(&self, schedule: S, policy: ErrorPolicy, mut scheduled_fn: F, mut on_error: H) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) -> Result<(), E> + Send + 'static,
+              E: fmt::Debug,
+              H: FnMut(&TaskHandle, &E) + Send + 'static
+    {
+        let task = self.new_task(None);
+        let task_handle = task.handle.clone();
+        self.spawn_task(task, schedule, move |handle| {
+            let error = match scheduled_fn(handle) {
+                Ok(()) => return,
+                Err(error) => error,
+            };
+            warn!("{} failed: {:?}", task_handle, error);
+            on_error(&task_handle, &error);
+            match policy {
+                ErrorPolicy::Continue => {},
+                ErrorPolicy::Stop => task_handle.stop(),
+                // Unwinds to the executor, without invoking the panic hook
+                ErrorPolicy::Escalate => panic::resume_unwind(Box::new(format!("{:?}", error))),
+            }
+        })
+    }
+
+    /// Schedule a function for running at fixed intervals. The executor will try to run the
+    /// function every `interval`, but if one execution takes longer than `interval` it will delay
+    /// all the subsequent calls.
+    pub fn schedule_fixed_interval<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with(FixedInterval::new(initial, interval), scheduled_fn)
+    }
+
+    /// Schedule a function for running at fixed rate. The executor will try to run the function
+    /// every `interval`, and if a task execution takes longer than `interval`, the wait time
+    /// between task will be reduced to decrease the overall delay. To skip the missed executions
+    /// instead, use `schedule_with` with `FixedRate::missed_tick_behavior`.
+    pub fn schedule_fixed_rate<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with(FixedRate::new(initial, interval), scheduled_fn)
+    }
+
+    /// Like `schedule_fixed_interval`, but the task is given a `name`. See `schedule_with_named`.
+    pub fn schedule_fixed_interval_named<F>(&self, name: &str, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with_named(name, FixedInterval::new(initial, interval), scheduled_fn)
+    }
+
+    /// Like `schedule_fixed_rate`, but the task is given a `name`. See `schedule_with_named`.
+    pub fn schedule_fixed_rate_named<F>(&self, name: &str, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with_named(name, FixedRate::new(initial, interval), scheduled_fn)
+    }
+
+    /// Schedule a function for running after each of the given delays. The first delay is
+    /// measured from now, and every following one from the completion of the previous execution.
+    /// The task stops once the delays are exhausted.
+    pub fn schedule_with_delays<I, F>(&self, delays: I, scheduled_fn: F) -> TaskHandle
+        where I: IntoIterator<Item=Duration>,
+              I::IntoIter: Send + 'static,
+              F: FnMut(&Handle) + Send + 'static
+    {
+        self.schedule_with(Delays::new(delays), scheduled_fn)
+    }
+
+    /// Schedule a function for running once, after the specified `delay`. The returned handle can
+    /// be used to prevent the execution, if the function hasn't been executed yet.
+    pub fn schedule_once<F>(&self, delay: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Handle) + Send + 'static
+    {
+        self.schedule_at(self.now() + delay, scheduled_fn)
+    }
+
+    /// Schedule a function for running once at the specified `instant`. If the instant is already
+    /// in the past, the function will be executed immediately.
+    pub fn schedule_at<F>(&self, instant: Instant, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Handle) + Send + 'static
+    {
+        self.schedule_once_anchored(instant, Anchor::Monotonic, scheduled_fn)
+    }
+
+    fn schedule_once_anchored<F>(&self, instant: Instant, anchor: Anchor, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Handle) + Send + 'static
+    {
+        let mut scheduled_fn = Some(scheduled_fn);
+        self.schedule_with(
+            Once(Some(instant)).anchored(anchor),
+            move |handle| {
+                if let Some(scheduled_fn) = scheduled_fn.take() {
+                    scheduled_fn(handle);
+                }
+            }
+        )
+    }
+
+    /// Schedule a function for running once at the specified system time, for example at a
+    /// given date and time. If the time is already in the past, the function will be executed
+    /// immediately. The execution is anchored to the wall clock: if the system clock is
+    /// adjusted in the meantime, the function still runs when the system clock reaches `time`
+    /// (see `schedule::Anchor`). See the [`calendar`] module for recurring wall-clock
+    /// schedules.
+    ///
+    /// [`calendar`]: ../schedule/calendar/index.html
+    pub fn schedule_at_system_time<F>(&self, time: SystemTime, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Handle) + Send + 'static
+    {
+        let instant = self.now() + delay_until(&*self.inner.settings.clock, time);
+        self.schedule_once_anchored(instant, Anchor::WallClock, scheduled_fn)
+    }
+
+    /// Schedule a function for running according to a cron expression, such as
+    /// `"0 */5 * * * *"`. See the [`cron`](../schedule/cron/index.html) module for the supported
+    /// syntax. Returns an error if the expression can't be parsed.
+    pub fn schedule_cron<F>(&self, expression: &str, scheduled_fn: F) -> Result<TaskHandle, ParseError>
+        where F: FnMut(&Handle) + Send + 'static
+    {
+        let cron = CronSchedule::parse(expression)?;
+        Ok(self.schedule_with(cron, scheduled_fn))
+    }
+
+    /// Schedules the enabled tasks of `config`, each running the job with the same name. See the
+    /// [`config`](../config/index.html) module. Nothing is scheduled if any of the tasks has an
+    /// invalid schedule or no matching job. Tasks already scheduled from a configuration with the
+    /// same name are updated in place, as in `apply_config`.
+    pub fn schedule_config(&self, config: &Config, jobs: &Jobs) -> Result<Vec<TaskHandle>, ConfigError> {
+        let tasks = config::resolve(config, jobs)?;
+        let mut running = self.inner.config_tasks.lock().unwrap();
+        let mut batch = Vec::new();
+        let handles = tasks.into_iter()
+            .map(|task| {
+                let name = task.name.clone();
+                self.apply_config_task(&mut running, task, &mut batch);
+                running[&name].handle.clone()
+            })
+            .collect();
+        self.spawn_batch(batch);
+        Ok(handles)
+    }
+
+    /// Makes the tasks scheduled from a configuration match `config`, without restarting the
+    /// ones that didn't change: new tasks are scheduled, tasks that have been removed or
+    /// disabled are stopped, and tasks whose schedule has changed are moved to the new schedule,
+    /// keeping their handle and statistics. The jobs of all the tasks are replaced with the ones
+    /// in `jobs`. Nothing is changed if the configuration can't be scheduled.
+    pub fn apply_config(&self, config: &Config, jobs: &Jobs) -> Result<ConfigChanges, ConfigError> {
+        let tasks = config::resolve(config, jobs)?;
+        let mut running = self.inner.config_tasks.lock().unwrap();
+        let mut changes = ConfigChanges::default();
+        let mut names = running.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            if !tasks.iter().any(|task| task.name == name) {
+                let task = running.remove(&name).unwrap();
+                if !task.handle.stopped() {
+                    task.handle.stop();
+                    changes.removed.push(name);
+                }
+            }
+        }
+        let mut batch = Vec::new();
+        for task in tasks {
+            let name = task.name.clone();
+            match self.apply_config_task(&mut running, task, &mut batch) {
+                Some(true) => changes.updated.push(name),
+                Some(false) => {},
+                None => changes.added.push(name),
+            }
+        }
+        self.spawn_batch(batch);
+        if !changes.is_empty() {
+            debug!("Configuration applied: {:?}", changes);
+        }
+        Ok(changes)
+    }
+
+    /// Schedules the jobs defined in `store`, binding them by name to the functions in `jobs` as
+    /// for `schedule_config`, and records their executions and outcomes in the store. See the
+    /// [`store`](../store/index.html) module.
+    pub fn schedule_store<T: JobStore>(&self, store: T, jobs: &Jobs) -> Result<Vec<TaskHandle>, ConfigError> {
+        let handles = self.schedule_config(&store.load_jobs()?, jobs)?;
+        self.add_listener(StoreListener::new(Arc::new(store), &handles));
+        Ok(handles)
+    }
+
+    /// Schedules the functions marked with the `#[scheduled]` attribute. See the
+    /// [`annotated`](../annotated/index.html) module. Nothing is scheduled if any of the
+    /// functions has an invalid schedule.
+    pub fn register_annotated<I>(&self, functions: I) -> Result<Vec<TaskHandle>, ConfigError>
+        where I: IntoIterator<Item=Scheduled>
+    {
+        let (config, jobs) = annotated::to_config(functions);
+        self.schedule_config(&config, &jobs)
+    }
+
+    /// Updates the running task with the name of `task`, or schedules it if there is none,
+    /// adding it to `batch`. Returns whether the schedule was updated, or `None` if the task was
+    /// scheduled.
+    fn apply_config_task(&self, running: &mut HashMap<String, RunningTask>, task: ResolvedTask,
+                         batch: &mut Vec<PendingTask>) -> Option<bool> {
+        if let Some(existing) = running.get_mut(&task.name) {
+            if !existing.handle.stopped() {
+                return Some(existing.update(task));
+            }
+            if !self.in_shard(&task.name) {
+                return Some(false);
+            }
+        }
+        let name = task.name.clone();
+        let task = RunningTask::new(task, |schedule, scheduled_fn| {
+            let task = self.new_task(Some(&name));
+            let handle = task.handle.clone();
+            if !handle.stopped() {
+                batch.push((task, Box::new(schedule), scheduled_fn));
+            }
+            handle
+        });
+        running.insert(name, task);
+        None
+    }
+
+    /// Schedule a function returning a future for running according to the given `schedule`.
+    /// An execution is considered complete once the returned future has completed, and the
+    /// `policy` defines what happens if an execution is due before the previous one is complete.
+    ///
+    /// Stopping the task cancels the executions in progress: their futures are dropped as soon as
+    /// the task is stopped, and they're never polled again. The futures are also dropped when the
+    /// executor thread terminates, or when the executor is stopped with `stop_now`.
+    pub fn schedule_async_with<S, F, R>(&self, schedule: S, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) -> R + Send + 'static,
+              R: Future<Item=(), Error=()> + 'static
+    {
+        self.spawn_async_task(self.new_task(None), schedule, policy, scheduled_fn)
+    }
+
+    /// Like `schedule_async_with`, but `on_cancel` is called every time the future of an
+    /// execution is dropped before completing, so that the resources it holds can be cleaned up.
+    /// The handler is called on the executor thread, right after the future is dropped.
+    pub fn schedule_async_with_cancel<S, F, R, H>(&self, schedule: S, policy: OverlapPolicy, scheduled_fn: F, on_cancel: H) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) -> R + Send + 'static,
+              R: Future<Item=(), Error=()> + 'static,
+              H: Fn(&TaskHandle) + Send + Sync + 'static
+    {
+        let task = self.new_task(None);
+        *task.handle.state.cancel_handler.lock().unwrap() = Some(Arc::new(on_cancel));
+        self.spawn_async_task(task, schedule, policy, scheduled_fn)
+    }
+
+    /// Schedule a function returning a future for running at fixed rate. See
+    /// `schedule_fixed_rate` and `schedule_async_with`.
+    pub fn schedule_fixed_rate_async<F, R>(&self, initial: Duration, interval: Duration, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) -> R + Send + 'static,
+              R: Future<Item=(), Error=()> + 'static
+    {
+        self.schedule_async_with(FixedRate::new(initial, interval), policy, scheduled_fn)
+    }
+
+    /// Schedule a function returning a future for running at fixed intervals. See
+    /// `schedule_fixed_interval` and `schedule_async_with`.
+    pub fn schedule_fixed_interval_async<F, R>(&self, initial: Duration, interval: Duration, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) -> R + Send + 'static,
+              R: Future<Item=(), Error=()> + 'static
+    {
+        self.schedule_async_with(FixedInterval::new(initial, interval), policy, scheduled_fn)
+    }
+
+    /// Schedule a function for running according to the given `schedule`, like `schedule_with`,
+    /// but the function runs on a pool of threads dedicated to blocking functions instead of the
+    /// executor thread, so that file I/O or synchronous calls to a database don't delay the
+    /// other tasks. The executions of the function never overlap: the next one is computed
+    /// once the previous one has completed. See also `ExecutorBuilder::blocking_threads`.
+    ///
+    /// ```rust,no_run
+    /// # use scheduled_executor::CoreExecutor;
+    /// # use std::fs;
+    /// # use std::time::Duration;
+    /// let executor = CoreExecutor::new().unwrap();
+    /// executor.schedule_fixed_rate_blocking(Duration::from_secs(0), Duration::from_secs(60), |_remote| {
+    ///     let _ = fs::write("/tmp/heartbeat", "alive");
+    /// });
+    /// ```
+    pub fn schedule_blocking_with<S, F>(&self, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_on_workers(schedule, Workers::Pool(self.blocking_pool().clone()), scheduled_fn)
+    }
+
+    /// Schedule a function for running according to the given `schedule` on an external pool,
+    /// such as a rayon `ThreadPool`. As with `schedule_blocking_with`, the executions never
+    /// overlap, and the next one is computed once the previous one has completed on the pool.
+    /// See the [`compute`](../compute/index.html) module.
+    pub fn schedule_on_pool<S, P, F>(&self, schedule: S, pool: P, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              P: ComputePool,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_on_workers(schedule, Workers::Compute(Arc::new(pool)), scheduled_fn)
+    }
+
+    fn schedule_on_workers<S, F>(&self, schedule: S, workers: Workers, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        let limit = ConcurrencyLimit::unlimited();
+        let scheduled_fn = Arc::new(Mutex::new(scheduled_fn));
+        self.schedule_async_with(schedule, OverlapPolicy::Queue, move |handle| {
+            let scheduled_fn = Arc::clone(&scheduled_fn);
+            let remote = handle.remote().clone();
+            run_on_pool(&workers, &limit, move || lock(&scheduled_fn)(&remote))
+        })
+    }
+
+    /// Schedules the given function to be executed every `interval` on the blocking threads.
+    /// See `schedule_blocking_with`.
+    pub fn schedule_fixed_rate_blocking<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_blocking_with(FixedRate::new(initial, interval), scheduled_fn)
+    }
+
+    /// Returns the pool running the blocking functions, starting it if needed.
+    fn blocking_pool(&self) -> &CpuPool {
+        self.inner.blocking_pool.get_or_init(|| {
+            let mut builder = Builder::new();
+            builder.pool_size(self.inner.blocking_threads).name_prefix(format!("{}_blocking_", self.inner.thread_name));
+            if let Some(size) = self.inner.stack_size {
+                builder.stack_size(size);
+            }
+            builder.create()
+        })
+    }
+
+    /// Schedule a function for running according to the given `schedule`, returning a stream of
+    /// the values it returns. The `policy` defines how many values are buffered when the stream
+    /// is not consumed fast enough. See the [`stream`](../stream/index.html) module.
+    pub fn schedule_stream_with<S, F, R>(&self, schedule: S, policy: BufferPolicy, mut scheduled_fn: F) -> TaskStream<R>
+        where S: Schedule,
+              F: FnMut(&Handle) -> R + Send + 'static,
+              R: Send + 'static
+    {
+        stream::task_stream(policy, |sender| {
+            self.schedule_with(schedule, move |handle| sender.send(scheduled_fn(handle)))
+        })
+    }
+
+    /// Schedule a function for running according to the given `schedule`, sending the values it
+    /// returns to `sender`. The task completes once the receiver has been dropped.
+    pub fn schedule_to_channel<S, F, R>(&self, schedule: S, sender: mpsc::Sender<R>, mut scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) -> R + Send + 'static,
+              R: Send + 'static
+    {
+        let schedule = ChannelSchedule::new(schedule);
+        let send = schedule.sender(sender);
+        self.schedule_with(schedule, move |handle| send(scheduled_fn(handle)))
+    }
+
+    /// Schedule a function for running at fixed rate, returning a stream of the values it
+    /// returns. See `schedule_stream_with`.
+    pub fn schedule_fixed_rate_stream<F, R>(&self, initial: Duration, interval: Duration, policy: BufferPolicy, scheduled_fn: F) -> TaskStream<R>
+        where F: FnMut(&Handle) -> R + Send + 'static,
+              R: Send + 'static
+    {
+        self.schedule_stream_with(FixedRate::new(initial, interval), policy, scheduled_fn)
+    }
+}
+
+static GLOBAL_EXECUTOR: OnceLock<CoreExecutor> = OnceLock::new();
+
+/// Returns the global executor, creating it on first use. It allows libraries to schedule
+/// background maintenance without requiring an executor from their users. The global executor
+/// runs on its own thread for the whole life of the process, and it shouldn't be stopped, as
+/// other users wouldn't be able to schedule tasks on it anymore.
+///
+/// # Panics
+///
+/// Panics if the executor thread can't be created.
+pub fn global() -> &'static CoreExecutor {
+    GLOBAL_EXECUTOR.get_or_init(|| {
+        ExecutorBuilder::new()
+            .name("global_executor")
+            .drop_behavior(DropBehavior::Detach)
+            .build()
+            .expect("failed to create the global executor")
+    })
+}
+
+
+/// Returns the sum of the mean execution times of the tasks. See `LoadBalancing::LeastBusy`.
+fn busy_time(tasks: &[TaskHandle]) -> Duration {
+    tasks.iter().filter_map(|task| task.stats().mean_duration).sum()
+}
+
+/// A `ThreadPoolExecutor` will use one thread for the task scheduling and a thread pool for
+/// task execution, allowing multiple tasks to run in parallel. When a large number of tasks is
+/// scheduled, multiple scheduling threads can be used (see `with_reactors`): tasks will be
+/// assigned to them in a round robin fashion, unless a different `LoadBalancing` is set.
+#[derive(Clone)]
+pub struct ThreadPoolExecutor {
+    executors: Vec<CoreExecutor>,
+    next_executor: Arc<AtomicUsize>,
+    load_balancing: Arc<Mutex<LoadBalancing>>,
+    workers: Workers,
+    pool: Arc<OnceLock<CpuPool>>,
+    threads: usize,
+    prefix: String,
+    affinity_pools: Arc<OnceLock<Vec<CpuPool>>>,
+    limit: ConcurrencyLimit,
+}
+
+impl ThreadPoolExecutor {
+    /// Creates a new `ThreadPoolExecutor` with the specified number of threads. Threads will
+    /// be named "pool_thread_0", "pool_thread_1" and so on.
+    pub fn new(threads: usize) -> Result<ThreadPoolExecutor, ScheduleError> {
+        ThreadPoolExecutor::with_prefix(threads, "pool_thread_")
+    }
+
+    /// Creates a new `ThreadPoolExecutor` with the specified number of threads and prefix for
+    /// the thread names.
+    pub fn with_prefix(threads: usize, prefix: &str) -> Result<ThreadPoolExecutor, ScheduleError> {
+        let new_executor = CoreExecutor::with_name(&format!("{}executor", prefix))?;
+        Ok(ThreadPoolExecutor::with_executor(threads, prefix, new_executor))
+    }
+
+    /// Creates a new `ThreadPoolExecutor` with the specified number of threads, prefix and
+    /// using the given `CoreExecutor` for scheduling.
+    pub fn with_executor(threads: usize, prefix: &str, executor: CoreExecutor) -> ThreadPoolExecutor {
+        ThreadPoolExecutor::with_executors(threads, prefix, vec![executor])
+    }
+
+    /// Creates a new `ThreadPoolExecutor` with the specified number of threads and prefix, using
+    /// `reactors` scheduling threads. The scheduling threads will be named "{prefix}executor_0",
+    /// "{prefix}executor_1" and so on.
+    pub fn with_reactors(threads: usize, reactors: usize, prefix: &str) -> Result<ThreadPoolExecutor, ScheduleError> {
+        assert!(reactors > 0, "at least one reactor is required");
+        let executors = (0..reactors)
+            .map(|i| CoreExecutor::with_name(&format!("{}executor_{}", prefix, i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ThreadPoolExecutor::with_executors(threads, prefix, executors))
+    }
+
+    /// Creates a new `ThreadPoolExecutor` with one scheduling thread, named "{prefix}executor",
+    /// running the task functions on `threads` worker threads with work stealing. Each worker
+    /// has its own queue of executions, and the idle workers take the executions queued on the
+    /// busy ones: an execution that is queued behind a slow one doesn't wait for it to complete
+    /// while other workers are idle.
+    pub fn with_work_stealing(threads: usize, prefix: &str) -> Result<ThreadPoolExecutor, ScheduleError> {
+        let executor = CoreExecutor::with_name(&format!("{}executor", prefix))?;
+        let workers = Workers::Stealing(Arc::new(StealingPool::new(threads, prefix)));
+        Ok(ThreadPoolExecutor::with_workers(threads, prefix, vec![executor], workers))
+    }
+
+    /// Creates a new `ThreadPoolExecutor` with the specified number of threads and prefix,
+    /// using the given `CoreExecutor` for scheduling, whose threads are pinned to the given CPU
+    /// cores in turn: the first thread to the first core, the second thread to the second core
+    /// and so on. The threads that can't be pinned log an error, and run on any core. Requires
+    /// the `affinity` feature, and is only available on Linux. See also
+    /// `ExecutorBuilder::core_affinity`.
+    ///
+    /// Panics if `cores` is empty.
+    #[cfg(all(target_os = "linux", feature = "affinity"))]
+    pub fn with_core_affinity(threads: usize, prefix: &str, executor: CoreExecutor, cores: &[usize]) -> ThreadPoolExecutor {
+        assert!(!cores.is_empty(), "at least one core is required");
+        let cores = cores.to_vec();
+        let started = AtomicUsize::new(0);
+        let pool = Builder::new()
+            .pool_size(threads)
+            .name_prefix(prefix)
+            .after_start(move || {
+                let core = cores[started.fetch_add(1, Ordering::Relaxed) % cores.len()];
+                if let Err(e) = affinity::pin_current_thread(&[core]) {
+                    error!("Failed to pin the thread to core {}: {}", core, e);
+                }
+            })
+            .create();
+        ThreadPoolExecutor::with_workers(threads, prefix, vec![executor], Workers::Pool(pool))
+    }
+
+    fn with_executors(threads: usize, prefix: &str, executors: Vec<CoreExecutor>) -> ThreadPoolExecutor {
+        let pool = Builder::new()
+            .pool_size(threads)
+            .name_prefix(prefix)
+            .create();
+        ThreadPoolExecutor::with_workers(threads, prefix, executors, Workers::Pool(pool))
+    }
+
+    fn with_workers(threads: usize, prefix: &str, executors: Vec<CoreExecutor>, workers: Workers) -> ThreadPoolExecutor {
+        let pool = Arc::new(OnceLock::new());
+        if let Workers::Pool(ref cpu_pool) = workers {
+            let _ = pool.set(cpu_pool.clone());
+        }
+        ThreadPoolExecutor {
+            workers,
+            pool,
+            executors,
+            next_executor: Arc::new(AtomicUsize::new(0)),
+            load_balancing: Arc::new(Mutex::new(LoadBalancing::RoundRobin)),
+            threads,
+            prefix: prefix.to_owned(),
+            affinity_pools: Arc::new(OnceLock::new()),
+            limit: ConcurrencyLimit::unlimited(),
+        }
+    }
+
+    /// Returns the executor that will schedule the next task, according to the load balancing
+    /// strategy.
+    fn executor(&self) -> &CoreExecutor {
+        if self.executors.len() == 1 {
+            return &self.executors[0];
+        }
+        let load_balancing = *self.load_balancing.lock().unwrap();
+        match load_balancing {
+            LoadBalancing::RoundRobin => {
+                let next = self.next_executor.fetch_add(1, Ordering::Relaxed);
+                &self.executors[next % self.executors.len()]
+            },
+            LoadBalancing::LeastTasks => {
+                self.executors.iter().min_by_key(|executor| executor.tasks().len()).unwrap()
+            },
+            LoadBalancing::LeastBusy => {
+                self.executors.iter().min_by_key(|executor| busy_time(&executor.tasks())).unwrap()
+            },
+        }
+    }
+
+    /// Sets how the scheduling thread of each new task is chosen. Tasks that are already
+    /// scheduled are not moved.
+    pub fn set_load_balancing(&self, load_balancing: LoadBalancing) {
+        *self.load_balancing.lock().unwrap() = load_balancing;
+    }
+
+    /// Returns how the scheduling thread of each new task is chosen.
+    pub fn load_balancing(&self) -> LoadBalancing {
+        *self.load_balancing.lock().unwrap()
+    }
+
+    /// Returns the single threaded pool running the tasks with the given affinity key. The
+    /// pools are created the first time a task with a key is scheduled.
+    fn affinity_pool(&self, key: &str) -> Workers {
+        let pools = self.affinity_pools.get_or_init(|| {
+            (0..self.threads)
+                .map(|i| Builder::new().pool_size(1).name_prefix(format!("{}affinity_{}_", self.prefix, i)).create())
+                .collect()
+        });
+        Workers::Pool(pools[coordination::shard_of(key, pools.len())].clone())
+    }
+
+    /// Returns the remote handle of one of the scheduling threads. See `CoreExecutor::remote`.
+    /// When the executor has multiple scheduling threads, they are used in a round robin fashion.
+    pub fn remote(&self) -> Remote {
+        self.executor().remote()
+    }
+
+    /// Spawns a future on one of the scheduling threads. See `CoreExecutor::spawn`.
+    pub fn spawn<F, R>(&self, f: F)
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: IntoFuture<Item=(), Error=()>,
+              R::Future: 'static
+    {
+        self.executor().spawn(f);
+    }
+
+    /// Returns true if all the scheduling threads are running. See `CoreExecutor::is_alive`.
+    pub fn is_alive(&self) -> bool {
+        self.executors.iter().all(|executor| executor.is_alive())
+    }
+
+    /// Returns the health of all the scheduling threads combined. See `CoreExecutor::health`.
+    pub fn health(&self, threshold: Duration) -> Health {
+        let mut health = Health { alive: true, tasks: 0, last_tick: None, overdue: Vec::new(), restarts: 0 };
+        for executor in &self.executors {
+            let executor_health = executor.health(threshold);
+            health.alive &= executor_health.alive;
+            health.tasks += executor_health.tasks;
+            health.last_tick = cmp::max(health.last_tick, executor_health.last_tick);
+            health.overdue.extend(executor_health.overdue);
+            health.restarts += executor_health.restarts;
+        }
+        health
+    }
+
+    /// Returns the handles of all the tasks currently scheduled. See `CoreExecutor::tasks`.
+    pub fn tasks(&self) -> Vec<TaskHandle> {
+        self.executors.iter()
+            .flat_map(|executor| executor.tasks())
+            .collect()
+    }
+
+    /// Returns the handle of the active task with the given name. See `CoreExecutor::task`.
+    pub fn task(&self, name: &str) -> Option<TaskHandle> {
+        self.tasks().into_iter().find(|task| task.name() == Some(name))
+    }
+
+    /// Stops all the active tasks with the given tag. See `CoreExecutor::cancel_tagged`.
+    pub fn cancel_tagged(&self, tag: &str) -> Vec<TaskHandle> {
+        self.executors.iter()
+            .flat_map(|executor| executor.cancel_tagged(tag))
+            .collect()
+    }
+
+    /// Returns the handles of the active tasks with the given tag. See
+    /// `CoreExecutor::tasks_with_tag`.
+    pub fn tasks_with_tag(&self, tag: &str) -> Vec<TaskHandle> {
+        self.executors.iter()
+            .flat_map(|executor| executor.tasks_with_tag(tag))
+            .collect()
+    }
+
+    /// Pauses all the active tasks with the given tag. See `CoreExecutor::pause_tagged`.
+    pub fn pause_tagged(&self, tag: &str) -> Vec<TaskHandle> {
+        self.executors.iter()
+            .flat_map(|executor| executor.pause_tagged(tag))
+            .collect()
+    }
+
+    /// Resumes all the active tasks with the given tag. See `CoreExecutor::resume_tagged`.
+    pub fn resume_tagged(&self, tag: &str) -> Vec<TaskHandle> {
+        self.executors.iter()
+            .flat_map(|executor| executor.resume_tagged(tag))
+            .collect()
+    }
+
+    /// Stops all the scheduling threads. See `CoreExecutor::stop`.
+    pub fn stop(&self) -> StopFuture {
+        self.shutdown().stop()
+    }
+
+    /// Stops all the scheduling threads, and waits up to `timeout` for them to terminate. See
+    /// `CoreExecutor::stop_with_timeout`. Tasks that are already running on the thread pool
+    /// will be allowed to complete.
+    pub fn stop_with_timeout(&self, timeout: Duration) -> bool {
+        self.shutdown().stop_with_timeout(timeout)
+    }
+
+    /// Stops all the scheduling threads, cancelling the executions that are still in progress.
+    /// See `CoreExecutor::stop_now`.
+    pub fn stop_now(&self) -> StopFuture {
+        self.shutdown().stop_now()
+    }
+
+    /// Stops all the scheduling threads once the executions in progress, including the ones on
+    /// the thread pool, have completed. See `Shutdown::drain`.
+    pub fn drain(&self, timeout: Option<Duration>) -> bool {
+        self.shutdown().drain(timeout)
+    }
+
+    /// Returns a token that can be used to stop the executor. See `CoreExecutor::shutdown`.
+    pub fn shutdown(&self) -> Shutdown {
+        Shutdown { executors: self.executors.clone() }
+    }
+
+    /// Sets the policy applied to tasks that panic. See `CoreExecutor::set_panic_policy`.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        for executor in &self.executors {
+            executor.set_panic_policy(policy);
+        }
+    }
+
+    /// Sets a function that will be called with the panic message every time a task panics.
+    pub fn on_panic<F>(&self, handler: F)
+        where F: Fn(&str) + Send + Sync + 'static
+    {
+        let handler = Arc::new(handler);
+        for executor in &self.executors {
+            let handler = Arc::clone(&handler);
+            executor.on_panic(move |message| handler(message));
+        }
+    }
+
+    /// Sets a function that will be called every time the timer of a task fails. See
+    /// `CoreExecutor::on_schedule_error`.
+    pub fn on_schedule_error<F>(&self, handler: F)
+        where F: Fn(&TaskHandle, &ScheduleError) + Send + Sync + 'static
+    {
+        let handler = Arc::new(handler);
+        for executor in &self.executors {
+            let handler = Arc::clone(&handler);
+            executor.on_schedule_error(move |task, error| handler(task, error));
+        }
+    }
+
+    /// Starts a watchdog for the tasks of all the scheduling threads. See
+    /// `CoreExecutor::set_watchdog`.
+    pub fn set_watchdog<F>(&self, threshold: Duration, handler: F) -> Result<(), ScheduleError>
+        where F: Fn(&TaskHandle, Duration) + Send + Sync + 'static
+    {
+        let handler = Arc::new(handler);
+        for executor in &self.executors {
+            let handler = Arc::clone(&handler);
+            executor.set_watchdog(threshold, move |task, running_for| handler(task, running_for))?;
+        }
+        Ok(())
+    }
+
+    /// Starts monitoring the clock for gaps on all the scheduling threads. See
+    /// `CoreExecutor::on_clock_gap`. The handler is called once per gap.
+    pub fn on_clock_gap<F>(&self, threshold: Duration, policy: GapPolicy, handler: F) -> Result<(), ScheduleError>
+        where F: Fn(Duration) + Send + Sync + 'static
+    {
+        let (first, others) = self.executors.split_first().expect("No scheduling thread");
+        first.on_clock_gap(threshold, policy, handler)?;
+        for executor in others {
+            executor.on_clock_gap(threshold, policy, |_gap| ())?;
+        }
+        Ok(())
+    }
+
+    /// Starts monitoring the system clock for jumps on all the scheduling threads. See
+    /// `CoreExecutor::on_clock_jump`. The handler is called once per jump.
+    pub fn on_clock_jump<F>(&self, threshold: Duration, handler: F) -> Result<(), ScheduleError>
+        where F: Fn(ClockJump) + Send + Sync + 'static
+    {
+        let (first, others) = self.executors.split_first().expect("No scheduling thread");
+        first.on_clock_jump(threshold, handler)?;
+        for executor in others {
+            executor.on_clock_jump(threshold, |_jump| ())?;
+        }
+        Ok(())
+    }
+
+    /// Adds a listener for the tasks of all the scheduling threads. See
+    /// `CoreExecutor::add_listener`. Note that the listener is called when the function is
+    /// dispatched to the thread pool and when it completes, but `on_start` might not run on the
+    /// same thread as the function.
+    pub fn add_listener<L: TaskListener>(&self, listener: L) {
+        let listener: Arc<dyn TaskListener> = Arc::new(listener);
+        for executor in &self.executors {
+            executor.inner.settings.listeners.write().unwrap().push(Arc::clone(&listener));
+        }
+    }
+
+    /// Adds a listener for the tasks with the given tag. See `CoreExecutor::add_tagged_listener`.
+    pub fn add_tagged_listener<L: TaskListener>(&self, tag: &str, listener: L) {
+        self.add_listener(TagFilter { tag: tag.to_owned(), listener });
+    }
+
+    /// Schedules the given function to be executed every `interval`. The function will be
+    /// scheduled on one of the threads in the thread pool.
+    pub fn schedule_fixed_rate<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: Fn(&Remote) + Send + Sync + 'static
+    {
+        // Fixed interval is enough
+        self.schedule_concurrent(None, FixedInterval::new(initial, interval), OverlapPolicy::Concurrent, scheduled_fn)
+    }
+
+    /// Like `schedule_fixed_rate`, but all the tasks with the same affinity `key` run on the same
+    /// thread, so they can share thread local state without locking. Since they run on a single
+    /// thread, the executions of the tasks sharing a key never overlap. The keyed tasks run on a
+    /// separate set of threads, as many as the threads in the pool, created the first time a
+    /// keyed task is scheduled.
+    ///
+    /// ```rust,no_run
+    /// # use scheduled_executor::ThreadPoolExecutor;
+    /// # use std::cell::RefCell;
+    /// # use std::collections::HashMap;
+    /// # use std::time::Duration;
+    /// thread_local!(static SESSIONS: RefCell<HashMap<String, u32>> = RefCell::new(HashMap::new()));
+    ///
+    /// let executor = ThreadPoolExecutor::new(4).unwrap();
+    /// executor.schedule_fixed_rate_keyed("tenant-42", Duration::from_secs(0), Duration::from_secs(5), |_remote| {
+    ///     SESSIONS.with(|sessions| *sessions.borrow_mut().entry("tenant-42".to_owned()).or_insert(0) += 1);
+    /// });
+    /// ```
+    pub fn schedule_fixed_rate_keyed<F>(&self, key: &str, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: Fn(&Remote) + Send + Sync + 'static
+    {
+        let pool = self.affinity_pool(key);
+        self.schedule_on(pool, None, FixedInterval::new(initial, interval), OverlapPolicy::Concurrent, scheduled_fn)
+    }
+
+    /// Schedules the given function to be executed according to the given `schedule`, on one of
+    /// the threads in the thread pool. The `policy` defines what happens when an execution is
+    /// due while the previous ones are still running, for example `OverlapPolicy::Limited` caps
+    /// the number of executions of the task running at the same time.
+    pub fn schedule_with_overlap<S, F>(&self, schedule: S, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: Fn(&Remote) + Send + Sync + 'static
+    {
+        self.schedule_concurrent(None, schedule, policy, scheduled_fn)
+    }
+
+    /// Like `schedule_fixed_rate`, but the task is given a `name`. See `schedule_with_named`.
+    pub fn schedule_fixed_rate_named<F>(&self, name: &str, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: Fn(&Remote) + Send + Sync + 'static
+    {
+        self.schedule_concurrent(Some(name), FixedInterval::new(initial, interval), OverlapPolicy::Concurrent, scheduled_fn)
+    }
+
+    /// Schedules the function on the thread pool, with the given overlap policy.
+    fn schedule_concurrent<S, F>(&self, name: Option<&str>, schedule: S, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: Fn(&Remote) + Send + Sync + 'static
+    {
+        self.schedule_on(self.workers.clone(), name, schedule, policy, scheduled_fn)
+    }
+
+    /// Schedules the function on the given pool, with the given overlap policy.
+    fn schedule_on<S, F>(&self, pool: Workers, name: Option<&str>, schedule: S, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: Fn(&Remote) + Send + Sync + 'static
+    {
+        let executor = self.executor();
+        let limit = self.limit.clone();
+        let arc_fn = Arc::new(scheduled_fn);
+        executor.spawn_async_task(
+            executor.new_task(name),
+            schedule,
+            policy,
+            move |handle| {
+                let arc_fn_clone = arc_fn.clone();
+                let remote = handle.remote().clone();
+                run_on_pool(&pool, &limit, move || arc_fn_clone(&remote))
+            }
+        )
+    }
+
+    /// Schedules the given function to be executed at fixed intervals. The function will be
+    /// scheduled on one of the threads in the thread pool, and executions won't overlap: if one
+    /// execution takes longer than `interval`, it will delay all the subsequent ones.
+    pub fn schedule_fixed_interval<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_with(FixedInterval::new(initial, interval), scheduled_fn)
+    }
+
+    /// Like `schedule_fixed_interval`, but the task is given a `name`. See `schedule_with_named`.
+    pub fn schedule_fixed_interval_named<F>(&self, name: &str, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_with_named(name, FixedInterval::new(initial, interval), scheduled_fn)
+    }
+
+    /// Schedules the given function to be executed according to the given `schedule`. The
+    /// function will be executed on one of the threads in the thread pool, and the next
+    /// execution will be computed once the previous one has completed. Since executions never
+    /// overlap, the function is allowed to mutate its own state.
+    pub fn schedule_with<S, F>(&self, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_sequential(None, schedule, OverlapPolicy::Queue, scheduled_fn)
+    }
+
+    /// Like `schedule_with`, but the executions that are due while the previous one is still
+    /// running are skipped. See `CoreExecutor::schedule_skipping_with`.
+    pub fn schedule_skipping_with<S, F>(&self, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_sequential(None, schedule, OverlapPolicy::Skip, scheduled_fn)
+    }
+
+    /// Like `schedule_with`, but the task is given a `name`, that can be used to look it up with
+    /// `task`.
+    pub fn schedule_with_named<S, F>(&self, name: &str, schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_sequential(Some(name), schedule, OverlapPolicy::Queue, scheduled_fn)
+    }
+
+    /// Schedules the function on the thread pool, waiting for each execution to complete before
+    /// computing the next one. The policy is either `Queue` or `Skip`.
+    fn schedule_sequential<S, F>(&self, name: Option<&str>, schedule: S, policy: OverlapPolicy, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        let executor = self.executor();
+        let pool = self.workers.clone();
+        let limit = self.limit.clone();
+        let arc_fn = Arc::new(Mutex::new(scheduled_fn));
+        executor.spawn_async_task(
+            executor.new_task(name),
+            schedule,
+            policy,
+            move |handle| {
+                let arc_fn_clone = arc_fn.clone();
+                let remote = handle.remote().clone();
+                // Executions never overlap, so the lock is never contended
+                run_on_pool(&pool, &limit, move || (*arc_fn_clone.lock().unwrap())(&remote))
+            }
+        )
+    }
+
+    /// Schedules the given function to be executed after each of the given delays, on one of the
+    /// threads in the thread pool. The first delay is measured from now, and every following one
+    /// from the completion of the previous execution. The task stops once the delays are
+    /// exhausted.
+    pub fn schedule_with_delays<I, F>(&self, delays: I, scheduled_fn: F) -> TaskHandle
+        where I: IntoIterator<Item=Duration>,
+              I::IntoIter: Send + 'static,
+              F: FnMut(&Remote) + Send + 'static
+    {
+        self.schedule_with(Delays::new(delays), scheduled_fn)
+    }
+
+    /// Schedules the given function to be executed once, after the specified `delay`. The
+    /// function will be executed on one of the threads in the thread pool.
+    pub fn schedule_once<F>(&self, delay: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Remote) + Send + 'static
+    {
+        self.schedule_at(self.executor().now() + delay, scheduled_fn)
+    }
+
+    /// Schedules the given function to be executed once at the specified `instant`. If the
+    /// instant is already in the past, the function will be executed immediately.
+    pub fn schedule_at<F>(&self, instant: Instant, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Remote) + Send + 'static
+    {
+        self.schedule_once_anchored(instant, Anchor::Monotonic, scheduled_fn)
+    }
+
+    fn schedule_once_anchored<F>(&self, instant: Instant, anchor: Anchor, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Remote) + Send + 'static
+    {
+        let pool = self.workers.clone();
+        let limit = self.limit.clone();
+        let mut scheduled_fn = Some(scheduled_fn);
+        self.executor().schedule_async_with(
+            Once(Some(instant)).anchored(anchor),
+            OverlapPolicy::Queue,
+            move |handle| {
+                let scheduled_fn = scheduled_fn.take();
+                let remote = handle.remote().clone();
+                run_on_pool(&pool, &limit, move || {
+                    if let Some(scheduled_fn) = scheduled_fn {
+                        scheduled_fn(&remote);
+                    }
+                })
+            }
+        )
+    }
+
+    /// Schedules the given function to be executed once at the specified system time. See
+    /// `CoreExecutor::schedule_at_system_time`.
+    pub fn schedule_at_system_time<F>(&self, time: SystemTime, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Remote) + Send + 'static
+    {
+        let executor = self.executor();
+        let instant = executor.now() + delay_until(&*executor.inner.settings.clock, time);
+        self.schedule_once_anchored(instant, Anchor::WallClock, scheduled_fn)
+    }
+
+    /// Schedules the given function to be executed according to a cron expression. The function
+    /// will be executed on one of the threads in the thread pool. Returns an error if the
+    /// expression can't be parsed.
+    pub fn schedule_cron<F>(&self, expression: &str, scheduled_fn: F) -> Result<TaskHandle, ParseError>
+        where F: Fn(&Remote) + Send + Sync + 'static
+    {
+        let cron = CronSchedule::parse(expression)?;
+        Ok(self.schedule_concurrent(None, cron, OverlapPolicy::Concurrent, scheduled_fn))
+    }
+
+    /// Limits the number of executions running at the same time on the thread pool, across all
+    /// the tasks. When the limit is reached, further executions wait for a running one to
+    /// complete, and are started in the order in which they became due. The wait counts as part
+    /// of the execution, so for example a task scheduled with `schedule_with` doesn't compute its
+    /// next execution before it's over. By default there is no limit, other than the number of
+    /// threads in the pool.
+    pub fn set_concurrency_limit(&self, max: usize) {
+        self.limit.set_max(max);
+    }
+
+    /// Returns the limit on the number of executions running at the same time.
+    pub fn concurrency_limit(&self) -> &ConcurrencyLimit {
+        &self.limit
+    }
+
+    // TODO: make pub(crate)
+    /// Returns the thread pool used internally. Executors created with `with_work_stealing`
+    /// don't use it to run the tasks, and create it the first time it is requested.
+    pub fn pool(&self) -> &CpuPool {
+        self.pool.get_or_init(|| Builder::new().pool_size(self.threads).name_prefix(self.prefix.clone()).create())
+    }
+}
+
+/// The threads running the task functions of a `ThreadPoolExecutor`.
+#[derive(Clone)]
+enum Workers {
+    Pool(CpuPool),
+    Stealing(Arc<StealingPool>),
+    Compute(Arc<dyn ComputePool>),
+}
+
+/// Runs the given function on the thread pool once the limit allows it, with the cancellation
+/// token of the current execution.
+fn run_on_pool<F>(workers: &Workers, limit: &ConcurrencyLimit, f: F) -> Box<dyn Future<Item=(), Error=()> + Send>
+    where F: FnOnce() + Send + 'static
+{
+    let workers = workers.clone();
+    let token = CancellationToken::current();
+    Box::new(limit.acquire().and_then(move |permit| {
+        let f = move || {
+            with_cancellation_token(token, f);
+            drop(permit);
+        };
+        match workers {
+            Workers::Pool(pool) => Either::A(pool.spawn_fn(move || {
+                f();
+                Ok::<(), ()>(())
+            })),
+            Workers::Stealing(pool) => Either::B(pool.spawn_fn(f)),
+            Workers::Compute(pool) => {
+                let (job, completion) = steal::completion(f);
+                pool.spawn(job);
+                Either::B(completion)
+            },
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::io;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::ptr;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use futures::Stream;
+    use futures::future::{self, Future};
+    use tokio_core::reactor::{Core, Handle, Timeout};
+
+    use clock::{Clock, SystemClock};
+    use error::ScheduleError;
+    use super::{CancellationToken, ClockJump, CoreExecutor, DropBehavior, ErrorPolicy, ExecutorBuilder, GapPolicy, LoadBalancing, OverlapPolicy,
+                Once, PanicPolicy, Shutdown, TaskHandle, TaskListener, TaskSpec, ThreadPoolExecutor, global};
+    use schedule::{Anchor, FixedInterval, FixedRate, Schedule};
+    use stream::BufferPolicy;
+
+    #[test]
+    fn fixed_interval_test() {
+        let timings = Arc::new(RwLock::new(Vec::new()));
+        {
+            let executor = CoreExecutor::new().unwrap();
+            let timings_clone = Arc::clone(&timings);
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    timings_clone.write().unwrap().push(Instant::now());
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+        }
+
+        let timings = timings.read().unwrap();
+        assert_eq!(timings.len(), 6);
+        for i in 1..6 {
+            let execution_interval = timings[i] - timings[i-1];
+            assert!(execution_interval < Duration::from_millis(1020));
+            assert!(execution_interval > Duration::from_millis(980));
+        }
+    }
+
+    #[test]
+    fn fixed_interval_slow_task_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            executor.schedule_fixed_interval(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    // TODO: use atomic int when available
+                    let counter = {
+                        let mut counter = counter_clone.write().unwrap();
+                        (*counter) += 1;
+                        *counter
+                    };
+                    if counter == 1 {
+                        thread::sleep(Duration::from_secs(3));
+                    }
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+        }
+        assert_eq!(*counter.read().unwrap(), 4);
+    }
+
+    #[test]
+    fn fixed_rate_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    let mut counter = counter_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+        }
+        assert_eq!(*counter.read().unwrap(), 6);
+    }
+
+    #[test]
+    fn fixed_rate_slow_task_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    // TODO: use atomic int when available
+                    let counter = {
+                        let mut counter = counter_clone.write().unwrap();
+                        (*counter) += 1;
+                        *counter
+                    };
+                    if counter == 1 {
+                        thread::sleep(Duration::from_secs(3));
+                    }
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+        }
+        assert_eq!(*counter.read().unwrap(), 6);
+    }
+
+    #[test]
+    fn fixed_rate_slow_task_test_pool() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        {
+            let executor = ThreadPoolExecutor::new(20).unwrap();
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_remote| {
+                    // TODO: use atomic int when available
+                    let counter = {
+                        let mut counter = counter_clone.write().unwrap();
+                        (*counter) += 1;
+                        *counter
+                    };
+                    if counter == 1 {
+                        thread::sleep(Duration::from_secs(3));
+                    }
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+        }
+        assert_eq!(*counter.read().unwrap(), 6);
+    }
+
+    #[test]
+    fn fixed_rate_stop_test() {
+        let counter1 = Arc::new(RwLock::new(0));
+        let counter2 = Arc::new(RwLock::new(0));
+        let counter1_clone = Arc::clone(&counter1);
+        let counter2_clone = Arc::clone(&counter2);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            let t1 = executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    let mut counter = counter1_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    let mut counter = counter2_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+            t1.stop();
+            thread::sleep(Duration::from_millis(5000));
+        }
+        assert_eq!(*counter1.read().unwrap(), 6);
+        assert_eq!(*counter2.read().unwrap(), 11);
+    }
+
+    #[test]
+    fn cancel_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let executor = CoreExecutor::new().unwrap();
+        let task = executor.schedule_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(50),
+            move |_handle| {
+                let mut counter = counter_clone.write().unwrap();
+                (*counter) += 1;
+            }
+        );
+        assert!(!task.is_cancelled());
+        let task_clone = task.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(120));
+            task_clone.cancel();
+        }).join().unwrap();
+        assert!(task.is_cancelled());
+        assert!(task.stopped());
+        // Lets an execution started before the cancellation complete
+        thread::sleep(Duration::from_millis(20));
+        let runs = *counter.read().unwrap();
+        assert!(runs >= 1);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), runs);
+    }
+
+    #[test]
+    fn fixed_interval_stop_test() {
+        let counter1 = Arc::new(RwLock::new(0));
+        let counter2 = Arc::new(RwLock::new(0));
+        let counter1_clone = Arc::clone(&counter1);
+        let counter2_clone = Arc::clone(&counter2);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            let t1 = executor.schedule_fixed_interval(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    let mut counter = counter1_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                move |_handle| {
+                    let mut counter = counter2_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            thread::sleep(Duration::from_millis(5500));
+            t1.stop();
+            thread::sleep(Duration::from_millis(5000));
+        }
+        assert_eq!(*counter1.read().unwrap(), 6);
+        assert_eq!(*counter2.read().unwrap(), 11);
+    }
+
+    #[test]
+    fn fixed_interval_stop_test_pool() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        {
+            let executor = ThreadPoolExecutor::new(4).unwrap();
+            let t = executor.schedule_fixed_interval(
+                Duration::from_secs(0),
+                Duration::from_millis(100),
+                move |_remote| {
+                    {
+                        let mut counter = counter_clone.write().unwrap();
+                        (*counter) += 1;
+                    }
+                    // Executions can't overlap, each cycle takes 200ms
+                    thread::sleep(Duration::from_millis(200));
+                }
+            );
+            thread::sleep(Duration::from_millis(700));
+            t.stop();
+            thread::sleep(Duration::from_millis(1000));
+        }
+        assert_eq!(*counter.read().unwrap(), 4);
+    }
+
+    #[test]
+    fn schedule_once_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter1_clone = Arc::clone(&counter);
+        let counter2_clone = Arc::clone(&counter);
+        {
+            let executor = ThreadPoolExecutor::new(4).unwrap();
+            executor.schedule_once(
+                Duration::from_millis(200),
+                move |_remote| {
+                    let mut counter = counter1_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            let t = executor.schedule_once(
+                Duration::from_millis(500),
+                move |_remote| {
+                    let mut counter = counter2_clone.write().unwrap();
+                    (*counter) += 10;
+                }
+            );
+            thread::sleep(Duration::from_millis(100));
+            assert_eq!(*counter.read().unwrap(), 0);
+            thread::sleep(Duration::from_millis(200));
+            assert_eq!(*counter.read().unwrap(), 1);
+            t.stop();
+            thread::sleep(Duration::from_millis(500));
+        }
+        assert_eq!(*counter.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn schedule_at_test() {
+        let timings = Arc::new(RwLock::new(Vec::new()));
+        let timings1_clone = Arc::clone(&timings);
+        let timings2_clone = Arc::clone(&timings);
+        let start = Instant::now();
+        {
+            let executor = CoreExecutor::new().unwrap();
+            executor.schedule_at(
+                start + Duration::from_millis(300),
+                move |_handle| timings1_clone.write().unwrap().push(Instant::now())
+            );
+            thread::sleep(Duration::from_millis(100));
+            // Already in the past, should run immediately
+            executor.schedule_at(
+                start,
+                move |_handle| timings2_clone.write().unwrap().push(Instant::now())
+            );
+            thread::sleep(Duration::from_millis(400));
+        }
+
+        let timings = timings.read().unwrap();
+        assert_eq!(timings.len(), 2);
+        assert!(timings[0] - start < Duration::from_millis(150));
+        assert!(timings[1] - start > Duration::from_millis(290));
+        assert!(timings[1] - start < Duration::from_millis(320));
+    }
+
+    #[test]
+    fn schedule_at_system_time_test() {
+        let (tx, rx) = mpsc::channel();
+        let executor = CoreExecutor::new().unwrap();
+        let start = Instant::now();
+        let tx_clone = tx.clone();
+        executor.schedule_at_system_time(SystemTime::now() + Duration::from_millis(200), move |_handle| {
+            tx_clone.send(Instant::now()).unwrap();
+        });
+        // Already in the past, should run immediately
+        executor.schedule_at_system_time(SystemTime::now() - Duration::from_secs(60), move |_handle| {
+            tx.send(Instant::now()).unwrap();
+        });
+
+        let first = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        let second = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(first - start < Duration::from_millis(100));
+        assert!(second - start >= Duration::from_millis(190));
+    }
+
+    /// A clock whose system time can be adjusted by the given number of milliseconds.
+    struct AdjustableClock(Arc<Mutex<i64>>);
+
+    impl Clock for AdjustableClock {
+        fn now(&self) -> Instant {
+            Instant::now()
+        }
+
+        fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            SystemClock.sleep_until(deadline, handle)
+        }
+
+        fn system_time(&self) -> SystemTime {
+            let offset = *self.0.lock().unwrap();
+            if offset >= 0 {
+                SystemTime::now() + Duration::from_millis(offset as u64)
+            } else {
+                SystemTime::now() - Duration::from_millis(offset.unsigned_abs())
+            }
+        }
+    }
+
+    #[test]
+    fn wall_clock_anchor_test() {
+        let offset = Arc::new(Mutex::new(0));
+        let executor = ExecutorBuilder::new().clock(AdjustableClock(Arc::clone(&offset))).build().unwrap();
+        let start = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let tasks = [("monotonic", 300, Anchor::Monotonic), ("wall_clock", 300, Anchor::WallClock), ("later", 3000, Anchor::WallClock)];
+        for &(name, delay, anchor) in &tasks {
+            let tx = tx.clone();
+            let schedule = FixedInterval::new(Duration::from_millis(delay), Duration::from_secs(3600)).anchored(anchor);
+            executor.schedule_with(schedule, move |_handle| tx.send((name, start.elapsed())).unwrap());
+        }
+
+        // The system clock is moved back by 200ms at 100ms, and then forward by 2.3s at 600ms
+        thread::sleep(Duration::from_millis(100));
+        *offset.lock().unwrap() = -200;
+        thread::sleep(Duration::from_millis(600).checked_sub(start.elapsed()).unwrap_or_default());
+        *offset.lock().unwrap() = 2100;
+        let runs = (0..3).map(|_| rx.recv_timeout(Duration::from_secs(2)).unwrap()).collect::<Vec<_>>();
+        assert_eq!(runs.iter().map(|&(name, _)| name).collect::<Vec<_>>(), vec!["monotonic", "wall_clock", "later"]);
+        assert!(runs[0].1 < Duration::from_millis(400));
+        assert!(runs[1].1 >= Duration::from_millis(490) && runs[1].1 < Duration::from_millis(600));
+        // Noticed at the next check of the system time, a second after being scheduled
+        assert!(runs[2].1 >= Duration::from_millis(900) && runs[2].1 < Duration::from_millis(1300));
+    }
+
+    #[test]
+    fn clock_gap_test() {
+        let offset = Arc::new(Mutex::new(0));
+        let executor = ExecutorBuilder::new().clock(AdjustableClock(Arc::clone(&offset))).build().unwrap();
+        let start = Instant::now();
+        let gaps = Arc::new(Mutex::new(Vec::new()));
+        let gaps_clone = Arc::clone(&gaps);
+        executor.on_clock_gap(Duration::from_millis(200), GapPolicy::CatchUp(2), move |gap| {
+            gaps_clone.lock().unwrap().push(gap);
+        }).unwrap();
+        let ms = Duration::from_millis;
+        let slow = executor.schedule_fixed_rate(ms(1000), ms(1000), |_handle| ());
+        let fast = executor.schedule_fixed_rate(ms(400), ms(100), |_handle| ());
+        let once = executor.schedule_once(ms(3000), |_handle| ());
+
+        // Like a suspend of 1.5s at 100ms, the system time moves but the monotonic clock doesn't
+        thread::sleep(ms(100));
+        *offset.lock().unwrap() = 1500;
+        thread::sleep(ms(330).checked_sub(start.elapsed()).unwrap_or_default());
+        let gaps = gaps.lock().unwrap().clone();
+        assert_eq!(gaps.len(), 1);
+        assert!(gaps[0] >= ms(1450) && gaps[0] < ms(1600));
+        // One missed execution for the slow task, capped to two for the fast one, none for the
+        // task that was due after the wake up
+        assert_eq!((slow.runs(), fast.runs(), once.runs()), (1, 2, 0));
+        assert!(slow.next_run().unwrap() > start + ms(900));
+        assert!(once.next_run().unwrap() > start + ms(2900));
+        thread::sleep(ms(450).checked_sub(start.elapsed()).unwrap_or_default());
+        assert_eq!(fast.runs(), 3);
+    }
+
+    #[test]
+    fn clock_jump_test() {
+        let offset = Arc::new(Mutex::new(0));
+        let executor = ExecutorBuilder::new().clock(AdjustableClock(Arc::clone(&offset))).build().unwrap();
+        let start = Instant::now();
+        let jumps = Arc::new(Mutex::new(Vec::new()));
+        let jumps_clone = Arc::clone(&jumps);
+        executor.on_clock_jump(Duration::from_millis(200), move |jump| jumps_clone.lock().unwrap().push(jump)).unwrap();
+        let (tx, rx) = mpsc::channel();
+        executor.schedule_at_system_time(SystemTime::now() + Duration::from_secs(2), move |_handle| {
+            tx.send(start.elapsed()).unwrap();
+        });
+
+        // Recomputed right away, without waiting for the next check of the system time
+        thread::sleep(Duration::from_millis(100));
+        *offset.lock().unwrap() = 1900;
+        assert!(rx.recv_timeout(Duration::from_millis(400)).unwrap() < Duration::from_millis(400));
+        *offset.lock().unwrap() = -500;
+        thread::sleep(Duration::from_millis(200));
+        let jumps = jumps.lock().unwrap().clone();
+        assert_eq!(jumps.len(), 2);
+        let within = |jump: Duration, expected: u64| jump > Duration::from_millis(expected - 50) && jump < Duration::from_millis(expected + 50);
+        assert!(matches!(jumps[0], ClockJump::Forward(jump) if within(jump, 1900)), "{:?}", jumps);
+        assert!(matches!(jumps[1], ClockJump::Backward(jump) if within(jump, 2400)), "{:?}", jumps);
+    }
+
+    #[test]
+    fn global_test() {
+        let (tx, rx) = mpsc::channel();
+        let handle = global().schedule_once(Duration::from_millis(10), move |_handle| {
+            tx.send(thread::current().name().map(|name| name.to_owned())).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), Some("global_executor".to_owned()));
+        assert_eq!(handle.runs(), 1);
+        assert!(ptr::eq(global(), global()));
+    }
+
+    #[test]
+    fn schedule_cron_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            assert!(executor.schedule_cron("* * * *", |_handle| ()).is_err());
+            executor.schedule_cron(
+                "* * * * * *",
+                move |_handle| {
+                    let mut counter = counter_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            ).unwrap();
+            thread::sleep(Duration::from_millis(3000));
+        }
+        let counter = *counter.read().unwrap();
+        assert!((2..=4).contains(&counter));
+    }
+
+    #[test]
+    fn custom_schedule_test() {
+        // Runs three times, waiting 100ms more after each execution
+        struct Increasing(u32);
+
+        impl Schedule for Increasing {
+            fn next_after(&mut self, after: Instant) -> Option<Instant> {
+                self.0 += 1;
+                if self.0 > 3 {
+                    None
+                } else {
+                    Some(after + Duration::from_millis(100) * self.0)
+                }
+            }
+        }
+
+        let start = Instant::now();
+        let timings = Arc::new(RwLock::new(Vec::new()));
+        let timings_clone = Arc::clone(&timings);
+        {
+            let executor = ThreadPoolExecutor::new(4).unwrap();
+            executor.schedule_with(
+                Increasing(0),
+                move |_remote| timings_clone.write().unwrap().push(Instant::now())
+            );
+            thread::sleep(Duration::from_millis(1000));
+        }
+
+        let timings = timings.read().unwrap();
+        assert_eq!(timings.len(), 3);
+        for (i, expected) in [100, 300, 600].iter().enumerate() {
+            let elapsed = timings[i] - start;
+            assert!(elapsed > Duration::from_millis(*expected));
+            assert!(elapsed < Duration::from_millis(*expected + 50));
+        }
+    }
+
+    #[test]
+    fn multiple_reactors_test() {
+        let counter = Arc::new(RwLock::new(0));
+        {
+            let executor = ThreadPoolExecutor::with_reactors(4, 3, "reactors_test_").unwrap();
+            assert_eq!(executor.executors.len(), 3);
+            for _ in 0..6 {
+                let counter_clone = Arc::clone(&counter);
+                executor.schedule_fixed_rate(
+                    Duration::from_secs(0),
+                    Duration::from_millis(100),
+                    move |_remote| {
+                        let mut counter = counter_clone.write().unwrap();
+                        (*counter) += 1;
+                    }
+                );
+            }
+            // Tasks are assigned to the reactors in turn
+            let first = Arc::clone(&executor.executor().inner);
+            let second = Arc::clone(&executor.executor().inner);
+            assert!(!Arc::ptr_eq(&first, &second));
+            executor.executor();
+            assert!(Arc::ptr_eq(&first, &executor.executor().inner));
+            thread::sleep(Duration::from_millis(450));
+        }
+        assert_eq!(*counter.read().unwrap(), 6 * 5);
+    }
+
+    #[test]
+    fn fixed_rate_async_test() {
+        fn starts_with_policy(policy: OverlapPolicy) -> usize {
+            let counter = Arc::new(RwLock::new(0));
+            let counter_clone = Arc::clone(&counter);
+            {
+                let executor = CoreExecutor::new().unwrap();
+                executor.schedule_fixed_rate_async(
+                    Duration::from_secs(0),
+                    Duration::from_millis(100),
+                    policy,
+                    move |handle| {
+                        (*counter_clone.write().unwrap()) += 1;
+                        // Each execution takes 250ms to complete
+                        Timeout::new(Duration::from_millis(250), handle).unwrap()
+                            .map_err(|_| ())
+                    }
+                );
+                thread::sleep(Duration::from_millis(850));
+            }
+            let starts = *counter.read().unwrap();
+            starts
+        }
+
+        // Starts at 0, 300, 600
+        assert_eq!(starts_with_policy(OverlapPolicy::Skip), 3);
+        // Starts at 0, 250, 500, 750
+        assert_eq!(starts_with_policy(OverlapPolicy::Queue), 4);
+        // Starts every 100ms
+        assert_eq!(starts_with_policy(OverlapPolicy::Concurrent), 9);
+    }
+
+    #[test]
+    fn skipping_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let starts_clone = Arc::clone(&starts);
+        let start = Instant::now();
+        let task = executor.schedule_skipping_with(FixedRate::new(Duration::from_secs(0), Duration::from_millis(100)), move |_handle| {
+            let mut starts = starts_clone.lock().unwrap();
+            starts.push(start.elapsed());
+            if starts.len() == 1 {
+                // The executions at 100 and 200 are skipped
+                thread::sleep(Duration::from_millis(250));
+            }
+        });
+        thread::sleep(Duration::from_millis(450));
+        let starts = starts.lock().unwrap().clone();
+        assert_eq!(starts.len(), 3);
+        assert!(starts[1] >= Duration::from_millis(300) && starts[1] < Duration::from_millis(350));
+        assert_eq!(task.skipped(), 2);
+
+        let pool = ThreadPoolExecutor::new(2).unwrap();
+        let task = pool.schedule_skipping_with(FixedRate::new(Duration::from_secs(0), Duration::from_millis(100)), |_remote| {
+            thread::sleep(Duration::from_millis(250));
+        });
+        thread::sleep(Duration::from_millis(650));
+        // Starts at 0, 300, 600
+        assert_eq!(task.runs(), 3);
+        assert_eq!(task.skipped(), 4);
+    }
+
+    #[test]
+    fn limited_concurrency_test() {
+        fn run_with_policy(policy: OverlapPolicy) -> (usize, TaskHandle) {
+            let running = Arc::new(AtomicUsize::new(0));
+            let max_running = Arc::new(AtomicUsize::new(0));
+            let (running_clone, max_running_clone) = (Arc::clone(&running), Arc::clone(&max_running));
+            let executor = CoreExecutor::new().unwrap();
+            let task = executor.schedule_fixed_rate_async(Duration::from_secs(0), Duration::from_millis(20), policy, move |handle| {
+                let now_running = running_clone.fetch_add(1, Ordering::Relaxed) + 1;
+                max_running_clone.fetch_max(now_running, Ordering::Relaxed);
+                let running = Arc::clone(&running_clone);
+                Timeout::new(Duration::from_millis(90), handle).unwrap()
+                    .map(move |_| { running.fetch_sub(1, Ordering::Relaxed); })
+                    .map_err(|_| ())
+            });
+            thread::sleep(Duration::from_millis(250));
+            let max_running = max_running.load(Ordering::Relaxed);
+            (max_running, task)
+        }
+
+        // Starts at 0, 20, 100, 120, 200, 220
+        let (max_running, task) = run_with_policy(OverlapPolicy::Limited { max: 2, skip: true });
+        assert_eq!(max_running, 2);
+        assert_eq!(task.runs(), 6);
+        assert_eq!(task.skipped(), 7);
+        // The delayed executions start as soon as a slot is available: 0, 20, 90, 110, 180, 200
+        let (max_running, task) = run_with_policy(OverlapPolicy::Limited { max: 2, skip: false });
+        assert_eq!(max_running, 2);
+        assert_eq!(task.runs(), 6);
+        assert_eq!(task.skipped(), 0);
+
+        let pool = ThreadPoolExecutor::new(4).unwrap();
+        let task = pool.schedule_with_overlap(FixedRate::new(Duration::from_secs(0), Duration::from_millis(20)), OverlapPolicy::Limited { max: 1, skip: true }, |_remote| {
+            thread::sleep(Duration::from_millis(50));
+        });
+        thread::sleep(Duration::from_millis(130));
+        // Starts at 0, 60, 120
+        assert_eq!(task.runs(), 3);
+        assert_eq!(task.skipped(), 4);
+    }
+
+    #[test]
+    fn concurrency_limit_test() {
+        let pool = ThreadPoolExecutor::new(4).unwrap();
+        pool.set_concurrency_limit(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let started = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..4 {
+            let running = Arc::clone(&running);
+            let max_running = Arc::clone(&max_running);
+            let started = Arc::clone(&started);
+            pool.schedule_once(Duration::from_millis(5 * i as u64), move |_remote| {
+                started.lock().unwrap().push(i);
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(60));
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        thread::sleep(Duration::from_millis(35));
+        assert_eq!(pool.concurrency_limit().running(), 2);
+        assert_eq!(pool.concurrency_limit().waiting(), 2);
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(*started.lock().unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(pool.concurrency_limit().running(), 0);
+    }
+
+    #[test]
+    fn stateful_task_test() {
+        let (core_tx, core_rx) = mpsc::channel();
+        let (pool_tx, pool_rx) = mpsc::channel();
+        {
+            let core_executor = CoreExecutor::new().unwrap();
+            let mut core_count = 0;
+            core_executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_millis(100),
+                move |_handle| {
+                    core_count += 1;
+                    core_tx.send(core_count).unwrap();
+                }
+            );
+            let pool_executor = ThreadPoolExecutor::new(4).unwrap();
+            let mut pool_count = 0;
+            pool_executor.schedule_fixed_interval(
+                Duration::from_secs(0),
+                Duration::from_millis(100),
+                move |_remote| {
+                    pool_count += 1;
+                    pool_tx.send(pool_count).unwrap();
+                }
+            );
+            thread::sleep(Duration::from_millis(350));
+        }
+        assert_eq!(core_rx.try_iter().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(pool_rx.try_iter().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn panic_restart_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let messages = Arc::new(RwLock::new(Vec::new()));
+        let counter_clone = Arc::clone(&counter);
+        let messages_clone = Arc::clone(&messages);
+        {
+            let executor = CoreExecutor::new().unwrap();
+            executor.on_panic(move |message| messages_clone.write().unwrap().push(message.to_owned()));
+            let mut runs = 0;
+            let t1 = executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_millis(100),
+                move |_handle| {
+                    runs += 1;
+                    if runs % 2 == 0 {
+                        panic!("run {}", runs);
+                    }
+                }
+            );
+            executor.schedule_fixed_rate(
+                Duration::from_secs(0),
+                Duration::from_millis(100),
+                move |_handle| {
+                    let mut counter = counter_clone.write().unwrap();
+                    (*counter) += 1;
+                }
+            );
+            thread::sleep(Duration::from_millis(550));
+            assert_eq!(t1.panics(), 3);
+        }
+        assert_eq!(*counter.read().unwrap(), 6);
+        assert_eq!(*messages.read().unwrap(), vec!["run 2", "run 4", "run 6"]);
+    }
+
+    #[test]
+    fn panic_stop_and_backoff_test() {
+        let executor = ThreadPoolExecutor::new(4).unwrap();
+        executor.set_panic_policy(PanicPolicy::StopAfter(2));
+        let pool_handle = executor.schedule_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(100),
+            |_remote| panic!("pool panic")
+        );
+
+        let core_executor = CoreExecutor::new().unwrap();
+        core_executor.set_panic_policy(PanicPolicy::Backoff {
+            initial: Duration::from_millis(200),
+            max: Duration::from_millis(300),
+        });
+        // Runs at 0, 200, 500, 800
+        let core_handle = core_executor.schedule_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(50),
+            |_handle| panic!("core panic")
+        );
+
+        thread::sleep(Duration::from_millis(900));
+        assert!(pool_handle.stopped());
+        assert_eq!(pool_handle.panics(), 2);
+        assert!(!core_handle.stopped());
+        assert_eq!(core_handle.panics(), 4);
+    }
+
+    #[test]
+    fn stop_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let executor = CoreExecutor::new().unwrap();
+        executor.schedule_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(100),
+            move |_handle| {
+                let mut counter = counter_clone.write().unwrap();
+                (*counter) += 1;
+            }
+        );
+        thread::sleep(Duration::from_millis(250));
+        executor.clone().stop().wait().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), 3);
+        // Stopping again is fine
+        executor.stop().wait().unwrap();
+    }
+
+    #[test]
+    fn with_handle_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let mut core = Core::new().unwrap();
+        let executor = CoreExecutor::with_handle(&core.handle());
+        executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(100), move |_handle| {
+            *counter_clone.write().unwrap() += 1;
+        });
+        core.run(Timeout::new(Duration::from_millis(250), &core.handle()).unwrap()).unwrap();
+        assert_eq!(*counter.read().unwrap(), 3);
+        core.run(executor.stop()).unwrap();
+        core.run(Timeout::new(Duration::from_millis(200), &core.handle()).unwrap()).unwrap();
+        assert_eq!(*counter.read().unwrap(), 3);
+    }
+
+    #[test]
+    fn run_on_current_thread_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = Arc::clone(&counter);
+        let start = Instant::now();
+        let test_thread = thread::current().id();
+        CoreExecutor::run_on_current_thread(|executor, _handle| {
+            let shutdown = Mutex::new(Some(executor.shutdown()));
+            executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(50), move |_handle| {
+                assert_eq!(thread::current().id(), test_thread);
+                let mut counter = counter_clone.write().unwrap();
+                *counter += 1;
+                if *counter == 3 {
+                    shutdown.lock().unwrap().take().unwrap().stop();
+                }
+            });
+        }).unwrap();
+        assert_eq!(*counter.read().unwrap(), 3);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn schedule_local_test() {
+        let counter = Rc::new(Cell::new(0));
+        let counter_clone = Rc::clone(&counter);
+        CoreExecutor::run_on_current_thread(|executor, handle| {
+            let mut shutdown = Some(executor.shutdown());
+            executor.schedule_local(handle, FixedRate::new(Duration::from_secs(0), Duration::from_millis(50)), move |_handle| {
+                counter_clone.set(counter_clone.get() + 1);
+                if counter_clone.get() == 3 {
+                    shutdown.take().unwrap().stop();
+                }
+            });
+        }).unwrap();
+        assert_eq!(counter.get(), 3);
+
+        let mut core = Core::new().unwrap();
+        let executor = CoreExecutor::new().unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            executor.schedule_local(&core.handle(), Once(Some(Instant::now())), |_handle| ());
+        }));
+        assert!(result.is_err());
+        let executor = CoreExecutor::with_handle(&core.handle());
+        let ran = Rc::new(Cell::new(false));
+        let ran_clone = Rc::clone(&ran);
+        executor.schedule_local(&core.handle(), Once(Some(Instant::now())), move |_handle| ran_clone.set(true));
+        core.run(Timeout::new(Duration::from_millis(50), &core.handle()).unwrap()).unwrap();
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn execute_test() {
+        let executor = CoreExecutor::with_name("execute_test").unwrap();
+        let name = executor.execute(|_handle| thread::current().name().map(|name| name.to_owned()));
+        assert_eq!(name.wait(), Ok(Some("execute_test".to_owned())));
+        assert!(executor.execute(|_handle| -> u32 { panic!("execute panic") }).wait().is_err());
+        assert_eq!(executor.execute(|_handle| 42).wait(), Ok(42));
+        executor.stop().wait().unwrap();
+        assert!(executor.execute(|_handle| 42).wait().is_err());
+    }
+
+    #[test]
+    fn stream_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let mut count = 0;
+        let stream = executor.schedule_stream_with(FixedRate::new(Duration::from_secs(0), Duration::from_millis(20)).max_runs(4),
+                                                   BufferPolicy::Unbounded, move |_handle| { count += 1; count });
+        assert_eq!(stream.wait().collect::<Result<Vec<_>, _>>(), Ok(vec![1, 2, 3, 4]));
+
+        let stream = executor.schedule_fixed_rate_stream(Duration::from_secs(0), Duration::from_millis(20),
+                                                         BufferPolicy::DropOldest(1), |_handle| Instant::now());
+        thread::sleep(Duration::from_millis(110));
+        let handle = stream.handle().clone();
+        let (latest, stream) = stream.into_future().wait().map_err(|_| ()).unwrap();
+        assert!(latest.unwrap().elapsed() < Duration::from_millis(50));
+        assert!(stream.dropped() >= 3);
+        drop(stream);
+        assert!(handle.stopped());
+    }
+
+    #[test]
+    fn schedule_to_channel_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let mut count = 0;
+        let handle = executor.schedule_to_channel(FixedRate::new(Duration::from_secs(0), Duration::from_millis(20)), tx,
+                                                  move |_handle| { count += 1; count });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(1));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(2));
+        drop(rx);
+        thread::sleep(Duration::from_millis(100));
+        assert!(handle.stopped());
+        assert!(handle.runs() <= 4);
+    }
+
+    #[test]
+    fn schedule_fallible_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let schedule = || FixedRate::new(Duration::from_secs(0), Duration::from_millis(20));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = Arc::clone(&errors);
+        let mut count = 0;
+        let continued = executor.schedule_fallible(schedule(), ErrorPolicy::Continue, move |_handle| {
+            count += 1;
+            if count % 2 == 0 { Err(count) } else { Ok(()) }
+        }, move |_task, error| errors_clone.lock().unwrap().push(*error));
+        let stopped = executor.schedule_fallible(schedule(), ErrorPolicy::Stop, |_handle| Err("failed"), |_task, _error| ());
+        executor.set_panic_policy(PanicPolicy::StopAfter(2));
+        let escalated = executor.schedule_fallible(schedule(), ErrorPolicy::Escalate, |_handle| Err("failed"), |_task, _error| ());
+        thread::sleep(Duration::from_millis(110));
+        assert!(!continued.stopped());
+        assert_eq!(errors.lock().unwrap()[..2], [2, 4]);
+        assert!(stopped.stopped());
+        assert_eq!(stopped.runs(), 1);
+        assert!(escalated.stopped());
+        assert_eq!(escalated.panics(), 2);
+    }
+
+    /// A clock whose timers fail a given number of times.
+    struct FailingClock {
+        failures: AtomicUsize,
+    }
+
+    impl Clock for FailingClock {
+        fn now(&self) -> Instant {
+            Instant::now()
+        }
+
+        fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            if self.failures.load(Ordering::Relaxed) == 0 {
+                return SystemClock.sleep_until(deadline, handle);
+            }
+            self.failures.fetch_sub(1, Ordering::Relaxed);
+            Box::new(future::err(ScheduleError::Timer(io::Error::other("no timer"))))
+        }
+    }
+
+    #[test]
+    fn timer_failure_test() {
+        let executor = ExecutorBuilder::new().clock(FailingClock { failures: AtomicUsize::new(3) }).build().unwrap();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_clone = Arc::clone(&errors);
+        executor.on_schedule_error(move |task, error| {
+            errors_clone.lock().unwrap().push((task.id(), error.to_string()));
+        });
+        let task = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(50), |_handle| ());
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(errors.lock().unwrap().len(), 3);
+        assert_eq!(errors.lock().unwrap()[0], (task.id(), "failed to create a timer: no timer".to_owned()));
+        assert!(task.runs() >= 2);
+        assert!(!task.stopped());
+
+        let executor = ExecutorBuilder::new().clock(FailingClock { failures: AtomicUsize::new(100) }).build().unwrap();
+        let task = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(50), |_handle| ());
+        thread::sleep(Duration::from_millis(50));
+        assert!(task.stopped());
+        assert_eq!(task.runs(), 0);
+    }
+
+    #[test]
+    fn is_alive_test() {
+        let executor = CoreExecutor::new().unwrap();
+        assert!(executor.is_alive());
+        assert!(executor.try_schedule_with(Once(Some(Instant::now())), |_handle| ()).is_ok());
+        // A panic outside of the tasks takes down the executor thread
+        executor.spawn(|_handle| -> Result<(), ()> { panic!("reactor panic") });
+        thread::sleep(Duration::from_millis(100));
+        assert!(!executor.is_alive());
+        let task = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(10), |_handle| ());
+        assert!(task.stopped());
+        assert!(executor.tasks().iter().all(|t| t.id() != task.id()));
+        match executor.try_schedule_with(Once(Some(Instant::now())), |_handle| ()) {
+            Err(ScheduleError::Disconnected) => {},
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let pool = ThreadPoolExecutor::with_reactors(2, 2, "is_alive_test_").unwrap();
+        assert!(pool.is_alive());
+        pool.stop().wait().unwrap();
+        assert!(!pool.is_alive());
+    }
+
+    #[test]
+    fn stop_now_test() {
+        struct DropFlag(Arc<AtomicBool>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let mut core = Core::new().unwrap();
+        let executor = CoreExecutor::with_handle(&core.handle());
+        let dropped = Arc::new(AtomicBool::new(false));
+        let dropped_clone = Arc::clone(&dropped);
+        let task = executor.schedule_async_with(Once(Some(Instant::now())), OverlapPolicy::Queue, move |handle| {
+            let flag = DropFlag(Arc::clone(&dropped_clone));
+            Timeout::new(Duration::from_secs(10), handle).unwrap()
+                .map(move |_| drop(flag))
+                .map_err(|_| ())
+        });
+        core.run(Timeout::new(Duration::from_millis(50), &core.handle()).unwrap()).unwrap();
+        assert!(task.running_for().is_some());
+        assert!(!dropped.load(Ordering::Relaxed));
+        core.run(executor.stop_now()).unwrap();
+        core.run(Timeout::new(Duration::from_millis(50), &core.handle()).unwrap()).unwrap();
+        assert!(dropped.load(Ordering::Relaxed));
+        assert!(task.running_for().is_none());
+        assert_eq!(task.stats().executions, 1);
+    }
+
+    #[test]
+    fn cancellation_token_test() {
+        assert!(CancellationToken::current().is_none());
+        let (tx, rx) = mpsc::channel();
+        let executor = CoreExecutor::with_name("cancellation_token_test").unwrap();
+        let task = executor.schedule_once(Duration::from_secs(0), move |_handle| {
+            let token = CancellationToken::current().unwrap();
+            tx.send(token.task().clone()).unwrap();
+            while !token.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            tx.send(token.task().clone()).unwrap();
+        });
+        assert_eq!(rx.recv().unwrap().id(), task.id());
+        task.stop();
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        let (tx, rx) = mpsc::channel();
+        let pool = ThreadPoolExecutor::new(1).unwrap();
+        pool.schedule_once(Duration::from_secs(0), move |_remote| {
+            let token = CancellationToken::current().unwrap();
+            while !token.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            tx.send(()).unwrap();
+        });
+        thread::sleep(Duration::from_millis(50));
+        pool.stop();
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+        assert!(CancellationToken::current().is_none());
+    }
+
+    #[test]
+    fn async_cancel_test() {
+        let executor = CoreExecutor::with_name("async_cancel_test").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let cancel_tx = Mutex::new(tx.clone());
+        let task = executor.schedule_async_with_cancel(FixedRate::new(Duration::from_secs(0), Duration::from_millis(10)), OverlapPolicy::Concurrent, move |handle| {
+            let tx = tx.clone();
+            Timeout::new(Duration::from_secs(10), handle).unwrap()
+                .map(move |_| tx.send("completed").unwrap())
+                .map_err(|_| ())
+        }, move |_task| cancel_tx.lock().unwrap().send("cancelled").unwrap());
+        thread::sleep(Duration::from_millis(35));
+        assert!(task.running_for().is_some());
+        task.stop();
+        thread::sleep(Duration::from_millis(20));
+        let events = rx.try_iter().collect::<Vec<_>>();
+        assert!(events.len() >= 3);
+        assert!(events.iter().all(|event| *event == "cancelled"));
+        assert!(task.running_for().is_none());
+        assert_eq!(task.runs(), events.len());
+
+        // Executions are also cancelled when the executor thread terminates
+        let (tx, rx) = mpsc::channel();
+        let cancel_tx = Mutex::new(tx);
+        let task = executor.schedule_async_with_cancel(Once(Some(Instant::now())), OverlapPolicy::Queue, |handle| {
+            Timeout::new(Duration::from_secs(10), handle).unwrap().map_err(|_| ())
+        }, move |task| cancel_tx.lock().unwrap().send(task.id()).unwrap());
+        thread::sleep(Duration::from_millis(20));
+        executor.stop().wait().unwrap();
+        assert_eq!(rx.try_recv(), Ok(task.id()));
+    }
+
+    #[test]
+    fn drain_test() {
+        let executor = CoreExecutor::with_name("drain_test").unwrap();
+        let completed = Arc::new(AtomicUsize::new(0));
+        let completed_clone = Arc::clone(&completed);
+        let task = executor.schedule_async_with(FixedRate::new(Duration::from_secs(0), Duration::from_millis(10)), OverlapPolicy::Queue, move |handle| {
+            let completed = Arc::clone(&completed_clone);
+            Timeout::new(Duration::from_millis(100), handle).unwrap()
+                .map(move |_| { completed.fetch_add(1, Ordering::Relaxed); })
+                .map_err(|_| ())
+        });
+        thread::sleep(Duration::from_millis(30));
+        assert!(executor.drain(Some(Duration::from_secs(1))));
+        assert_eq!(completed.load(Ordering::Relaxed), 1);
+        assert_eq!(task.runs(), 1);
+        assert!(!executor.is_alive());
+
+        let executor = CoreExecutor::with_name("drain_timeout_test").unwrap();
+        executor.schedule_async_with(Once(Some(Instant::now())), OverlapPolicy::Queue, |handle| {
+            Timeout::new(Duration::from_secs(10), handle).unwrap().map_err(|_| ())
+        });
+        thread::sleep(Duration::from_millis(30));
+        let start = Instant::now();
+        assert!(!executor.drain(Some(Duration::from_millis(50))));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let pool = ThreadPoolExecutor::new(2).unwrap();
+        let completed = Arc::new(AtomicUsize::new(0));
+        let completed_clone = Arc::clone(&completed);
+        pool.schedule_once(Duration::from_secs(0), move |_remote| {
+            thread::sleep(Duration::from_millis(100));
+            completed_clone.fetch_add(1, Ordering::Relaxed);
+        });
+        thread::sleep(Duration::from_millis(30));
+        assert!(pool.drain(None));
+        assert_eq!(completed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn shutdown_phase_test() {
+        let executor = CoreExecutor::with_name("shutdown_phase_test").unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let ingest_events = Arc::clone(&events);
+        let ingest = executor.schedule_async_with(FixedRate::new(Duration::from_secs(0), Duration::from_millis(10)), OverlapPolicy::Queue, move |handle| {
+            let events = Arc::clone(&ingest_events);
+            Timeout::new(Duration::from_millis(50), handle).unwrap()
+                .map(move |_| events.lock().unwrap().push("ingest"))
+                .map_err(|_| ())
+        });
+        let flush_events = Arc::clone(&events);
+        let flush = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(5), move |_handle| {
+            flush_events.lock().unwrap().push("flush");
+        });
+        flush.set_shutdown_phase(1);
+        assert_eq!((ingest.shutdown_phase(), flush.shutdown_phase()), (0, 1));
+        thread::sleep(Duration::from_millis(20));
+        events.lock().unwrap().push("drain");
+        assert!(executor.drain(Some(Duration::from_secs(1))));
+        // The flush task keeps running while the execution of the ingest task completes
+        let events = events.lock().unwrap();
+        let drain = events.iter().position(|event| *event == "drain").unwrap();
+        let ingest = events.iter().rposition(|event| *event == "ingest").unwrap();
+        assert!(drain < ingest);
+        assert!(events[drain..ingest].contains(&"flush"));
+    }
+
+    #[test]
+    fn health_test() {
+        let executor = CoreExecutor::with_name("health_test").unwrap();
+        let health = executor.health(Duration::from_millis(50));
+        assert!(health.is_healthy());
+        assert_eq!((health.tasks, health.last_tick, health.restarts), (0, None, 0));
+
+        let blocking = executor.schedule_once(Duration::from_millis(10), |_handle| thread::sleep(Duration::from_millis(300)));
+        let task = executor.schedule_fixed_rate(Duration::from_millis(20), Duration::from_millis(10), |_handle| ());
+        thread::sleep(Duration::from_millis(150));
+        let health = executor.health(Duration::from_millis(50));
+        assert!(health.alive);
+        assert_eq!(health.tasks, 2);
+        assert!(health.last_tick.is_some());
+        assert_eq!(health.overdue.iter().map(|t| t.id()).collect::<Vec<_>>(), vec![task.id()]);
+        assert!(!health.is_healthy());
+
+        thread::sleep(Duration::from_millis(250));
+        assert!(blocking.stopped());
+        assert!(executor.health(Duration::from_millis(50)).is_healthy());
+        executor.stop().wait().unwrap();
+        assert!(!executor.health(Duration::from_millis(50)).alive);
+    }
+
+    #[test]
+    fn supervised_test() {
+        let executor = ExecutorBuilder::new().name("supervised_test").supervised(true).build().unwrap();
+        let counter = Arc::new(AtomicUsize::new(0));
         let counter_clone = Arc::clone(&counter);
-        {
-            let executor = CoreExecutor::new().unwrap();
-            executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    // TODO: use atomic int when available
-                    let counter = {
-                        let mut counter = counter_clone.write().unwrap();
-                        (*counter) += 1;
-                        *counter
-                    };
-                    if counter == 1 {
-                        thread::sleep(Duration::from_secs(3));
-                    }
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
+        let task = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(10), move |_handle| {
+            counter_clone.fetch_add(1, Ordering::Relaxed);
+        });
+        let stopped = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(10), |_handle| ());
+        stopped.stop();
+        thread::sleep(Duration::from_millis(50));
+        executor.spawn(|_handle| -> Result<(), ()> { panic!("reactor panic") });
+        thread::sleep(Duration::from_millis(50));
+        assert!(executor.is_alive());
+        assert_eq!(executor.restarts(), 1);
+        let runs = counter.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(100));
+        assert!(counter.load(Ordering::Relaxed) > runs);
+        assert!(!task.stopped());
+        assert!(stopped.stopped());
+
+        let (tx, rx) = mpsc::channel();
+        executor.schedule_once(Duration::from_millis(10), move |_handle| tx.send(()).unwrap());
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        executor.stop().wait().unwrap();
+    }
+
+    #[test]
+    fn priority_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let due = executor.now() + Duration::from_millis(50);
+        for &(name, priority) in &[("bulk", -1), ("report", 0), ("heartbeat", 10), ("cleanup", 0), ("metrics", 5)] {
+            let order = Arc::clone(&order);
+            let task = executor.schedule_with(Once(Some(due)), move |_handle| order.lock().unwrap().push(name));
+            task.set_priority(priority);
+            assert_eq!(task.priority(), priority);
         }
-        assert_eq!(*counter.read().unwrap(), 6);
+        // Due earlier, but the executor is busy until the others are due
+        let order_clone = Arc::clone(&order);
+        executor.schedule_with(Once(Some(due - Duration::from_millis(10))), move |_handle| {
+            thread::sleep(Duration::from_millis(20));
+            order_clone.lock().unwrap().push("slow");
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(*order.lock().unwrap(), vec!["slow", "heartbeat", "metrics", "report", "cleanup", "bulk"]);
     }
 
     #[test]
-    fn fixed_rate_slow_task_test_pool() {
+    fn earliest_deadline_first_test() {
+        struct Misses(Arc<Mutex<Vec<usize>>>);
+        impl TaskListener for Misses {
+            fn on_deadline_miss(&self, task: &TaskHandle, lateness: Duration) {
+                assert!(lateness > Duration::from_millis(0));
+                self.0.lock().unwrap().push(task.id());
+            }
+        }
+        let executor = ExecutorBuilder::new().earliest_deadline_first(true).build().unwrap();
+        let misses = Arc::new(Mutex::new(Vec::new()));
+        executor.add_listener(Misses(Arc::clone(&misses)));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let due = executor.now() + Duration::from_millis(50);
+        let mut tasks = Vec::new();
+        let tasks_config = [("none", 10, None), ("late", 5, Some(100)), ("later", 0, Some(100)), ("soon", 0, Some(40))];
+        for &(name, priority, deadline) in &tasks_config {
+            let order = Arc::clone(&order);
+            let task = executor.schedule_with(Once(Some(due)), move |_handle| {
+                thread::sleep(Duration::from_millis(10));
+                order.lock().unwrap().push(name);
+            });
+            task.set_priority(priority);
+            task.set_deadline(deadline.map(Duration::from_millis));
+            tasks.push(task);
+        }
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(*order.lock().unwrap(), vec!["soon", "late", "later", "none"]);
+        assert!(tasks.iter().all(|task| task.deadline_misses() == 0));
+
+        // Completes after 10ms, with a deadline of 5ms
+        let (tx, rx) = mpsc::channel();
+        let missed = executor.schedule_once(Duration::from_millis(20), move |_handle| {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(()).unwrap();
+        });
+        missed.set_deadline(Some(Duration::from_millis(5)));
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(missed.deadline_misses(), 1);
+        assert_eq!(*misses.lock().unwrap(), vec![missed.id()]);
+    }
+
+    #[test]
+    fn timer_slack_test() {
+        let executor = ExecutorBuilder::new().timer_slack(Duration::from_millis(100)).build().unwrap();
+        let start = Instant::now();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        for &delay in &[10, 60, 130] {
+            let runs = Arc::clone(&runs);
+            executor.schedule_once(Duration::from_millis(delay), move |_handle| {
+                runs.lock().unwrap().push((delay, Instant::now()));
+            });
+        }
+        thread::sleep(Duration::from_millis(250));
+        let runs = runs.lock().unwrap();
+        assert_eq!(runs.iter().map(|&(delay, _)| delay).collect::<Vec<_>>(), vec![10, 60, 130]);
+        for &(delay, instant) in runs.iter() {
+            assert!(instant - start >= Duration::from_millis(delay));
+        }
+        // The first two together at 100ms, the last one at 200ms
+        assert!(runs[1].1 - runs[0].1 < Duration::from_millis(5));
+        assert!(runs[2].1 - runs[1].1 >= Duration::from_millis(95));
+    }
+
+    #[test]
+    fn coarse_test() {
+        let executor = ExecutorBuilder::new().coarse(Duration::from_millis(100)).build().unwrap();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        for &delay in &[10, 30, 50, 70] {
+            let runs = Arc::clone(&runs);
+            executor.schedule_once(Duration::from_millis(delay), move |_handle| {
+                runs.lock().unwrap().push(SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
+            });
+        }
+        thread::sleep(Duration::from_millis(300));
+        let runs = runs.lock().unwrap();
+        assert_eq!(runs.len(), 4);
+        // At the start of a tenth of second of the system clock
+        for run in runs.iter() {
+            assert!(run.subsec_millis() % 100 < 20, "ran at {:?}", run);
+        }
+        // Due within less than the granularity, so grouped in at most two wakeups
+        let windows = runs.iter().map(|run| run.as_millis() / 100).collect::<HashSet<_>>();
+        assert!(windows.len() <= 2);
+    }
+
+    #[test]
+    fn schedule_all_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let specs = (0..100).map(|i| {
+            let counter = Arc::clone(&counter);
+            let spec = TaskSpec::new(Once(Some(executor.now() + Duration::from_millis(20))), move |_handle| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+            if i % 2 == 0 { spec.named(&format!("task {}", i)) } else { spec }
+        });
+        let handles = executor.schedule_all(specs);
+        assert_eq!(handles.len(), 100);
+        assert_eq!(handles[0].name(), Some("task 0"));
+        assert_eq!(handles[1].name(), None);
+        assert_eq!(executor.tasks().len(), 100);
+        assert_eq!(executor.task("task 42").map(|task| task.id()), Some(handles[42].id()));
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(counter.load(Ordering::SeqCst), 100);
+        assert!(handles.iter().all(|handle| handle.stopped()));
+        assert!(executor.schedule_all(Vec::new()).is_empty());
+
+        executor.stop().wait().unwrap();
+        let handles = executor.schedule_all(vec![TaskSpec::new(FixedRate::new(Duration::from_millis(0), Duration::from_millis(10)), |_handle| ())]);
+        assert!(handles[0].stopped());
+    }
+
+    #[test]
+    fn cancel_tagged_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let schedule = || FixedRate::new(Duration::from_millis(50), Duration::from_millis(100));
+        let handles = executor.schedule_all((0..6).map(|i| {
+            let counter = Arc::clone(&counter);
+            let spec = TaskSpec::new(schedule(), move |_handle| { counter.fetch_add(1, Ordering::SeqCst); });
+            spec.tagged(if i < 4 { "tenant-42" } else { "tenant-7" })
+        }));
+        let untagged = executor.schedule_with(schedule(), |_handle| ());
+        handles[0].add_tag("tenant-42");
+        handles[0].add_tag("first");
+        assert_eq!(handles[0].tags(), vec!["tenant-42", "first"]);
+        assert!(handles[5].has_tag("tenant-7"));
+        assert!(!untagged.has_tag("tenant-42"));
+
+        let cancelled = executor.cancel_tagged("tenant-42");
+        assert_eq!(cancelled.iter().map(|task| task.id()).collect::<Vec<_>>(),
+                   handles[..4].iter().map(|task| task.id()).collect::<Vec<_>>());
+        assert!(handles[..4].iter().all(|task| task.stopped()));
+        assert!(!handles[4].stopped() && !untagged.stopped());
+        assert!(executor.cancel_tagged("tenant-42").is_empty());
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn pause_tagged_test() {
+        struct Started(Arc<Mutex<Vec<usize>>>);
+        impl TaskListener for Started {
+            fn on_start(&self, task: &TaskHandle) {
+                self.0.lock().unwrap().push(task.id());
+            }
+        }
+        let executor = CoreExecutor::new().unwrap();
+        let started = Arc::new(Mutex::new(Vec::new()));
+        executor.add_tagged_listener("reports", Started(Arc::clone(&started)));
+        let start = Instant::now();
+        let schedule = || FixedRate::new(Duration::from_millis(50), Duration::from_millis(100));
+        let reports = (0..2).map(|_| executor.schedule_with(schedule(), |_handle| ())).collect::<Vec<_>>();
+        for report in &reports {
+            report.add_tag("reports");
+        }
+        let other = executor.schedule_with(schedule(), |_handle| ());
+        assert_eq!(executor.tasks_with_tag("reports").len(), 2);
+        assert!(executor.tasks_with_tag("other").is_empty());
+
+        // Paused at 50ms
+        assert_eq!(executor.pause_tagged("reports").len(), 2);
+        assert!(reports.iter().all(|report| report.paused()) && !other.paused());
+        thread::sleep(Duration::from_millis(100).checked_sub(start.elapsed()).unwrap_or_default());
+        assert!(started.lock().unwrap().is_empty());
+        assert_eq!(other.runs(), 1);
+        // Running again at 150ms
+        executor.resume_tagged("reports");
+        thread::sleep(Duration::from_millis(200).checked_sub(start.elapsed()).unwrap_or_default());
+        let mut started = started.lock().unwrap().clone();
+        started.sort();
+        assert_eq!(started, reports.iter().map(|report| report.id()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn shard_test() {
+        let executors = (0..3).map(|index| ExecutorBuilder::new().shard(index, 3).build().unwrap()).collect::<Vec<_>>();
+        let counter = Arc::new(AtomicUsize::new(0));
+        for executor in &executors {
+            for i in 0..10 {
+                let counter = Arc::clone(&counter);
+                let name = format!("task {}", i);
+                let once = Once(Some(executor.now() + Duration::from_millis(20)));
+                let task = executor.schedule_with_named(&name, once, move |_handle| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+                assert_eq!(task.stopped(), !executor.in_shard(&name));
+            }
+            // Unnamed tasks run on every shard
+            let counter = Arc::clone(&counter);
+            executor.schedule_once(Duration::from_millis(0), move |_handle| {
+                counter.fetch_add(100, Ordering::SeqCst);
+            });
+        }
+        thread::sleep(Duration::from_millis(50));
+        // Each named task runs on exactly one of the shards
+        assert_eq!(counter.load(Ordering::SeqCst), 310);
+        for i in 0..10 {
+            let name = format!("task {}", i);
+            assert_eq!(executors.iter().filter(|executor| executor.in_shard(&name)).count(), 1);
+        }
+        assert!(CoreExecutor::new().unwrap().in_shard("task 0"));
+    }
+
+    #[test]
+    fn spawn_test() {
+        let (tx, rx) = mpsc::channel();
+        let executor = CoreExecutor::with_name("spawn_test").unwrap();
+        let tx_clone = tx.clone();
+        executor.spawn(move |handle| {
+            Timeout::new(Duration::from_millis(50), handle).unwrap()
+                .map(move |_| tx_clone.send(thread::current().name().map(|name| name.to_owned())).unwrap())
+                .map_err(|_| ())
+        });
+        executor.remote().spawn(move |_handle| {
+            tx.send(None).unwrap();
+            Ok(())
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), None);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), Some("spawn_test".to_owned()));
+        assert!(executor.tasks().is_empty());
+
+        let pool = ThreadPoolExecutor::new(2).unwrap();
+        let (tx, rx) = mpsc::channel();
+        pool.spawn(move |_handle| tx.send(()).map_err(|_| ()));
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn shutdown_test() {
         let counter = Arc::new(RwLock::new(0));
         let counter_clone = Arc::clone(&counter);
+        let executor = CoreExecutor::new().unwrap();
+        let shutdown = executor.shutdown();
+        let component = executor.clone();
+        drop(executor);
+        thread::spawn(move || {
+            component.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(100), move |_handle| {
+                *counter_clone.write().unwrap() += 1;
+            });
+        }).join().unwrap();
+        thread::sleep(Duration::from_millis(150));
+        assert!(shutdown.stop_with_timeout(Duration::from_millis(500)));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), 2);
+
+        let executor = ThreadPoolExecutor::with_reactors(2, 2, "shutdown_test_").unwrap();
+        executor.shutdown().stop().wait().unwrap();
+    }
+
+    #[test]
+    fn combined_shutdown_test() {
+        let first = CoreExecutor::with_name("combined_shutdown_test_1").unwrap();
+        let second = CoreExecutor::with_name("combined_shutdown_test_2").unwrap();
+        let pool = ThreadPoolExecutor::with_reactors(2, 2, "combined_shutdown_test_").unwrap();
+        let mut shutdown = vec![first.shutdown(), pool.shutdown()].into_iter().collect::<Shutdown>();
+        shutdown.add(second.shutdown());
+        shutdown.add(first.shutdown());
+        // The pool has two scheduling threads, and the first executor is only added once
+        assert_eq!(shutdown.executors.len(), 4);
+        assert!(first.is_alive() && second.is_alive() && pool.is_alive());
+        shutdown.stop().wait().unwrap();
+        assert!(!first.is_alive() && !second.is_alive() && !pool.is_alive());
+        assert!(Shutdown::new().stop_with_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn stop_with_timeout_test() {
+        let executor = ThreadPoolExecutor::with_reactors(2, 2, "stop_timeout_test_").unwrap();
+        executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(100), |_remote| ());
+        assert!(executor.stop_with_timeout(Duration::from_millis(500)));
+
+        let executor = CoreExecutor::new().unwrap();
+        executor.schedule_once(Duration::from_secs(0), |_handle| thread::sleep(Duration::from_millis(500)));
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        assert!(!executor.stop_with_timeout(Duration::from_millis(100)));
+        // The executor thread has been detached, so dropping the executor doesn't block
+        drop(executor);
+        assert!(start.elapsed() < Duration::from_millis(300));
+    }
+
+    #[test]
+    fn drop_behavior_test() {
+        let counter = Arc::new(RwLock::new(0));
         {
-            let executor = ThreadPoolExecutor::new(20).unwrap();
-            executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_remote| {
-                    // TODO: use atomic int when available
-                    let counter = {
-                        let mut counter = counter_clone.write().unwrap();
-                        (*counter) += 1;
-                        *counter
-                    };
-                    if counter == 1 {
-                        thread::sleep(Duration::from_secs(3));
-                    }
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
+            let counter = counter.clone();
+            let executor = ExecutorBuilder::new()
+                .name("detached_executor")
+                .drop_behavior(DropBehavior::Detach)
+                .build()
+                .unwrap();
+            executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(50), move |_handle| {
+                *counter.write().unwrap() += 1;
+            });
         }
-        assert_eq!(*counter.read().unwrap(), 6);
+        thread::sleep(Duration::from_millis(225));
+        // The detached executor keeps running the task after being dropped
+        assert!(*counter.read().unwrap() >= 4);
+
+        let counter = Arc::new(RwLock::new(0));
+        let start = Instant::now();
+        {
+            let counter = counter.clone();
+            let executor = ExecutorBuilder::new().drop_behavior(DropBehavior::Stop).build().unwrap();
+            executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(50), move |_handle| {
+                *counter.write().unwrap() += 1;
+                thread::sleep(Duration::from_millis(300));
+            });
+            thread::sleep(Duration::from_millis(100));
+        }
+        // Dropping doesn't wait for the running task
+        assert!(start.elapsed() < Duration::from_millis(300));
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(*counter.read().unwrap(), 1);
     }
 
     #[test]
-    fn fixed_rate_stop_test() {
-        let counter1 = Arc::new(RwLock::new(0));
-        let counter2 = Arc::new(RwLock::new(0));
-        let counter1_clone = Arc::clone(&counter1);
-        let counter2_clone = Arc::clone(&counter2);
-        {
-            let executor = CoreExecutor::new().unwrap();
-            let t1 = executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    let mut counter = counter1_clone.write().unwrap();
-                    (*counter) += 1;
-                }
-            );
-            executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    let mut counter = counter2_clone.write().unwrap();
-                    (*counter) += 1;
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
-            t1.stop();
-            thread::sleep(Duration::from_millis(5000));
+    fn max_runs_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = counter.clone();
+        let executor = CoreExecutor::new().unwrap();
+        let schedule = FixedRate::new(Duration::from_secs(0), Duration::from_millis(20)).max_runs(3);
+        let handle = executor.schedule_with(schedule, move |_handle| {
+            *counter_clone.write().unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), 3);
+        assert!(handle.stopped());
+        // The schedule has completed, so the closure has been released
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn max_runs_pause_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = counter.clone();
+        let executor = CoreExecutor::new().unwrap();
+        let schedule = FixedRate::new(Duration::from_millis(50), Duration::from_millis(20)).max_runs(3);
+        let handle = executor.schedule_with(schedule, move |_handle| {
+            *counter_clone.write().unwrap() += 1;
+        });
+        handle.pause();
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), 0);
+        assert!(!handle.stopped());
+        // The executions skipped while paused don't count
+        handle.resume();
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), 3);
+        assert!(handle.stopped());
+    }
+
+    #[test]
+    fn for_duration_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = counter.clone();
+        let executor = CoreExecutor::new().unwrap();
+        let schedule = FixedRate::new(Duration::from_secs(0), Duration::from_millis(100))
+            .for_duration(Duration::from_millis(250));
+        let handle = executor.schedule_with(schedule, move |_handle| {
+            *counter_clone.write().unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(*counter.read().unwrap(), 3);
+        assert!(handle.stopped());
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn schedule_with_delays_test() {
+        let (tx, rx) = mpsc::channel();
+        let executor = ThreadPoolExecutor::new(2).unwrap();
+        let start = Instant::now();
+        let delays = (1..4).map(|n| Duration::from_millis(n * 50));
+        let handle = executor.schedule_with_delays(delays, move |_remote| {
+            tx.send(Instant::now()).unwrap();
+        });
+        let executions = rx.iter().collect::<Vec<_>>();
+        assert_eq!(executions.len(), 3);
+        assert!(executions[2] - start >= Duration::from_millis(300));
+        assert!(handle.stopped());
+    }
+
+    #[test]
+    fn pause_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = counter.clone();
+        let executor = CoreExecutor::new().unwrap();
+        let handle = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(100), move |_handle| {
+            *counter_clone.write().unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(*counter.read().unwrap(), 3);
+        handle.pause();
+        assert!(handle.paused());
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(*counter.read().unwrap(), 3);
+        handle.resume();
+        // The executions skipped while paused are not recovered
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*counter.read().unwrap(), 5);
+    }
+
+    #[test]
+    fn set_interval_test() {
+        let counter = Arc::new(RwLock::new(0));
+        let counter_clone = counter.clone();
+        let executor = CoreExecutor::new().unwrap();
+        let handle = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(400), move |_handle| {
+            *counter_clone.write().unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(*counter.read().unwrap(), 1);
+        handle.set_interval(Duration::from_millis(100));
+        // The next execution is still at 400ms, and then every 100ms
+        thread::sleep(Duration::from_millis(450));
+        assert_eq!(*counter.read().unwrap(), 3);
+    }
+
+    #[test]
+    fn tasks_test() {
+        let executor = ThreadPoolExecutor::with_reactors(2, 2, "tasks_test_").unwrap();
+        let first = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(100), |_remote| ());
+        let second = executor.schedule_once(Duration::from_secs(10), |_remote| ());
+        thread::sleep(Duration::from_millis(50));
+
+        let mut tasks = executor.tasks();
+        tasks.sort_by_key(|task| task.id());
+        assert_eq!(tasks.iter().map(|task| task.id()).collect::<Vec<_>>(), vec![first.id(), second.id()]);
+        assert_eq!(tasks[0].interval(), Some(Duration::from_millis(100)));
+        assert_eq!(tasks[0].runs(), 1);
+        assert!(tasks[0].next_run().is_some());
+        assert_eq!(tasks[1].interval(), None);
+        assert_eq!(tasks[1].runs(), 0);
+
+        second.stop();
+        assert_eq!(executor.tasks().len(), 1);
+    }
+
+    #[test]
+    fn named_tasks_test() {
+        let executor = ThreadPoolExecutor::with_reactors(2, 2, "named_tasks_test_").unwrap();
+        executor.schedule_fixed_rate_named("flush", Duration::from_secs(0), Duration::from_millis(100), |_remote| ());
+        executor.schedule_fixed_interval_named("poll", Duration::from_secs(0), Duration::from_millis(100), |_remote| ());
+        executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(100), |_remote| ());
+
+        let flush = executor.task("flush").unwrap();
+        assert_eq!(flush.name(), Some("flush"));
+        assert_eq!(executor.task("poll").unwrap().name(), Some("poll"));
+        assert!(executor.task("missing").is_none());
+
+        assert_eq!(flush.to_string(), "task \"flush\"");
+        flush.stop();
+        assert!(executor.task("flush").is_none());
+        assert_eq!(executor.tasks().len(), 2);
+    }
+
+    #[test]
+    fn load_balancing_test() {
+        let executor = ThreadPoolExecutor::with_reactors(2, 3, "load_balancing_test_").unwrap();
+        let tasks_per_reactor = |executor: &ThreadPoolExecutor| {
+            executor.executors.iter().map(|executor| executor.tasks().len()).collect::<Vec<_>>()
+        };
+        let every = || FixedInterval::new(Duration::from_secs(0), Duration::from_millis(100));
+        assert_eq!(executor.load_balancing(), LoadBalancing::RoundRobin);
+        let tasks = (0..3).map(|_| executor.schedule_with(every(), |_remote| ())).collect::<Vec<_>>();
+        assert_eq!(tasks_per_reactor(&executor), vec![1, 1, 1]);
+
+        executor.set_load_balancing(LoadBalancing::LeastTasks);
+        tasks[0].stop();
+        tasks[2].stop();
+        executor.schedule_with(every(), |_remote| ());
+        executor.schedule_with(every(), |_remote| ());
+        assert_eq!(tasks_per_reactor(&executor), vec![1, 1, 1]);
+
+        // The slow task makes its reactor the busiest one
+        let slow = executor.schedule_with(every(), |_remote| thread::sleep(Duration::from_millis(50)));
+        thread::sleep(Duration::from_millis(80));
+        executor.set_load_balancing(LoadBalancing::LeastBusy);
+        let before = tasks_per_reactor(&executor);
+        for _ in 0..5 {
+            executor.schedule_with(every(), |_remote| ());
         }
-        assert_eq!(*counter1.read().unwrap(), 6);
-        assert_eq!(*counter2.read().unwrap(), 11);
+        let busiest = executor.executors.iter().position(|executor| executor.tasks().iter().any(|task| task.id() == slow.id())).unwrap();
+        assert_eq!(tasks_per_reactor(&executor)[busiest], before[busiest]);
+        assert_eq!(tasks_per_reactor(&executor).iter().sum::<usize>(), 9);
     }
 
     #[test]
-    fn fixed_interval_stop_test() {
-        let counter1 = Arc::new(RwLock::new(0));
-        let counter2 = Arc::new(RwLock::new(0));
-        let counter1_clone = Arc::clone(&counter1);
-        let counter2_clone = Arc::clone(&counter2);
-        {
-            let executor = CoreExecutor::new().unwrap();
-            let t1 = executor.schedule_fixed_interval(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    let mut counter = counter1_clone.write().unwrap();
-                    (*counter) += 1;
-                }
-            );
-            executor.schedule_fixed_rate(
-                Duration::from_secs(0),
-                Duration::from_secs(1),
-                move |_handle| {
-                    let mut counter = counter2_clone.write().unwrap();
-                    (*counter) += 1;
-                }
-            );
-            thread::sleep(Duration::from_millis(5500));
-            t1.stop();
-            thread::sleep(Duration::from_millis(5000));
+    fn work_stealing_test() {
+        let executor = ThreadPoolExecutor::with_work_stealing(2, "work_stealing_test_").unwrap();
+        let start = Instant::now();
+        executor.schedule_once(Duration::from_millis(0), |_remote| thread::sleep(Duration::from_millis(300)));
+        let fast = executor.schedule_fixed_rate(Duration::from_millis(10), Duration::from_millis(20), |_remote| ());
+        let panicking = executor.schedule_fixed_rate(Duration::from_millis(10), Duration::from_millis(50), |_remote| panic!("stealing panic"));
+        // The fast task keeps running on the idle worker
+        thread::sleep(Duration::from_millis(200).checked_sub(start.elapsed()).unwrap_or_default());
+        assert!(fast.runs() >= 8, "{} runs", fast.runs());
+        assert!(panicking.panics() >= 3);
+        assert!(!panicking.stopped());
+    }
+
+    #[test]
+    fn blocking_test() {
+        let executor = ExecutorBuilder::new().name("blocking_test").blocking_threads(1).build().unwrap();
+        let start = Instant::now();
+        let thread_name = Arc::new(Mutex::new(None));
+        let thread_name_clone = Arc::clone(&thread_name);
+        let blocking = executor.schedule_fixed_rate_blocking(Duration::from_millis(0), Duration::from_millis(50), move |_remote| {
+            *thread_name_clone.lock().unwrap() = thread::current().name().map(|name| name.to_owned());
+            thread::sleep(Duration::from_millis(100));
+        });
+        let fast = executor.schedule_fixed_rate(Duration::from_millis(10), Duration::from_millis(20), |_handle| ());
+        // The executions of the blocking task don't overlap, and don't delay the other task
+        thread::sleep(Duration::from_millis(250).checked_sub(start.elapsed()).unwrap_or_default());
+        assert_eq!(blocking.runs(), 3);
+        assert!(fast.runs() >= 10, "{} runs", fast.runs());
+        assert!(thread_name.lock().unwrap().as_ref().is_some_and(|name| name.starts_with("blocking_test_blocking_")));
+    }
+
+    #[test]
+    fn thread_builder_test() {
+        let executor = ExecutorBuilder::new()
+            .stack_size(16 << 20)
+            .thread_builder(|builder| builder.name("custom_thread".to_owned()))
+            .build()
+            .unwrap();
+        let thread_name = executor.execute(|_handle| thread::current().name().map(|name| name.to_owned()));
+        assert_eq!(thread_name.wait().unwrap(), Some("custom_thread".to_owned()));
+        // Larger than the default stack
+        let large = executor.execute(|_handle| std::hint::black_box([1u8; 4 << 20]).iter().map(|&b| b as usize).sum::<usize>());
+        assert_eq!(large.wait().unwrap(), 4 << 20);
+        let blocking = executor.schedule_blocking_with(Once(Some(Instant::now())), |_remote| {
+            std::hint::black_box([0u8; 4 << 20]);
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(blocking.runs(), 1);
+    }
+
+    #[test]
+    fn high_resolution_test() {
+        let executor = ExecutorBuilder::new().high_resolution(Duration::from_millis(3)).build().unwrap();
+        let task = executor.schedule_fixed_rate(Duration::from_millis(10), Duration::from_millis(10), |_handle| ());
+        thread::sleep(Duration::from_millis(205));
+        let stats = task.stats();
+        assert!(stats.executions >= 15);
+        // Generous bound, the actual lag depends on the load of the machine
+        assert!(stats.mean_lag.unwrap() < Duration::from_millis(5), "mean lag {:?}", stats.mean_lag);
+    }
+
+    #[test]
+    fn keyed_test() {
+        let executor = ThreadPoolExecutor::new(4).unwrap();
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..12 {
+            let key = i % 3;
+            let threads = Arc::clone(&threads);
+            executor.schedule_fixed_rate_keyed(&format!("tenant-{}", key), Duration::from_secs(0), Duration::from_millis(20), move |_remote| {
+                threads.lock().unwrap().push((key, thread::current().id()));
+            });
         }
-        assert_eq!(*counter1.read().unwrap(), 6);
-        assert_eq!(*counter2.read().unwrap(), 11);
+        thread::sleep(Duration::from_millis(100));
+        let threads = threads.lock().unwrap();
+        assert!(threads.len() >= 12);
+        for &(key, id) in threads.iter() {
+            let first = threads.iter().find(|&&(k, _)| k == key).unwrap().1;
+            assert_eq!(id, first);
+        }
+    }
+
+    #[test]
+    fn stats_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let slow = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(200), |_handle| {
+            thread::sleep(Duration::from_millis(50));
+        });
+        let delayed = executor.schedule_once(Duration::from_millis(10), |_handle| ());
+        assert_eq!(slow.stats().executions, 0);
+        assert_eq!(slow.stats().mean_duration, None);
+        thread::sleep(Duration::from_millis(300));
+
+        let stats = slow.stats();
+        assert_eq!(stats.executions, 2);
+        assert!(stats.last_duration.unwrap() >= Duration::from_millis(50));
+        assert!(stats.mean_duration.unwrap() >= Duration::from_millis(50));
+        assert!(stats.max_duration.unwrap() < Duration::from_millis(150));
+        // The second task had to wait for the first execution of the slow one
+        let stats = delayed.stats();
+        assert_eq!(stats.executions, 1);
+        assert!(stats.last_lag.unwrap() >= Duration::from_millis(30));
+        assert_eq!(stats.max_lag, stats.last_lag);
+    }
+
+    #[derive(Default)]
+    struct CountingListener {
+        events: Mutex<Vec<(String, &'static str)>>,
+    }
+
+    impl TaskListener for Arc<CountingListener> {
+        fn on_start(&self, task: &TaskHandle) {
+            self.events.lock().unwrap().push((task.to_string(), "start"));
+        }
+
+        fn on_complete(&self, task: &TaskHandle, _duration: Duration) {
+            self.events.lock().unwrap().push((task.to_string(), "complete"));
+        }
+
+        fn on_panic(&self, task: &TaskHandle, _message: &str) {
+            self.events.lock().unwrap().push((task.to_string(), "panic"));
+        }
+    }
+
+    #[test]
+    fn listener_test() {
+        let listener = Arc::new(CountingListener::default());
+        let executor = CoreExecutor::new().unwrap();
+        executor.add_listener(listener.clone());
+        executor.schedule_with_named("ok", FixedRate::new(Duration::from_secs(0), Duration::from_millis(100)).max_runs(2), |_handle| ());
+        thread::sleep(Duration::from_millis(50));
+        executor.schedule_with_named("panic", Once(Some(Instant::now())), |_handle| panic!("failure"));
+        thread::sleep(Duration::from_millis(150));
+
+        let events = listener.events.lock().unwrap().iter()
+            .map(|&(ref task, event)| format!("{} {}", task, event))
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![
+            "task \"ok\" start", "task \"ok\" complete",
+            "task \"panic\" start", "task \"panic\" panic",
+            "task \"ok\" start", "task \"ok\" complete",
+        ]);
+    }
+
+    #[test]
+    fn watchdog_test() {
+        let (tx, rx) = mpsc::channel();
+        let executor = ThreadPoolExecutor::new(2).unwrap();
+        executor.set_watchdog(Duration::from_millis(100), move |task, running_for| {
+            tx.send((task.name().map(|name| name.to_owned()), running_for)).unwrap();
+        }).unwrap();
+        executor.schedule_fixed_rate_named("fast", Duration::from_secs(0), Duration::from_millis(50), |_remote| ());
+        let schedule = FixedRate::new(Duration::from_secs(0), Duration::from_millis(50)).max_runs(1);
+        let stuck = executor.schedule_with_named("stuck", schedule, |_remote| {
+            thread::sleep(Duration::from_millis(400));
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert!(stuck.running_for().unwrap() >= Duration::from_millis(100));
+
+        let (name, running_for) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(name.as_deref(), Some("stuck"));
+        assert!(running_for >= Duration::from_millis(100));
+        // Every execution is reported only once
+        thread::sleep(Duration::from_millis(100));
+        assert!(rx.try_recv().is_err());
     }
 }
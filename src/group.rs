@@ -0,0 +1,182 @@
+//! Groups of related schedules, owned together. A `ScheduleGroup` holds the handles of its
+//! tasks: the whole group can be paused, resumed and stopped at once, and dropping the group
+//! stops all its tasks, so that the lifetime of the schedules can be tied to the component that
+//! created them:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::group::ScheduleGroup;
+//! # use std::time::Duration;
+//! let executor = CoreExecutor::new().unwrap();
+//! let group = ScheduleGroup::new();
+//! group.add(executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_secs(10), |_handle| {
+//!     // Refresh a cache
+//! }));
+//! group.add(executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_secs(60), |_handle| {
+//!     // Send a report
+//! }));
+//! group.pause();
+//! // Both tasks are stopped when the group is dropped
+//! drop(group);
+//! ```
+//!
+//! Not to be confused with [`TaskGroup`], which spreads the executions of a changing set of
+//! tasks over an interval.
+//!
+//! [`TaskGroup`]: ../task_group/trait.TaskGroup.html
+use executor::TaskHandle;
+
+use std::fmt;
+use std::mem;
+use std::sync::Mutex;
+
+struct GroupState {
+    tasks: Vec<TaskHandle>,
+    paused: bool,
+}
+
+/// Owns a set of tasks, that are paused, resumed and stopped together. See the
+/// [module documentation].
+///
+/// [module documentation]: index.html
+pub struct ScheduleGroup {
+    state: Mutex<GroupState>,
+}
+
+impl ScheduleGroup {
+    /// Creates a new empty group.
+    pub fn new() -> ScheduleGroup {
+        ScheduleGroup { state: Mutex::new(GroupState { tasks: Vec::new(), paused: false }) }
+    }
+
+    /// Adds the task to the group, and returns its handle. The task is paused if the group is
+    /// paused, and stopped if the group is dropped.
+    pub fn add(&self, task: TaskHandle) -> TaskHandle {
+        let mut state = self.state.lock().unwrap();
+        if state.paused {
+            task.pause();
+        }
+        state.tasks.retain(|task| !task.stopped());
+        state.tasks.push(task.clone());
+        task
+    }
+
+    /// Removes the task from the group, without stopping it. Returns true if the task was in
+    /// the group.
+    pub fn remove(&self, task: &TaskHandle) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let len = state.tasks.len();
+        state.tasks.retain(|t| t.id() != task.id());
+        state.tasks.len() != len
+    }
+
+    /// Returns the handles of the active tasks of the group, in the order in which they were
+    /// added.
+    pub fn tasks(&self) -> Vec<TaskHandle> {
+        let mut state = self.state.lock().unwrap();
+        state.tasks.retain(|task| !task.stopped());
+        state.tasks.clone()
+    }
+
+    /// Pauses all the tasks of the group, including the ones added later, until the group is
+    /// resumed. See `TaskHandle::pause`.
+    pub fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = true;
+        for task in &state.tasks {
+            task.pause();
+        }
+    }
+
+    /// Resumes all the tasks of the group.
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = false;
+        for task in &state.tasks {
+            task.resume();
+        }
+    }
+
+    /// Returns true if the group is paused.
+    pub fn paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    /// Stops all the tasks of the group, and removes them from it.
+    pub fn stop(&self) {
+        let tasks = mem::take(&mut self.state.lock().unwrap().tasks);
+        for task in tasks {
+            task.stop();
+        }
+    }
+
+    /// Releases the tasks of the group, that will keep running after the group is dropped, and
+    /// returns their handles.
+    pub fn detach(self) -> Vec<TaskHandle> {
+        mem::take(&mut self.state.lock().unwrap().tasks)
+    }
+}
+
+impl Default for ScheduleGroup {
+    fn default() -> ScheduleGroup {
+        ScheduleGroup::new()
+    }
+}
+
+impl Drop for ScheduleGroup {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl fmt::Debug for ScheduleGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("ScheduleGroup")
+            .field("tasks", &state.tasks.len())
+            .field("paused", &state.paused)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use executor::CoreExecutor;
+    use super::ScheduleGroup;
+
+    #[test]
+    fn group_test() {
+        let executor = CoreExecutor::new().unwrap();
+        let group = ScheduleGroup::new();
+        let start = Instant::now();
+        let every = |_| executor.schedule_fixed_rate(Duration::from_millis(50), Duration::from_millis(100), |_handle| ());
+        let tasks = (0..3).map(every).map(|task| group.add(task)).collect::<Vec<_>>();
+        assert_eq!(group.tasks().len(), 3);
+
+        // Paused at 50ms, including the task added while paused
+        group.pause();
+        let late = group.add(every(3));
+        assert!(late.paused());
+        thread::sleep(Duration::from_millis(100).checked_sub(start.elapsed()).unwrap_or_default());
+        assert!(tasks.iter().chain(Some(&late)).all(|task| task.runs() == 0));
+
+        // Running at 150ms
+        group.resume();
+        assert!(group.remove(&late));
+        assert!(!group.remove(&late));
+        thread::sleep(Duration::from_millis(200).checked_sub(start.elapsed()).unwrap_or_default());
+        assert!(tasks.iter().chain(Some(&late)).all(|task| task.runs() == 1));
+
+        drop(group);
+        assert!(tasks.iter().all(|task| task.stopped()));
+        assert!(!late.stopped());
+
+        let group = ScheduleGroup::new();
+        let detached = group.add(every(4));
+        assert_eq!(group.detach().len(), 1);
+        assert!(!detached.stopped());
+    }
+}
@@ -0,0 +1,446 @@
+//! Composable wrappers for the scheduled functions. A [`Layer`] wraps a function, adding some
+//! behavior around each of its executions, such as logging or timing. Layers are stacked using
+//! [`Layers`], and the result is a function that can be scheduled on any executor:
+//!
+//! ```rust,no_run
+//! # extern crate scheduled_executor;
+//! # extern crate tokio_core;
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::layer::{Layers, Logging, Timing};
+//! # use std::time::Duration;
+//! # use tokio_core::reactor::Handle;
+//! # fn main() {
+//! let executor = CoreExecutor::new().unwrap();
+//! let flush = Layers::new()
+//!     .layer(Logging::new("flush"))
+//!     .layer(Timing::new(|duration| println!("Flushed in {:?}", duration)))
+//!     .wrap(|_handle: &Handle| {
+//!         // Flush
+//!     });
+//! executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_secs(10), flush);
+//! # }
+//! ```
+//!
+//! Custom layers can be created by implementing the [`Layer`] and [`TaskFn`] traits. Functions
+//! returning a `Result` can be retried on failure using [`Retry`], before being wrapped in other
+//! layers. For functions returning a future, [`timeout`] limits the duration of each execution.
+//!
+//! [`Layer`]: trait.Layer.html
+//! [`Layers`]: struct.Layers.html
+//! [`TaskFn`]: trait.TaskFn.html
+//! [`Retry`]: struct.Retry.html
+//! [`timeout`]: fn.timeout.html
+use futures::future::{self, Either, Future};
+use tokio_core::reactor::Handle;
+
+use clock;
+
+use std::cmp;
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A function that can be scheduled, taking a reference to an `A` (the `Handle` for the
+/// `CoreExecutor`, and the `Remote` for the `ThreadPoolExecutor`). It is implemented for all
+/// the closures taking a reference to `A`.
+pub trait TaskFn<A: ?Sized>: Send + 'static {
+    /// Executes the function.
+    fn call(&mut self, arg: &A);
+}
+
+impl<A: ?Sized, F> TaskFn<A> for F
+    where F: FnMut(&A) + Send + 'static
+{
+    fn call(&mut self, arg: &A) {
+        self(arg)
+    }
+}
+
+/// Wraps a function into another function.
+pub trait Layer<A: ?Sized, T: TaskFn<A>> {
+    /// The wrapped function.
+    type Wrapped: TaskFn<A>;
+
+    /// Wraps the given function.
+    fn wrap(&self, inner: T) -> Self::Wrapped;
+}
+
+/// The layer that doesn't modify the function.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl<A: ?Sized, T: TaskFn<A>> Layer<A, T> for Identity {
+    type Wrapped = T;
+
+    fn wrap(&self, inner: T) -> T {
+        inner
+    }
+}
+
+/// Two layers, one wrapping the other.
+#[derive(Debug, Clone)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<A, T, Inner, Outer> Layer<A, T> for Stack<Inner, Outer>
+    where A: ?Sized,
+          T: TaskFn<A>,
+          Inner: Layer<A, T>,
+          Outer: Layer<A, Inner::Wrapped>
+{
+    type Wrapped = Outer::Wrapped;
+
+    fn wrap(&self, inner: T) -> Outer::Wrapped {
+        self.outer.wrap(self.inner.wrap(inner))
+    }
+}
+
+/// A stack of layers. The layers added first are the outermost ones.
+#[derive(Debug, Clone)]
+pub struct Layers<L> {
+    layer: L,
+}
+
+impl Default for Layers<Identity> {
+    fn default() -> Layers<Identity> {
+        Layers { layer: Identity }
+    }
+}
+
+impl Layers<Identity> {
+    /// Creates an empty stack of layers.
+    pub fn new() -> Layers<Identity> {
+        Layers::default()
+    }
+}
+
+impl<L> Layers<L> {
+    /// Adds a layer, wrapping the function inside all the layers added so far.
+    pub fn layer<N>(self, layer: N) -> Layers<Stack<N, L>> {
+        Layers { layer: Stack { inner: layer, outer: self.layer } }
+    }
+
+    /// Wraps the function in all the layers, returning a closure that can be scheduled.
+    pub fn wrap<A, T>(&self, scheduled_fn: T) -> impl FnMut(&A) + Send + 'static
+        where A: ?Sized + 'static,
+              T: TaskFn<A>,
+              L: Layer<A, T>
+    {
+        let mut wrapped = self.layer.wrap(scheduled_fn);
+        move |arg| wrapped.call(arg)
+    }
+}
+
+/// Logs the beginning and the end of every execution, at debug level.
+#[derive(Debug, Clone)]
+pub struct Logging {
+    name: Arc<str>,
+}
+
+impl Logging {
+    /// Creates a new logging layer, using `name` to identify the function in the logs.
+    pub fn new(name: &str) -> Logging {
+        Logging { name: Arc::from(name) }
+    }
+}
+
+/// A function wrapped by `Logging`.
+pub struct Logged<T> {
+    name: Arc<str>,
+    inner: T,
+}
+
+impl<A: ?Sized, T: TaskFn<A>> Layer<A, T> for Logging {
+    type Wrapped = Logged<T>;
+
+    fn wrap(&self, inner: T) -> Logged<T> {
+        Logged { name: Arc::clone(&self.name), inner }
+    }
+}
+
+impl<A: ?Sized, T: TaskFn<A>> TaskFn<A> for Logged<T> {
+    fn call(&mut self, arg: &A) {
+        debug!("Running {}", self.name);
+        let clock = clock::current();
+        let start = clock.now();
+        self.inner.call(arg);
+        debug!("{} completed in {:?}", self.name, clock.now().saturating_duration_since(start));
+    }
+}
+
+/// Measures the duration of every execution, and passes it to a callback.
+pub struct Timing<C> {
+    callback: Arc<C>,
+}
+
+impl<C: Fn(Duration) + Send + Sync + 'static> Timing<C> {
+    /// Creates a new timing layer, that will call `callback` after every execution.
+    pub fn new(callback: C) -> Timing<C> {
+        Timing { callback: Arc::new(callback) }
+    }
+}
+
+/// A function wrapped by `Timing`.
+pub struct Timed<T, C> {
+    callback: Arc<C>,
+    inner: T,
+}
+
+impl<A, T, C> Layer<A, T> for Timing<C>
+    where A: ?Sized,
+          T: TaskFn<A>,
+          C: Fn(Duration) + Send + Sync + 'static
+{
+    type Wrapped = Timed<T, C>;
+
+    fn wrap(&self, inner: T) -> Timed<T, C> {
+        Timed { callback: Arc::clone(&self.callback), inner }
+    }
+}
+
+impl<A, T, C> TaskFn<A> for Timed<T, C>
+    where A: ?Sized,
+          T: TaskFn<A>,
+          C: Fn(Duration) + Send + Sync + 'static
+{
+    fn call(&mut self, arg: &A) {
+        let clock = clock::current();
+        let start = clock.now();
+        self.inner.call(arg);
+        (self.callback)(clock.now().saturating_duration_since(start));
+    }
+}
+
+/// Retries a fallible function within the same scheduled execution, so that transient failures
+/// don't have to wait for the next execution to be retried. Note that the executing thread is
+/// blocked while waiting between attempts, so on a `CoreExecutor` the other tasks will be
+/// delayed as well.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    max_attempts: usize,
+    delay: Duration,
+    multiplier: f64,
+    max_delay: Duration,
+}
+
+impl Retry {
+    /// Creates a new retry policy, running the function up to `max_attempts` times per
+    /// execution, without waiting between attempts.
+    pub fn new(max_attempts: usize) -> Retry {
+        Retry {
+            max_attempts: cmp::max(max_attempts, 1),
+            delay: Duration::from_secs(0),
+            multiplier: 1.0,
+            max_delay: Duration::from_secs(0),
+        }
+    }
+
+    /// Waits `delay` between attempts.
+    pub fn delay(mut self, delay: Duration) -> Retry {
+        self.delay = delay;
+        self.max_delay = cmp::max(self.max_delay, delay);
+        self
+    }
+
+    /// Multiplies the delay by `multiplier` after every failed attempt, up to `max_delay`.
+    ///
+    /// Panics if `multiplier` is less than 1.
+    pub fn backoff(mut self, multiplier: f64, max_delay: Duration) -> Retry {
+        assert!(multiplier >= 1.0, "The backoff multiplier should be at least 1");
+        self.multiplier = multiplier;
+        self.max_delay = cmp::max(self.delay, max_delay);
+        self
+    }
+
+    fn next_delay(&self, delay: Duration) -> Duration {
+        let increased = delay.as_secs_f64() * self.multiplier;
+        if increased >= self.max_delay.as_secs_f64() {
+            self.max_delay
+        } else {
+            Duration::from_secs_f64(increased)
+        }
+    }
+
+    /// Wraps a fallible function. If all the attempts of an execution fail, the last error is
+    /// logged, and the function will run again at its next scheduled execution.
+    pub fn wrap<A, E, F>(&self, mut scheduled_fn: F) -> impl FnMut(&A) + Send + 'static
+        where A: ?Sized + 'static,
+              E: fmt::Debug,
+              F: FnMut(&A) -> Result<(), E> + Send + 'static
+    {
+        let policy = self.clone();
+        move |arg| {
+            let mut delay = policy.delay;
+            for attempt in 1..=policy.max_attempts {
+                match scheduled_fn(arg) {
+                    Ok(()) => return,
+                    Err(e) if attempt == policy.max_attempts => {
+                        error!("Execution failed after {} attempts: {:?}", attempt, e);
+                    },
+                    Err(e) => {
+                        warn!("Attempt {} failed, retrying in {:?}: {:?}", attempt, delay, e);
+                        thread::sleep(delay);
+                        delay = policy.next_delay(delay);
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Wraps a function returning a future, limiting the duration of each execution. If the future
+/// doesn't complete within `timeout`, it's dropped, `on_timeout` is called, and the execution
+/// is considered complete: the task will keep running according to its schedule. The timeout
+/// is measured with the clock of the executor.
+pub fn timeout<F, R, C>(timeout: Duration, on_timeout: C, mut scheduled_fn: F)
+    -> impl FnMut(&Handle) -> Box<dyn Future<Item=(), Error=()>> + Send + 'static
+    where F: FnMut(&Handle) -> R + Send + 'static,
+          R: Future<Item=(), Error=()> + 'static,
+          C: Fn() + Send + Sync + 'static
+{
+    let on_timeout = Arc::new(on_timeout);
+    move |handle| {
+        let execution = scheduled_fn(handle);
+        let clock = clock::current();
+        let timer = clock.sleep_until(clock.now() + timeout, handle);
+        let on_timeout = Arc::clone(&on_timeout);
+        Box::new(execution.select2(timer).then(move |result| -> Box<dyn Future<Item=(), Error=()>> {
+            match result {
+                Ok(Either::B(_)) => {
+                    warn!("Execution timed out after {:?}", timeout);
+                    on_timeout();
+                    Box::new(future::ok(()))
+                },
+                Err(Either::B((e, execution))) => {
+                    error!("Failed to create execution timeout: {}", e);
+                    Box::new(execution)
+                },
+                Ok(Either::A(_)) | Err(Either::A(_)) => Box::new(future::ok(())),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use futures::future;
+
+    use executor::{CoreExecutor, OverlapPolicy};
+    use testing::TestExecutor;
+    use super::{timeout, Layer, Layers, Logging, Retry, TaskFn, Timing};
+
+    /// Records its name before and after each execution.
+    struct Tracing {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Traced<T> {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+        inner: T,
+    }
+
+    impl<T: TaskFn<u32>> Layer<u32, T> for Tracing {
+        type Wrapped = Traced<T>;
+
+        fn wrap(&self, inner: T) -> Traced<T> {
+            Traced { name: self.name, events: Arc::clone(&self.events), inner }
+        }
+    }
+
+    impl<T: TaskFn<u32>> TaskFn<u32> for Traced<T> {
+        fn call(&mut self, arg: &u32) {
+            self.events.lock().unwrap().push(format!("{} before", self.name));
+            self.inner.call(arg);
+            self.events.lock().unwrap().push(format!("{} after", self.name));
+        }
+    }
+
+    #[test]
+    fn layers_test() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let durations = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = Arc::clone(&events);
+        let durations_clone = Arc::clone(&durations);
+        let mut wrapped = Layers::new()
+            .layer(Tracing { name: "outer", events: Arc::clone(&events) })
+            .layer(Logging::new("test"))
+            .layer(Timing::new(move |duration| durations_clone.lock().unwrap().push(duration)))
+            .layer(Tracing { name: "inner", events: Arc::clone(&events) })
+            .wrap(move |arg: &u32| events_clone.lock().unwrap().push(format!("call {}", arg)));
+        wrapped(&1);
+        wrapped(&2);
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            "outer before", "inner before", "call 1", "inner after", "outer after",
+            "outer before", "inner before", "call 2", "inner after", "outer after",
+        ]);
+        assert_eq!(durations.lock().unwrap().len(), 2);
+        assert!(durations.lock().unwrap().iter().all(|&duration| duration < Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn retry_test() {
+        let attempts = Arc::new(Mutex::new(0));
+        let attempts_clone = Arc::clone(&attempts);
+        let mut wrapped = Retry::new(4)
+            .delay(Duration::from_millis(10))
+            .backoff(2.0, Duration::from_millis(25))
+            .wrap(move |fail_until: &u32| {
+                let mut attempts = attempts_clone.lock().unwrap();
+                *attempts += 1;
+                if *attempts < *fail_until { Err("failure") } else { Ok(()) }
+            });
+
+        // Succeeds at the third attempt, after waiting 10ms and 20ms
+        let start = Instant::now();
+        wrapped(&3);
+        assert_eq!(*attempts.lock().unwrap(), 3);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // Gives up after four attempts, the last delay is capped to 25ms
+        *attempts.lock().unwrap() = 0;
+        let start = Instant::now();
+        wrapped(&10);
+        assert_eq!(*attempts.lock().unwrap(), 4);
+        assert!(start.elapsed() >= Duration::from_millis(55));
+    }
+
+    #[test]
+    fn timeout_test() {
+        let timeouts = Arc::new(Mutex::new(0));
+        let timeouts_clone = Arc::clone(&timeouts);
+        let executor = CoreExecutor::new().unwrap();
+        let handle = executor.schedule_fixed_rate_async(
+            Duration::from_secs(0),
+            Duration::from_millis(100),
+            OverlapPolicy::Queue,
+            timeout(Duration::from_millis(50), move || *timeouts_clone.lock().unwrap() += 1, |_handle| future::empty()),
+        );
+        thread::sleep(Duration::from_millis(300));
+        // The executions never complete, but the task keeps running
+        assert_eq!(*timeouts.lock().unwrap(), 3);
+        assert_eq!(handle.stats().executions, 3);
+    }
+
+    #[test]
+    fn virtual_timeout_test() {
+        let timeouts = Arc::new(Mutex::new(0));
+        let timeouts_clone = Arc::clone(&timeouts);
+        let mut execution = timeout(Duration::from_secs(5), move || *timeouts_clone.lock().unwrap() += 1, |_handle| future::empty());
+        let executor = TestExecutor::new().unwrap();
+        executor.schedule_once(Duration::from_secs(0), move |handle| handle.spawn(execution(handle)));
+        executor.advance(Duration::from_secs(4));
+        assert_eq!(*timeouts.lock().unwrap(), 0);
+        executor.advance(Duration::from_secs(1));
+        assert_eq!(*timeouts.lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Leader election for clustered schedulers. When several instances of a service run the same
+//! schedules, the cluster-wide tasks should only run on the current leader. The election itself
+//! is left to a [`LeadershipProvider`], which can be implemented on top of etcd, Consul or
+//! Kubernetes leases; the executor only asks it whether this instance is the leader before each
+//! execution:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::leadership::{LeaderFlag, Leadership};
+//! # use scheduled_executor::schedule::FixedRate;
+//! # use std::time::Duration;
+//! // Updated by the lease renewal loop of the application
+//! let flag = LeaderFlag::new();
+//! let leadership = Leadership::new(flag.clone());
+//! leadership.on_lost(|| println!("No longer the leader"));
+//!
+//! let executor = CoreExecutor::new().unwrap();
+//! let every_minute = FixedRate::new(Duration::from_secs(0), Duration::from_secs(60));
+//! executor.schedule_cluster_wide(every_minute, &leadership, |_handle| {
+//!     // Runs on the leader only
+//! });
+//! flag.set_leader(true);
+//! ```
+//!
+//! Unlike the [`coordination`] module, leadership doesn't guarantee that each execution happens
+//! exactly once: an execution can be missed, or run twice, while the leadership moves between
+//! instances.
+//!
+//! [`LeadershipProvider`]: trait.LeadershipProvider.html
+//! [`coordination`]: ../coordination/index.html
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A function called on a change of leadership.
+pub type LeadershipCallback = Box<dyn Fn() + Send + Sync>;
+
+/// Tells whether this instance is the leader of the cluster.
+pub trait LeadershipProvider: Send + Sync + 'static {
+    /// Returns true if this instance currently holds the leadership.
+    fn is_leader(&self) -> bool;
+
+    /// Registers a function to call when this instance becomes the leader. Providers that don't
+    /// notify the changes of leadership can keep the default implementation, which does nothing.
+    fn on_gained(&self, _callback: LeadershipCallback) {}
+
+    /// Registers a function to call when this instance stops being the leader. Providers that
+    /// don't notify the changes of leadership can keep the default implementation, which does
+    /// nothing.
+    fn on_lost(&self, _callback: LeadershipCallback) {}
+}
+
+#[derive(Default)]
+struct FlagState {
+    leader: AtomicBool,
+    on_gained: Mutex<Vec<LeadershipCallback>>,
+    on_lost: Mutex<Vec<LeadershipCallback>>,
+}
+
+/// A provider whose leadership is set by the application, for instance from the loop renewing
+/// a lease. Cloning it returns a new reference to the same flag.
+#[derive(Clone, Default)]
+pub struct LeaderFlag {
+    state: Arc<FlagState>,
+}
+
+impl LeaderFlag {
+    /// Creates a new flag, initially not the leader.
+    pub fn new() -> LeaderFlag {
+        LeaderFlag::default()
+    }
+
+    /// Sets whether this instance is the leader, calling the registered functions if the
+    /// leadership changed.
+    pub fn set_leader(&self, leader: bool) {
+        if self.state.leader.swap(leader, Ordering::SeqCst) == leader {
+            return;
+        }
+        let callbacks = if leader { &self.state.on_gained } else { &self.state.on_lost };
+        for callback in callbacks.lock().unwrap().iter() {
+            callback();
+        }
+    }
+}
+
+impl LeadershipProvider for LeaderFlag {
+    fn is_leader(&self) -> bool {
+        self.state.leader.load(Ordering::SeqCst)
+    }
+
+    fn on_gained(&self, callback: LeadershipCallback) {
+        self.state.on_gained.lock().unwrap().push(callback);
+    }
+
+    fn on_lost(&self, callback: LeadershipCallback) {
+        self.state.on_lost.lock().unwrap().push(callback);
+    }
+}
+
+/// Restricts the execution of functions to the leader of the cluster, according to a
+/// `LeadershipProvider`. See the [module documentation]. Cloning it returns a new reference to
+/// the same provider.
+///
+/// [module documentation]: index.html
+#[derive(Clone)]
+pub struct Leadership {
+    provider: Arc<dyn LeadershipProvider>,
+}
+
+impl Leadership {
+    /// Creates a new `Leadership` from the provider, logging the changes of leadership.
+    pub fn new<P: LeadershipProvider>(provider: P) -> Leadership {
+        provider.on_gained(Box::new(|| info!("Leadership gained, running the cluster-wide tasks")));
+        provider.on_lost(Box::new(|| info!("Leadership lost, skipping the cluster-wide tasks")));
+        Leadership { provider: Arc::new(provider) }
+    }
+
+    /// Returns true if this instance is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.provider.is_leader()
+    }
+
+    /// Calls `callback` whenever this instance becomes the leader.
+    pub fn on_gained<F: Fn() + Send + Sync + 'static>(&self, callback: F) {
+        self.provider.on_gained(Box::new(callback))
+    }
+
+    /// Calls `callback` whenever this instance stops being the leader.
+    pub fn on_lost<F: Fn() + Send + Sync + 'static>(&self, callback: F) {
+        self.provider.on_lost(Box::new(callback))
+    }
+
+    /// Wraps the function, so that it only runs while this instance is the leader.
+    pub fn wrap<A, F>(&self, mut scheduled_fn: F) -> impl FnMut(&A) + Send + 'static
+        where A: ?Sized,
+              F: FnMut(&A) + Send + 'static
+    {
+        let provider = Arc::clone(&self.provider);
+        move |arg| {
+            if provider.is_leader() {
+                scheduled_fn(arg);
+            } else {
+                debug!("Not the leader, skipping the execution");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use executor::CoreExecutor;
+    use schedule::FixedRate;
+    use super::{LeaderFlag, Leadership};
+
+    #[test]
+    fn leader_flag_test() {
+        let flag = LeaderFlag::new();
+        let leadership = Leadership::new(flag.clone());
+        let changes = Arc::new(AtomicUsize::new(0));
+        let gained = Arc::clone(&changes);
+        leadership.on_gained(move || { gained.fetch_add(1, Ordering::SeqCst); });
+        let lost = Arc::clone(&changes);
+        leadership.on_lost(move || { lost.fetch_add(10, Ordering::SeqCst); });
+
+        assert!(!leadership.is_leader());
+        flag.set_leader(true);
+        flag.set_leader(true);
+        assert!(leadership.is_leader());
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+        flag.set_leader(false);
+        flag.set_leader(false);
+        assert!(!leadership.is_leader());
+        assert_eq!(changes.load(Ordering::SeqCst), 11);
+    }
+
+    #[test]
+    fn schedule_cluster_wide_test() {
+        let flag = LeaderFlag::new();
+        let leadership = Leadership::new(flag.clone());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let executor = CoreExecutor::new().unwrap();
+        let start = Instant::now();
+        let runs_clone = Arc::clone(&runs);
+        let schedule = FixedRate::new(Duration::from_millis(50), Duration::from_millis(100));
+        executor.schedule_cluster_wide(schedule, &leadership, move |_handle| {
+            runs_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        // Not the leader at 50 and 150ms
+        thread::sleep(Duration::from_millis(200).checked_sub(start.elapsed()).unwrap_or_default());
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        // The leader at 250 and 350ms
+        flag.set_leader(true);
+        thread::sleep(Duration::from_millis(400).checked_sub(start.elapsed()).unwrap_or_default());
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,17 +1,39 @@
 //! ## The library
 //!
 //! This library provides a series of utilities for scheduling and executing tasks (functions and
-//! closures). Tasks can be executed at fixed interval or at fixed rates, and can be executed
-//! sequentially in the main executor thread or in parallel using a thread pool.
+//! closures). Tasks can be executed once after a delay, at fixed interval, at fixed rates or
+//! following a cron expression, and can be executed sequentially in the main executor thread or in
+//! parallel using a thread pool.
 //!
 //! ### Executors
 //!
 //! - [`CoreExecutor`]: schedule and execute tasks on a single thread, ideal for short running tasks.
 //! - [`ThreadPoolExecutor`]: schedule and execute tasks on a thread pool. Can be used for long
-//! running tasks.
+//!   running tasks.
+//!
+//! Libraries that need to schedule background work without requiring an executor from their users
+//! can use the global `CoreExecutor` returned by `scheduled_executor::global()`.
+//!
+//! Every scheduling method returns a [`TaskHandle`], which can be used to stop or pause the task
+//! without affecting the executor or any other task scheduled on it, and to inspect its state.
+//! The handles of all the active tasks are also available from the `tasks` method of the
+//! executors. Panics in the scheduled tasks are caught as well, and they won't affect the
+//! executor or the other tasks: the [`PanicPolicy`] of the executor defines whether the task
+//! should keep running.
 //!
 //! [`CoreExecutor`]: https://fede1024.github.io/rust-scheduled-executor/scheduled_executor/executor/struct.CoreExecutor.html
 //! [`ThreadPoolExecutor`]: https://fede1024.github.io/rust-scheduled-executor/scheduled_executor/executor/struct.ThreadPoolExecutor.html
+//! [`TaskHandle`]: https://fede1024.github.io/rust-scheduled-executor/scheduled_executor/executor/struct.TaskHandle.html
+//! [`PanicPolicy`]: https://fede1024.github.io/rust-scheduled-executor/scheduled_executor/executor/enum.PanicPolicy.html
+//!
+//! ### Schedules
+//! Besides the fixed rate, fixed interval and cron schedules, tasks can be scheduled with any type
+//! implementing the [`Schedule`] trait, using the `schedule_with` method of the executors.
+//! Schedules can be adapted, for example `delayed` defers the first execution of a schedule to
+//! allow for a warm-up period. Wall-clock schedules, such as "every day at 02:00 UTC", are
+//! provided by the `schedule::calendar` module.
+//!
+//! [`Schedule`]: https://fede1024.github.io/rust-scheduled-executor/scheduled_executor/schedule/trait.Schedule.html
 //!
 //! ### Task group
 //! The scheduled-executor crate also provides an abstraction for the execution of groups of tasks
@@ -52,9 +74,49 @@
 extern crate futures;
 extern crate tokio_core;
 extern crate futures_cpupool;
+extern crate rand;
+extern crate scheduled_executor_macros;
+#[cfg(any(all(unix, feature = "signal"), all(target_os = "linux", any(feature = "affinity", feature = "priority"))))]
+extern crate libc;
 
+#[cfg(all(target_os = "linux", feature = "affinity"))]
+pub mod affinity;
+pub mod annotated;
+pub mod clock;
+pub mod compat;
+pub mod compute;
+pub mod config;
+pub mod coordination;
+pub mod error;
 pub mod executor;
+pub mod group;
+pub mod layer;
+pub mod leadership;
+pub mod limit;
+pub mod metrics;
+pub mod persist;
+#[cfg(all(target_os = "linux", feature = "priority"))]
+pub mod priority;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod schedule;
+#[cfg(all(unix, feature = "signal"))]
+pub mod signal;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+mod steal;
+pub mod stream;
 pub mod task_group;
+pub mod testing;
+mod wheel;
 
-pub use executor::{CoreExecutor, ThreadPoolExecutor};
+pub use clock::{Clock, SystemClock};
+pub use error::ScheduleError;
+pub use limit::ConcurrencyLimit;
+pub use executor::{CancellationToken, ClockJump, CoreExecutor, DropBehavior, ErrorPolicy, ExecuteFuture, ExecutorBuilder, GapPolicy,
+                   Health, LoadBalancing, OverlapPolicy, PanicPolicy, Shutdown, StopFuture, TaskHandle, TaskListener, TaskSpec,
+                   TaskStats, ThreadPoolExecutor, global};
+pub use schedule::Schedule;
+pub use scheduled_executor_macros::{scheduled, scheduled_tasks};
 pub use task_group::{TaskGroup, TaskGroupScheduler};
@@ -0,0 +1,204 @@
+//! Limits on the number of executions running at the same time, across all the tasks of an
+//! executor. See `ThreadPoolExecutor::set_concurrency_limit`.
+use futures::{Async, Future, Poll};
+use futures::task::{self, Task};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A request for a permit, waiting in the queue of the limit.
+struct Waiter {
+    granted: bool,
+    cancelled: bool,
+    task: Option<Task>,
+}
+
+struct State {
+    max: usize,
+    running: usize,
+    queue: VecDeque<Arc<Mutex<Waiter>>>,
+}
+
+impl State {
+    /// Hands the available permits to the oldest waiters.
+    fn grant(&mut self) {
+        while self.running < self.max {
+            let waiter = match self.queue.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            let mut waiter = waiter.lock().unwrap();
+            if waiter.cancelled {
+                continue;
+            }
+            self.running += 1;
+            waiter.granted = true;
+            if let Some(task) = waiter.task.take() {
+                task.notify();
+            }
+        }
+    }
+}
+
+/// A semaphore bounding the number of executions running at the same time. Permits are handed
+/// out in the order in which they have been requested, so that a task firing often can't starve
+/// the others. Cloning the limit returns a new reference to the same semaphore.
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    state: Arc<Mutex<State>>,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a limit allowing at most `max` executions at the same time.
+    pub fn new(max: usize) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            state: Arc::new(Mutex::new(State { max, running: 0, queue: VecDeque::new() })),
+        }
+    }
+
+    /// Creates a limit that never makes executions wait.
+    pub fn unlimited() -> ConcurrencyLimit {
+        ConcurrencyLimit::new(usize::MAX)
+    }
+
+    /// Changes the maximum number of executions running at the same time. When the limit is
+    /// lowered, the executions already running are not affected.
+    pub fn set_max(&self, max: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.max = max;
+        state.grant();
+    }
+
+    /// Returns the maximum number of executions running at the same time.
+    pub fn max(&self) -> usize {
+        self.state.lock().unwrap().max
+    }
+
+    /// Returns the number of permits currently held.
+    pub fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+
+    /// Returns the number of requests waiting for a permit.
+    pub fn waiting(&self) -> usize {
+        self.state.lock().unwrap().queue.iter()
+            .filter(|waiter| !waiter.lock().unwrap().cancelled)
+            .count()
+    }
+
+    /// Returns a future resolving to a permit once one is available. The permit is given back
+    /// when dropped. Dropping the future gives up its place in the queue.
+    pub fn acquire(&self) -> Acquire {
+        let mut state = self.state.lock().unwrap();
+        let waiter = Arc::new(Mutex::new(Waiter { granted: false, cancelled: false, task: None }));
+        state.queue.push_back(Arc::clone(&waiter));
+        state.grant();
+        Acquire { limit: self.clone(), waiter: Some(waiter) }
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("ConcurrencyLimit")
+            .field("max", &state.max)
+            .field("running", &state.running)
+            .field("queued", &state.queue.len())
+            .finish()
+    }
+}
+
+/// The future returned by `ConcurrencyLimit::acquire`.
+pub struct Acquire {
+    limit: ConcurrencyLimit,
+    waiter: Option<Arc<Mutex<Waiter>>>,
+}
+
+impl Future for Acquire {
+    type Item = Permit;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Permit, ()> {
+        let granted = {
+            let waiter = self.waiter.as_ref().expect("Acquire polled after completion");
+            let mut waiter = waiter.lock().unwrap();
+            if !waiter.granted {
+                waiter.task = Some(task::current());
+            }
+            waiter.granted
+        };
+        if granted {
+            self.waiter = None;
+            Ok(Async::Ready(Permit { limit: self.limit.clone() }))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        let waiter = match self.waiter.take() {
+            Some(waiter) => waiter,
+            None => return,
+        };
+        let mut state = self.limit.state.lock().unwrap();
+        let mut waiter = waiter.lock().unwrap();
+        waiter.cancelled = true;
+        if waiter.granted {
+            // The permit was handed out, but never collected
+            state.running -= 1;
+            drop(waiter);
+            state.grant();
+        }
+    }
+}
+
+/// A permit to run, obtained from a `ConcurrencyLimit`. The permit is given back to the limit
+/// when dropped.
+pub struct Permit {
+    limit: ConcurrencyLimit,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.limit.state.lock().unwrap();
+        state.running -= 1;
+        state.grant();
+    }
+}
+
+impl fmt::Debug for Permit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Permit").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+
+    use super::ConcurrencyLimit;
+
+    #[test]
+    fn fair_queue_test() {
+        let limit = ConcurrencyLimit::new(1);
+        let first = limit.acquire().wait().unwrap();
+        let second = limit.acquire();
+        let third = limit.acquire();
+        assert_eq!((limit.running(), limit.waiting()), (1, 2));
+
+        // Giving up a place in the queue lets the next request through
+        drop(first);
+        drop(second);
+        assert_eq!((limit.running(), limit.waiting()), (1, 0));
+        let third = third.wait().unwrap();
+
+        limit.set_max(3);
+        let fourth = limit.acquire().wait().unwrap();
+        assert_eq!(limit.running(), 2);
+        drop((third, fourth));
+        assert_eq!(limit.running(), 0);
+    }
+}
@@ -0,0 +1,138 @@
+//! Exports the statistics of the tasks in the Prometheus text exposition format, so that they can
+//! be served on a metrics endpoint:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::{metrics, CoreExecutor};
+//! let executor = CoreExecutor::new().unwrap();
+//! // ...
+//! let body = metrics::encode(&executor.tasks());
+//! ```
+//!
+//! Every metric has a `task` label, containing the name of the task, or its id for tasks without
+//! a name. Tasks with tags also have a `tags` label, with their comma separated tags.
+use executor::TaskHandle;
+
+use std::fmt::Write;
+use std::time::Duration;
+
+struct Metric {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    value: fn(&TaskHandle) -> Option<f64>,
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs_f64()
+}
+
+const METRICS: [Metric; 8] = [
+    Metric {
+        name: "scheduled_executor_task_executions_total",
+        kind: "counter",
+        help: "Number of completed executions.",
+        value: |task| Some(task.stats().executions as f64),
+    },
+    Metric {
+        name: "scheduled_executor_task_panics_total",
+        kind: "counter",
+        help: "Number of panicked executions.",
+        value: |task| Some(task.panics() as f64),
+    },
+    Metric {
+        name: "scheduled_executor_task_duration_seconds_total",
+        kind: "counter",
+        help: "Total duration of the completed executions.",
+        value: |task| Some(seconds(task.stats().total_duration)),
+    },
+    Metric {
+        name: "scheduled_executor_task_last_duration_seconds",
+        kind: "gauge",
+        help: "Duration of the last completed execution.",
+        value: |task| task.stats().last_duration.map(seconds),
+    },
+    Metric {
+        name: "scheduled_executor_task_max_duration_seconds",
+        kind: "gauge",
+        help: "Duration of the longest execution.",
+        value: |task| task.stats().max_duration.map(seconds),
+    },
+    Metric {
+        name: "scheduled_executor_task_last_lag_seconds",
+        kind: "gauge",
+        help: "Delay between the planned and the actual start of the last execution.",
+        value: |task| task.stats().last_lag.map(seconds),
+    },
+    Metric {
+        name: "scheduled_executor_task_max_lag_seconds",
+        kind: "gauge",
+        help: "Maximum delay between the planned and the actual start of an execution.",
+        value: |task| task.stats().max_lag.map(seconds),
+    },
+    Metric {
+        name: "scheduled_executor_task_mean_lag_seconds",
+        kind: "gauge",
+        help: "Mean delay between the planned and the actual start of the executions.",
+        value: |task| task.stats().mean_lag.map(seconds),
+    },
+];
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn labels(task: &TaskHandle) -> String {
+    let name = match task.name() {
+        Some(name) => escape(name),
+        None => task.id().to_string(),
+    };
+    let tags = task.tags();
+    if tags.is_empty() {
+        format!("task=\"{}\"", name)
+    } else {
+        format!("task=\"{}\",tags=\"{}\"", name, escape(&tags.join(",")))
+    }
+}
+
+/// Encodes the statistics of the given tasks in the Prometheus text format.
+pub fn encode(tasks: &[TaskHandle]) -> String {
+    let mut output = String::new();
+    for metric in &METRICS {
+        let _ = writeln!(output, "# HELP {} {}", metric.name, metric.help);
+        let _ = writeln!(output, "# TYPE {} {}", metric.name, metric.kind);
+        for task in tasks {
+            if let Some(value) = (metric.value)(task) {
+                let _ = writeln!(output, "{}{{{}}} {}", metric.name, labels(task), value);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use executor::CoreExecutor;
+    use super::encode;
+
+    #[test]
+    fn encode_test() {
+        let executor = CoreExecutor::new().unwrap();
+        executor.schedule_fixed_rate_named("say \"hi\"", Duration::from_secs(0), Duration::from_secs(10), |_handle| ());
+        let unnamed = executor.schedule_once(Duration::from_secs(10), |_handle| ());
+        let tagged = executor.schedule_once(Duration::from_secs(10), |_handle| ());
+        tagged.add_tag("tenant-42");
+        tagged.add_tag("reports");
+        thread::sleep(Duration::from_millis(100));
+
+        let output = encode(&executor.tasks());
+        assert!(output.contains("# TYPE scheduled_executor_task_executions_total counter\n"));
+        assert!(output.contains("scheduled_executor_task_executions_total{task=\"say \\\"hi\\\"\"} 1\n"));
+        assert!(output.contains(&format!("scheduled_executor_task_executions_total{{task=\"{}\"}} 0\n", unnamed.id())));
+        assert!(output.contains(&format!("scheduled_executor_task_executions_total{{task=\"{}\",tags=\"tenant-42,reports\"}} 0\n", tagged.id())));
+        // Tasks that never ran don't have a duration
+        assert!(!output.contains(&format!("scheduled_executor_task_last_duration_seconds{{task=\"{}\"}}", unnamed.id())));
+    }
+}
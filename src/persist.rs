@@ -0,0 +1,306 @@
+//! Persistence of the last successful execution of the named tasks, so that a task that must
+//! run at least once in a given period still does when the process restarts often. The
+//! instants are saved in a [`LastRunStore`], such as a [`FileStore`]; on startup, a task whose
+//! last successful execution is too old (or unknown) can be run immediately:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::persist::{FileStore, Persistence};
+//! # use scheduled_executor::schedule::cron::CronSchedule;
+//! # use std::time::Duration;
+//! let executor = CoreExecutor::new().unwrap();
+//! let persistence = Persistence::new(FileStore::new("/var/lib/myapp/last_runs"))
+//!     .run_missed(Duration::from_secs(24 * 3600));
+//! let nightly = CronSchedule::parse("0 0 3 * * *").unwrap();
+//! executor.schedule_persistent("cleanup", nightly, &persistence, |_handle| {
+//!     // Clean up
+//! });
+//! ```
+//!
+//! The same can be done with any executor, by scheduling `Persistence::schedule` with the
+//! function wrapped by `Persistence::record`.
+//!
+//! [`LastRunStore`]: trait.LastRunStore.html
+//! [`FileStore`]: struct.FileStore.html
+use clock;
+use schedule::{Anchor, Schedule};
+use store::{escape, unescape};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Stores the instant of the last successful execution of each task.
+pub trait LastRunStore: Send + Sync + 'static {
+    /// Returns the last successful execution of the task, if any.
+    fn load(&self, task: &str) -> io::Result<Option<SystemTime>>;
+
+    /// Records a successful execution of the task.
+    fn save(&self, task: &str, time: SystemTime) -> io::Result<()>;
+}
+
+/// A store keeping the instants in memory, mostly useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    runs: Arc<Mutex<HashMap<String, SystemTime>>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl LastRunStore for MemoryStore {
+    fn load(&self, task: &str) -> io::Result<Option<SystemTime>> {
+        Ok(self.runs.lock().unwrap().get(task).cloned())
+    }
+
+    fn save(&self, task: &str, time: SystemTime) -> io::Result<()> {
+        self.runs.lock().unwrap().insert(task.to_owned(), time);
+        Ok(())
+    }
+}
+
+/// A store keeping the instants in a text file, with one line per task. The file is replaced
+/// atomically on every save, and created if missing.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    /// Creates a store saving the instants in the file at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileStore {
+        FileStore { path: path.into(), lock: Mutex::new(()) }
+    }
+
+    fn read(&self) -> io::Result<HashMap<String, SystemTime>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e),
+        };
+        let mut runs = HashMap::new();
+        for line in content.lines() {
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid line {:?}", line));
+            let (task, time) = line.rsplit_once('\t').ok_or_else(invalid)?;
+            let (secs, nanos) = time.split_once('.').ok_or_else(invalid)?;
+            let secs = secs.parse().map_err(|_| invalid())?;
+            let nanos = nanos.parse().map_err(|_| invalid())?;
+            runs.insert(unescape(task), UNIX_EPOCH + Duration::new(secs, nanos));
+        }
+        Ok(runs)
+    }
+}
+
+impl LastRunStore for FileStore {
+    fn load(&self, task: &str) -> io::Result<Option<SystemTime>> {
+        let _lock = self.lock.lock().unwrap();
+        Ok(self.read()?.remove(task))
+    }
+
+    fn save(&self, task: &str, time: SystemTime) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let mut runs = self.read()?;
+        runs.insert(task.to_owned(), time);
+        let mut tasks = runs.into_iter().collect::<Vec<_>>();
+        tasks.sort();
+        let temporary = self.path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&temporary)?;
+            for (task, time) in tasks {
+                let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                writeln!(file, "{}\t{}.{:09}", escape(&task), since_epoch.as_secs(), since_epoch.subsec_nanos())?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+/// Records the successful executions of the tasks in a store, and finds out which ones were
+/// missed while the process wasn't running. Cloning it returns a new reference to the same
+/// store.
+#[derive(Clone)]
+pub struct Persistence {
+    store: Arc<dyn LastRunStore>,
+    max_age: Option<Duration>,
+}
+
+impl Persistence {
+    /// Creates a new `Persistence` saving the executions in `store`.
+    pub fn new<T: LastRunStore>(store: T) -> Persistence {
+        Persistence { store: Arc::new(store), max_age: None }
+    }
+
+    /// Makes the tasks run as soon as they are scheduled, if their last successful execution is
+    /// older than `max_age`, or if they never ran.
+    pub fn run_missed(mut self, max_age: Duration) -> Persistence {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the last successful execution of the task. Errors of the store are logged, and
+    /// reported as `None`.
+    pub fn last_run(&self, task: &str) -> Option<SystemTime> {
+        self.store.load(task).unwrap_or_else(|e| {
+            error!("Failed to load the last execution of {}: {}", task, e);
+            None
+        })
+    }
+
+    /// Returns true if the task didn't run successfully during the last `max_age`, according to
+    /// the clock of the current executor.
+    pub fn missed(&self, task: &str, max_age: Duration) -> bool {
+        missed(self.last_run(task), max_age)
+    }
+
+    /// Returns a schedule following `schedule`, with an immediate first execution if the task
+    /// missed a run. See `run_missed`. The last execution is loaded right away, but it's
+    /// compared with the time of the executor when the task is scheduled.
+    pub fn schedule<S: Schedule>(&self, task: &str, schedule: S) -> PersistentSchedule<S> {
+        let last_run = self.max_age.and_then(|_| self.last_run(task));
+        PersistentSchedule { schedule, task: task.to_owned(), last_run, max_age: self.max_age }
+    }
+
+    /// Wraps the function, recording each of its successful executions. Executions that panic
+    /// are not recorded.
+    pub fn record<A, F>(&self, task: &str, mut scheduled_fn: F) -> impl FnMut(&A) + Send + 'static
+        where A: ?Sized,
+              F: FnMut(&A) + Send + 'static
+    {
+        let store = Arc::clone(&self.store);
+        let task = task.to_owned();
+        move |arg| {
+            scheduled_fn(arg);
+            if let Err(e) = store.save(&task, clock::current().system_time()) {
+                error!("Failed to save the last execution of {}: {}", task, e);
+            }
+        }
+    }
+}
+
+fn missed(last_run: Option<SystemTime>, max_age: Duration) -> bool {
+    match last_run {
+        Some(last_run) => clock::current().system_time().duration_since(last_run).map(|age| age > max_age).unwrap_or(false),
+        None => true,
+    }
+}
+
+/// A schedule running the task immediately when it missed a run. See `Persistence::schedule`.
+#[derive(Debug, Clone)]
+pub struct PersistentSchedule<S> {
+    schedule: S,
+    task: String,
+    last_run: Option<SystemTime>,
+    /// Cleared once the first execution has been planned.
+    max_age: Option<Duration>,
+}
+
+impl<S: Schedule> Schedule for PersistentSchedule<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        if let Some(max_age) = self.max_age.take() {
+            if missed(self.last_run, max_age) {
+                info!("Task {} missed a run, running it immediately", self.task);
+                return Some(after);
+            }
+        }
+        self.schedule.next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.schedule.anchor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use schedule::{FixedRate, Schedule};
+    use super::{FileStore, LastRunStore, MemoryStore, Persistence};
+    use testing::TestExecutor;
+
+    fn s(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn file_store_test() {
+        let path = env::temp_dir().join(format!("scheduled_executor_last_runs_{}", process::id()));
+        let store = FileStore::new(&path);
+        assert_eq!(store.load("report").unwrap(), None);
+        store.save("daily report", UNIX_EPOCH + Duration::new(1700000000, 5)).unwrap();
+        store.save("cleanup", UNIX_EPOCH + s(1600000000)).unwrap();
+        store.save("daily report", UNIX_EPOCH + Duration::new(1800000000, 123)).unwrap();
+        store.save("key=value\nnext\tline\\", UNIX_EPOCH + s(1500000000)).unwrap();
+
+        let store = FileStore::new(&path);
+        assert_eq!(store.load("daily report").unwrap(), Some(UNIX_EPOCH + Duration::new(1800000000, 123)));
+        assert_eq!(store.load("cleanup").unwrap(), Some(UNIX_EPOCH + s(1600000000)));
+        assert_eq!(store.load("key=value\nnext\tline\\").unwrap(), Some(UNIX_EPOCH + s(1500000000)));
+        assert_eq!(store.load("key=value").unwrap(), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn run_missed_test() {
+        let store = MemoryStore::new();
+        store.save("recent", SystemTime::now() - s(60)).unwrap();
+        store.save("old", SystemTime::now() - s(7200)).unwrap();
+        let persistence = Persistence::new(store.clone()).run_missed(s(3600));
+        assert!(!persistence.missed("recent", s(3600)));
+        assert!(persistence.missed("old", s(3600)));
+        assert!(persistence.missed("never", s(3600)));
+
+        let start = Instant::now();
+        let mut recent = persistence.schedule("recent", FixedRate::new(s(10), s(10)));
+        assert_eq!(recent.next_after(start), Some(start + s(10)));
+        let mut old = persistence.schedule("old", FixedRate::new(s(10), s(10)));
+        assert_eq!(old.next_after(start), Some(start));
+        assert_eq!(old.next_after(start + s(1)), Some(start + s(11)));
+
+        let mut record = persistence.record("old", |_: &()| {});
+        record(&());
+        assert!(!persistence.missed("old", s(3600)));
+        assert!(!Persistence::new(store).missed("old", s(3600)));
+    }
+
+    #[test]
+    fn virtual_time_test() {
+        let start = UNIX_EPOCH + s(1_704_067_200);
+        let store = MemoryStore::new();
+        store.save("recent", start - s(60)).unwrap();
+        store.save("old", start - s(7200)).unwrap();
+        let persistence = Persistence::new(store.clone()).run_missed(s(3600));
+        let executor = TestExecutor::starting_at(start).unwrap();
+        let now = executor.now();
+        let recent = executor.schedule_with(persistence.schedule("recent", FixedRate::new(s(10), s(10))), |_handle| ());
+        assert_eq!(recent.next_run(), Some(now + s(10)));
+        let old = executor.schedule_with(persistence.schedule("old", FixedRate::new(s(10), s(10))), persistence.record("old", |_handle| ()));
+        assert_eq!(old.next_run(), Some(now));
+
+        executor.advance(s(3600));
+        assert_eq!(store.load("old").unwrap(), Some(start + s(3600)));
+        assert_eq!(old.runs(), 361);
+    }
+}
@@ -0,0 +1,94 @@
+//! Setting the scheduling priority of the executor threads, so that high frequency timing loops
+//! aren't preempted by bulk work running elsewhere in the process. Requires the `priority`
+//! feature, and is only available on Linux.
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::ExecutorBuilder;
+//! # use scheduled_executor::priority::ThreadPriority;
+//! let executor = ExecutorBuilder::new()
+//!     .thread_priority(ThreadPriority::Fifo(10))
+//!     .build()
+//!     .expect("Failed to start the executor, real-time priorities need CAP_SYS_NICE");
+//! ```
+//!
+//! Not to be confused with the priorities of the tasks, see `TaskHandle::set_priority`, which
+//! only order the executions of the tasks of an executor.
+use libc;
+
+use std::io;
+use std::mem;
+
+/// The scheduling priority of an operating system thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadPriority {
+    /// The default time-sharing policy, with the given nice value, from -20 (highest priority)
+    /// to 19 (lowest priority). Lowering the nice value requires the `CAP_SYS_NICE` capability.
+    Nice(i32),
+    /// The `SCHED_FIFO` real-time policy, with the given priority, from 1 to 99: the thread runs
+    /// until it blocks, or a thread with a higher real-time priority is ready. Requires the
+    /// `CAP_SYS_NICE` capability.
+    Fifo(i32),
+    /// The `SCHED_RR` real-time policy, with the given priority, from 1 to 99: like `Fifo`, but
+    /// the threads with the same priority run in turn. Requires the `CAP_SYS_NICE`
+    /// capability.
+    RoundRobin(i32),
+}
+
+/// Sets the scheduling priority of the current thread.
+pub fn set_current_thread_priority(priority: ThreadPriority) -> io::Result<()> {
+    let (policy, value) = match priority {
+        ThreadPriority::Nice(nice) => (libc::SCHED_OTHER, nice),
+        ThreadPriority::Fifo(value) => (libc::SCHED_FIFO, value),
+        ThreadPriority::RoundRobin(value) => (libc::SCHED_RR, value),
+    };
+    let mut param: libc::sched_param = unsafe { mem::zeroed() };
+    param.sched_priority = if policy == libc::SCHED_OTHER { 0 } else { value };
+    // A pid of 0 is the calling thread
+    if unsafe { libc::sched_setscheduler(0, policy, &param) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if let ThreadPriority::Nice(nice) = priority {
+        let tid = unsafe { libc::gettid() };
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use libc;
+
+    use std::io;
+    use std::thread;
+
+    use executor::ExecutorBuilder;
+    use super::{set_current_thread_priority, ThreadPriority};
+
+    fn nice() -> i32 {
+        unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) }
+    }
+
+    #[test]
+    fn set_current_thread_priority_test() {
+        thread::spawn(|| {
+            set_current_thread_priority(ThreadPriority::Nice(5)).unwrap();
+            assert_eq!(nice(), 5);
+            assert!(set_current_thread_priority(ThreadPriority::Fifo(1000)).is_err());
+            // Only with CAP_SYS_NICE
+            match set_current_thread_priority(ThreadPriority::Fifo(1)) {
+                Ok(()) => assert_eq!(unsafe { libc::sched_getscheduler(0) }, libc::SCHED_FIFO),
+                Err(e) => assert_eq!(e.kind(), io::ErrorKind::PermissionDenied),
+            }
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn thread_priority_test() {
+        let executor = ExecutorBuilder::new().thread_priority(ThreadPriority::Nice(3)).build().unwrap();
+        assert_eq!(executor.execute(|_handle| nice()).wait().unwrap(), 3);
+        assert!(ExecutorBuilder::new().thread_priority(ThreadPriority::RoundRobin(-1)).build().is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! A [`Coordinator`] backed by Redis, so that the instances of a service running the same
+//! schedules execute each named job on exactly one of them. Requires the `redis` feature.
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::coordination::Coordination;
+//! # use scheduled_executor::redis::RedisCoordinator;
+//! # use scheduled_executor::schedule::cron::CronSchedule;
+//! let coordination = Coordination::new(RedisCoordinator::new("redis.internal:6379").prefix("billing:"));
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_coordinated("invoices", CronSchedule::parse("0 0 * * * *").unwrap(), &coordination, |_handle| {
+//!     // Runs on a single instance every hour
+//! });
+//! ```
+//!
+//! Each execution is claimed with `SET <prefix><job>:<tick> <instance> NX PX <ttl>`, which only
+//! succeeds for the first instance. Exclusive jobs are locked in the same way in the key
+//! `<prefix><job>:lock`, which is only deleted by its owner. The connection is opened on the
+//! first command, and opened again after a failure.
+//!
+//! [`Coordinator`]: ../coordination/trait.Coordinator.html
+use coordination::{Coordinator, instance_id};
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A reply of the Redis server.
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Status(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<Reply>>),
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid reply from redis: {}", reason))
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by redis"));
+    }
+    if !line.ends_with("\r\n") {
+        return Err(invalid("missing line terminator"));
+    }
+    line.truncate(line.len() - 2);
+    Ok(line)
+}
+
+fn read_reply<R: BufRead>(reader: &mut R) -> io::Result<Reply> {
+    let line = read_line(reader)?;
+    if line.is_empty() {
+        return Err(invalid("empty line"));
+    }
+    let (kind, value) = line.split_at(1);
+    let length = || value.parse::<i64>().map_err(|_| invalid("invalid length"));
+    match kind {
+        "+" => Ok(Reply::Status(value.to_owned())),
+        "-" => Ok(Reply::Error(value.to_owned())),
+        ":" => Ok(Reply::Integer(length()?)),
+        // The null of RESP3
+        "_" => Ok(Reply::Bulk(None)),
+        "$" if length()? < 0 => Ok(Reply::Bulk(None)),
+        "$" => {
+            let mut data = vec![0; length()? as usize + 2];
+            reader.read_exact(&mut data)?;
+            if !data.ends_with(b"\r\n") {
+                return Err(invalid("missing bulk terminator"));
+            }
+            data.truncate(data.len() - 2);
+            Ok(Reply::Bulk(Some(data)))
+        },
+        "*" if length()? < 0 => Ok(Reply::Array(None)),
+        "*" => (0..length()?).map(|_| read_reply(reader)).collect::<io::Result<_>>().map(|r| Reply::Array(Some(r))),
+        _ => Err(invalid("unknown reply type")),
+    }
+}
+
+/// Deletes the key `KEYS[1]` only if its value is `ARGV[1]`.
+const UNLOCK_SCRIPT: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then return redis.call('del', KEYS[1]) else return 0 end";
+
+fn encode(args: &[&[u8]]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        command.extend_from_slice(arg);
+        command.extend_from_slice(b"\r\n");
+    }
+    command
+}
+
+/// A coordinator claiming the executions in Redis. See the [module documentation].
+///
+/// [module documentation]: index.html
+#[derive(Debug)]
+pub struct RedisCoordinator {
+    address: String,
+    prefix: String,
+    password: Option<String>,
+    instance: String,
+    timeout: Duration,
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl RedisCoordinator {
+    /// Creates a coordinator using the Redis server at `address`, such as `"localhost:6379"`.
+    pub fn new(address: &str) -> RedisCoordinator {
+        RedisCoordinator {
+            address: address.to_owned(),
+            prefix: "scheduled_executor:".to_owned(),
+            password: None,
+            instance: instance_id().to_owned(),
+            timeout: Duration::from_secs(5),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Sets the prefix of the keys, `"scheduled_executor:"` by default. Services sharing a
+    /// Redis server should use different prefixes.
+    pub fn prefix(mut self, prefix: &str) -> RedisCoordinator {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Authenticates with the given password after connecting.
+    pub fn password(mut self, password: &str) -> RedisCoordinator {
+        self.password = Some(password.to_owned());
+        self
+    }
+
+    /// Sets the value stored in the claimed keys. Useful to find out which instance ran an
+    /// execution. By default, the host name followed by the process id and a random part.
+    pub fn instance(mut self, instance: &str) -> RedisCoordinator {
+        self.instance = instance.to_owned();
+        self
+    }
+
+    /// Sets the timeout of the connection and of the commands, 5 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> RedisCoordinator {
+        self.timeout = timeout;
+        self
+    }
+
+    fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let mut last_error = None;
+        let mut stream = None;
+        for address in self.address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.timeout) {
+                Ok(connected) => {
+                    stream = Some(connected);
+                    break;
+                },
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let stream = match stream {
+            Some(stream) => stream,
+            None => return Err(last_error.unwrap_or_else(|| invalid(&format!("no address for {}", self.address)))),
+        };
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut connection = BufReader::new(stream);
+        if let Some(ref password) = self.password {
+            match send(&mut connection, &[b"AUTH", password.as_bytes()])? {
+                Reply::Status(_) => {},
+                Reply::Error(e) => return Err(io::Error::new(io::ErrorKind::PermissionDenied, e)),
+                other => return Err(invalid(&format!("unexpected reply to AUTH: {:?}", other))),
+            }
+        }
+        Ok(connection)
+    }
+
+    /// Sends a command, connecting first if needed. The connection is dropped after a failure.
+    fn command(&self, args: &[&[u8]]) -> io::Result<Reply> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            *connection = Some(self.connect()?);
+        }
+        let result = send(connection.as_mut().unwrap(), args);
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+
+    /// Sends `SET key value NX PX ttl`, returning true if the key was set.
+    fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> io::Result<bool> {
+        let ttl = format!("{}", ttl.as_millis().max(1));
+        let args: [&[u8]; 6] = [b"SET", key.as_bytes(), value.as_bytes(), b"NX", b"PX", ttl.as_bytes()];
+        match self.command(&args)? {
+            Reply::Status(_) => Ok(true),
+            Reply::Bulk(None) => Ok(false),
+            Reply::Error(e) => Err(io::Error::other(format!("redis: {}", e))),
+            other => Err(invalid(&format!("unexpected reply to SET: {:?}", other))),
+        }
+    }
+}
+
+fn send(connection: &mut BufReader<TcpStream>, args: &[&[u8]]) -> io::Result<Reply> {
+    connection.get_mut().write_all(&encode(args))?;
+    read_reply(connection)
+}
+
+impl Coordinator for RedisCoordinator {
+    fn claim(&self, job: &str, tick: u64, ttl: Duration) -> io::Result<bool> {
+        self.set_nx(&format!("{}{}:{}", self.prefix, job, tick), &self.instance, ttl)
+    }
+
+    fn lock(&self, job: &str, owner: &str, ttl: Duration) -> io::Result<bool> {
+        self.set_nx(&format!("{}{}:lock", self.prefix, job), owner, ttl)
+    }
+
+    fn unlock(&self, job: &str, owner: &str) -> io::Result<()> {
+        let key = format!("{}{}:lock", self.prefix, job);
+        let args: [&[u8]; 5] = [b"EVAL", UNLOCK_SCRIPT.as_bytes(), b"1", key.as_bytes(), owner.as_bytes()];
+        match self.command(&args)? {
+            Reply::Integer(_) => Ok(()),
+            Reply::Error(e) => Err(io::Error::other(format!("redis: {}", e))),
+            other => Err(invalid(&format!("unexpected reply to EVAL: {:?}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufReader, Cursor, Write};
+    use std::net::TcpListener;
+    use std::process;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use coordination::Coordinator;
+    use super::{RedisCoordinator, Reply, encode, read_reply};
+
+    #[test]
+    fn reply_test() {
+        let mut input = Cursor::new(&b"+OK\r\n$-1\r\n:42\r\n$5\r\nhe\r\no\r\n*2\r\n$1\r\na\r\n-ERR nope\r\n_\r\n"[..]);
+        assert_eq!(read_reply(&mut input).unwrap(), Reply::Status("OK".to_owned()));
+        assert_eq!(read_reply(&mut input).unwrap(), Reply::Bulk(None));
+        assert_eq!(read_reply(&mut input).unwrap(), Reply::Integer(42));
+        assert_eq!(read_reply(&mut input).unwrap(), Reply::Bulk(Some(b"he\r\no".to_vec())));
+        assert_eq!(read_reply(&mut input).unwrap(), Reply::Array(Some(vec![
+            Reply::Bulk(Some(b"a".to_vec())),
+            Reply::Error("ERR nope".to_owned()),
+        ])));
+        assert_eq!(read_reply(&mut input).unwrap(), Reply::Bulk(None));
+        assert!(read_reply(&mut input).is_err());
+        assert_eq!(encode(&[b"GET", b"key"]), b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".to_vec());
+    }
+
+    fn bulk(reply: &Reply) -> Vec<u8> {
+        match *reply {
+            Reply::Bulk(Some(ref data)) => data.clone(),
+            ref other => panic!("Unexpected argument {:?}", other),
+        }
+    }
+
+    /// A server answering `AUTH`, `SET ... NX` and the unlock script like Redis, ignoring the
+    /// expiry.
+    fn fake_redis() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let keys = Arc::new(Mutex::new(HashMap::new()));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let keys = Arc::clone(&keys);
+                let mut reader = BufReader::new(stream.unwrap());
+                thread::spawn(move || {
+                    while let Ok(Reply::Array(Some(args))) = read_reply(&mut reader) {
+                        let args = args.iter().map(bulk).collect::<Vec<_>>();
+                        let mut keys = keys.lock().unwrap();
+                        let reply: &[u8] = match &args[0][..] {
+                            b"AUTH" => b"+OK\r\n",
+                            b"SET" if keys.contains_key(&args[1]) => b"$-1\r\n",
+                            b"SET" => {
+                                keys.insert(args[1].clone(), args[2].clone());
+                                b"+OK\r\n"
+                            },
+                            b"EVAL" if keys.get(&args[3]) == Some(&args[4]) => {
+                                keys.remove(&args[3]);
+                                b":1\r\n"
+                            },
+                            b"EVAL" => b":0\r\n",
+                            _ => b"-ERR unknown command\r\n",
+                        };
+                        reader.get_mut().write_all(reply).unwrap();
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn redis_coordinator_test() {
+        let address = fake_redis();
+        let first = RedisCoordinator::new(&address).password("secret");
+        let second = RedisCoordinator::new(&address).prefix("other:");
+        let ttl = Duration::from_secs(1);
+        assert!(first.claim("report", 1000, ttl).unwrap());
+        assert!(!first.claim("report", 1000, ttl).unwrap());
+        assert!(first.claim("report", 2000, ttl).unwrap());
+        assert!(second.claim("report", 1000, ttl).unwrap());
+        assert!(RedisCoordinator::new("127.0.0.1:1").claim("report", 1000, ttl).is_err());
+
+        assert!(first.lock("report", "a", ttl).unwrap());
+        assert!(second.lock("report", "b", ttl).unwrap());
+        assert!(!first.lock("report", "b", ttl).unwrap());
+        first.unlock("report", "b").unwrap();
+        assert!(!first.lock("report", "b", ttl).unwrap());
+        first.unlock("report", "a").unwrap();
+        assert!(first.lock("report", "b", ttl).unwrap());
+    }
+
+    #[test]
+    fn connect_timeout_test() {
+        // Not routable, the connection never completes
+        let coordinator = RedisCoordinator::new("10.255.255.1:6379").timeout(Duration::from_millis(200));
+        let start = Instant::now();
+        assert!(coordinator.claim("report", 1000, Duration::from_secs(1)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn instance_test() {
+        let instance = RedisCoordinator::new("localhost:6379").instance;
+        assert!(instance.contains(&format!("-{}-", process::id())));
+        assert_eq!(RedisCoordinator::new("localhost:6379").instance, instance);
+        assert_eq!(RedisCoordinator::new("localhost:6379").instance("a").instance, "a");
+    }
+}
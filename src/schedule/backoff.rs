@@ -0,0 +1,162 @@
+//! An exponential backoff schedule, useful for retry-style polling. The task runs immediately,
+//! and then waits `initial` after the first execution, multiplying the wait time by `multiplier`
+//! after each execution, up to `max`. The task itself can use a [`BackoffReset`] to go back to
+//! the initial interval, for example after polling some data successfully:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::schedule::backoff::Backoff;
+//! # use std::time::Duration;
+//! # fn poll() -> bool { true }
+//! let executor = CoreExecutor::new().unwrap();
+//! let backoff = Backoff::new(Duration::from_secs(1), 2.0, Duration::from_secs(60));
+//! let reset = backoff.reset_handle();
+//! executor.schedule_with(backoff, move |_handle| {
+//!     if poll() {
+//!         reset.reset();
+//!     }
+//! });
+//! ```
+//!
+//! [`BackoffReset`]: struct.BackoffReset.html
+use schedule::Schedule;
+
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Allows a `Backoff` schedule to be reset to its initial interval. It can be cloned, and moved
+/// into the scheduled function.
+#[derive(Debug, Clone)]
+pub struct BackoffReset {
+    reset: Arc<AtomicBool>,
+}
+
+impl BackoffReset {
+    /// Resets the schedule: the next execution will happen after the initial interval.
+    pub fn reset(&self) {
+        self.reset.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A schedule with exponentially increasing intervals. See the [module documentation].
+///
+/// [module documentation]: index.html
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    multiplier: f64,
+    max: Duration,
+    max_elapsed: Option<Duration>,
+    current: Option<Duration>,
+    started: Option<Instant>,
+    reset: Arc<AtomicBool>,
+}
+
+impl Backoff {
+    /// Creates a new `Backoff` schedule.
+    ///
+    /// Panics if `multiplier` is less than 1.
+    pub fn new(initial: Duration, multiplier: f64, max: Duration) -> Backoff {
+        assert!(multiplier >= 1.0, "The backoff multiplier should be at least 1");
+        Backoff {
+            initial,
+            multiplier,
+            max: cmp::max(initial, max),
+            max_elapsed: None,
+            current: None,
+            started: None,
+            reset: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Stops the task once the next execution would be more than `max_elapsed` after the first
+    /// execution, or after the last reset.
+    pub fn max_elapsed(mut self, max_elapsed: Duration) -> Backoff {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    /// Returns a handle that can be used to reset the schedule.
+    pub fn reset_handle(&self) -> BackoffReset {
+        BackoffReset { reset: Arc::clone(&self.reset) }
+    }
+
+    fn increase(&self, interval: Duration) -> Duration {
+        let increased = interval.as_secs_f64() * self.multiplier;
+        if increased >= self.max.as_secs_f64() {
+            self.max
+        } else {
+            Duration::from_secs_f64(increased)
+        }
+    }
+}
+
+impl Schedule for Backoff {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let interval = match self.current {
+            None => {
+                self.started = Some(after);
+                self.current = Some(self.initial);
+                return Some(after);
+            },
+            Some(_) if self.reset.swap(false, Ordering::Relaxed) => {
+                self.started = Some(after);
+                self.initial
+            },
+            Some(current) => current,
+        };
+        let next = after + interval;
+        if let (Some(max_elapsed), Some(started)) = (self.max_elapsed, self.started) {
+            if next - started > max_elapsed {
+                return None;
+            }
+        }
+        self.current = Some(self.increase(interval));
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use schedule::Schedule;
+    use super::Backoff;
+
+    fn s(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn backoff_test() {
+        let start = Instant::now();
+        let mut schedule = Backoff::new(s(1), 2.0, s(5));
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start), Some(start + s(1)));
+        assert_eq!(schedule.next_after(start + s(1)), Some(start + s(3)));
+        assert_eq!(schedule.next_after(start + s(3)), Some(start + s(7)));
+        // The interval is capped
+        assert_eq!(schedule.next_after(start + s(7)), Some(start + s(12)));
+        assert_eq!(schedule.next_after(start + s(12)), Some(start + s(17)));
+
+        schedule.reset_handle().reset();
+        assert_eq!(schedule.next_after(start + s(17)), Some(start + s(18)));
+        assert_eq!(schedule.next_after(start + s(18)), Some(start + s(20)));
+    }
+
+    #[test]
+    fn backoff_max_elapsed_test() {
+        let start = Instant::now();
+        let mut schedule = Backoff::new(s(1), 2.0, s(60)).max_elapsed(s(10));
+        let reset = schedule.reset_handle();
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start), Some(start + s(1)));
+        assert_eq!(schedule.next_after(start + s(1)), Some(start + s(3)));
+        assert_eq!(schedule.next_after(start + s(3)), Some(start + s(7)));
+        assert_eq!(schedule.next_after(start + s(7)), None);
+
+        // A reset restarts the elapsed time
+        reset.reset();
+        assert_eq!(schedule.next_after(start + s(20)), Some(start + s(21)));
+    }
+}
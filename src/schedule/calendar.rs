@@ -0,0 +1,576 @@
+//! Schedules based on the wall clock, such as "every day at 02:00". By default the times are
+//! in UTC, and `in_time_zone` can be used to express them in a different time zone (see the
+//! [`time_zone`] module).
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::schedule::calendar::{Daily, Monthly, Weekday, Weekly};
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_with(Daily::at(2, 0, 0), |_handle| println!("Nightly job"));
+//! executor.schedule_with(Weekly::on(Weekday::Monday, 9, 30, 0), |_handle| println!("Weekly report"));
+//! executor.schedule_with(Monthly::on_last_day(23, 0, 0), |_handle| println!("Monthly invoices"));
+//! ```
+//!
+//! The next execution is computed from the system time every time the task runs, so changes of
+//! the system clock are taken into account from the following execution.
+//!
+//! In time zones with daylight saving time, the schedules keep running at the same local time
+//! across the transitions. On the day the clocks are turned forward the time might not exist
+//! (such as 02:30 in most of Europe), and on the day they are turned back it might happen twice:
+//! by default the execution is shifted forward by the length of the transition in the first
+//! case, and happens only at the first occurrence in the second case. This can be changed with
+//! `on_missing_time` and `on_repeated_time`.
+//!
+//! [`time_zone`]: ../time_zone/index.html
+use clock;
+use schedule::{Anchor, Schedule};
+use schedule::time_zone::{LocalTime, TimeZone, Utc, from_local};
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Returns the number of days since Sunday.
+    fn days_from_sunday(self) -> i64 {
+        match self {
+            Weekday::Sunday => 0,
+            Weekday::Monday => 1,
+            Weekday::Tuesday => 2,
+            Weekday::Wednesday => 3,
+            Weekday::Thursday => 4,
+            Weekday::Friday => 5,
+            Weekday::Saturday => 6,
+        }
+    }
+}
+
+/// What a daily or weekly schedule does when its time doesn't exist on a given day, because
+/// the clocks are turned forward for daylight saving time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingTime {
+    /// Runs the task later, by the length of the transition (e.g. at 03:30 instead of 02:30).
+    /// This is the default.
+    Shift,
+    /// Doesn't run the task on that day.
+    Skip,
+}
+
+/// What a daily or weekly schedule does when its time happens twice on a given day, because the
+/// clocks are turned back at the end of daylight saving time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeatedTime {
+    /// Runs the task at the first occurrence. This is the default.
+    First,
+    /// Runs the task at the second occurrence.
+    Last,
+}
+
+/// A time of the day in a time zone, shared by the daily and weekly schedules.
+#[derive(Debug, Clone)]
+struct LocalTimeOfDay<Z> {
+    seconds: i64,
+    time_zone: Z,
+    missing: MissingTime,
+    repeated: RepeatedTime,
+}
+
+impl LocalTimeOfDay<Utc> {
+    fn new(hour: u32, minute: u32, second: u32) -> LocalTimeOfDay<Utc> {
+        assert!(hour < 24 && minute < 60 && second < 60, "Invalid time of the day {:02}:{:02}:{:02}", hour, minute, second);
+        LocalTimeOfDay {
+            seconds: i64::from(hour * 3600 + minute * 60 + second),
+            time_zone: Utc,
+            missing: MissingTime::Shift,
+            repeated: RepeatedTime::First,
+        }
+    }
+}
+
+impl<Z: TimeZone> LocalTimeOfDay<Z> {
+    fn in_time_zone<T: TimeZone>(self, time_zone: T) -> LocalTimeOfDay<T> {
+        LocalTimeOfDay { seconds: self.seconds, time_zone, missing: self.missing, repeated: self.repeated }
+    }
+
+    /// Returns the UTC time of this time of the day on the given local day, if any.
+    fn on_day(&self, day: i64) -> Option<i64> {
+        match from_local(&self.time_zone, day * 86400 + self.seconds) {
+            LocalTime::Unique(time) => Some(time),
+            LocalTime::Repeated(first, _) if self.repeated == RepeatedTime::First => Some(first),
+            LocalTime::Repeated(_, last) => Some(last),
+            LocalTime::Missing(shifted) if self.missing == MissingTime::Shift => Some(shifted),
+            LocalTime::Missing(_) => None,
+        }
+    }
+
+    /// Returns the first time strictly after `after` matching this time of the day, on one of
+    /// the local days accepted by `day_matches`.
+    fn next_after<F: Fn(i64) -> bool>(&self, after: SystemTime, day_matches: F) -> SystemTime {
+        let after = unix_seconds(after);
+        let mut day = (after + self.time_zone.offset_at(after)).div_euclid(86400) - 1;
+        loop {
+            if day_matches(day) {
+                match self.on_day(day) {
+                    Some(next) if next > after => return from_unix_seconds(next),
+                    _ => {},
+                }
+            }
+            day += 1;
+        }
+    }
+}
+
+/// Runs the task every day at the given time.
+#[derive(Debug, Clone)]
+pub struct Daily<Z = Utc> {
+    time: LocalTimeOfDay<Z>,
+    wall_clock: WallClock,
+}
+
+impl Daily {
+    /// Creates a schedule running every day at `hour:minute:second` UTC.
+    ///
+    /// Panics if the time is not valid.
+    pub fn at(hour: u32, minute: u32, second: u32) -> Daily {
+        Daily { time: LocalTimeOfDay::new(hour, minute, second), wall_clock: WallClock::default() }
+    }
+}
+
+impl<Z: TimeZone> Daily<Z> {
+    /// Interprets the time of the day in the given time zone, instead of UTC.
+    pub fn in_time_zone<T: TimeZone>(self, time_zone: T) -> Daily<T> {
+        Daily { time: self.time.in_time_zone(time_zone), wall_clock: self.wall_clock }
+    }
+
+    /// Sets what happens on the days the time doesn't exist. Defaults to `MissingTime::Shift`.
+    pub fn on_missing_time(mut self, missing: MissingTime) -> Daily<Z> {
+        self.time.missing = missing;
+        self
+    }
+
+    /// Sets what happens on the days the time happens twice. Defaults to `RepeatedTime::First`.
+    pub fn on_repeated_time(mut self, repeated: RepeatedTime) -> Daily<Z> {
+        self.time.repeated = repeated;
+        self
+    }
+
+    /// Returns the first matching time strictly after `after`.
+    pub fn next_match_after(&self, after: SystemTime) -> SystemTime {
+        self.time.next_after(after, |_| true)
+    }
+}
+
+impl<Z: TimeZone> Schedule for Daily<Z> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let Daily { ref time, ref mut wall_clock } = *self;
+        wall_clock.next_after(after, |after| Some(time.next_after(after, |_| true)))
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor::WallClock
+    }
+}
+
+/// Runs the task every week, on the given day and time.
+#[derive(Debug, Clone)]
+pub struct Weekly<Z = Utc> {
+    weekday: Weekday,
+    time: LocalTimeOfDay<Z>,
+    wall_clock: WallClock,
+}
+
+impl Weekly {
+    /// Creates a schedule running every week on `weekday`, at `hour:minute:second` UTC.
+    ///
+    /// Panics if the time is not valid.
+    pub fn on(weekday: Weekday, hour: u32, minute: u32, second: u32) -> Weekly {
+        Weekly { weekday, time: LocalTimeOfDay::new(hour, minute, second), wall_clock: WallClock::default() }
+    }
+}
+
+impl<Z: TimeZone> Weekly<Z> {
+    /// Interprets the day and the time in the given time zone, instead of UTC.
+    pub fn in_time_zone<T: TimeZone>(self, time_zone: T) -> Weekly<T> {
+        Weekly { weekday: self.weekday, time: self.time.in_time_zone(time_zone), wall_clock: self.wall_clock }
+    }
+
+    /// Sets what happens on the days the time doesn't exist. Defaults to `MissingTime::Shift`.
+    pub fn on_missing_time(mut self, missing: MissingTime) -> Weekly<Z> {
+        self.time.missing = missing;
+        self
+    }
+
+    /// Sets what happens on the days the time happens twice. Defaults to `RepeatedTime::First`.
+    pub fn on_repeated_time(mut self, repeated: RepeatedTime) -> Weekly<Z> {
+        self.time.repeated = repeated;
+        self
+    }
+
+    /// Returns the first matching time strictly after `after`.
+    pub fn next_match_after(&self, after: SystemTime) -> SystemTime {
+        self.time.next_after(after, weekday_matcher(self.weekday))
+    }
+}
+
+/// Returns a function checking if a day since the epoch is the given day of the week.
+fn weekday_matcher(weekday: Weekday) -> impl Fn(i64) -> bool {
+    // The epoch was a Thursday
+    move |day| (day + 4).rem_euclid(7) == weekday.days_from_sunday()
+}
+
+impl<Z: TimeZone> Schedule for Weekly<Z> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let Weekly { weekday, ref time, ref mut wall_clock } = *self;
+        wall_clock.next_after(after, |after| Some(time.next_after(after, weekday_matcher(weekday))))
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor::WallClock
+    }
+}
+
+/// What a monthly schedule does in the months shorter than its day of the month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShortMonth {
+    /// Doesn't run the task in those months. This is the default.
+    Skip,
+    /// Runs the task on the last day of those months.
+    LastDay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MonthDay {
+    Day(u32),
+    Last,
+}
+
+/// Runs the task every month, on the given day and time.
+#[derive(Debug, Clone)]
+pub struct Monthly<Z = Utc> {
+    day: MonthDay,
+    short_month: ShortMonth,
+    time: LocalTimeOfDay<Z>,
+    wall_clock: WallClock,
+}
+
+impl Monthly {
+    /// Creates a schedule running every month on `day`, at `hour:minute:second` UTC. By default
+    /// the months without that day are skipped, see `on_short_month`.
+    ///
+    /// Panics if the day or the time are not valid.
+    pub fn on(day: u32, hour: u32, minute: u32, second: u32) -> Monthly {
+        assert!((1..=31).contains(&day), "Invalid day of the month {}", day);
+        Monthly::new(MonthDay::Day(day), hour, minute, second)
+    }
+
+    /// Creates a schedule running on the last day of every month, at `hour:minute:second` UTC.
+    ///
+    /// Panics if the time is not valid.
+    pub fn on_last_day(hour: u32, minute: u32, second: u32) -> Monthly {
+        Monthly::new(MonthDay::Last, hour, minute, second)
+    }
+
+    fn new(day: MonthDay, hour: u32, minute: u32, second: u32) -> Monthly {
+        Monthly {
+            day,
+            short_month: ShortMonth::Skip,
+            time: LocalTimeOfDay::new(hour, minute, second),
+            wall_clock: WallClock::default(),
+        }
+    }
+}
+
+impl<Z: TimeZone> Monthly<Z> {
+    /// Interprets the day and the time in the given time zone, instead of UTC.
+    pub fn in_time_zone<T: TimeZone>(self, time_zone: T) -> Monthly<T> {
+        Monthly {
+            day: self.day,
+            short_month: self.short_month,
+            time: self.time.in_time_zone(time_zone),
+            wall_clock: self.wall_clock,
+        }
+    }
+
+    /// Sets what happens in the months shorter than the day of the schedule. Defaults to
+    /// `ShortMonth::Skip`.
+    pub fn on_short_month(mut self, short_month: ShortMonth) -> Monthly<Z> {
+        self.short_month = short_month;
+        self
+    }
+
+    /// Sets what happens on the days the time doesn't exist. Defaults to `MissingTime::Shift`.
+    pub fn on_missing_time(mut self, missing: MissingTime) -> Monthly<Z> {
+        self.time.missing = missing;
+        self
+    }
+
+    /// Sets what happens on the days the time happens twice. Defaults to `RepeatedTime::First`.
+    pub fn on_repeated_time(mut self, repeated: RepeatedTime) -> Monthly<Z> {
+        self.time.repeated = repeated;
+        self
+    }
+
+    /// Returns the first matching time strictly after `after`.
+    pub fn next_match_after(&self, after: SystemTime) -> SystemTime {
+        self.time.next_after(after, month_day_matcher(self.day, self.short_month))
+    }
+}
+
+/// Returns a function checking if a day since the epoch is the given day of its month.
+fn month_day_matcher(month_day: MonthDay, short_month: ShortMonth) -> impl Fn(i64) -> bool {
+    move |day| {
+        let (year, month, day_of_month) = civil_from_days(day);
+        let last = days_in_month(year, month);
+        match month_day {
+            MonthDay::Last => day_of_month == last,
+            MonthDay::Day(target) if target > last => short_month == ShortMonth::LastDay && day_of_month == last,
+            MonthDay::Day(target) => day_of_month == target,
+        }
+    }
+}
+
+impl<Z: TimeZone> Schedule for Monthly<Z> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let Monthly { day, short_month, ref time, ref mut wall_clock } = *self;
+        wall_clock.next_after(after, |after| Some(time.next_after(after, month_day_matcher(day, short_month))))
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor::WallClock
+    }
+}
+
+/// Converts between instants and system times for the wall-clock schedules.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WallClock {
+    previous: Option<SystemTime>,
+}
+
+impl WallClock {
+    /// Converts `after` to a system time, and returns the instant corresponding to the system
+    /// time returned by `next_match`, according to the clock of the current executor.
+    pub(crate) fn next_after<F>(&mut self, after: Instant, next_match: F) -> Option<Instant>
+        where F: FnOnce(SystemTime) -> Option<SystemTime>
+    {
+        let clock = clock::current();
+        let now = clock.now();
+        let system_now = clock.system_time();
+        let mut system_after = if after > now {
+            system_now + (after - now)
+        } else {
+            system_now - (now - after)
+        };
+        // Never fire twice for the same second, even if the timer woke up slightly early
+        if let Some(previous) = self.previous {
+            if previous > system_after {
+                system_after = previous;
+            }
+        }
+        let next = next_match(system_after)?;
+        self.previous = Some(next);
+        Some(match next.duration_since(system_now) {
+            Ok(ahead) => now + ahead,
+            Err(_) => now,
+        })
+    }
+}
+
+/// Returns the whole seconds since the epoch.
+pub(crate) fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    }
+}
+
+pub(crate) fn from_unix_seconds(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
+
+/// Returns the number of days of the given month.
+pub(crate) fn days_in_month(year: i64, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)) as u32
+}
+
+// Conversion between days since the epoch and civil dates, from
+// http://howardhinnant.github.io/date_algorithms.html
+
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::Future;
+    use tokio_core::reactor::Handle;
+
+    use std::sync::Arc;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use clock::{self, Clock, SystemClock};
+    use error::ScheduleError;
+    use schedule::Schedule;
+    use schedule::time_zone::{FixedOffset, PosixTimeZone};
+    use super::{Daily, MissingTime, Monthly, RepeatedTime, ShortMonth, Weekday, Weekly, civil_from_days, days_from_civil};
+
+    struct FixedClock(Instant, SystemTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> Instant {
+            self.0
+        }
+
+        fn sleep_until(&self, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            SystemClock.sleep_until(deadline, handle)
+        }
+
+        fn system_time(&self) -> SystemTime {
+            self.1
+        }
+    }
+
+    fn time(year: i64, month: u32, day: u32, hour: u64, min: u64, sec: u64) -> SystemTime {
+        let days = days_from_civil(year, month, day) as u64;
+        UNIX_EPOCH + Duration::from_secs(days * 86400 + hour * 3600 + min * 60 + sec)
+    }
+
+    #[test]
+    fn civil_conversion_test() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        for days in &[0, 59, 365, 11016, 11017, 19782, 47541] {
+            let (y, m, d) = civil_from_days(*days);
+            assert_eq!(days_from_civil(y, m, d), *days);
+        }
+    }
+
+    #[test]
+    fn daily_test() {
+        let daily = Daily::at(2, 0, 0);
+        assert_eq!(daily.next_match_after(time(2024, 1, 1, 0, 0, 0)), time(2024, 1, 1, 2, 0, 0));
+        assert_eq!(daily.next_match_after(time(2024, 1, 1, 2, 0, 0)), time(2024, 1, 2, 2, 0, 0));
+        assert_eq!(daily.next_match_after(time(2024, 2, 28, 13, 0, 0)), time(2024, 2, 29, 2, 0, 0));
+        assert_eq!(daily.next_match_after(time(2024, 12, 31, 2, 0, 1)), time(2025, 1, 1, 2, 0, 0));
+    }
+
+    #[test]
+    fn weekly_test() {
+        // 2024-01-01 is a Monday
+        let weekly = Weekly::on(Weekday::Monday, 9, 30, 0);
+        assert_eq!(weekly.next_match_after(time(2024, 1, 1, 0, 0, 0)), time(2024, 1, 1, 9, 30, 0));
+        assert_eq!(weekly.next_match_after(time(2024, 1, 1, 9, 30, 0)), time(2024, 1, 8, 9, 30, 0));
+        let weekly = Weekly::on(Weekday::Sunday, 0, 0, 0);
+        assert_eq!(weekly.next_match_after(time(2024, 1, 1, 0, 0, 0)), time(2024, 1, 7, 0, 0, 0));
+        assert_eq!(weekly.next_match_after(time(2024, 1, 6, 23, 59, 59)), time(2024, 1, 7, 0, 0, 0));
+        let weekly = Weekly::on(Weekday::Thursday, 12, 0, 0);
+        assert_eq!(weekly.next_match_after(UNIX_EPOCH), time(1970, 1, 1, 12, 0, 0));
+    }
+
+    #[test]
+    fn time_zone_test() {
+        let rome = PosixTimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let daily = Daily::at(9, 0, 0).in_time_zone(rome.clone());
+        assert_eq!(daily.next_match_after(time(2024, 3, 29, 12, 0, 0)), time(2024, 3, 30, 8, 0, 0));
+        // Still 09:00 local time after the start of DST
+        assert_eq!(daily.next_match_after(time(2024, 3, 30, 12, 0, 0)), time(2024, 3, 31, 7, 0, 0));
+        assert_eq!(daily.next_match_after(time(2024, 3, 31, 6, 0, 0)), time(2024, 3, 31, 7, 0, 0));
+
+        // Midnight in Rome is still Sunday in UTC
+        let weekly = Weekly::on(Weekday::Monday, 0, 30, 0).in_time_zone(rome);
+        assert_eq!(weekly.next_match_after(time(2024, 1, 1, 0, 0, 0)), time(2024, 1, 7, 23, 30, 0));
+
+        let daily = Daily::at(23, 0, 0).in_time_zone(FixedOffset::east(-5 * 3600));
+        assert_eq!(daily.next_match_after(time(2024, 1, 1, 12, 0, 0)), time(2024, 1, 2, 4, 0, 0));
+        assert_eq!(daily.next_match_after(time(2024, 1, 2, 3, 0, 0)), time(2024, 1, 2, 4, 0, 0));
+    }
+
+    #[test]
+    fn dst_transitions_test() {
+        let rome = PosixTimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        // 02:30 doesn't exist on 2024-03-31, and happens twice on 2024-10-27
+        let daily = Daily::at(2, 30, 0).in_time_zone(rome.clone());
+        assert_eq!(daily.next_match_after(time(2024, 3, 30, 12, 0, 0)), time(2024, 3, 31, 1, 30, 0));
+        assert_eq!(daily.next_match_after(time(2024, 3, 31, 1, 30, 0)), time(2024, 4, 1, 0, 30, 0));
+        assert_eq!(daily.next_match_after(time(2024, 10, 26, 12, 0, 0)), time(2024, 10, 27, 0, 30, 0));
+        // Only once per day
+        assert_eq!(daily.next_match_after(time(2024, 10, 27, 0, 30, 0)), time(2024, 10, 28, 1, 30, 0));
+
+        let daily = daily.on_missing_time(MissingTime::Skip).on_repeated_time(RepeatedTime::Last);
+        assert_eq!(daily.next_match_after(time(2024, 3, 30, 12, 0, 0)), time(2024, 4, 1, 0, 30, 0));
+        assert_eq!(daily.next_match_after(time(2024, 10, 26, 12, 0, 0)), time(2024, 10, 27, 1, 30, 0));
+        assert_eq!(daily.next_match_after(time(2024, 10, 27, 1, 30, 0)), time(2024, 10, 28, 1, 30, 0));
+
+        let weekly = Weekly::on(Weekday::Sunday, 2, 30, 0).in_time_zone(rome).on_missing_time(MissingTime::Skip);
+        assert_eq!(weekly.next_match_after(time(2024, 3, 25, 0, 0, 0)), time(2024, 4, 7, 0, 30, 0));
+    }
+
+    #[test]
+    fn monthly_test() {
+        let last = Monthly::on_last_day(23, 0, 0);
+        assert_eq!(last.next_match_after(time(2024, 1, 31, 12, 0, 0)), time(2024, 1, 31, 23, 0, 0));
+        assert_eq!(last.next_match_after(time(2024, 1, 31, 23, 0, 0)), time(2024, 2, 29, 23, 0, 0));
+        assert_eq!(last.next_match_after(time(2023, 2, 1, 0, 0, 0)), time(2023, 2, 28, 23, 0, 0));
+        assert_eq!(last.next_match_after(time(2024, 4, 1, 0, 0, 0)), time(2024, 4, 30, 23, 0, 0));
+
+        let thirty_first = Monthly::on(31, 8, 0, 0);
+        assert_eq!(thirty_first.next_match_after(time(2024, 1, 31, 9, 0, 0)), time(2024, 3, 31, 8, 0, 0));
+        assert_eq!(thirty_first.next_match_after(time(2024, 3, 31, 9, 0, 0)), time(2024, 5, 31, 8, 0, 0));
+        let thirty_first = thirty_first.on_short_month(ShortMonth::LastDay);
+        assert_eq!(thirty_first.next_match_after(time(2024, 1, 31, 9, 0, 0)), time(2024, 2, 29, 8, 0, 0));
+        assert_eq!(thirty_first.next_match_after(time(2024, 3, 31, 9, 0, 0)), time(2024, 4, 30, 8, 0, 0));
+
+        let fifteenth = Monthly::on(15, 0, 0, 0).in_time_zone(FixedOffset::east(3600));
+        assert_eq!(fifteenth.next_match_after(time(2024, 1, 1, 0, 0, 0)), time(2024, 1, 14, 23, 0, 0));
+    }
+
+    #[test]
+    fn clock_test() {
+        let now = Instant::now();
+        let clock: Arc<dyn Clock> = Arc::new(FixedClock(now, time(2024, 1, 1, 1, 0, 0)));
+        let mut daily = Daily::at(2, 0, 0);
+        clock::with_clock(&clock, || {
+            assert_eq!(daily.next_after(now), Some(now + Duration::from_secs(3600)));
+            assert_eq!(daily.next_after(now + Duration::from_secs(3600)), Some(now + Duration::from_secs(25 * 3600)));
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_time_test() {
+        Daily::at(24, 0, 0);
+    }
+}
@@ -0,0 +1,266 @@
+//! Cron expressions with a seconds field, in the form:
+//!
+//! ```text
+//! sec   min   hour   day-of-month   month   day-of-week
+//! 0     */5   *      *              *       *
+//! ```
+//!
+//! Each field accepts `*`, single values, ranges (`1-5`), lists (`1,3,5`) and steps (`*/15`,
+//! `10-30/5`, `5/10`). Months and days of the week can also be specified by their three letters
+//! english name (`JAN`, `MON`), and both `0` and `7` represent Sunday. The day-of-month and
+//! day-of-week fields also accept `?`, which is equivalent to `*`. As in most cron
+//! implementations, if both the day-of-month and the day-of-week fields are restricted, a day
+//! matches if it matches either of them.
+//!
+//! All the times are computed in UTC.
+use schedule::{Anchor, Schedule};
+use schedule::calendar::{WallClock, civil_from_days, days_from_civil, from_unix_seconds, unix_seconds};
+
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::time::{Instant, SystemTime};
+
+const MONTH_NAMES: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN",
+                                 "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Cron expressions without a match in this many years are considered to never fire
+/// (e.g. `0 0 0 30 2 *`). The longest gap between two matches is between two February 29th
+/// across a non-leap century year, such as 2096 and 2104.
+const MAX_YEARS_AHEAD: i64 = 8;
+
+/// The error returned when a cron expression can't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    expression: String,
+    reason: String,
+}
+
+impl ParseError {
+    fn new(expression: &str, reason: String) -> ParseError {
+        ParseError { expression: expression.to_owned(), reason }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cron expression '{}': {}", self.expression, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+/// A single field of a cron expression, stored as a bit set of the allowed values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Field {
+    bits: u64,
+    restricted: bool,
+}
+
+impl Field {
+    fn parse(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Field, String> {
+        let mut bits = 0u64;
+        let restricted = field != "*" && field != "?";
+        for part in field.split(',') {
+            let (range, step) = match part.find('/') {
+                Some(pos) => {
+                    let step = part[pos + 1..].parse::<u32>()
+                        .map_err(|_| format!("invalid step in '{}'", part))?;
+                    if step == 0 {
+                        return Err(format!("step can't be zero in '{}'", part));
+                    }
+                    (&part[..pos], step)
+                },
+                None => (part, 1),
+            };
+            let (start, end) = if range == "*" || range == "?" {
+                (min, max)
+            } else if let Some(pos) = range.find('-') {
+                (parse_value(&range[..pos], names, min)?, parse_value(&range[pos + 1..], names, min)?)
+            } else {
+                let value = parse_value(range, names, min)?;
+                // "5/10" means from 5 to the end of the range, every 10
+                (value, if part.contains('/') { max } else { value })
+            };
+            if start < min || end > max || start > end {
+                return Err(format!("'{}' is out of range {}-{}", part, min, max));
+            }
+            let mut value = start;
+            loop {
+                bits |= 1 << value;
+                match value.checked_add(step) {
+                    Some(next) if next <= end => value = next,
+                    _ => break,
+                }
+            }
+        }
+        Ok(Field { bits, restricted })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+fn parse_value(value: &str, names: &[&str], first_name: u32) -> Result<u32, String> {
+    if let Ok(number) = value.parse::<u32>() {
+        return Ok(number);
+    }
+    let upper = value.to_uppercase();
+    names.iter()
+        .position(|name| *name == upper)
+        .map(|pos| pos as u32 + first_name)
+        .ok_or_else(|| format!("invalid value '{}'", value))
+}
+
+/// A parsed cron expression. See the module documentation for the supported syntax.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    seconds: Field,
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+    wall_clock: WallClock,
+}
+
+impl CronSchedule {
+    /// Parses a cron expression.
+    pub fn parse(expression: &str) -> Result<CronSchedule, ParseError> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 6 {
+            return Err(ParseError::new(expression, format!("expected 6 fields, found {}", fields.len())));
+        }
+        let parse_field = |i: usize, min, max, names: &[&str]| {
+            Field::parse(fields[i], min, max, names).map_err(|reason| ParseError::new(expression, reason))
+        };
+        let mut days_of_week = parse_field(5, 0, 7, &DAY_NAMES)?;
+        if days_of_week.matches(7) {
+            days_of_week.bits |= 1;
+        }
+        Ok(CronSchedule {
+            seconds: parse_field(0, 0, 59, &[])?,
+            minutes: parse_field(1, 0, 59, &[])?,
+            hours: parse_field(2, 0, 23, &[])?,
+            days_of_month: parse_field(3, 1, 31, &[])?,
+            months: parse_field(4, 1, 12, &MONTH_NAMES)?,
+            days_of_week,
+            wall_clock: WallClock::default(),
+        })
+    }
+
+    fn day_matches(&self, day_of_month: u32, day_of_week: u32) -> bool {
+        let dom = self.days_of_month.matches(day_of_month);
+        let dow = self.days_of_week.matches(day_of_week);
+        if self.days_of_month.restricted && self.days_of_week.restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+
+    /// Returns the first time matching the expression strictly after `after`, or `None` if the
+    /// expression will never match.
+    pub fn next_match_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let mut t = unix_seconds(after) + 1;
+        let max_year = civil_from_days(t.div_euclid(86400)).0 + MAX_YEARS_AHEAD;
+        loop {
+            let days = t.div_euclid(86400);
+            let (year, month, day) = civil_from_days(days);
+            if year > max_year {
+                return None;
+            }
+            let secs_of_day = t.rem_euclid(86400);
+            if !self.months.matches(month) {
+                let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                t = days_from_civil(next_year, next_month, 1) * 86400;
+            } else if !self.day_matches(day, (days + 4).rem_euclid(7) as u32) {
+                t = (days + 1) * 86400;
+            } else if !self.hours.matches((secs_of_day / 3600) as u32) {
+                t = t - t.rem_euclid(3600) + 3600;
+            } else if !self.minutes.matches((secs_of_day % 3600 / 60) as u32) {
+                t = t - t.rem_euclid(60) + 60;
+            } else if !self.seconds.matches((secs_of_day % 60) as u32) {
+                t += 1;
+            } else {
+                return Some(from_unix_seconds(t));
+            }
+        }
+    }
+}
+
+impl Schedule for CronSchedule {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let mut wall_clock = mem::take(&mut self.wall_clock);
+        let next = wall_clock.next_after(after, |after| self.next_match_after(after));
+        self.wall_clock = wall_clock;
+        next
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor::WallClock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use schedule::calendar::days_from_civil;
+    use super::CronSchedule;
+
+    fn time(year: i64, month: u32, day: u32, hour: u64, min: u64, sec: u64) -> SystemTime {
+        let days = days_from_civil(year, month, day) as u64;
+        UNIX_EPOCH + Duration::from_secs(days * 86400 + hour * 3600 + min * 60 + sec)
+    }
+
+    #[test]
+    fn parse_test() {
+        assert!(CronSchedule::parse("0 */5 * * * *").is_ok());
+        assert!(CronSchedule::parse("0 0 9-17/2 ? JAN-MAR mon,fri").is_ok());
+        assert!(CronSchedule::parse("* * * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 0 0 * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 0 * FOO *").is_err());
+        assert!(CronSchedule::parse("0 5-1 * * * *").is_err());
+    }
+
+    #[test]
+    fn next_match_after_test() {
+        let cron = CronSchedule::parse("0 */5 * * * *").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 10, 3, 20)), Some(time(2024, 1, 1, 10, 5, 0)));
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 10, 5, 0)), Some(time(2024, 1, 1, 10, 10, 0)));
+        assert_eq!(cron.next_match_after(time(2024, 12, 31, 23, 59, 59)), Some(time(2025, 1, 1, 0, 0, 0)));
+
+        let cron = CronSchedule::parse("30 0 12 29 FEB *").unwrap();
+        assert_eq!(cron.next_match_after(time(2021, 3, 1, 0, 0, 0)), Some(time(2024, 2, 29, 12, 0, 30)));
+
+        // 2024-01-01 is a Monday
+        let cron = CronSchedule::parse("0 0 8 ? * SUN").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 0, 0, 0)), Some(time(2024, 1, 7, 8, 0, 0)));
+        let cron = CronSchedule::parse("0 0 8 ? * 7").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 0, 0, 0)), Some(time(2024, 1, 7, 8, 0, 0)));
+
+        // Either the 15th or a Friday
+        let cron = CronSchedule::parse("0 0 0 15 * FRI").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 0, 0, 0)), Some(time(2024, 1, 5, 0, 0, 0)));
+        assert_eq!(cron.next_match_after(time(2024, 1, 12, 0, 0, 0)), Some(time(2024, 1, 15, 0, 0, 0)));
+
+        let cron = CronSchedule::parse("0 0 0 30 2 *").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 0, 0, 0)), None);
+
+        // 2100 is not a leap year
+        let cron = CronSchedule::parse("0 0 0 29 2 *").unwrap();
+        assert_eq!(cron.next_match_after(time(2096, 3, 1, 0, 0, 0)), Some(time(2104, 2, 29, 0, 0, 0)));
+
+        // A step alone means from the value to the end of the range, even larger than the range
+        let cron = CronSchedule::parse("5/1 * * * * *").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 10, 0, 5)), Some(time(2024, 1, 1, 10, 0, 6)));
+        let cron = CronSchedule::parse("5/4294967295 * * * * *").unwrap();
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 10, 0, 0)), Some(time(2024, 1, 1, 10, 0, 5)));
+        assert_eq!(cron.next_match_after(time(2024, 1, 1, 10, 0, 5)), Some(time(2024, 1, 1, 10, 1, 5)));
+    }
+}
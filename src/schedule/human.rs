@@ -0,0 +1,158 @@
+//! Parsing of human-readable durations and schedules, so that they can be defined in
+//! configuration files or environment variables:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::schedule::human;
+//! let executor = CoreExecutor::new().unwrap();
+//! let schedule = human::parse_schedule("every 5m 30s").unwrap();
+//! executor.schedule_with(schedule, |_handle| println!("Running"));
+//! ```
+//!
+//! Durations are sequences of numbers followed by a unit, optionally separated by spaces, such
+//! as `"1h 30m"` or `"2d12h"`. The supported units are `ns`, `us`, `ms`, `s`, `m`, `h`, `d` and
+//! `w`, and their long forms (`sec`, `secs`, `second`, `seconds`, `min`, `minutes`, `hour`,
+//! ...). The supported schedules are:
+//!
+//! - `every <duration>`: runs the task immediately, and then at a fixed rate.
+//! - `every <duration> after <duration>`: as above, with a delay before the first execution.
+//! - `once in <duration>`: runs the task once, after the given delay.
+use schedule::{Delays, FixedRate, Schedule};
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// The error returned when a duration or a schedule can't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    input: String,
+    reason: String,
+}
+
+impl ParseError {
+    fn new(input: &str, reason: String) -> ParseError {
+        ParseError { input: input.to_owned(), reason }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid schedule '{}': {}", self.input, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+fn unit_nanos(unit: &str) -> Option<u64> {
+    let nanos = match unit {
+        "ns" | "nsec" | "nanos" => 1,
+        "us" | "usec" | "micros" => 1_000,
+        "ms" | "msec" | "millis" => 1_000_000,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1_000_000_000,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60 * 1_000_000_000,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600 * 1_000_000_000,
+        "d" | "day" | "days" => 86400 * 1_000_000_000,
+        "w" | "week" | "weeks" => 7 * 86400 * 1_000_000_000,
+        _ => return None,
+    };
+    Some(nanos)
+}
+
+fn duration(input: &str) -> Result<Duration, String> {
+    let mut total = Duration::from_secs(0);
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        return Err("missing duration".to_owned());
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let number = rest[..digits].parse::<u64>()
+            .map_err(|_| format!("expected a number at '{}'", rest))?;
+        rest = rest[digits..].trim_start();
+        let letters = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = &rest[..letters];
+        let nanos = unit_nanos(unit).ok_or_else(|| format!("invalid unit '{}'", unit))?;
+        let value = number.checked_mul(nanos).ok_or_else(|| "duration too long".to_owned())?;
+        total += Duration::from_nanos(value);
+        rest = rest[letters..].trim_start();
+    }
+    Ok(total)
+}
+
+/// Parses a duration such as `"5m 30s"`.
+pub fn parse_duration(input: &str) -> Result<Duration, ParseError> {
+    duration(input).map_err(|reason| ParseError::new(input, reason))
+}
+
+fn schedule(input: &str) -> Result<Box<dyn Schedule>, String> {
+    let input = input.trim();
+    if let Some(rest) = input.strip_prefix("once in ") {
+        return Ok(Box::new(Delays::new(vec![duration(rest)?])));
+    }
+    if let Some(rest) = input.strip_prefix("every ") {
+        let (interval, initial) = match rest.find(" after ") {
+            Some(pos) => (duration(&rest[..pos])?, duration(&rest[pos + 7..])?),
+            None => (duration(rest)?, Duration::from_secs(0)),
+        };
+        if interval == Duration::from_secs(0) {
+            return Err("the interval can't be zero".to_owned());
+        }
+        return Ok(Box::new(FixedRate::new(initial, interval)));
+    }
+    Err("expected 'every ...' or 'once in ...'".to_owned())
+}
+
+/// Parses a schedule such as `"every 5m 30s"` or `"once in 2h"`. See the [module documentation]
+/// for the supported syntax.
+///
+/// [module documentation]: index.html
+pub fn parse_schedule(input: &str) -> Result<Box<dyn Schedule>, ParseError> {
+    schedule(input).map_err(|reason| ParseError::new(input, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{parse_duration, parse_schedule};
+
+    fn s(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn parse_duration_test() {
+        assert_eq!(parse_duration("30s"), Ok(s(30)));
+        assert_eq!(parse_duration("5m 30s"), Ok(s(330)));
+        assert_eq!(parse_duration("2d12h"), Ok(s(60 * 3600)));
+        assert_eq!(parse_duration("1 hour 5 minutes"), Ok(s(3900)));
+        assert_eq!(parse_duration("1w"), Ok(s(7 * 86400)));
+        assert_eq!(parse_duration(" 250ms "), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("10us"), Ok(Duration::from_micros(10)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5 parsecs").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn parse_schedule_test() {
+        let now = Instant::now();
+        let mut every = parse_schedule("every 5m 30s").unwrap();
+        assert_eq!(every.next_after(now), Some(now));
+        assert_eq!(every.next_after(now), Some(now + s(330)));
+
+        let mut delayed = parse_schedule("every 1h after 10m").unwrap();
+        assert_eq!(delayed.next_after(now), Some(now + s(600)));
+        assert_eq!(delayed.next_after(now + s(600)), Some(now + s(4200)));
+
+        let mut once = parse_schedule("once in 2h").unwrap();
+        assert_eq!(once.next_after(now), Some(now + s(7200)));
+        assert_eq!(once.next_after(now + s(7200)), None);
+
+        assert!(parse_schedule("every 0s").is_err());
+        assert!(parse_schedule("twice in 2h").is_err());
+        assert!(parse_schedule("every").is_err());
+        assert!(parse_schedule("once in soon").is_err());
+    }
+}
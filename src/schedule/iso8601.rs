@@ -0,0 +1,391 @@
+//! ISO 8601 repeating intervals, in the form `R[n]/start/duration`:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::schedule::iso8601::RepeatingInterval;
+//! let executor = CoreExecutor::new().unwrap();
+//! // Five hourly executions, starting at midnight UTC of January 1st 2030
+//! let schedule = RepeatingInterval::parse("R5/2030-01-01T00:00:00Z/PT1H").unwrap();
+//! executor.schedule_with(schedule, |_handle| println!("Running"));
+//! ```
+//!
+//! The following forms are supported:
+//!
+//! - `R[n]/<start>/<duration>`: runs the task at `start`, and then after every `duration`.
+//! - `R[n]/<start>/<end>`: as above, with the duration between `start` and `end`.
+//! - `R[n]/<duration>`: runs the task immediately, and then after every `duration`.
+//!
+//! If the number of repetitions `n` is omitted, the task runs forever; otherwise it runs at most
+//! `n` times. Executions planned before the task is scheduled are skipped, but still count
+//! towards the repetitions.
+//!
+//! Start and end times are written as `YYYY-MM-DDThh:mm[:ss[.fff]]`, followed by `Z` or by an
+//! offset such as `+01:00` (UTC is assumed if omitted). Durations are written as
+//! `PnYnMnDTnHnMnS` or `PnW`. Years and months are added according to the calendar, and the
+//! executions on days missing in shorter months happen on the last day of the month. Durations
+//! longer than 10000 years are rejected.
+use schedule::{Anchor, Schedule};
+use schedule::calendar::{WallClock, civil_from_days, days_from_civil, days_in_month, from_unix_seconds, unix_seconds};
+
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::time::Instant;
+
+/// The error returned when a repeating interval can't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    expression: String,
+    reason: String,
+}
+
+impl ParseError {
+    fn new(expression: &str, reason: String) -> ParseError {
+        ParseError { expression: expression.to_owned(), reason }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid repeating interval '{}': {}", self.expression, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+/// The longest supported duration, so that the executions can be computed without overflowing.
+const MAX_YEARS: i64 = 10_000;
+
+/// A duration, split in its calendar-dependent and fixed parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Period {
+    months: i64,
+    seconds: i64,
+}
+
+impl Period {
+    fn parse(period: &str) -> Result<Period, String> {
+        let invalid = || format!("invalid duration '{}'", period);
+        let too_long = || format!("the duration '{}' is too long", period);
+        let add = |total: i64, value: i64, factor: i64| {
+            value.checked_mul(factor).and_then(|value| total.checked_add(value)).ok_or_else(too_long)
+        };
+        let rest = period.strip_prefix('P').ok_or_else(invalid)?;
+        let (date, time) = match rest.find('T') {
+            Some(pos) => (&rest[..pos], Some(&rest[pos + 1..])),
+            None => (rest, None),
+        };
+        let mut result = Period { months: 0, seconds: 0 };
+        let mut components = 0;
+        for (value, unit) in period_components(date).ok_or_else(invalid)? {
+            match unit {
+                'Y' => result.months = add(result.months, value, 12)?,
+                'M' => result.months = add(result.months, value, 1)?,
+                'W' => result.seconds = add(result.seconds, value, 7 * 86400)?,
+                'D' => result.seconds = add(result.seconds, value, 86400)?,
+                _ => return Err(invalid()),
+            }
+            components += 1;
+        }
+        if let Some(time) = time {
+            let time_components = period_components(time).ok_or_else(invalid)?;
+            if time_components.is_empty() {
+                return Err(invalid());
+            }
+            for (value, unit) in time_components {
+                match unit {
+                    'H' => result.seconds = add(result.seconds, value, 3600)?,
+                    'M' => result.seconds = add(result.seconds, value, 60)?,
+                    'S' => result.seconds = add(result.seconds, value, 1)?,
+                    _ => return Err(invalid()),
+                }
+                components += 1;
+            }
+        }
+        if components == 0 {
+            return Err(invalid());
+        }
+        if result.months == 0 && result.seconds == 0 {
+            return Err(format!("the duration '{}' can't be zero", period));
+        }
+        if result.months > MAX_YEARS * 12 || result.seconds / 86400 > MAX_YEARS * 366 {
+            return Err(too_long());
+        }
+        Ok(result)
+    }
+
+    /// Returns the time `count` periods after `start`, in seconds since the epoch.
+    fn after(self, start: i64, count: i64) -> i64 {
+        let mut time = start;
+        if self.months != 0 {
+            let days = start.div_euclid(86400);
+            let (year, month, day) = civil_from_days(days);
+            let months = i64::from(month - 1) + self.months * count;
+            let (year, month) = (year + months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+            let day = cmp::min(day, days_in_month(year, month));
+            time = days_from_civil(year, month, day) * 86400 + start.rem_euclid(86400);
+        }
+        time + self.seconds * count
+    }
+}
+
+/// Splits `5D3H` into `[(5, 'D'), (3, 'H')]`.
+fn period_components(input: &str) -> Option<Vec<(i64, char)>> {
+    let mut components = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let value = rest[..digits].parse::<i64>().ok()?;
+        let unit = rest[digits..].chars().next()?;
+        components.push((value, unit));
+        rest = &rest[digits + unit.len_utf8()..];
+    }
+    Some(components)
+}
+
+fn parse_number(value: &str, min: u32, max: u32) -> Option<u32> {
+    match value.parse::<u32>() {
+        Ok(number) if number >= min && number <= max && value.len() >= 2 => Some(number),
+        _ => None,
+    }
+}
+
+/// Parses a date and time, returning the seconds since the epoch.
+fn parse_date_time(input: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid date and time '{}'", input);
+    let pos = input.find('T').ok_or_else(invalid)?;
+    let (date, time) = (&input[..pos], &input[pos + 1..]);
+    let date_parts = date.split('-').collect::<Vec<_>>();
+    if date_parts.len() != 3 || date_parts[0].len() != 4 {
+        return Err(invalid());
+    }
+    let year = i64::from(parse_number(date_parts[0], 0, 9999).ok_or_else(invalid)?);
+    let month = parse_number(date_parts[1], 1, 12).ok_or_else(invalid)?;
+    let day = parse_number(date_parts[2], 1, days_in_month(year, month)).ok_or_else(invalid)?;
+
+    let (time, offset) = if let Some(time) = time.strip_suffix('Z') {
+        (time, 0)
+    } else if let Some(pos) = time.rfind(['+', '-']) {
+        let sign = if time[pos..].starts_with('-') { -1 } else { 1 };
+        let offset_parts = time[pos + 1..].split(':').collect::<Vec<_>>();
+        let hours = parse_number(offset_parts[0], 0, 23).ok_or_else(invalid)?;
+        let minutes = match offset_parts.get(1) {
+            Some(minutes) => parse_number(minutes, 0, 59).ok_or_else(invalid)?,
+            None => 0,
+        };
+        (&time[..pos], sign * i64::from(hours * 3600 + minutes * 60))
+    } else {
+        (time, 0)
+    };
+    // Fractional seconds are ignored
+    let time = time.split('.').next().unwrap_or(time);
+    let time_parts = time.split(':').collect::<Vec<_>>();
+    if time_parts.len() < 2 || time_parts.len() > 3 {
+        return Err(invalid());
+    }
+    let hour = parse_number(time_parts[0], 0, 23).ok_or_else(invalid)?;
+    let minute = parse_number(time_parts[1], 0, 59).ok_or_else(invalid)?;
+    let second = match time_parts.get(2) {
+        Some(second) => parse_number(second, 0, 60).ok_or_else(invalid)?,
+        None => 0,
+    };
+    let local = days_from_civil(year, month, day) * 86400 + i64::from(hour * 3600 + minute * 60 + second);
+    Ok(local - offset)
+}
+
+/// A parsed ISO 8601 repeating interval. See the [module documentation].
+///
+/// [module documentation]: index.html
+#[derive(Debug, Clone)]
+pub struct RepeatingInterval {
+    start: Option<i64>,
+    period: Period,
+    repetitions: Option<i64>,
+    /// The index of the next execution.
+    next: i64,
+    wall_clock: WallClock,
+}
+
+impl RepeatingInterval {
+    /// Parses a repeating interval.
+    pub fn parse(expression: &str) -> Result<RepeatingInterval, ParseError> {
+        RepeatingInterval::parse_interval(expression).map_err(|reason| ParseError::new(expression, reason))
+    }
+
+    fn parse_interval(expression: &str) -> Result<RepeatingInterval, String> {
+        let parts = expression.trim().split('/').collect::<Vec<_>>();
+        let repetitions = match parts[0].strip_prefix('R') {
+            Some("") => None,
+            Some(count) => match count.parse::<i64>() {
+                Ok(count) if count >= 0 => Some(count),
+                _ => return Err(format!("invalid repetitions '{}'", parts[0])),
+            },
+            None => return Err("expected 'R' followed by the number of repetitions".to_owned()),
+        };
+        let (start, period) = match parts.len() {
+            2 => (None, Period::parse(parts[1])?),
+            3 if parts[1].starts_with('P') => return Err("intervals defined by their end are not supported".to_owned()),
+            3 if parts[2].starts_with('P') => (Some(parse_date_time(parts[1])?), Period::parse(parts[2])?),
+            3 => {
+                let start = parse_date_time(parts[1])?;
+                let end = parse_date_time(parts[2])?;
+                if end <= start {
+                    return Err("the end should be after the start".to_owned());
+                }
+                (Some(start), Period { months: 0, seconds: end - start })
+            },
+            _ => return Err("expected 'R[n]/start/duration', 'R[n]/start/end' or 'R[n]/duration'".to_owned()),
+        };
+        Ok(RepeatingInterval { start, period, repetitions, next: 0, wall_clock: WallClock::default() })
+    }
+
+    /// Returns the number of executions left, or `None` if the schedule repeats forever.
+    pub fn remaining(&self) -> Option<u64> {
+        self.repetitions.map(|repetitions| cmp::max(repetitions - self.next, 0) as u64)
+    }
+
+    /// Returns the time of the first execution strictly after `after`, and its index.
+    fn next_occurrence(&self, start: i64, after: i64) -> Option<(i64, i64)> {
+        let mut index = self.next;
+        if self.period.months == 0 && after >= start {
+            index = cmp::max(index, (after - start) / self.period.seconds + 1);
+        }
+        loop {
+            if self.repetitions.is_some_and(|repetitions| index >= repetitions) {
+                return None;
+            }
+            let time = self.period.after(start, index);
+            if time > after {
+                return Some((time, index));
+            }
+            index += 1;
+        }
+    }
+}
+
+impl Schedule for RepeatingInterval {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let mut wall_clock = mem::take(&mut self.wall_clock);
+        let next = wall_clock.next_after(after, |after| {
+            let after = unix_seconds(after);
+            let (start, after) = match self.start {
+                Some(start) => (start, after),
+                None => {
+                    // Unanchored intervals start immediately
+                    self.start = Some(after);
+                    (after, after - 1)
+                }
+            };
+            let (time, index) = self.next_occurrence(start, after)?;
+            self.next = index + 1;
+            Some(from_unix_seconds(time))
+        });
+        self.wall_clock = wall_clock;
+        next
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor::WallClock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use schedule::Schedule;
+    use schedule::calendar::days_from_civil;
+    use super::{RepeatingInterval, parse_date_time};
+
+    fn time(year: i64, month: u32, day: u32, hour: i64, min: i64, sec: i64) -> i64 {
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + min * 60 + sec
+    }
+
+    fn occurrences(expression: &str, after: i64, count: usize) -> Vec<i64> {
+        let interval = RepeatingInterval::parse(expression).unwrap();
+        let start = interval.start.unwrap();
+        let mut result = Vec::new();
+        let mut after = after;
+        let mut interval = interval;
+        while let Some((time, index)) = interval.next_occurrence(start, after) {
+            result.push(time);
+            interval.next = index + 1;
+            after = time;
+            if result.len() == count {
+                break;
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn parse_test() {
+        assert!(RepeatingInterval::parse("R5/2024-01-01T00:00:00Z/PT1H").is_ok());
+        assert!(RepeatingInterval::parse("R/2024-01-01T00:00Z/P1M").is_ok());
+        assert!(RepeatingInterval::parse("R/PT30S").is_ok());
+        assert!(RepeatingInterval::parse("R3/2024-01-01T00:00:00+01:00/2024-01-02T00:00:00+01:00").is_ok());
+        assert!(RepeatingInterval::parse("R2/P1Y2M10DT2H30M").is_ok());
+        assert!(RepeatingInterval::parse("R/P2W").is_ok());
+        assert!(RepeatingInterval::parse("5/PT1H").is_err());
+        assert!(RepeatingInterval::parse("R/PT0S").is_err());
+        assert!(RepeatingInterval::parse("R/P").is_err());
+        assert!(RepeatingInterval::parse("R/PT").is_err());
+        assert!(RepeatingInterval::parse("R/1H").is_err());
+        assert!(RepeatingInterval::parse("R/P1é").is_err());
+        assert!(RepeatingInterval::parse("R/PT1Hé").is_err());
+        assert!(RepeatingInterval::parse("R/P999999999999999999Y").is_err());
+        assert!(RepeatingInterval::parse("R/PT9223372036854775807H").is_err());
+        assert!(RepeatingInterval::parse("R/P1000000Y").is_err());
+        assert!(RepeatingInterval::parse("R-3/PT1H").is_err());
+        assert!(RepeatingInterval::parse("R0/PT1H").is_ok());
+        assert!(RepeatingInterval::parse("R/P1H").is_err());
+        assert!(RepeatingInterval::parse("R5/PT1H/2024-01-01T00:00:00Z").is_err());
+        assert!(RepeatingInterval::parse("R/2024-02-30T00:00:00Z/PT1H").is_err());
+        assert!(RepeatingInterval::parse("R/2024-01-02T00:00:00Z/2024-01-01T00:00:00Z").is_err());
+    }
+
+    #[test]
+    fn parse_date_time_test() {
+        assert_eq!(parse_date_time("2024-01-01T00:00:00Z"), Ok(time(2024, 1, 1, 0, 0, 0)));
+        assert_eq!(parse_date_time("2024-01-01T10:30"), Ok(time(2024, 1, 1, 10, 30, 0)));
+        assert_eq!(parse_date_time("2024-01-01T10:30:15.250+02:00"), Ok(time(2024, 1, 1, 8, 30, 15)));
+        assert_eq!(parse_date_time("2024-01-01T00:00:00-05"), Ok(time(2024, 1, 1, 5, 0, 0)));
+        assert!(parse_date_time("2024-01-01").is_err());
+        assert!(parse_date_time("2024-1-01T00:00").is_err());
+        assert!(parse_date_time("2024-01-01T25:00").is_err());
+    }
+
+    #[test]
+    fn occurrences_test() {
+        let start = time(2024, 1, 1, 0, 0, 0);
+        assert_eq!(occurrences("R3/2024-01-01T00:00:00Z/PT1H", start - 1, 10),
+                   vec![start, start + 3600, start + 7200]);
+        // Past executions are skipped, but count towards the repetitions
+        assert_eq!(occurrences("R3/2024-01-01T00:00:00Z/PT1H", start + 1800, 10), vec![start + 3600, start + 7200]);
+        assert_eq!(occurrences("R/2024-01-01T00:00:00Z/PT1H", start + 100 * 3600, 1), vec![start + 101 * 3600]);
+        assert_eq!(occurrences("R0/2024-01-01T00:00:00Z/PT1H", start - 1, 10), vec![]);
+        assert_eq!(occurrences("R/2024-01-31T12:00:00Z/P1M", start, 4),
+                   vec![time(2024, 1, 31, 12, 0, 0), time(2024, 2, 29, 12, 0, 0),
+                        time(2024, 3, 31, 12, 0, 0), time(2024, 4, 30, 12, 0, 0)]);
+        assert_eq!(occurrences("R2/2024-01-01T00:00:00Z/2024-01-01T00:10:00Z", start - 1, 10), vec![start, start + 600]);
+    }
+
+    #[test]
+    fn unanchored_test() {
+        let now = Instant::now();
+        let mut schedule = RepeatingInterval::parse("R3/PT10S").unwrap();
+        assert_eq!(schedule.remaining(), Some(3));
+        let first = schedule.next_after(now).unwrap();
+        assert!(first <= now + Duration::from_millis(10));
+        let second = schedule.next_after(first).unwrap();
+        assert!(second - first > Duration::from_secs(8) && second - first <= Duration::from_secs(11));
+        assert!(schedule.next_after(second).is_some());
+        assert_eq!(schedule.next_after(second + Duration::from_secs(10)), None);
+        assert_eq!(schedule.remaining(), Some(0));
+
+        // Anchored in the past: the executions already happened
+        let mut schedule = RepeatingInterval::parse("R2/2020-01-01T00:00:00Z/PT1H").unwrap();
+        assert_eq!(schedule.next_after(now), None);
+    }
+}
@@ -0,0 +1,702 @@
+//! Schedules define when a task should be executed. Any type implementing the [`Schedule`] trait
+//! can be used to schedule a task, using `schedule_with` on the executors. The following
+//! schedules are provided:
+//!
+//! - [`FixedInterval`]: runs the task at fixed intervals, delaying all the following executions
+//!   after a slow one.
+//! - [`FixedRate`]: runs the task at a fixed rate, reducing the wait time after slow executions.
+//! - [`CronSchedule`]: cron expressions with a seconds field, such as `"0 */5 * * * *"`.
+//! - [`CalendarEvent`]: systemd calendar events, such as `"Mon..Fri *-*-* 09:00:00"`.
+//! - [`RepeatingInterval`]: ISO 8601 repeating intervals, such as
+//!   `"R5/2024-01-01T00:00:00Z/PT1H"`.
+//! - [`Daily`], [`Weekly`] and [`Monthly`]: run the task at a given time of the day, in UTC or
+//!   in any time zone.
+//! - [`Backoff`]: exponentially increasing intervals, for retry-style polling.
+//! - [`TokenBucket`]: runs the task at a given average rate, allowing bursts after quiet periods,
+//!   to use the executor as a rate limiter for outgoing work.
+//! - [`Delays`]: waits the delays produced by any iterator, stopping when the iterator ends.
+//!
+//! Schedules can be modified using the adapters provided by the `Schedule` trait, for example
+//! `schedule.delayed(warm_up)` defers the first execution of any schedule, and
+//! `schedule.max_runs(10)` stops the task after ten executions, and `schedule.until(deadline)`
+//! stops it at the given deadline. Random jitter can be added with `jitter` and `jitter_ratio`,
+//! so that many tasks started at the same time don't run in lock-step.
+//!
+//! Each schedule is anchored either to the monotonic clock or to the system clock, see
+//! [`Anchor`]: the calendar schedules follow the system clock when it's adjusted, the other
+//! schedules don't. The anchor of any schedule can be changed with `anchored`.
+//!
+//! Schedules can also be parsed from strings such as `"every 5m 30s"` with the [`human`] module.
+//!
+//! [`Schedule`]: trait.Schedule.html
+//! [`Anchor`]: enum.Anchor.html
+//! [`human`]: human/index.html
+//! [`FixedInterval`]: struct.FixedInterval.html
+//! [`FixedRate`]: struct.FixedRate.html
+//! [`CronSchedule`]: cron/struct.CronSchedule.html
+//! [`CalendarEvent`]: systemd/struct.CalendarEvent.html
+//! [`RepeatingInterval`]: iso8601/struct.RepeatingInterval.html
+//! [`Daily`]: calendar/struct.Daily.html
+//! [`Weekly`]: calendar/struct.Weekly.html
+//! [`Monthly`]: calendar/struct.Monthly.html
+//! [`Backoff`]: backoff/struct.Backoff.html
+//! [`TokenBucket`]: token_bucket/struct.TokenBucket.html
+//! [`Delays`]: struct.Delays.html
+pub mod backoff;
+pub mod calendar;
+pub mod cron;
+pub mod human;
+pub mod iso8601;
+pub mod systemd;
+pub mod time_zone;
+pub mod token_bucket;
+
+use rand::{self, Rng};
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+/// Defines when a task should be executed. The executor will call `next_after` once when the
+/// task is scheduled, with the current instant, and then after each execution, with the instant
+/// at which the execution completed. The returned value is the instant of the next execution
+/// (if it's in the past, the task will be executed immediately). Returning `None` stops the task.
+pub trait Schedule: Send + 'static {
+    /// Returns the instant of the next execution, or `None` if the task shouldn't run anymore.
+    ///
+    /// Takes `&mut self` because most schedules keep state between the calls: `FixedRate` and
+    /// the ISO 8601 intervals remember the previous execution to stay aligned, `Delays`
+    /// consumes its iterator, and `Backoff`, `Until` or `Jitter` update their own state. Stateless
+    /// schedules can simply ignore the mutability.
+    fn next_after(&mut self, after: Instant) -> Option<Instant>;
+
+    /// Changes the interval between executions, for the schedules that have one. The new interval
+    /// is used starting from the next call to `next_after`. By default, this does nothing.
+    fn set_interval(&mut self, _interval: Duration) {}
+
+    /// Returns the interval between executions, for the schedules that have one.
+    fn interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called by the executors once an execution of the task has started. The executions skipped
+    /// because the task was paused or rescheduled, or because the previous one was still
+    /// running, are not reported. Used by the schedules counting the executions, such as
+    /// `max_runs`. By default, this does nothing.
+    fn executed(&mut self) {}
+
+    /// Returns the clock the executions of the schedule are anchored to. Defaults to
+    /// `Anchor::Monotonic`.
+    fn anchor(&self) -> Anchor {
+        Anchor::Monotonic
+    }
+
+    /// Anchors the executions of the schedule to the given clock, see `Anchor`.
+    fn anchored(self, anchor: Anchor) -> Anchored<Self> where Self: Sized {
+        Anchored { schedule: self, anchor }
+    }
+
+    /// Defers the first execution of the schedule by `delay`: the first execution will be the
+    /// first one the schedule would run after the delay expires.
+    fn delayed(self, delay: Duration) -> Delayed<Self> where Self: Sized {
+        Delayed { schedule: self, delay: Some(delay) }
+    }
+
+    /// Limits the schedule to `max_runs` executions, after which the task is stopped. Only the
+    /// executions reported by `executed` count, so a paused task keeps its remaining runs.
+    fn max_runs(self, max_runs: usize) -> MaxRuns<Self> where Self: Sized {
+        MaxRuns { schedule: self, remaining: max_runs }
+    }
+
+    /// Stops the task once the next execution would be after `deadline`.
+    fn until(self, deadline: Instant) -> Until<Self> where Self: Sized {
+        Until { schedule: self, deadline: Some(deadline), duration: None }
+    }
+
+    /// Stops the task once the next execution would be more than `duration` after the task was
+    /// scheduled.
+    fn for_duration(self, duration: Duration) -> Until<Self> where Self: Sized {
+        Until { schedule: self, deadline: None, duration: Some(duration) }
+    }
+
+    /// Delays every execution by a random amount of time, up to `max`.
+    fn jitter(self, max: Duration) -> Jitter<Self> where Self: Sized {
+        Jitter { schedule: self, amount: JitterAmount::Max(max), previous: None }
+    }
+
+    /// Moves every execution earlier or later by a random amount of time, up to `ratio` times the
+    /// time since the previous execution was due (for the first execution, since the task was
+    /// scheduled). For example, with a ratio of `0.1`, a task running every 10 seconds will run
+    /// with an interval between 9 and 11 seconds. The jitter doesn't accumulate over time.
+    ///
+    /// Panics if `ratio` is not between 0 and 1.
+    fn jitter_ratio(self, ratio: f64) -> Jitter<Self> where Self: Sized {
+        assert!((0.0..=1.0).contains(&ratio), "The jitter ratio should be between 0 and 1");
+        Jitter { schedule: self, amount: JitterAmount::Ratio(ratio), previous: None }
+    }
+}
+
+impl<S: Schedule + ?Sized> Schedule for Box<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        (**self).next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        (**self).set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        (**self).interval()
+    }
+
+    fn executed(&mut self) {
+        (**self).executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        (**self).anchor()
+    }
+}
+
+/// The clock the executions of a schedule are anchored to, which defines what happens when the
+/// system clock is adjusted, by NTP or manually. "Every 3600 seconds of uptime" is anchored to
+/// the monotonic clock, while "every hour on the hour" is anchored to the system clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// The executor waits for the time to the next execution, as computed by the schedule,
+    /// whatever happens to the system clock in the meantime. This is the default, used by the
+    /// interval based schedules like `FixedRate`.
+    Monotonic,
+    /// The system time of the next execution is computed when the schedule returns it, and the
+    /// executor runs the task once the system clock reaches that time: if the system clock is
+    /// moved backwards while waiting, the execution is delayed accordingly, and if it's moved
+    /// forwards, the execution starts within a second (see `Clock::system_time`), or right away
+    /// if the executor monitors the jumps of the system clock (see
+    /// `CoreExecutor::on_clock_jump`). Used by the calendar schedules, like `CronSchedule` and
+    /// `Daily`.
+    WallClock,
+}
+
+/// A schedule with a different anchor. See `Schedule::anchored`.
+#[derive(Debug, Clone)]
+pub struct Anchored<S> {
+    schedule: S,
+    anchor: Anchor,
+}
+
+impl<S: Schedule> Schedule for Anchored<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        self.schedule.next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.anchor
+    }
+}
+
+/// Runs a task after an `initial` delay, and then every `interval`. If one execution takes longer
+/// than `interval`, the next execution will start immediately, and all the following executions
+/// will be delayed.
+#[derive(Debug, Clone)]
+pub struct FixedInterval {
+    initial: Duration,
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl FixedInterval {
+    /// Creates a new `FixedInterval` schedule.
+    pub fn new(initial: Duration, interval: Duration) -> FixedInterval {
+        FixedInterval { initial, interval, next: None }
+    }
+}
+
+impl Schedule for FixedInterval {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let next = match self.next {
+            Some(previous) if previous + self.interval > after => previous + self.interval,
+            Some(_) => after,
+            None => after + self.initial,
+        };
+        self.next = Some(next);
+        Some(next)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+}
+
+/// Defines how a `FixedRate` schedule recovers from executions that were missed, because the
+/// previous execution took longer than the interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedTickBehavior {
+    /// Runs all the missed executions back to back, until the accumulated delay is recovered.
+    #[default]
+    Burst,
+    /// Runs one execution immediately, and shifts the schedule so that the following executions
+    /// are `interval` apart from it.
+    Delay,
+    /// Runs one execution immediately, and skips the other missed executions, so that the
+    /// following ones keep running at the planned instants.
+    Skip,
+}
+
+/// Runs a task after an `initial` delay, and then every `interval`. If one execution takes
+/// longer than `interval`, by default the following executions will run without waiting until
+/// the accumulated delay is recovered. See `missed_tick_behavior`.
+#[derive(Debug, Clone)]
+pub struct FixedRate {
+    initial: Duration,
+    interval: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+    max_catch_up: Option<usize>,
+    catch_up: usize,
+    next: Option<Instant>,
+}
+
+impl FixedRate {
+    /// Creates a new `FixedRate` schedule.
+    pub fn new(initial: Duration, interval: Duration) -> FixedRate {
+        FixedRate {
+            initial,
+            interval,
+            missed_tick_behavior: MissedTickBehavior::Burst,
+            max_catch_up: None,
+            catch_up: 0,
+            next: None,
+        }
+    }
+
+    /// Sets what happens to the executions missed because of a slow execution.
+    pub fn missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> FixedRate {
+        self.missed_tick_behavior = behavior;
+        self
+    }
+
+    /// Limits the number of missed executions run back to back with `MissedTickBehavior::Burst`.
+    /// Once the limit is reached, the remaining missed executions are dropped, and the schedule
+    /// is re-anchored: the next execution runs `interval` after the last one has completed.
+    /// Useful when the task can fall far behind, for example after the machine was suspended.
+    pub fn max_catch_up(mut self, max_catch_up: usize) -> FixedRate {
+        self.max_catch_up = Some(max_catch_up);
+        self
+    }
+}
+
+impl Schedule for FixedRate {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let next = match self.next {
+            Some(previous) if previous + self.interval >= after => {
+                self.catch_up = 0;
+                previous + self.interval
+            },
+            Some(previous) => match self.missed_tick_behavior {
+                MissedTickBehavior::Burst if self.max_catch_up.is_some_and(|max| self.catch_up >= max) => {
+                    self.catch_up = 0;
+                    after + self.interval
+                },
+                MissedTickBehavior::Burst => {
+                    self.catch_up += 1;
+                    previous + self.interval
+                },
+                MissedTickBehavior::Delay => after,
+                MissedTickBehavior::Skip => {
+                    // The most recent of the missed executions
+                    let interval = self.interval.as_nanos();
+                    let missed = (after - previous).as_nanos() / cmp::max(interval, 1);
+                    u64::try_from(interval * missed)
+                        .map(|skipped| previous + Duration::from_nanos(skipped))
+                        .unwrap_or(after)
+                },
+            },
+            None => after + self.initial,
+        };
+        self.next = Some(next);
+        Some(next)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+}
+
+/// Runs a task after each of the delays returned by an iterator: the first delay is measured from
+/// when the task is scheduled, and the following ones from the completion of the previous
+/// execution. The task is stopped once the iterator is exhausted.
+#[derive(Debug, Clone)]
+pub struct Delays<I> {
+    delays: I,
+}
+
+impl<I: Iterator<Item=Duration>> Delays<I> {
+    /// Creates a new `Delays` schedule.
+    pub fn new<T: IntoIterator<IntoIter=I, Item=Duration>>(delays: T) -> Delays<I> {
+        Delays { delays: delays.into_iter() }
+    }
+}
+
+impl<I: Iterator<Item=Duration> + Send + 'static> Schedule for Delays<I> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        self.delays.next().map(|delay| after + delay)
+    }
+}
+
+/// A schedule with a delayed first execution. See `Schedule::delayed`.
+#[derive(Debug, Clone)]
+pub struct Delayed<S> {
+    schedule: S,
+    delay: Option<Duration>,
+}
+
+impl<S: Schedule> Schedule for Delayed<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        match self.delay.take() {
+            Some(delay) => self.schedule.next_after(after + delay),
+            None => self.schedule.next_after(after),
+        }
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.schedule.anchor()
+    }
+}
+
+/// A schedule with a limited number of executions. See `Schedule::max_runs`.
+#[derive(Debug, Clone)]
+pub struct MaxRuns<S> {
+    schedule: S,
+    remaining: usize,
+}
+
+impl<S: Schedule> Schedule for MaxRuns<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.schedule.next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.schedule.anchor()
+    }
+}
+
+/// A schedule that ends at a given deadline. See `Schedule::until` and `Schedule::for_duration`.
+#[derive(Debug, Clone)]
+pub struct Until<S> {
+    schedule: S,
+    deadline: Option<Instant>,
+    duration: Option<Duration>,
+}
+
+impl<S: Schedule> Schedule for Until<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        if let Some(duration) = self.duration.take() {
+            self.deadline = Some(after + duration);
+        }
+        let next = self.schedule.next_after(after)?;
+        match self.deadline {
+            Some(deadline) if next > deadline => None,
+            _ => Some(next),
+        }
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.schedule.anchor()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum JitterAmount {
+    Max(Duration),
+    Ratio(f64),
+}
+
+/// Returns a random duration between zero and `max`, included.
+fn random_duration(max: Duration) -> Duration {
+    let max_nanos = max.as_secs().saturating_mul(1_000_000_000)
+        .saturating_add(u64::from(max.subsec_nanos()));
+    if max_nanos == 0 {
+        return Duration::from_secs(0);
+    }
+    Duration::from_nanos(rand::thread_rng().gen_range(0, max_nanos.saturating_add(1)))
+}
+
+/// A schedule with a random jitter applied to every execution. See `Schedule::jitter` and
+/// `Schedule::jitter_ratio`.
+#[derive(Debug, Clone)]
+pub struct Jitter<S> {
+    schedule: S,
+    amount: JitterAmount,
+    previous: Option<Instant>,
+}
+
+impl<S: Schedule> Schedule for Jitter<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let next = self.schedule.next_after(after)?;
+        let reference = self.previous.unwrap_or(after);
+        self.previous = Some(next);
+        match self.amount {
+            JitterAmount::Max(max) => Some(next + random_duration(max)),
+            JitterAmount::Ratio(ratio) => {
+                let interval = if next > reference { next - reference } else { Duration::from_secs(0) };
+                let max = interval.mul_f64(ratio);
+                let jittered = next + random_duration(max * 2);
+                Some(jittered.checked_sub(max).unwrap_or(next))
+            },
+        }
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.schedule.anchor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Anchor, Delays, FixedInterval, FixedRate, MissedTickBehavior, Schedule};
+    use super::calendar::Daily;
+    use super::cron::CronSchedule;
+
+    fn s(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn fixed_interval_test() {
+        let start = Instant::now();
+        let mut schedule = FixedInterval::new(s(2), s(10));
+        assert_eq!(schedule.next_after(start), Some(start + s(2)));
+        assert_eq!(schedule.next_after(start + s(5)), Some(start + s(12)));
+        // A slow execution delays all the following ones
+        assert_eq!(schedule.next_after(start + s(30)), Some(start + s(30)));
+        assert_eq!(schedule.next_after(start + s(31)), Some(start + s(40)));
+    }
+
+    #[test]
+    fn fixed_rate_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10));
+        assert_eq!(schedule.next_after(start), Some(start));
+        // A fast execution doesn't change the rate
+        assert_eq!(schedule.next_after(start + s(3)), Some(start + s(10)));
+        // A slow execution makes the next one run immediately
+        assert_eq!(schedule.next_after(start + s(21)), Some(start + s(20)));
+        assert_eq!(schedule.next_after(start + s(24)), Some(start + s(30)));
+        // Multiple slow executions accumulate delay, which gets recovered later
+        assert_eq!(schedule.next_after(start + s(42)), Some(start + s(40)));
+        assert_eq!(schedule.next_after(start + s(54)), Some(start + s(50)));
+        assert_eq!(schedule.next_after(start + s(57)), Some(start + s(60)));
+    }
+
+    #[test]
+    fn missed_tick_behavior_test() {
+        let start = Instant::now();
+        let mut delay = FixedRate::new(s(0), s(10)).missed_tick_behavior(MissedTickBehavior::Delay);
+        assert_eq!(delay.next_after(start), Some(start));
+        // A slow execution makes the next one run immediately, and shifts the schedule
+        assert_eq!(delay.next_after(start + s(35)), Some(start + s(35)));
+        assert_eq!(delay.next_after(start + s(36)), Some(start + s(45)));
+        assert_eq!(delay.next_after(start + s(45)), Some(start + s(55)));
+
+        let mut skip = FixedRate::new(s(0), s(10)).missed_tick_behavior(MissedTickBehavior::Skip);
+        assert_eq!(skip.next_after(start), Some(start));
+        // The executions at 10 and 20 are skipped
+        assert_eq!(skip.next_after(start + s(35)), Some(start + s(30)));
+        assert_eq!(skip.next_after(start + s(36)), Some(start + s(40)));
+        assert_eq!(skip.next_after(start + s(40)), Some(start + s(50)));
+
+        // More than u32::MAX executions missed
+        let mut skip = FixedRate::new(s(0), Duration::from_nanos(1)).missed_tick_behavior(MissedTickBehavior::Skip);
+        assert_eq!(skip.next_after(start), Some(start));
+        assert_eq!(skip.next_after(start + s(10) + Duration::from_nanos(1)), Some(start + s(10) + Duration::from_nanos(1)));
+        assert_eq!(skip.next_after(start + s(3600)), Some(start + s(3600)));
+    }
+
+    #[test]
+    fn max_catch_up_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10)).max_catch_up(2);
+        assert_eq!(schedule.next_after(start), Some(start));
+        // Fell behind by ten executions, only two of them are run
+        assert_eq!(schedule.next_after(start + s(101)), Some(start + s(10)));
+        assert_eq!(schedule.next_after(start + s(102)), Some(start + s(20)));
+        assert_eq!(schedule.next_after(start + s(103)), Some(start + s(113)));
+        assert_eq!(schedule.next_after(start + s(114)), Some(start + s(123)));
+        // The limit applies to consecutive catch-up runs
+        assert_eq!(schedule.next_after(start + s(135)), Some(start + s(133)));
+        assert_eq!(schedule.next_after(start + s(136)), Some(start + s(143)));
+        assert_eq!(schedule.next_after(start + s(155)), Some(start + s(153)));
+        assert_eq!(schedule.next_after(start + s(156)), Some(start + s(163)));
+    }
+
+    #[test]
+    fn delayed_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10)).delayed(s(5));
+        assert_eq!(schedule.next_after(start), Some(start + s(5)));
+        assert_eq!(schedule.next_after(start + s(6)), Some(start + s(15)));
+
+        // The first execution is the first match after the delay
+        let mut schedule = CronSchedule::parse("*/10 * * * * *").unwrap().delayed(s(25));
+        let first = schedule.next_after(start).unwrap();
+        assert!(first > start + s(24) && first < start + s(36));
+    }
+
+    #[test]
+    fn max_runs_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10)).max_runs(2);
+        assert_eq!(schedule.next_after(start), Some(start));
+        schedule.executed();
+        assert_eq!(schedule.next_after(start), Some(start + s(10)));
+        // Skipped, without counting as a run
+        assert_eq!(schedule.next_after(start + s(10)), Some(start + s(20)));
+        schedule.executed();
+        assert_eq!(schedule.next_after(start + s(20)), None);
+        assert_eq!(schedule.next_after(start + s(30)), None);
+    }
+
+    #[test]
+    fn until_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10)).until(start + s(15));
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start + s(1)), Some(start + s(10)));
+        assert_eq!(schedule.next_after(start + s(11)), None);
+
+        let mut schedule = FixedInterval::new(s(5), s(10)).for_duration(s(20));
+        assert_eq!(schedule.next_after(start + s(100)), Some(start + s(105)));
+        assert_eq!(schedule.next_after(start + s(106)), Some(start + s(115)));
+        assert_eq!(schedule.next_after(start + s(116)), None);
+    }
+
+    #[test]
+    fn jitter_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10)).jitter(s(1));
+        let mut delays = Vec::new();
+        for i in 0..20 {
+            let next = schedule.next_after(start + s(i * 10)).unwrap();
+            let planned = start + s(i * 10);
+            assert!(next >= planned && next <= planned + s(1));
+            delays.push(next - planned);
+        }
+        assert!(delays.iter().any(|&delay| delay != delays[0]));
+
+        let mut schedule = FixedRate::new(s(10), s(10)).jitter_ratio(0.1);
+        let mut offsets = Vec::new();
+        for i in 1..21 {
+            let next = schedule.next_after(start + s((i - 1) * 10)).unwrap();
+            let planned = start + s(i * 10);
+            // The jitter doesn't accumulate, it's always relative to the planned execution
+            assert!(next >= planned - s(1) && next <= planned + s(1));
+            offsets.push(if next > planned { next - planned } else { planned - next });
+        }
+        assert!(offsets.iter().any(|&offset| offset != offsets[0]));
+    }
+
+    #[test]
+    fn delays_test() {
+        let start = Instant::now();
+        let mut schedule = Delays::new(vec![s(0), s(5), s(1)]);
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start + s(2)), Some(start + s(7)));
+        assert_eq!(schedule.next_after(start + s(7)), Some(start + s(8)));
+        assert_eq!(schedule.next_after(start + s(8)), None);
+    }
+
+    #[test]
+    fn set_interval_test() {
+        let start = Instant::now();
+        let mut schedule = FixedRate::new(s(0), s(10)).max_runs(10);
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start), Some(start + s(10)));
+        schedule.set_interval(s(2));
+        assert_eq!(schedule.next_after(start + s(10)), Some(start + s(12)));
+        assert_eq!(schedule.next_after(start + s(12)), Some(start + s(14)));
+    }
+
+    #[test]
+    fn anchor_test() {
+        assert_eq!(FixedRate::new(s(0), s(10)).anchor(), Anchor::Monotonic);
+        assert_eq!(Daily::at(9, 0, 0).max_runs(3).jitter(s(1)).anchor(), Anchor::WallClock);
+        let cron: Box<dyn Schedule> = Box::new(CronSchedule::parse("0 0 * * * *").unwrap());
+        assert_eq!(cron.anchor(), Anchor::WallClock);
+        assert_eq!(Daily::at(9, 0, 0).anchored(Anchor::Monotonic).anchor(), Anchor::Monotonic);
+        let mut schedule = FixedInterval::new(s(1), s(10)).anchored(Anchor::WallClock);
+        let start = Instant::now();
+        assert_eq!(schedule.next_after(start), Some(start + s(1)));
+        assert_eq!(schedule.interval(), Some(s(10)));
+    }
+}
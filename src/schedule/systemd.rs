@@ -0,0 +1,343 @@
+//! Calendar events in the syntax used by the `OnCalendar` setting of systemd timers (see
+//! `man systemd.time`), in the form:
+//!
+//! ```text
+//! [weekdays] [year-]month-day [hour:minute[:second]]
+//! Mon..Fri   *-*-*            09:00:00
+//! ```
+//!
+//! Each component accepts `*`, single values, ranges (`1..5`), lists (`1,3,5`) and repetitions
+//! (`*/15`, `0/15`, `10..30/5`). Days of the week are written by their english name (`Mon` or
+//! `Monday`), and can be combined in lists and ranges (`Mon..Wed,Fri`). If the date is omitted it
+//! defaults to `*-*-*`, if the time is omitted it defaults to `00:00:00`, and if the seconds are
+//! omitted they default to `00`. The shorthands `minutely`, `hourly`, `daily`, `weekly`,
+//! `monthly`, `quarterly`, `semiannually` and `yearly` (or `annually`) are also supported.
+//!
+//! All the times are computed in UTC, which can be specified explicitly with a trailing `UTC`.
+//! Fractional seconds and the `~` syntax for the last days of the month are not supported.
+use schedule::{Anchor, Schedule};
+use schedule::calendar::{WallClock, civil_from_days, days_from_civil, from_unix_seconds, unix_seconds};
+
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::time::{Instant, SystemTime};
+
+const DAY_NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// Calendar events without a match in this many years are considered to never fire, unless a
+/// later year is explicitly requested. Since the days of the week restrict the days of the
+/// month, the longest gap is between two February 29th on the same day of the week across a
+/// non-leap century year, such as 2072 and 2112.
+const MAX_YEARS_AHEAD: i64 = 40;
+
+/// The error returned when a calendar event can't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    expression: String,
+    reason: String,
+}
+
+impl ParseError {
+    fn new(expression: &str, reason: String) -> ParseError {
+        ParseError { expression: expression.to_owned(), reason }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid calendar event '{}': {}", self.expression, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+/// A component of a calendar event, as a list of `(start, end, step)` ranges.
+#[derive(Debug, Clone, PartialEq)]
+struct Component {
+    ranges: Vec<(i64, i64, i64)>,
+}
+
+impl Component {
+    fn any() -> Component {
+        Component { ranges: vec![(i64::MIN, i64::MAX, 1)] }
+    }
+
+    fn value(value: i64) -> Component {
+        Component { ranges: vec![(value, value, 1)] }
+    }
+
+    fn parse(component: &str, min: i64, max: i64) -> Result<Component, String> {
+        let mut ranges = Vec::new();
+        for part in component.split(',') {
+            let (range, step) = match part.find('/') {
+                Some(pos) => {
+                    let step = part[pos + 1..].parse::<i64>()
+                        .map_err(|_| format!("invalid repetition in '{}'", part))?;
+                    if step <= 0 {
+                        return Err(format!("repetition must be positive in '{}'", part));
+                    }
+                    (&part[..pos], step)
+                },
+                None => (part, 1),
+            };
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some(pos) = range.find("..") {
+                (parse_number(&range[..pos])?, parse_number(&range[pos + 2..])?)
+            } else {
+                let value = parse_number(range)?;
+                // "0/15" means from 0 to the end of the range, every 15
+                (value, if step > 1 { max } else { value })
+            };
+            if start < min || end > max || start > end {
+                return Err(format!("'{}' is out of range {}..{}", part, min, max));
+            }
+            ranges.push((start, end, step));
+        }
+        Ok(Component { ranges })
+    }
+
+    fn matches(&self, value: i64) -> bool {
+        self.ranges.iter()
+            .any(|&(start, end, step)| value >= start && value <= end && (step == 1 || (value - start) % step == 0))
+    }
+
+    /// Returns the largest value accepted, if the component is bounded.
+    fn max(&self) -> Option<i64> {
+        self.ranges.iter().map(|&(_, end, _)| end).max().filter(|&max| max != i64::MAX)
+    }
+}
+
+fn parse_number(value: &str) -> Result<i64, String> {
+    value.parse::<i64>().map_err(|_| format!("invalid value '{}'", value))
+}
+
+fn parse_weekday(name: &str) -> Result<u32, String> {
+    let lower = name.to_lowercase();
+    DAY_NAMES.iter()
+        .position(|day| lower.len() >= 3 && day.starts_with(&lower))
+        .map(|pos| pos as u32)
+        .ok_or_else(|| format!("invalid day of the week '{}'", name))
+}
+
+/// Parses a list of days of the week, returning a bit set with Monday as bit 0.
+fn parse_weekdays(weekdays: &str) -> Result<u8, String> {
+    let mut bits = 0u8;
+    for part in weekdays.split(',') {
+        let (start, end) = match part.find("..") {
+            Some(pos) => (parse_weekday(&part[..pos])?, parse_weekday(&part[pos + 2..])?),
+            None => {
+                let day = parse_weekday(part)?;
+                (day, day)
+            }
+        };
+        if start > end {
+            return Err(format!("invalid range of days '{}'", part));
+        }
+        for day in start..=end {
+            bits |= 1 << day;
+        }
+    }
+    Ok(bits)
+}
+
+/// A parsed calendar event. See the module documentation for the supported syntax.
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+    weekdays: Option<u8>,
+    years: Component,
+    months: Component,
+    days: Component,
+    hours: Component,
+    minutes: Component,
+    seconds: Component,
+    wall_clock: WallClock,
+}
+
+impl CalendarEvent {
+    /// Parses a calendar event.
+    pub fn parse(expression: &str) -> Result<CalendarEvent, ParseError> {
+        CalendarEvent::parse_event(expression).map_err(|reason| ParseError::new(expression, reason))
+    }
+
+    fn shorthand(expression: &str) -> Option<&'static str> {
+        let normalized = match expression {
+            "minutely" => "*-*-* *:*:00",
+            "hourly" => "*-*-* *:00:00",
+            "daily" => "*-*-* 00:00:00",
+            "weekly" => "Mon *-*-* 00:00:00",
+            "monthly" => "*-*-01 00:00:00",
+            "quarterly" => "*-01,04,07,10-01 00:00:00",
+            "semiannually" => "*-01,07-01 00:00:00",
+            "yearly" | "annually" => "*-01-01 00:00:00",
+            _ => return None,
+        };
+        Some(normalized)
+    }
+
+    fn parse_event(expression: &str) -> Result<CalendarEvent, String> {
+        let expression = expression.trim();
+        let expression = CalendarEvent::shorthand(&expression.to_lowercase()).unwrap_or(expression);
+        let mut tokens = expression.split_whitespace().collect::<Vec<_>>();
+        if tokens.last().is_some_and(|token| token.eq_ignore_ascii_case("UTC")) {
+            tokens.pop();
+        }
+        if tokens.is_empty() {
+            return Err("empty calendar event".to_owned());
+        }
+        let mut tokens = tokens.into_iter().peekable();
+        let weekdays = match tokens.peek() {
+            Some(token) if token.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                Some(parse_weekdays(tokens.next().unwrap())?)
+            },
+            _ => None,
+        };
+
+        let (mut years, mut months, mut days) = (Component::any(), Component::any(), Component::any());
+        if let Some(date) = tokens.peek().cloned().filter(|token| !token.contains(':')) {
+            tokens.next();
+            let parts = date.split('-').collect::<Vec<_>>();
+            let (year, month, day) = match parts.len() {
+                3 => (Some(parts[0]), parts[1], parts[2]),
+                2 => (None, parts[0], parts[1]),
+                _ => return Err(format!("invalid date '{}'", date)),
+            };
+            if let Some(year) = year.filter(|&year| year != "*") {
+                years = Component::parse(year, 1970, 2199)?;
+            }
+            months = Component::parse(month, 1, 12)?;
+            days = Component::parse(day, 1, 31)?;
+        }
+
+        let (mut hours, mut minutes, mut seconds) = (Component::value(0), Component::value(0), Component::value(0));
+        if let Some(time) = tokens.next() {
+            let parts = time.split(':').collect::<Vec<_>>();
+            if parts.len() < 2 || parts.len() > 3 {
+                return Err(format!("invalid time '{}'", time));
+            }
+            hours = Component::parse(parts[0], 0, 23)?;
+            minutes = Component::parse(parts[1], 0, 59)?;
+            if parts.len() == 3 {
+                seconds = Component::parse(parts[2], 0, 59)?;
+            }
+        }
+        if let Some(token) = tokens.next() {
+            return Err(format!("unexpected '{}'", token));
+        }
+        Ok(CalendarEvent { weekdays, years, months, days, hours, minutes, seconds, wall_clock: WallClock::default() })
+    }
+
+    fn day_matches(&self, days: i64, day_of_month: u32) -> bool {
+        // The epoch was a Thursday
+        let weekday = (days + 3).rem_euclid(7);
+        self.days.matches(i64::from(day_of_month)) && self.weekdays.is_none_or(|bits| bits & (1 << weekday) != 0)
+    }
+
+    /// Returns the first time matching the event strictly after `after`, or `None` if the event
+    /// will never happen.
+    pub fn next_match_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let mut t = unix_seconds(after) + 1;
+        let first_year = civil_from_days(t.div_euclid(86400)).0;
+        let max_year = match self.years.max() {
+            Some(max) => max,
+            None => first_year + MAX_YEARS_AHEAD,
+        };
+        loop {
+            let days = t.div_euclid(86400);
+            let (year, month, day) = civil_from_days(days);
+            if year > max_year {
+                return None;
+            }
+            let secs_of_day = t.rem_euclid(86400);
+            if !self.years.matches(year) {
+                t = days_from_civil(year + 1, 1, 1) * 86400;
+            } else if !self.months.matches(i64::from(month)) {
+                let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                t = days_from_civil(next_year, next_month, 1) * 86400;
+            } else if !self.day_matches(days, day) {
+                t = (days + 1) * 86400;
+            } else if !self.hours.matches(secs_of_day / 3600) {
+                t = t - t.rem_euclid(3600) + 3600;
+            } else if !self.minutes.matches(secs_of_day % 3600 / 60) {
+                t = t - t.rem_euclid(60) + 60;
+            } else if !self.seconds.matches(secs_of_day % 60) {
+                t += 1;
+            } else {
+                return Some(from_unix_seconds(t));
+            }
+        }
+    }
+}
+
+impl Schedule for CalendarEvent {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        let mut wall_clock = mem::take(&mut self.wall_clock);
+        let next = wall_clock.next_after(after, |after| self.next_match_after(after));
+        self.wall_clock = wall_clock;
+        next
+    }
+
+    fn anchor(&self) -> Anchor {
+        Anchor::WallClock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use schedule::calendar::days_from_civil;
+    use super::CalendarEvent;
+
+    fn time(year: i64, month: u32, day: u32, hour: u64, min: u64, sec: u64) -> SystemTime {
+        let days = days_from_civil(year, month, day) as u64;
+        UNIX_EPOCH + Duration::from_secs(days * 86400 + hour * 3600 + min * 60 + sec)
+    }
+
+    fn next(expression: &str, after: SystemTime) -> Option<SystemTime> {
+        CalendarEvent::parse(expression).unwrap().next_match_after(after)
+    }
+
+    #[test]
+    fn parse_test() {
+        assert!(CalendarEvent::parse("Mon..Fri *-*-* 09:00:00").is_ok());
+        assert!(CalendarEvent::parse("Sat,Sunday 10:00").is_ok());
+        assert!(CalendarEvent::parse("*-*-01 00:00 UTC").is_ok());
+        assert!(CalendarEvent::parse("2025-01..06-15").is_ok());
+        assert!(CalendarEvent::parse("*:0/15").is_ok());
+        assert!(CalendarEvent::parse("Daily").is_ok());
+        assert!(CalendarEvent::parse("").is_err());
+        assert!(CalendarEvent::parse("Mo 10:00").is_err());
+        assert!(CalendarEvent::parse("Fri..Mon").is_err());
+        assert!(CalendarEvent::parse("*-13-01").is_err());
+        assert!(CalendarEvent::parse("24:00").is_err());
+        assert!(CalendarEvent::parse("*:*/0").is_err());
+        assert!(CalendarEvent::parse("10:00 Europe/Rome").is_err());
+        assert!(CalendarEvent::parse("*-02~03").is_err());
+    }
+
+    #[test]
+    fn next_match_after_test() {
+        // 2024-01-05 is a Friday
+        let after = time(2024, 1, 5, 10, 0, 0);
+        assert_eq!(next("Mon..Fri *-*-* 09:00:00", after), Some(time(2024, 1, 8, 9, 0, 0)));
+        assert_eq!(next("Sat,Sun 10:00", after), Some(time(2024, 1, 6, 10, 0, 0)));
+        assert_eq!(next("*:0/15", after), Some(time(2024, 1, 5, 10, 15, 0)));
+        assert_eq!(next("*:*:30", after), Some(time(2024, 1, 5, 10, 0, 30)));
+        assert_eq!(next("hourly", after), Some(time(2024, 1, 5, 11, 0, 0)));
+        assert_eq!(next("daily", after), Some(time(2024, 1, 6, 0, 0, 0)));
+        assert_eq!(next("weekly", after), Some(time(2024, 1, 8, 0, 0, 0)));
+        assert_eq!(next("monthly", after), Some(time(2024, 2, 1, 0, 0, 0)));
+        assert_eq!(next("quarterly", after), Some(time(2024, 4, 1, 0, 0, 0)));
+        assert_eq!(next("yearly", after), Some(time(2025, 1, 1, 0, 0, 0)));
+        assert_eq!(next("*-02-29 12:00", after), Some(time(2024, 2, 29, 12, 0, 0)));
+        assert_eq!(next("Fri *-*-13", after), Some(time(2024, 9, 13, 0, 0, 0)));
+        assert_eq!(next("2030-06-01 08:30", after), Some(time(2030, 6, 1, 8, 30, 0)));
+        assert_eq!(next("2023-06-01", after), None);
+        assert_eq!(next("*-02-30", after), None);
+        // 2100 is not a leap year
+        assert_eq!(next("*-02-29", time(2096, 3, 1, 0, 0, 0)), Some(time(2104, 2, 29, 0, 0, 0)));
+        assert_eq!(next("Mon *-02-29", time(2072, 3, 1, 0, 0, 0)), Some(time(2112, 2, 29, 0, 0, 0)));
+    }
+}
@@ -0,0 +1,394 @@
+//! Time zones for the wall-clock schedules of the [`calendar`] module. Besides [`Utc`] and
+//! [`FixedOffset`], time zones with daylight saving time can be described with a POSIX `TZ`
+//! string, which contains the offsets and the rules for the transitions:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::schedule::calendar::Daily;
+//! # use scheduled_executor::schedule::time_zone::PosixTimeZone;
+//! // Europe/Rome: UTC+1, or UTC+2 from the last Sunday of March to the last Sunday of October
+//! let rome = PosixTimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_with(Daily::at(9, 0, 0).in_time_zone(rome), |_handle| println!("Good morning"));
+//! ```
+//!
+//! The POSIX string for most time zones can be found at the end of the corresponding file of
+//! the system time zone database (for example `tail -1 /usr/share/zoneinfo/Europe/Rome`). Other
+//! time zone implementations can be used by implementing the [`TimeZone`] trait.
+//!
+//! [`calendar`]: ../calendar/index.html
+//! [`Utc`]: struct.Utc.html
+//! [`FixedOffset`]: struct.FixedOffset.html
+//! [`TimeZone`]: trait.TimeZone.html
+use schedule::calendar::{civil_from_days, days_from_civil, days_in_month};
+
+use std::error::Error;
+use std::fmt;
+
+/// A time zone, mapping UTC times to local times.
+pub trait TimeZone: Send + 'static {
+    /// Returns the offset from UTC in seconds (positive east of Greenwich) in effect at `utc`,
+    /// expressed in seconds since the epoch.
+    fn offset_at(&self, utc: i64) -> i64;
+}
+
+/// The UTC time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Utc;
+
+impl TimeZone for Utc {
+    fn offset_at(&self, _utc: i64) -> i64 {
+        0
+    }
+}
+
+/// A time zone with a constant offset from UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedOffset {
+    seconds: i64,
+}
+
+impl FixedOffset {
+    /// Creates a time zone `seconds` ahead of UTC (negative values are behind UTC).
+    ///
+    /// Panics if the offset is a day or more.
+    pub fn east(seconds: i32) -> FixedOffset {
+        assert!(seconds.abs() < 86400, "Invalid UTC offset {}", seconds);
+        FixedOffset { seconds: i64::from(seconds) }
+    }
+}
+
+impl TimeZone for FixedOffset {
+    fn offset_at(&self, _utc: i64) -> i64 {
+        self.seconds
+    }
+}
+
+/// The UTC times corresponding to a local time, in seconds since the epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LocalTime {
+    /// The local time happens once.
+    Unique(i64),
+    /// The local time happens twice, because the clocks are turned back.
+    Repeated(i64, i64),
+    /// The local time doesn't exist, because the clocks are turned forward. Contains the time
+    /// shifted forward by the length of the transition.
+    Missing(i64),
+}
+
+/// Returns the UTC times corresponding to the local time `local`, in seconds since the epoch.
+pub(crate) fn from_local<Z: TimeZone>(time_zone: &Z, local: i64) -> LocalTime {
+    let before = time_zone.offset_at(local - 86400);
+    let after = time_zone.offset_at(local + 86400);
+    let valid = |offset| time_zone.offset_at(local - offset) == offset;
+    match (valid(before), valid(after)) {
+        (true, true) if before != after => {
+            let (first, second) = if before > after { (before, after) } else { (after, before) };
+            LocalTime::Repeated(local - first, local - second)
+        },
+        (true, _) => LocalTime::Unique(local - before),
+        (false, true) => LocalTime::Unique(local - after),
+        (false, false) => LocalTime::Missing(local - before),
+    }
+}
+
+/// The error returned when a time zone can't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    spec: String,
+    reason: String,
+}
+
+impl ParseError {
+    fn new(spec: &str, reason: &str) -> ParseError {
+        ParseError { spec: spec.to_owned(), reason: reason.to_owned() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid time zone '{}': {}", self.spec, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+/// The day of a daylight saving time transition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransitionDay {
+    /// `Jn`: day of the year from 1 to 365, ignoring February 29.
+    Julian(u32),
+    /// `n`: day of the year from 0 to 365, counting February 29.
+    Ordinal(u32),
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 is the last one) of month `m`.
+    Month { month: u32, week: u32, weekday: u32 },
+}
+
+impl TransitionDay {
+    /// Returns the day of the transition in `year`, in days since the epoch.
+    fn day(self, year: i64) -> i64 {
+        let first_of_year = days_from_civil(year, 1, 1);
+        match self {
+            TransitionDay::Julian(day) => {
+                let leap = days_in_month(year, 2) == 29;
+                first_of_year + i64::from(day) - 1 + if leap && day >= 60 { 1 } else { 0 }
+            },
+            TransitionDay::Ordinal(day) => first_of_year + i64::from(day),
+            TransitionDay::Month { month, week, weekday } => {
+                let first = days_from_civil(year, month, 1);
+                let next_month = first + i64::from(days_in_month(year, month));
+                // The epoch was a Thursday
+                let mut day = first + (i64::from(weekday) - (first + 4)).rem_euclid(7) + i64::from(week - 1) * 7;
+                while day >= next_month {
+                    day -= 7;
+                }
+                day
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Transition {
+    day: TransitionDay,
+    /// Local time of the transition, in seconds from midnight.
+    time: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Dst {
+    name: String,
+    offset: i64,
+    start: Transition,
+    end: Transition,
+}
+
+/// A time zone described by a POSIX `TZ` string, such as `"CET-1CEST,M3.5.0,M10.5.0/3"`. See
+/// the [module documentation].
+///
+/// If the daylight saving time rules are omitted (as in `"EST5EDT"`), the current United States
+/// rules are used.
+///
+/// [module documentation]: index.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct PosixTimeZone {
+    name: String,
+    offset: i64,
+    dst: Option<Dst>,
+}
+
+impl PosixTimeZone {
+    /// Parses a POSIX `TZ` string.
+    pub fn parse(spec: &str) -> Result<PosixTimeZone, ParseError> {
+        let mut parser = Parser { input: spec.as_bytes(), pos: 0 };
+        let time_zone = parser.time_zone().map_err(|reason| ParseError::new(spec, reason))?;
+        if parser.pos != spec.len() {
+            return Err(ParseError::new(spec, "unexpected trailing characters"));
+        }
+        Ok(time_zone)
+    }
+
+    /// Returns the abbreviation of the time zone in effect at `utc`, in seconds since the epoch.
+    pub fn name_at(&self, utc: i64) -> &str {
+        match self.dst {
+            Some(ref dst) if self.in_dst(dst, utc) => &dst.name,
+            _ => &self.name,
+        }
+    }
+
+    fn in_dst(&self, dst: &Dst, utc: i64) -> bool {
+        let (year, _, _) = civil_from_days((utc + self.offset).div_euclid(86400));
+        // The start is expressed in standard time, and the end in daylight saving time
+        let start = dst.start.day.day(year) * 86400 + dst.start.time - self.offset;
+        let end = dst.end.day.day(year) * 86400 + dst.end.time - dst.offset;
+        if start < end {
+            utc >= start && utc < end
+        } else {
+            // Southern hemisphere
+            utc >= start || utc < end
+        }
+    }
+}
+
+impl TimeZone for PosixTimeZone {
+    fn offset_at(&self, utc: i64) -> i64 {
+        match self.dst {
+            Some(ref dst) if self.in_dst(dst, utc) => dst.offset,
+            _ => self.offset,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn time_zone(&mut self) -> Result<PosixTimeZone, &'static str> {
+        let name = self.name()?;
+        let offset = -self.time(24)?;
+        if self.peek().is_none() {
+            return Ok(PosixTimeZone { name, offset, dst: None });
+        }
+        let dst_name = self.name()?;
+        let dst_offset = match self.peek() {
+            Some(c) if c == b'+' || c == b'-' || c.is_ascii_digit() => -self.time(24)?,
+            _ => offset + 3600,
+        };
+        let (start, end) = if self.eat(b',') {
+            let start = self.transition()?;
+            if !self.eat(b',') {
+                return Err("missing end of daylight saving time");
+            }
+            (start, self.transition()?)
+        } else {
+            (Transition { day: TransitionDay::Month { month: 3, week: 2, weekday: 0 }, time: 7200 },
+             Transition { day: TransitionDay::Month { month: 11, week: 1, weekday: 0 }, time: 7200 })
+        };
+        Ok(PosixTimeZone { name, offset, dst: Some(Dst { name: dst_name, offset: dst_offset, start, end }) })
+    }
+
+    fn name(&mut self) -> Result<String, &'static str> {
+        let start = self.pos;
+        let name = if self.eat(b'<') {
+            while self.peek().is_some_and(|c| c != b'>') {
+                self.pos += 1;
+            }
+            if !self.eat(b'>') {
+                return Err("unterminated time zone name");
+            }
+            &self.input[start + 1..self.pos - 1]
+        } else {
+            while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                self.pos += 1;
+            }
+            &self.input[start..self.pos]
+        };
+        if name.len() < 3 {
+            return Err("time zone names should have at least 3 characters");
+        }
+        Ok(String::from_utf8_lossy(name).into_owned())
+    }
+
+    fn number(&mut self, max: u32) -> Result<u32, &'static str> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        match String::from_utf8_lossy(&self.input[start..self.pos]).parse::<u32>() {
+            Ok(number) if number <= max => Ok(number),
+            Ok(_) => Err("value out of range"),
+            Err(_) => Err("expected a number"),
+        }
+    }
+
+    /// Parses `[+-]hh[:mm[:ss]]`, returning the number of seconds.
+    fn time(&mut self, max_hours: u32) -> Result<i64, &'static str> {
+        let sign = if self.eat(b'-') { -1 } else { self.eat(b'+'); 1 };
+        let mut seconds = self.number(max_hours)? * 3600;
+        if self.eat(b':') {
+            seconds += self.number(59)? * 60;
+            if self.eat(b':') {
+                seconds += self.number(59)?;
+            }
+        }
+        Ok(sign * i64::from(seconds))
+    }
+
+    fn transition(&mut self) -> Result<Transition, &'static str> {
+        let day = if self.eat(b'M') {
+            let month = self.number(12)?;
+            if month == 0 || !self.eat(b'.') {
+                return Err("invalid month rule");
+            }
+            let week = self.number(5)?;
+            if week == 0 || !self.eat(b'.') {
+                return Err("invalid month rule");
+            }
+            TransitionDay::Month { month, week, weekday: self.number(6)? }
+        } else if self.eat(b'J') {
+            match self.number(365)? {
+                0 => return Err("julian days start from 1"),
+                day => TransitionDay::Julian(day),
+            }
+        } else {
+            TransitionDay::Ordinal(self.number(365)?)
+        };
+        let time = if self.eat(b'/') { self.time(167)? } else { 7200 };
+        Ok(Transition { day, time })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use schedule::calendar::days_from_civil;
+    use super::{FixedOffset, LocalTime, PosixTimeZone, TimeZone, from_local};
+
+    fn time(year: i64, month: u32, day: u32, hour: i64, min: i64) -> i64 {
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + min * 60
+    }
+
+    #[test]
+    fn parse_test() {
+        assert!(PosixTimeZone::parse("UTC0").is_ok());
+        assert!(PosixTimeZone::parse("<+0330>-3:30").is_ok());
+        assert!(PosixTimeZone::parse("EST5EDT").is_ok());
+        assert!(PosixTimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").is_ok());
+        assert!(PosixTimeZone::parse("<-03>3<-02>,M3.5.0/-2,M10.5.0/-1").is_ok());
+        assert!(PosixTimeZone::parse("IST-2IDT,M3.4.4/26,M10.5.0").is_ok());
+        assert!(PosixTimeZone::parse("XX0").is_err());
+        assert!(PosixTimeZone::parse("CET").is_err());
+        assert!(PosixTimeZone::parse("CET-1CEST,M3.5.0").is_err());
+        assert!(PosixTimeZone::parse("CET-1CEST,M13.5.0,M10.5.0").is_err());
+        assert!(PosixTimeZone::parse("CET-1 ").is_err());
+    }
+
+    #[test]
+    fn offset_test() {
+        let rome = PosixTimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(rome.offset_at(time(2024, 1, 15, 12, 0)), 3600);
+        assert_eq!(rome.offset_at(time(2024, 3, 31, 0, 59)), 3600);
+        assert_eq!(rome.offset_at(time(2024, 3, 31, 1, 0)), 7200);
+        assert_eq!(rome.name_at(time(2024, 7, 1, 0, 0)), "CEST");
+        assert_eq!(rome.offset_at(time(2024, 10, 27, 0, 59)), 7200);
+        assert_eq!(rome.offset_at(time(2024, 10, 27, 1, 0)), 3600);
+
+        let new_york = PosixTimeZone::parse("EST5EDT").unwrap();
+        assert_eq!(new_york.offset_at(time(2024, 3, 10, 6, 59)), -5 * 3600);
+        assert_eq!(new_york.offset_at(time(2024, 3, 10, 7, 0)), -4 * 3600);
+        assert_eq!(new_york.offset_at(time(2024, 11, 3, 6, 0)), -5 * 3600);
+
+        let sydney = PosixTimeZone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_at(time(2024, 1, 1, 0, 0)), 11 * 3600);
+        assert_eq!(sydney.offset_at(time(2024, 4, 6, 16, 0)), 10 * 3600);
+        assert_eq!(sydney.offset_at(time(2024, 10, 5, 16, 0)), 11 * 3600);
+
+        assert_eq!(FixedOffset::east(-9000).offset_at(0), -9000);
+    }
+
+    #[test]
+    fn from_local_test() {
+        let rome = PosixTimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(from_local(&rome, time(2024, 1, 15, 9, 0)), LocalTime::Unique(time(2024, 1, 15, 8, 0)));
+        assert_eq!(from_local(&rome, time(2024, 7, 15, 9, 0)), LocalTime::Unique(time(2024, 7, 15, 7, 0)));
+        // 02:30 doesn't exist on the day DST starts, and happens twice when it ends
+        assert_eq!(from_local(&rome, time(2024, 3, 31, 2, 30)), LocalTime::Missing(time(2024, 3, 31, 1, 30)));
+        assert_eq!(from_local(&rome, time(2024, 10, 27, 2, 30)),
+                   LocalTime::Repeated(time(2024, 10, 27, 0, 30), time(2024, 10, 27, 1, 30)));
+        assert_eq!(from_local(&rome, time(2024, 10, 27, 3, 0)), LocalTime::Unique(time(2024, 10, 27, 2, 0)));
+    }
+}
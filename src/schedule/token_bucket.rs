@@ -0,0 +1,158 @@
+//! A token bucket schedule, to use the executor as a rate limiter for outgoing work. The bucket
+//! holds up to `burst` tokens, and gets a new token every `interval`: each execution takes a
+//! token, so the task runs at most once per `interval` on average, but after a quiet period it
+//! can run up to `burst` times back to back.
+//!
+//! When an execution finds nothing to do, it can give its token back with a [`TokenRefund`]:
+//! the task then checks again after one `interval`, and the unused tokens accumulate for the
+//! next burst.
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::schedule::token_bucket::TokenBucket;
+//! # use std::time::Duration;
+//! # fn send_next_request() -> bool { true }
+//! let executor = CoreExecutor::new().unwrap();
+//! // 10 requests per second, with bursts of at most 50 requests
+//! let bucket = TokenBucket::new(Duration::from_millis(100), 50);
+//! let refund = bucket.refund_handle();
+//! executor.schedule_with(bucket, move |_handle| {
+//!     if !send_next_request() {
+//!         refund.refund();
+//!     }
+//! });
+//! ```
+//!
+//! [`TokenRefund`]: struct.TokenRefund.html
+use schedule::Schedule;
+
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Allows the scheduled function to give back the token of the current execution, when it had
+/// nothing to do. It can be cloned, and moved into the scheduled function.
+#[derive(Debug, Clone)]
+pub struct TokenRefund {
+    refund: Arc<AtomicBool>,
+}
+
+impl TokenRefund {
+    /// Gives back the token of the current execution: the task will run again after one
+    /// interval, without the tokens being consumed in the meantime.
+    pub fn refund(&self) {
+        self.refund.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A schedule limiting the rate of the executions with a token bucket. See the
+/// [module documentation].
+///
+/// [module documentation]: index.html
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    interval: Duration,
+    burst: usize,
+    tokens: usize,
+    refilled_at: Option<Instant>,
+    refund: Arc<AtomicBool>,
+}
+
+impl TokenBucket {
+    /// Creates a new `TokenBucket` schedule, getting a new token every `interval` and holding up
+    /// to `burst` tokens. The bucket starts full.
+    ///
+    /// Panics if `burst` is 0.
+    pub fn new(interval: Duration, burst: usize) -> TokenBucket {
+        assert!(burst > 0, "The burst of a token bucket should be at least 1");
+        TokenBucket { interval, burst, tokens: burst, refilled_at: None, refund: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Returns a handle that can be used to give back the token of an execution.
+    pub fn refund_handle(&self) -> TokenRefund {
+        TokenRefund { refund: Arc::clone(&self.refund) }
+    }
+
+    /// Adds the tokens produced since the last refill.
+    fn refill(&mut self, now: Instant) {
+        let refilled_at = match self.refilled_at {
+            Some(refilled_at) if refilled_at < now => refilled_at,
+            Some(_) => return,
+            None => {
+                self.refilled_at = Some(now);
+                return;
+            }
+        };
+        // With a zero interval the bucket is always full
+        let produced = (now - refilled_at).as_nanos().checked_div(self.interval.as_nanos())
+            .unwrap_or(self.burst as u128);
+        self.tokens = cmp::min(self.burst as u128, self.tokens as u128 + produced) as usize;
+        self.refilled_at = if self.tokens == self.burst {
+            Some(now)
+        } else {
+            Some(refilled_at + self.interval * produced as u32)
+        };
+    }
+}
+
+impl Schedule for TokenBucket {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        self.refill(after);
+        if self.refund.swap(false, Ordering::Relaxed) {
+            self.tokens = cmp::min(self.burst, self.tokens + 1);
+            return Some(after + self.interval);
+        }
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            return Some(after);
+        }
+        // Takes the next token in advance
+        let next = self.refilled_at.unwrap_or(after) + self.interval;
+        self.refilled_at = Some(next);
+        Some(next)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use schedule::Schedule;
+    use super::TokenBucket;
+
+    fn s(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn token_bucket_test() {
+        let start = Instant::now();
+        let mut schedule = TokenBucket::new(s(10), 3);
+        // The bucket starts full
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start), Some(start));
+        assert_eq!(schedule.next_after(start + s(1)), Some(start + s(1)));
+        // Then runs once per interval
+        assert_eq!(schedule.next_after(start + s(2)), Some(start + s(10)));
+        assert_eq!(schedule.next_after(start + s(10)), Some(start + s(20)));
+        assert_eq!(schedule.next_after(start + s(21)), Some(start + s(30)));
+
+        // The tokens accumulate during a quiet period, up to the burst
+        let refund = schedule.refund_handle();
+        refund.refund();
+        assert_eq!(schedule.next_after(start + s(30)), Some(start + s(40)));
+        refund.refund();
+        assert_eq!(schedule.next_after(start + s(40)), Some(start + s(50)));
+        assert_eq!(schedule.next_after(start + s(95)), Some(start + s(95)));
+        assert_eq!(schedule.next_after(start + s(95)), Some(start + s(95)));
+        assert_eq!(schedule.next_after(start + s(95)), Some(start + s(95)));
+        assert_eq!(schedule.next_after(start + s(95)), Some(start + s(105)));
+    }
+}
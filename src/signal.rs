@@ -0,0 +1,164 @@
+//! Stopping the executors gracefully when the process receives `SIGTERM` or `SIGINT`, as most
+//! daemons do. Requires the `signal` feature, and is only available on Unix.
+//!
+//! ```rust,no_run
+//! extern crate futures;
+//! extern crate scheduled_executor;
+//!
+//! use futures::Future;
+//! use scheduled_executor::{CoreExecutor, signal};
+//! use std::time::Duration;
+//!
+//! fn main() {
+//!     let executor = CoreExecutor::new().unwrap();
+//!     // Schedule the tasks...
+//!     let shutdown = signal::shutdown_on_signal(executor.shutdown(), Some(Duration::from_secs(30)))
+//!         .expect("Failed to install the signal handlers");
+//!     let drained = shutdown.wait().unwrap();
+//!     println!("Executor stopped, all the executions completed: {}", drained);
+//! }
+//! ```
+use futures::{Future, Poll};
+use futures::sync::oneshot::{channel, Canceled, Receiver, Sender};
+use libc::{self, c_int, c_void};
+
+use error::ScheduleError;
+use executor::Shutdown;
+
+use std::io;
+use std::mem;
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// An executor to drain when a signal is received.
+struct Registration {
+    shutdown: Shutdown,
+    timeout: Option<Duration>,
+    sender: Sender<bool>,
+}
+
+static REGISTRATIONS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+static INSTALLED: Mutex<bool> = Mutex::new(false);
+/// The write end of the pipe used by the signal handler to wake up the watcher thread.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(signal: c_int) {
+    // Only async-signal-safe functions can be called here
+    let byte = signal as u8;
+    unsafe {
+        libc::write(SIGNAL_PIPE.load(Ordering::Relaxed), &byte as *const u8 as *const c_void, 1);
+    }
+}
+
+/// Installs the signal handlers and starts the watcher thread, the first time it's called.
+fn install() -> Result<(), ScheduleError> {
+    let mut installed = INSTALLED.lock().unwrap();
+    if *installed {
+        return Ok(());
+    }
+    let mut fds = [0 as c_int; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(ScheduleError::Signal(io::Error::last_os_error()));
+    }
+    SIGNAL_PIPE.store(fds[1], Ordering::Relaxed);
+    let read_fd = fds[0];
+    thread::Builder::new()
+        .name("signal_shutdown".to_owned())
+        .spawn(move || watch(read_fd))
+        .map_err(ScheduleError::Thread)?;
+    for &signal in &[libc::SIGTERM, libc::SIGINT] {
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, ptr::null_mut()) != 0 {
+                return Err(ScheduleError::Signal(io::Error::last_os_error()));
+            }
+        }
+    }
+    *installed = true;
+    Ok(())
+}
+
+/// Waits for the signals, draining all the registered executors when one is received.
+fn watch(fd: c_int) {
+    loop {
+        let mut signal = 0u8;
+        let read = unsafe { libc::read(fd, &mut signal as *mut u8 as *mut c_void, 1) };
+        if read < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            error!("Failed to wait for signals: {}", error);
+            return;
+        } else if read == 0 {
+            return;
+        }
+        let registrations = mem::take(&mut *REGISTRATIONS.lock().unwrap());
+        info!("Received signal {}, stopping {} executors", signal, registrations.len());
+        for registration in registrations {
+            let drained = registration.shutdown.drain(registration.timeout);
+            let _ = registration.sender.send(drained);
+        }
+    }
+}
+
+/// A future that completes once the executor has been stopped after a signal. It resolves to
+/// the result of `Shutdown::drain`, that is, to false if the executions didn't complete in time.
+#[derive(Debug)]
+pub struct SignalShutdown {
+    receiver: Receiver<bool>,
+}
+
+impl Future for SignalShutdown {
+    type Item = bool;
+    type Error = Canceled;
+
+    fn poll(&mut self) -> Poll<bool, Canceled> {
+        self.receiver.poll()
+    }
+}
+
+/// Drains the executor (see `Shutdown::drain`) when the process receives `SIGTERM` or `SIGINT`,
+/// waiting up to `timeout` for the executions in progress to complete. The signal handlers are
+/// installed the first time this function is called, replacing any existing handler, and all
+/// the executors registered before the signal are stopped by it. Returns an error if the
+/// handlers can't be installed.
+pub fn shutdown_on_signal(shutdown: Shutdown, timeout: Option<Duration>) -> Result<SignalShutdown, ScheduleError> {
+    install()?;
+    let (sender, receiver) = channel();
+    REGISTRATIONS.lock().unwrap().push(Registration { shutdown, timeout, sender });
+    Ok(SignalShutdown { receiver })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+    use libc;
+
+    use executor::CoreExecutor;
+    use super::shutdown_on_signal;
+
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn shutdown_on_signal_test() {
+        let executor = CoreExecutor::with_name("shutdown_on_signal_test").unwrap();
+        let task = executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_millis(10), |_handle| ());
+        let shutdown = shutdown_on_signal(executor.shutdown(), Some(Duration::from_secs(1))).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(executor.is_alive());
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+        assert!(shutdown.wait().unwrap());
+        assert!(!executor.is_alive());
+        assert!(task.runs() > 0);
+    }
+}
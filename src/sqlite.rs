@@ -0,0 +1,389 @@
+//! A [`JobStore`] keeping the jobs and their execution history in an SQLite database, for
+//! single-node services that need a durable history without extra infrastructure. Requires the
+//! `sqlite` feature, and links to the system `libsqlite3`.
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::config::{Jobs, TaskConfig};
+//! # use scheduled_executor::sqlite::SqliteJobStore;
+//! # use scheduled_executor::store::JobStore;
+//! # use std::sync::Arc;
+//! let store = Arc::new(SqliteJobStore::open("/var/lib/myapp/jobs.db").unwrap());
+//! let jobs = Jobs::new().add("report", |_handle| println!("Sending the report"));
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_store(Arc::clone(&store), &jobs).unwrap();
+//! // Later
+//! for execution in store.history("report", 10).unwrap() {
+//!     println!("{:?}", execution);
+//! }
+//! ```
+//!
+//! The jobs are in the `jobs` table, and their executions in the `executions` table, with the
+//! instants stored as nanoseconds since the Unix epoch.
+//!
+//! [`JobStore`]: ../store/trait.JobStore.html
+use config::{Config, TaskConfig};
+use store::{Execution, JobStore, Outcome};
+
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[allow(non_camel_case_types)]
+enum sqlite3 {}
+#[allow(non_camel_case_types)]
+enum sqlite3_stmt {}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_NULL: c_int = 5;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+const SQLITE_OPEN_FULLMUTEX: c_int = 0x10000;
+/// Makes SQLite copy the bound strings.
+const SQLITE_TRANSIENT: isize = -1;
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut sqlite3, flags: c_int, vfs: *const c_char) -> c_int;
+    fn sqlite3_close(db: *mut sqlite3) -> c_int;
+    fn sqlite3_errmsg(db: *mut sqlite3) -> *const c_char;
+    fn sqlite3_busy_timeout(db: *mut sqlite3, ms: c_int) -> c_int;
+    fn sqlite3_prepare_v2(db: *mut sqlite3, sql: *const c_char, len: c_int, stmt: *mut *mut sqlite3_stmt, tail: *mut *const c_char) -> c_int;
+    fn sqlite3_finalize(stmt: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_step(stmt: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_bind_text(stmt: *mut sqlite3_stmt, index: c_int, text: *const c_char, len: c_int, destructor: isize) -> c_int;
+    fn sqlite3_bind_int64(stmt: *mut sqlite3_stmt, index: c_int, value: i64) -> c_int;
+    fn sqlite3_bind_null(stmt: *mut sqlite3_stmt, index: c_int) -> c_int;
+    fn sqlite3_column_type(stmt: *mut sqlite3_stmt, column: c_int) -> c_int;
+    fn sqlite3_column_int64(stmt: *mut sqlite3_stmt, column: c_int) -> i64;
+    fn sqlite3_column_text(stmt: *mut sqlite3_stmt, column: c_int) -> *const c_char;
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS jobs (
+        name TEXT PRIMARY KEY,
+        schedule TEXT NOT NULL,
+        enabled INTEGER NOT NULL,
+        position INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS executions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job TEXT NOT NULL,
+        started INTEGER NOT NULL,
+        finished INTEGER,
+        duration INTEGER,
+        panic TEXT
+    );
+    CREATE INDEX IF NOT EXISTS executions_by_job ON executions (job, id);
+";
+
+/// A parameter of a statement.
+enum Value<'a> {
+    Text(&'a str),
+    Int(i64),
+    Null,
+}
+
+/// A connection to a database. SQLite is opened in serialized mode, so the connection can be
+/// used from any thread.
+struct Connection {
+    db: *mut sqlite3,
+}
+
+unsafe impl Send for Connection {}
+
+impl Connection {
+    fn open(path: &str) -> io::Result<Connection> {
+        let path = CString::new(path).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid database path"))?;
+        let mut db = ptr::null_mut();
+        let flags = SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE | SQLITE_OPEN_FULLMUTEX;
+        let result = unsafe { sqlite3_open_v2(path.as_ptr(), &mut db, flags, ptr::null()) };
+        // A connection is returned even when the database can't be opened, to report the error
+        let connection = Connection { db };
+        if result != SQLITE_OK {
+            return Err(connection.error());
+        }
+        unsafe { sqlite3_busy_timeout(db, 5000) };
+        for statement in SCHEMA.split(';').filter(|statement| !statement.trim().is_empty()) {
+            connection.execute(statement, &[])?;
+        }
+        Ok(connection)
+    }
+
+    fn error(&self) -> io::Error {
+        if self.db.is_null() {
+            return io::Error::other("sqlite: out of memory");
+        }
+        let message = unsafe { CStr::from_ptr(sqlite3_errmsg(self.db)) };
+        io::Error::other(format!("sqlite: {}", message.to_string_lossy()))
+    }
+
+    fn prepare(&self, sql: &str, params: &[Value]) -> io::Result<Statement<'_>> {
+        let sql = CString::new(sql).expect("SQL statements don't contain null bytes");
+        let mut stmt = ptr::null_mut();
+        if unsafe { sqlite3_prepare_v2(self.db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()) } != SQLITE_OK {
+            return Err(self.error());
+        }
+        let statement = Statement { stmt, connection: self };
+        for (index, param) in params.iter().enumerate() {
+            let index = index as c_int + 1;
+            let result = match *param {
+                Value::Text(text) => unsafe {
+                    sqlite3_bind_text(stmt, index, text.as_ptr() as *const c_char, text.len() as c_int, SQLITE_TRANSIENT)
+                },
+                Value::Int(value) => unsafe { sqlite3_bind_int64(stmt, index, value) },
+                Value::Null => unsafe { sqlite3_bind_null(stmt, index) },
+            };
+            if result != SQLITE_OK {
+                return Err(self.error());
+            }
+        }
+        Ok(statement)
+    }
+
+    fn execute(&self, sql: &str, params: &[Value]) -> io::Result<()> {
+        let mut statement = self.prepare(sql, params)?;
+        while statement.step()? {}
+        Ok(())
+    }
+
+    fn query<T, F>(&self, sql: &str, params: &[Value], mut row: F) -> io::Result<Vec<T>>
+        where F: FnMut(&Statement) -> io::Result<T>
+    {
+        let mut statement = self.prepare(sql, params)?;
+        let mut rows = Vec::new();
+        while statement.step()? {
+            rows.push(row(&statement)?);
+        }
+        Ok(rows)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { sqlite3_close(self.db) };
+    }
+}
+
+struct Statement<'a> {
+    stmt: *mut sqlite3_stmt,
+    connection: &'a Connection,
+}
+
+impl<'a> Statement<'a> {
+    /// Executes the statement up to the next row, returns false once it's done.
+    fn step(&mut self) -> io::Result<bool> {
+        match unsafe { sqlite3_step(self.stmt) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            _ => Err(self.connection.error()),
+        }
+    }
+
+    fn int(&self, column: c_int) -> Option<i64> {
+        if unsafe { sqlite3_column_type(self.stmt, column) } == SQLITE_NULL {
+            return None;
+        }
+        Some(unsafe { sqlite3_column_int64(self.stmt, column) })
+    }
+
+    fn text(&self, column: c_int) -> Option<String> {
+        let text = unsafe { sqlite3_column_text(self.stmt, column) };
+        if text.is_null() {
+            return None;
+        }
+        Some(unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned())
+    }
+}
+
+impl<'a> Drop for Statement<'a> {
+    fn drop(&mut self) {
+        unsafe { sqlite3_finalize(self.stmt) };
+    }
+}
+
+fn to_nanos(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as i64
+}
+
+fn from_nanos(nanos: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos as u64)
+}
+
+fn missing(column: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("missing value in column {}", column))
+}
+
+/// Reads an execution from the `started, finished, duration, panic` columns.
+fn execution(row: &Statement) -> io::Result<Execution> {
+    let started = from_nanos(row.int(0).ok_or_else(|| missing("started"))?);
+    let finished = row.int(1).map(|finished| {
+        let outcome = match row.text(3) {
+            Some(message) => Outcome::Panicked(message),
+            None => Outcome::Completed(Duration::from_nanos(row.int(2).unwrap_or(0) as u64)),
+        };
+        (from_nanos(finished), outcome)
+    });
+    Ok(Execution { started, finished })
+}
+
+/// A job store backed by an SQLite database. See the [module documentation].
+///
+/// [module documentation]: index.html
+pub struct SqliteJobStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteJobStore {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SqliteJobStore> {
+        let path = path.as_ref().to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid database path"))?;
+        Ok(SqliteJobStore { connection: Mutex::new(Connection::open(path)?) })
+    }
+
+    /// Opens a database that only lives in memory, mostly useful for tests.
+    pub fn in_memory() -> io::Result<SqliteJobStore> {
+        SqliteJobStore::open(":memory:")
+    }
+
+    /// Returns the last `limit` executions of the job, oldest first.
+    pub fn history(&self, name: &str, limit: usize) -> io::Result<Vec<Execution>> {
+        let connection = self.connection.lock().unwrap();
+        connection.query(
+            "SELECT started, finished, duration, panic FROM (
+                SELECT * FROM executions WHERE job = ?1 ORDER BY id DESC LIMIT ?2
+            ) ORDER BY id",
+            &[Value::Text(name), Value::Int(limit as i64)],
+            execution)
+    }
+
+    /// Returns the end of the last successful execution of the job.
+    pub fn last_success(&self, name: &str) -> io::Result<Option<SystemTime>> {
+        let connection = self.connection.lock().unwrap();
+        let rows = connection.query(
+            "SELECT MAX(finished) FROM executions WHERE job = ?1 AND panic IS NULL",
+            &[Value::Text(name)],
+            |row| Ok(row.int(0).map(from_nanos)))?;
+        Ok(rows.into_iter().next().and_then(|last| last))
+    }
+
+    /// Returns the executions of all the jobs that panicked since `since`, with the name of
+    /// their job, oldest first.
+    pub fn failures(&self, since: SystemTime) -> io::Result<Vec<(String, Execution)>> {
+        let connection = self.connection.lock().unwrap();
+        connection.query(
+            "SELECT started, finished, duration, panic, job FROM executions
+                WHERE panic IS NOT NULL AND started >= ?1 ORDER BY id",
+            &[Value::Int(to_nanos(since))],
+            |row| Ok((row.text(4).ok_or_else(|| missing("job"))?, execution(row)?)))
+    }
+}
+
+impl JobStore for SqliteJobStore {
+    fn load_jobs(&self) -> io::Result<Config> {
+        let connection = self.connection.lock().unwrap();
+        let tasks = connection.query("SELECT name, schedule, enabled FROM jobs ORDER BY position", &[], |row| {
+            Ok(TaskConfig {
+                name: row.text(0).ok_or_else(|| missing("name"))?,
+                schedule: row.text(1).ok_or_else(|| missing("schedule"))?,
+                enabled: row.int(2) != Some(0),
+            })
+        })?;
+        Ok(Config { tasks })
+    }
+
+    fn save_job(&self, job: &TaskConfig) -> io::Result<()> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO jobs (name, schedule, enabled, position)
+                VALUES (?1, ?2, ?3, (SELECT COALESCE(MAX(position), 0) + 1 FROM jobs))
+                ON CONFLICT (name) DO UPDATE SET schedule = excluded.schedule, enabled = excluded.enabled",
+            &[Value::Text(&job.name), Value::Text(&job.schedule), Value::Int(job.enabled as i64)])
+    }
+
+    fn remove_job(&self, name: &str) -> io::Result<()> {
+        self.connection.lock().unwrap().execute("DELETE FROM jobs WHERE name = ?1", &[Value::Text(name)])
+    }
+
+    fn record_start(&self, name: &str, time: SystemTime) -> io::Result<()> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO executions (job, started) VALUES (?1, ?2)",
+            &[Value::Text(name), Value::Int(to_nanos(time))])
+    }
+
+    fn record_outcome(&self, name: &str, time: SystemTime, outcome: &Outcome) -> io::Result<()> {
+        let (duration, panic) = match *outcome {
+            Outcome::Completed(duration) => (Value::Int(duration.as_nanos() as i64), Value::Null),
+            Outcome::Panicked(ref message) => (Value::Null, Value::Text(message)),
+        };
+        // The oldest execution still running: executions of the same job complete in the order
+        // in which they started, unless they overlap
+        self.connection.lock().unwrap().execute(
+            "UPDATE executions SET finished = ?2, duration = ?3, panic = ?4 WHERE id = (
+                SELECT id FROM executions WHERE job = ?1 AND finished IS NULL ORDER BY id LIMIT 1
+            )",
+            &[Value::Text(name), Value::Int(to_nanos(time)), duration, panic])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    use futures::Future;
+
+    use config::{Jobs, TaskConfig};
+    use executor::CoreExecutor;
+    use store::{JobStore, Outcome};
+    use super::SqliteJobStore;
+
+    fn job(name: &str, schedule: &str, enabled: bool) -> TaskConfig {
+        TaskConfig { name: name.to_owned(), schedule: schedule.to_owned(), enabled }
+    }
+
+    #[test]
+    fn sqlite_job_store_test() {
+        let store = Arc::new(SqliteJobStore::in_memory().unwrap());
+        store.save_job(&job("count", "every 1h", true)).unwrap();
+        store.save_job(&job("fail", "every 40ms", true)).unwrap();
+        store.save_job(&job("disabled", "every 40ms", false)).unwrap();
+        store.save_job(&job("count", "every 40ms", true)).unwrap();
+        store.save_job(&job("removed", "every 40ms", true)).unwrap();
+        store.remove_job("removed").unwrap();
+        assert_eq!(store.load_jobs().unwrap().tasks, vec![
+            job("count", "every 40ms", true),
+            job("fail", "every 40ms", true),
+            job("disabled", "every 40ms", false),
+        ]);
+
+        let jobs = Jobs::new()
+            .add("count", |_handle| {})
+            .add("fail", |_handle| panic!("it's a 'trap'"));
+        let start = SystemTime::now();
+        let executor = CoreExecutor::new().unwrap();
+        executor.schedule_store(Arc::clone(&store), &jobs).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        executor.stop().wait().unwrap();
+
+        let history = store.history("count", 100).unwrap();
+        assert!(history.len() >= 2);
+        assert!(history.iter().all(|execution| matches!(execution.finished, Some((_, Outcome::Completed(_))))));
+        assert_eq!(store.history("count", 1).unwrap(), vec![history[history.len() - 1].clone()]);
+        assert!(store.last_success("count").unwrap().unwrap() >= history[0].started);
+        assert_eq!(store.last_success("fail").unwrap(), None);
+
+        let failures = store.failures(start).unwrap();
+        assert!(failures.len() >= 2);
+        assert_eq!(failures[0].0, "fail");
+        assert_eq!(failures[0].1.finished.as_ref().unwrap().1, Outcome::Panicked("it's a 'trap'".to_owned()));
+        assert!(store.failures(SystemTime::now()).unwrap().is_empty());
+    }
+}
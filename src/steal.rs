@@ -0,0 +1,177 @@
+//! A pool of worker threads with work stealing, used to run the bodies of the tasks of a
+//! `ThreadPoolExecutor` created with `with_work_stealing`. Jobs are distributed to the queues of
+//! the workers in turn; a worker runs the jobs of its own queue first, and when its queue is
+//! empty it takes the oldest jobs from the queues of the other workers, so that the jobs queued
+//! behind a slow one are picked up by the idle workers.
+use futures::{Async, Future, Poll};
+use futures::sync::oneshot::{channel, Receiver};
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use compute::Job;
+
+/// Wraps the function in a job that reports its completion to the returned future, which
+/// resumes the panic of the function, if it panicked, like the futures of a `CpuPool`.
+pub fn completion<F: FnOnce() + Send + 'static>(f: F) -> (Job, Completion) {
+    let (sender, receiver) = channel();
+    let job = Box::new(move || {
+        let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(f)));
+    });
+    (job, Completion { receiver })
+}
+
+struct Shared {
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    pending: Mutex<usize>,
+    available: Condvar,
+    next_queue: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    /// Takes the next job from the queue of the worker, or from the queues of the others.
+    fn pop(&self, worker: usize) -> Option<Job> {
+        let job = self.queues[worker].lock().unwrap().pop_front().or_else(|| {
+            (1..self.queues.len())
+                .map(|offset| (worker + offset) % self.queues.len())
+                .find_map(|other| self.queues[other].lock().unwrap().pop_back())
+        });
+        if job.is_some() {
+            *self.pending.lock().unwrap() -= 1;
+        }
+        job
+    }
+
+    fn run(&self, worker: usize) {
+        loop {
+            if let Some(job) = self.pop(worker) {
+                job();
+                continue;
+            }
+            let pending = self.pending.lock().unwrap();
+            if *pending == 0 {
+                if self.shutdown.load(Ordering::SeqCst) {
+                    return;
+                }
+                drop(self.available.wait(pending).unwrap());
+            } else {
+                // A job is being taken by another worker
+                drop(pending);
+                thread::yield_now();
+            }
+        }
+    }
+}
+
+/// The pool of workers. The workers exit once the pool is dropped and all the queued jobs have
+/// run.
+pub struct StealingPool {
+    shared: Arc<Shared>,
+}
+
+impl StealingPool {
+    /// Starts `threads` workers, named "{prefix}0", "{prefix}1" and so on.
+    pub fn new(threads: usize, prefix: &str) -> StealingPool {
+        assert!(threads > 0, "at least one worker is required");
+        let shared = Arc::new(Shared {
+            queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            pending: Mutex::new(0),
+            available: Condvar::new(),
+            next_queue: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+        for worker in 0..threads {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name(format!("{}{}", prefix, worker))
+                .spawn(move || shared.run(worker))
+                .expect("failed to start a worker thread");
+        }
+        StealingPool { shared }
+    }
+
+    /// Runs the function on one of the workers. See `completion` for the returned future.
+    pub fn spawn_fn<F: FnOnce() + Send + 'static>(&self, f: F) -> Completion {
+        let (job, completion) = completion(f);
+        let queue = self.shared.next_queue.fetch_add(1, Ordering::Relaxed) % self.shared.queues.len();
+        self.shared.queues[queue].lock().unwrap().push_back(job);
+        *self.shared.pending.lock().unwrap() += 1;
+        self.shared.available.notify_one();
+        completion
+    }
+}
+
+impl Drop for StealingPool {
+    fn drop(&mut self) {
+        let _pending = self.shared.pending.lock().unwrap();
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        self.shared.available.notify_all();
+    }
+}
+
+/// Completes once the job returned by `completion` has run.
+pub struct Completion {
+    receiver: Receiver<Result<(), Box<dyn Any + Send>>>,
+}
+
+impl Future for Completion {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        match self.receiver.poll() {
+            Ok(Async::Ready(Ok(()))) => Ok(Async::Ready(())),
+            Ok(Async::Ready(Err(payload))) => panic::resume_unwind(payload),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::StealingPool;
+
+    #[test]
+    fn stealing_test() {
+        let pool = StealingPool::new(2, "stealing_test_");
+        let start = Instant::now();
+        let slow = pool.spawn_fn(|| thread::sleep(Duration::from_millis(200)));
+        let counter = Arc::new(AtomicUsize::new(0));
+        // Half of the jobs are queued behind the slow one
+        let jobs = (0..10)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                pool.spawn_fn(move || { counter.fetch_add(1, Ordering::SeqCst); })
+            })
+            .collect::<Vec<_>>();
+        for job in jobs {
+            job.wait().unwrap();
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+        assert!(start.elapsed() < Duration::from_millis(150));
+        slow.wait().unwrap();
+    }
+
+    #[test]
+    fn panic_test() {
+        let pool = StealingPool::new(1, "stealing_panic_test_");
+        let panicked = pool.spawn_fn(|| panic!("job panicked"));
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| panicked.wait())).is_err());
+        // The worker is still running
+        assert!(pool.spawn_fn(|| ()).wait().is_ok());
+    }
+}
@@ -0,0 +1,345 @@
+//! Durable jobs. A [`JobStore`] keeps the definitions of the jobs, and records their executions
+//! and outcomes, so that the executor can operate as a durable job scheduler: the jobs survive
+//! restarts, and their history can be inspected. [`FileJobStore`] keeps everything in a
+//! directory, with the jobs in the format of the [`config`] files:
+//!
+//! ```rust,no_run
+//! # use scheduled_executor::CoreExecutor;
+//! # use scheduled_executor::config::{Jobs, TaskConfig};
+//! # use scheduled_executor::store::{FileJobStore, JobStore};
+//! let store = FileJobStore::new("/var/lib/myapp/jobs").unwrap();
+//! store.save_job(&TaskConfig {
+//!     name: "report".to_owned(),
+//!     schedule: "0 0 8 * * *".to_owned(),
+//!     enabled: true,
+//! }).unwrap();
+//! let jobs = Jobs::new().add("report", |_handle| println!("Sending the report"));
+//! let executor = CoreExecutor::new().unwrap();
+//! executor.schedule_store(store, &jobs).unwrap();
+//! ```
+//!
+//! [`JobStore`]: trait.JobStore.html
+//! [`FileJobStore`]: struct.FileJobStore.html
+//! [`config`]: ../config/index.html
+use config::{Config, TaskConfig};
+use executor::{TaskHandle, TaskListener};
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The outcome of an execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The execution completed, after the given duration.
+    Completed(Duration),
+    /// The execution panicked with the given message.
+    Panicked(String),
+}
+
+/// An execution of a job, as recorded in a store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    /// When the execution started.
+    pub started: SystemTime,
+    /// When the execution finished and its outcome, or `None` if it was still running (or if
+    /// the process terminated while it was running).
+    pub finished: Option<(SystemTime, Outcome)>,
+}
+
+/// Stores the definitions of the jobs, and their executions.
+pub trait JobStore: Send + Sync + 'static {
+    /// Returns the definitions of all the jobs.
+    fn load_jobs(&self) -> io::Result<Config>;
+
+    /// Adds a job, replacing any job with the same name.
+    fn save_job(&self, job: &TaskConfig) -> io::Result<()>;
+
+    /// Removes the job with the given name, if any.
+    fn remove_job(&self, name: &str) -> io::Result<()>;
+
+    /// Records the start of an execution of the job.
+    fn record_start(&self, name: &str, time: SystemTime) -> io::Result<()>;
+
+    /// Records the end of an execution of the job, with its outcome.
+    fn record_outcome(&self, name: &str, time: SystemTime, outcome: &Outcome) -> io::Result<()>;
+}
+
+impl<T: JobStore + ?Sized> JobStore for Arc<T> {
+    fn load_jobs(&self) -> io::Result<Config> {
+        (**self).load_jobs()
+    }
+
+    fn save_job(&self, job: &TaskConfig) -> io::Result<()> {
+        (**self).save_job(job)
+    }
+
+    fn remove_job(&self, name: &str) -> io::Result<()> {
+        (**self).remove_job(name)
+    }
+
+    fn record_start(&self, name: &str, time: SystemTime) -> io::Result<()> {
+        (**self).record_start(name, time)
+    }
+
+    fn record_outcome(&self, name: &str, time: SystemTime, outcome: &Outcome) -> io::Result<()> {
+        (**self).record_outcome(name, time, outcome)
+    }
+}
+
+/// A store keeping the jobs in `jobs.toml`, and their executions in `history.log`, in a
+/// directory. The history is append only.
+#[derive(Debug)]
+pub struct FileJobStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileJobStore {
+    /// Creates a store in the given directory, creating the directory if needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<FileJobStore> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileJobStore { dir, lock: Mutex::new(()) })
+    }
+
+    /// Returns the recorded executions of the job, oldest first.
+    pub fn history(&self, name: &str) -> io::Result<Vec<Execution>> {
+        let _lock = self.lock.lock().unwrap();
+        let content = match fs::read_to_string(self.dir.join("history.log")) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut executions = Vec::new();
+        let mut running = VecDeque::new();
+        for line in content.lines() {
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid history line {:?}", line));
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() < 3 || unescape(fields[1]) != name {
+                continue;
+            }
+            let time = parse_time(fields[0]).ok_or_else(invalid)?;
+            let outcome = match (fields[2], fields.get(3)) {
+                ("start", None) => {
+                    running.push_back(executions.len());
+                    executions.push(Execution { started: time, finished: None });
+                    continue;
+                },
+                ("completed", Some(nanos)) => Outcome::Completed(duration_from_nanos(nanos.parse().map_err(|_| invalid())?)),
+                ("panicked", Some(message)) => Outcome::Panicked(unescape(message)),
+                _ => return Err(invalid()),
+            };
+            // Executions of the same job complete in the order in which they started, unless
+            // they overlap
+            if let Some(index) = running.pop_front() {
+                executions[index].finished = Some((time, outcome));
+            }
+        }
+        Ok(executions)
+    }
+
+    fn jobs_path(&self) -> PathBuf {
+        self.dir.join("jobs.toml")
+    }
+
+    fn read_jobs(&self) -> io::Result<Config> {
+        match fs::read_to_string(self.jobs_path()) {
+            Ok(content) => Config::parse(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_jobs(&self, config: &Config) -> io::Result<()> {
+        let temporary = self.dir.join("jobs.toml.tmp");
+        {
+            let mut file = fs::File::create(&temporary)?;
+            write!(file, "{}", config)?;
+            file.sync_all()?;
+        }
+        fs::rename(&temporary, self.jobs_path())
+    }
+
+    fn append(&self, name: &str, time: SystemTime, event: &str) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new().create(true).append(true).open(self.dir.join("history.log"))?;
+        writeln!(file, "{}\t{}\t{}", format_time(time), escape(name), event)
+    }
+}
+
+impl JobStore for FileJobStore {
+    fn load_jobs(&self) -> io::Result<Config> {
+        let _lock = self.lock.lock().unwrap();
+        self.read_jobs()
+    }
+
+    fn save_job(&self, job: &TaskConfig) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let mut config = self.read_jobs()?;
+        match config.tasks.iter_mut().find(|task| task.name == job.name) {
+            Some(task) => *task = job.clone(),
+            None => config.tasks.push(job.clone()),
+        }
+        self.write_jobs(&config)
+    }
+
+    fn remove_job(&self, name: &str) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let mut config = self.read_jobs()?;
+        config.tasks.retain(|task| task.name != name);
+        self.write_jobs(&config)
+    }
+
+    fn record_start(&self, name: &str, time: SystemTime) -> io::Result<()> {
+        self.append(name, time, "start")
+    }
+
+    fn record_outcome(&self, name: &str, time: SystemTime, outcome: &Outcome) -> io::Result<()> {
+        let event = match *outcome {
+            Outcome::Completed(duration) => format!("completed\t{}", duration.as_nanos()),
+            Outcome::Panicked(ref message) => format!("panicked\t{}", escape(message)),
+        };
+        self.append(name, time, &event)
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:09}", since_epoch.as_secs(), since_epoch.subsec_nanos())
+}
+
+fn parse_time(time: &str) -> Option<SystemTime> {
+    let (secs, nanos) = time.split_once('.')?;
+    Some(UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?))
+}
+
+fn duration_from_nanos(nanos: u64) -> Duration {
+    Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+}
+
+/// Escapes the backslashes, tabs and newlines, so that the value fits in a field of a line.
+pub(crate) fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('t')) => result.push('\t'),
+            ('\\', Some('n')) => result.push('\n'),
+            ('\\', Some('\\')) => result.push('\\'),
+            (c, _) => {
+                result.push(c);
+                continue;
+            },
+        }
+        chars.next();
+    }
+    result
+}
+
+/// Records the executions of the tasks scheduled from a store. See
+/// `CoreExecutor::schedule_store`.
+pub(crate) struct StoreListener {
+    store: Arc<dyn JobStore>,
+    tasks: HashMap<usize, String>,
+}
+
+impl StoreListener {
+    pub(crate) fn new(store: Arc<dyn JobStore>, handles: &[TaskHandle]) -> StoreListener {
+        let tasks = handles.iter()
+            .filter_map(|handle| handle.name().map(|name| (handle.id(), name.to_owned())))
+            .collect::<HashMap<_, _>>();
+        StoreListener { store, tasks }
+    }
+
+    fn record<F: FnOnce(&dyn JobStore, &str) -> io::Result<()>>(&self, task: &TaskHandle, record: F) {
+        if let Some(name) = self.tasks.get(&task.id()) {
+            if let Err(e) = record(&*self.store, name) {
+                error!("Failed to record the execution of {} in the job store: {}", task, e);
+            }
+        }
+    }
+}
+
+impl TaskListener for StoreListener {
+    fn on_start(&self, task: &TaskHandle) {
+        self.record(task, |store, name| store.record_start(name, SystemTime::now()));
+    }
+
+    fn on_complete(&self, task: &TaskHandle, duration: Duration) {
+        self.record(task, |store, name| store.record_outcome(name, SystemTime::now(), &Outcome::Completed(duration)));
+    }
+
+    fn on_panic(&self, task: &TaskHandle, message: &str) {
+        let outcome = Outcome::Panicked(message.to_owned());
+        self.record(task, |store, name| store.record_outcome(name, SystemTime::now(), &outcome));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Future;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use config::{Jobs, TaskConfig};
+    use executor::CoreExecutor;
+    use super::{FileJobStore, JobStore, Outcome};
+
+    fn job(name: &str, schedule: &str) -> TaskConfig {
+        TaskConfig { name: name.to_owned(), schedule: schedule.to_owned(), enabled: true }
+    }
+
+    #[test]
+    fn file_job_store_test() {
+        let dir = env::temp_dir().join(format!("scheduled_executor_jobs_{}", process::id()));
+        let store = Arc::new(FileJobStore::new(&dir).unwrap());
+        store.save_job(&job("count", "every 1h")).unwrap();
+        store.save_job(&job("fail\tloudly", "every 40ms")).unwrap();
+        store.save_job(&job("count", "every 40ms")).unwrap();
+        store.save_job(&job("removed", "every 40ms")).unwrap();
+        store.remove_job("removed").unwrap();
+        assert_eq!(store.load_jobs().unwrap().tasks, vec![job("count", "every 40ms"), job("fail\tloudly", "every 40ms")]);
+
+        let jobs = Jobs::new()
+            .add("count", |_handle| {})
+            .add("fail\tloudly", |_handle| panic!("oops\n"));
+        let executor = CoreExecutor::new().unwrap();
+        let handles = executor.schedule_store(Arc::clone(&store), &jobs).unwrap();
+        assert_eq!(handles.len(), 2);
+        thread::sleep(Duration::from_millis(100));
+        executor.stop().wait().unwrap();
+
+        let history = store.history("count").unwrap();
+        assert!(history.len() >= 2);
+        assert!(history.iter().all(|execution| matches!(execution.finished, Some((_, Outcome::Completed(_))))));
+        let failures = store.history("fail\tloudly").unwrap();
+        assert!(failures.len() >= 2);
+        assert_eq!(failures[0].finished.as_ref().unwrap().1, Outcome::Panicked("oops\n".to_owned()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn quoted_name_test() {
+        let dir = env::temp_dir().join(format!("scheduled_executor_quoted_jobs_{}", process::id()));
+        let store = FileJobStore::new(&dir).unwrap();
+        store.save_job(&job("say \"#hi\"", "every 1h")).unwrap();
+        store.save_job(&job("other", "every 1h")).unwrap();
+        store.record_start("say \"#hi\"", UNIX_EPOCH).unwrap();
+        assert_eq!(store.load_jobs().unwrap().tasks, vec![job("say \"#hi\"", "every 1h"), job("other", "every 1h")]);
+        assert_eq!(store.history("say \"#hi\"").unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
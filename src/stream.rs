@@ -0,0 +1,232 @@
+//! Streams and channels of the values returned by the executions of a task. See
+//! `CoreExecutor::schedule_stream_with` and `CoreExecutor::schedule_to_channel`.
+use futures::{Async, Poll, Stream};
+use futures::task::{self, Task};
+
+use executor::TaskHandle;
+use schedule::{Anchor, Schedule};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Defines what happens to the values produced by a task when the consumer of the stream is
+/// slower than the task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferPolicy {
+    /// All the values are kept until consumed.
+    Unbounded,
+    /// Keeps at most the given number of values, dropping the oldest ones.
+    DropOldest(usize),
+    /// Keeps at most the given number of values, dropping the new ones.
+    DropNewest(usize),
+}
+
+struct Buffer<R> {
+    values: VecDeque<R>,
+    dropped: usize,
+    closed: bool,
+    consumer: Option<Task>,
+}
+
+type Shared<R> = Arc<Mutex<Buffer<R>>>;
+
+fn notify<R>(buffer: &mut Buffer<R>) {
+    if let Some(consumer) = buffer.consumer.take() {
+        consumer.notify();
+    }
+}
+
+/// The producing side of a stream, owned by the scheduled function. The stream ends when it's
+/// dropped, that is, when the task has completed or has been stopped.
+pub(crate) struct StreamSender<R> {
+    buffer: Shared<R>,
+    policy: BufferPolicy,
+}
+
+impl<R> StreamSender<R> {
+    pub(crate) fn send(&self, value: R) {
+        let capacity = self.capacity();
+        let mut buffer = self.buffer.lock().unwrap();
+        if let BufferPolicy::DropOldest(_) = self.policy {
+            while buffer.values.len() >= capacity && buffer.values.pop_front().is_some() {
+                buffer.dropped += 1;
+            }
+        }
+        if buffer.values.len() < capacity {
+            buffer.values.push_back(value);
+        } else {
+            buffer.dropped += 1;
+        }
+        notify(&mut buffer);
+    }
+
+    fn capacity(&self) -> usize {
+        match self.policy {
+            BufferPolicy::Unbounded => usize::MAX,
+            BufferPolicy::DropOldest(capacity) | BufferPolicy::DropNewest(capacity) => capacity,
+        }
+    }
+}
+
+impl<R> Drop for StreamSender<R> {
+    fn drop(&mut self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.closed = true;
+        notify(&mut buffer);
+    }
+}
+
+/// A stream of the values returned by the executions of a task. The stream ends once the task
+/// is stopped or its schedule has completed, and dropping the stream stops the task.
+pub struct TaskStream<R> {
+    buffer: Shared<R>,
+    handle: TaskHandle,
+}
+
+impl<R> TaskStream<R> {
+    /// Returns the handle of the task producing the values.
+    pub fn handle(&self) -> &TaskHandle {
+        &self.handle
+    }
+
+    /// Returns the number of values dropped because of the buffer policy.
+    pub fn dropped(&self) -> usize {
+        self.buffer.lock().unwrap().dropped
+    }
+}
+
+impl<R> Stream for TaskStream<R> {
+    type Item = R;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<R>, ()> {
+        let mut buffer = self.buffer.lock().unwrap();
+        if let Some(value) = buffer.values.pop_front() {
+            return Ok(Async::Ready(Some(value)));
+        }
+        if buffer.closed {
+            return Ok(Async::Ready(None));
+        }
+        buffer.consumer = Some(task::current());
+        Ok(Async::NotReady)
+    }
+}
+
+impl<R> Drop for TaskStream<R> {
+    fn drop(&mut self) {
+        self.handle.stop();
+    }
+}
+
+impl<R> fmt::Debug for TaskStream<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let buffer = self.buffer.lock().unwrap();
+        f.debug_struct("TaskStream")
+            .field("task", &self.handle)
+            .field("buffered", &buffer.values.len())
+            .field("dropped", &buffer.dropped)
+            .finish()
+    }
+}
+
+/// Creates the two sides of a stream. The task producing the values is scheduled by `schedule`,
+/// which receives the sender.
+pub(crate) fn task_stream<R, F>(policy: BufferPolicy, schedule: F) -> TaskStream<R>
+    where F: FnOnce(StreamSender<R>) -> TaskHandle
+{
+    let buffer = Arc::new(Mutex::new(Buffer {
+        values: VecDeque::new(),
+        dropped: 0,
+        closed: false,
+        consumer: None,
+    }));
+    let sender = StreamSender { buffer: Arc::clone(&buffer), policy };
+    let handle = schedule(sender);
+    TaskStream { buffer, handle }
+}
+
+/// A schedule that completes once the receiver of a channel has been dropped.
+pub(crate) struct ChannelSchedule<S> {
+    schedule: S,
+    disconnected: Arc<AtomicBool>,
+}
+
+impl<S> ChannelSchedule<S> {
+    pub(crate) fn new(schedule: S) -> ChannelSchedule<S> {
+        ChannelSchedule { schedule, disconnected: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Returns a function sending the values to `sender`, and completing the schedule once the
+    /// receiver has been dropped.
+    pub(crate) fn sender<R>(&self, sender: Sender<R>) -> impl Fn(R) + Send
+        where R: Send
+    {
+        let disconnected = Arc::clone(&self.disconnected);
+        move |value| {
+            if sender.send(value).is_err() {
+                disconnected.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<S: Schedule> Schedule for ChannelSchedule<S> {
+    fn next_after(&mut self, after: Instant) -> Option<Instant> {
+        if self.disconnected.load(Ordering::Relaxed) {
+            debug!("Receiver dropped, completing the schedule");
+            return None;
+        }
+        self.schedule.next_after(after)
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        self.schedule.set_interval(interval)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.schedule.interval()
+    }
+
+    fn executed(&mut self) {
+        self.schedule.executed()
+    }
+
+    fn anchor(&self) -> Anchor {
+        self.schedule.anchor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+
+    use executor::TaskHandle;
+    use super::{BufferPolicy, task_stream};
+
+    #[test]
+    fn buffer_policy_test() {
+        let values = |policy| {
+            let mut sender = None;
+            let stream = task_stream(policy, |s| {
+                sender = Some(s);
+                TaskHandle::new(None)
+            });
+            let sender = sender.unwrap();
+            for value in 0..5 {
+                sender.send(value);
+            }
+            drop(sender);
+            let dropped = stream.dropped();
+            (stream.wait().collect::<Result<Vec<_>, _>>().unwrap(), dropped)
+        };
+        assert_eq!(values(BufferPolicy::Unbounded), (vec![0, 1, 2, 3, 4], 0));
+        assert_eq!(values(BufferPolicy::DropOldest(2)), (vec![3, 4], 3));
+        assert_eq!(values(BufferPolicy::DropNewest(2)), (vec![0, 1], 3));
+        assert_eq!(values(BufferPolicy::DropNewest(0)), (vec![], 5));
+        assert_eq!(values(BufferPolicy::DropOldest(0)), (vec![], 5));
+    }
+}
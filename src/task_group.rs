@@ -8,8 +8,9 @@
 //!
 use futures::future::Future;
 use futures_cpupool::CpuPool;
-use tokio_core::reactor::{Handle, Remote, Timeout};
+use tokio_core::reactor::{Handle, Remote};
 
+use clock;
 use executor::{CoreExecutor, ThreadPoolExecutor};
 
 use std::sync::Arc;
@@ -28,7 +29,7 @@ pub trait TaskGroup: Send + Sync + Sized + 'static {
     fn get_tasks(&self) -> Vec<Self::TaskId>;
 
     /// Runs once per task id per cycle.
-    fn execute(&self, Self::TaskId);
+    fn execute(&self, task_id: Self::TaskId);
 }
 
 fn schedule_tasks_local<T: TaskGroup>(task_group: &Arc<T>, interval: Duration, handle: &Handle) {
@@ -37,11 +38,15 @@ fn schedule_tasks_local<T: TaskGroup>(task_group: &Arc<T>, interval: Duration, h
         return
     }
     let task_interval = interval / tasks.len() as u32;
+    let clock = clock::current();
     for (i, task) in tasks.into_iter().enumerate() {
         let task_group_clone = task_group.clone();
-        let t = Timeout::new(task_interval * i as u32, handle).unwrap()
-            .then(move |_| {
-                task_group_clone.execute(task);
+        let t = clock.sleep_until(clock.now() + task_interval * i as u32, handle)
+            .then(move |result| {
+                match result {
+                    Ok(()) => task_group_clone.execute(task),
+                    Err(e) => error!("Skipping a task of the group: {}", e),
+                }
                 Ok::<(), ()>(())
             });
         handle.spawn(t);
@@ -54,19 +59,26 @@ fn schedule_tasks_remote<T: TaskGroup>(task_group: &Arc<T>, interval: Duration,
         return
     }
     let task_interval = interval / tasks.len() as u32;
+    let clock = clock::current();
     for (i, task) in tasks.into_iter().enumerate() {
         let task_group = task_group.clone();
         let pool = pool.clone();
+        let clock = clock.clone();
 
         remote.spawn(move |handle| {
-            let task_group = task_group.clone();
-            let pool = pool.clone();
-            let t = Timeout::new(task_interval * i as u32, handle).unwrap()
-                .then(move |_| {
-                    task_group.execute(task);
+            // The timer runs on the event loop, and the execution on the pool
+            let t = clock.sleep_until(clock.now() + task_interval * i as u32, handle)
+                .then(move |result| {
+                    match result {
+                        Ok(()) => pool.spawn_fn(move || {
+                            task_group.execute(task);
+                            Ok::<(), ()>(())
+                        }).forget(),
+                        Err(e) => error!("Skipping a task of the group: {}", e),
+                    }
                     Ok::<(), ()>(())
                 });
-            handle.spawn(pool.spawn(t));
+            handle.spawn(t);
             Ok::<(), ()>(())
         })
     }
@@ -110,11 +122,18 @@ impl TaskGroupScheduler for ThreadPoolExecutor {
 
 #[cfg(test)]
 mod tests {
+    use futures::future::{self, Future};
+    use futures_cpupool::CpuPool;
+    use tokio_core::reactor::{Core, Handle, Timeout};
+
+    use std::io;
     use std::sync::{Arc, RwLock};
     use std::thread;
     use std::time::{Duration, Instant};
 
-    use task_group::{TaskGroup, TaskGroupScheduler};
+    use clock::{self, Clock};
+    use error::ScheduleError;
+    use task_group::{TaskGroup, TaskGroupScheduler, schedule_tasks_local, schedule_tasks_remote};
     use executor::ThreadPoolExecutor;
 
     type TaskExecutions = Vec<Vec<Instant>>;
@@ -181,4 +200,32 @@ mod tests {
             assert!(inter_task_interval > Duration::from_millis(500));
         }
     }
+
+    struct FailingClock;
+
+    impl Clock for FailingClock {
+        fn now(&self) -> Instant {
+            Instant::now()
+        }
+
+        fn sleep_until(&self, _deadline: Instant, _handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+            Box::new(future::err(ScheduleError::Timer(io::Error::other("no timer"))))
+        }
+    }
+
+    #[test]
+    fn timer_failure_test() {
+        let group = Arc::new(TestGroup::new());
+        let executions_lock = group.executions_lock();
+        let mut core = Core::new().unwrap();
+        let pool = CpuPool::new(1);
+        let clock: Arc<dyn Clock> = Arc::new(FailingClock);
+        clock::with_clock(&clock, || {
+            schedule_tasks_local(&group, Duration::from_millis(50), &core.handle());
+            schedule_tasks_remote(&group, Duration::from_millis(50), &core.remote(), &pool);
+        });
+        // The executions are skipped, without panicking
+        core.run(Timeout::new(Duration::from_millis(100), &core.handle()).unwrap()).unwrap();
+        assert!(executions_lock.read().unwrap().iter().all(|executions| executions.is_empty()));
+    }
 }
@@ -0,0 +1,433 @@
+//! A deterministic executor for testing scheduled tasks. The [`TestExecutor`] doesn't use a
+//! thread, and its clock only moves when `advance` is called: all the executions due in the
+//! advanced time are run synchronously, in order, as if the time had actually passed. This
+//! allows periodic behavior to be tested instantly, without sleeping:
+//!
+//! ```rust
+//! # use scheduled_executor::testing::TestExecutor;
+//! # use std::cell::Cell;
+//! # use std::rc::Rc;
+//! # use std::time::Duration;
+//! let executor = TestExecutor::new().unwrap();
+//! let counter = Rc::new(Cell::new(0));
+//! let counter_clone = Rc::clone(&counter);
+//! executor.schedule_fixed_rate(Duration::from_secs(0), Duration::from_secs(60), move |_handle| {
+//!     counter_clone.set(counter_clone.get() + 1);
+//! });
+//! executor.advance(Duration::from_secs(3600));
+//! assert_eq!(counter.get(), 61);
+//! ```
+//!
+//! Besides `advance`, which runs everything due in a given amount of time, `step` jumps
+//! directly to the next due execution, which allows long periods of activity (for example a
+//! week of cron schedules) to be simulated in a few milliseconds. The system time of the
+//! virtual clock moves with it: `starting_at` sets the system time the clock starts from, so
+//! that the calendar schedules are simulated from a known date.
+//!
+//! Executions don't take any virtual time, and the functions don't need to be `Send`. The
+//! statistics of the tasks are measured with the virtual clock, so the executions always have a
+//! duration and a lag of zero. The futures spawned on the `Handle` passed to the functions are
+//! polled whenever the virtual clock moves. The timers created with the clock of the executor,
+//! such as the ones of `layer::timeout`, follow the virtual clock, while the other timers use
+//! the real clock.
+//!
+//! [`TestExecutor`]: struct.TestExecutor.html
+use futures::{Async, Future, Poll};
+use futures::task::{self as future_task, Task as FutureTask};
+use tokio_core::reactor::{Core, Handle};
+
+use clock::{self, Clock};
+use error::ScheduleError;
+use executor::{Once, PanicPolicy, Settings, Task, TaskHandle};
+use schedule::{FixedInterval, FixedRate, Schedule};
+use schedule::cron::{CronSchedule, ParseError};
+
+use std::cell::{Cell, RefCell};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The virtual clock of a `TestExecutor`.
+struct VirtualClock {
+    now: Arc<Mutex<Instant>>,
+    /// The futures waiting for the clock to move, see `VirtualSleep`.
+    sleepers: Arc<Mutex<Vec<FutureTask>>>,
+    origin: Instant,
+    system_origin: SystemTime,
+}
+
+impl VirtualClock {
+    fn set(&self, now: Instant) {
+        *self.now.lock().unwrap() = now;
+        for sleeper in self.sleepers.lock().unwrap().drain(..) {
+            sleeper.notify();
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    // Used by the futures spawned by the tasks
+    fn sleep_until(&self, deadline: Instant, _handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        Box::new(VirtualSleep { deadline, now: Arc::clone(&self.now), sleepers: Arc::clone(&self.sleepers) })
+    }
+
+    // The virtual clock never goes back, so the system time only moves with it
+    fn system_time(&self) -> SystemTime {
+        self.system_origin + (self.now() - self.origin)
+    }
+}
+
+/// A future completing once the virtual clock reaches `deadline`.
+struct VirtualSleep {
+    deadline: Instant,
+    now: Arc<Mutex<Instant>>,
+    sleepers: Arc<Mutex<Vec<FutureTask>>>,
+}
+
+impl Future for VirtualSleep {
+    type Item = ();
+    type Error = ScheduleError;
+
+    fn poll(&mut self) -> Poll<(), ScheduleError> {
+        if *self.now.lock().unwrap() >= self.deadline {
+            return Ok(Async::Ready(()));
+        }
+        self.sleepers.lock().unwrap().push(future_task::current());
+        Ok(Async::NotReady)
+    }
+}
+
+struct TestTask {
+    task: Task,
+    schedule: Box<dyn Schedule>,
+    scheduled_fn: Box<dyn FnMut(&Handle)>,
+    next: Instant,
+    sequence: usize,
+}
+
+/// An executor driven by a virtual clock. See the [module documentation].
+///
+/// [module documentation]: index.html
+pub struct TestExecutor {
+    core: RefCell<Core>,
+    clock: Arc<VirtualClock>,
+    settings: Arc<Settings>,
+    tasks: RefCell<Vec<TestTask>>,
+    sequence: Cell<usize>,
+}
+
+impl TestExecutor {
+    /// Creates a new `TestExecutor`. The virtual clock starts at the current instant.
+    pub fn new() -> Result<TestExecutor, ScheduleError> {
+        TestExecutor::starting_at(SystemTime::now())
+    }
+
+    /// Creates a new `TestExecutor` whose virtual clock starts at the system time `start`, so
+    /// that the calendar schedules, such as cron expressions, fire at the same times whenever
+    /// the test runs.
+    pub fn starting_at(start: SystemTime) -> Result<TestExecutor, ScheduleError> {
+        let origin = Instant::now();
+        let clock = Arc::new(VirtualClock {
+            now: Arc::new(Mutex::new(origin)),
+            sleepers: Arc::new(Mutex::new(Vec::new())),
+            origin,
+            system_origin: start,
+        });
+        Ok(TestExecutor {
+            core: RefCell::new(Core::new().map_err(ScheduleError::Reactor)?),
+            settings: Arc::new(Settings::new(Arc::clone(&clock) as Arc<dyn Clock>)),
+            clock,
+            tasks: RefCell::new(Vec::new()),
+            sequence: Cell::new(0),
+        })
+    }
+
+    /// Returns the current instant of the virtual clock.
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Returns the current system time of the virtual clock.
+    pub fn system_time(&self) -> SystemTime {
+        self.clock.system_time()
+    }
+
+    /// Sets the policy applied to the tasks that panic. See `CoreExecutor::set_panic_policy`.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        *self.settings.panic_policy.write().unwrap() = policy;
+    }
+
+    /// Schedules a function according to the given `schedule`. See `CoreExecutor::schedule_with`.
+    pub fn schedule_with<S, F>(&self, mut schedule: S, scheduled_fn: F) -> TaskHandle
+        where S: Schedule,
+              F: FnMut(&Handle) + 'static
+    {
+        let task = Task {
+            handle: TaskHandle::new(None),
+            settings: Arc::clone(&self.settings),
+        };
+        let handle = task.handle.clone();
+        task.update_schedule(&mut schedule);
+        match clock::with_clock(&self.settings.clock, || schedule.next_after(self.now())) {
+            Some(next) => {
+                let next = task.next_execution(&schedule, next);
+                self.push(TestTask {
+                    task,
+                    schedule: Box::new(schedule),
+                    scheduled_fn: Box::new(scheduled_fn),
+                    next,
+                    sequence: 0,
+                });
+            },
+            None => task.finished(),
+        }
+        handle
+    }
+
+    /// Schedules a function for running at fixed intervals. See
+    /// `CoreExecutor::schedule_fixed_interval`.
+    pub fn schedule_fixed_interval<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) + 'static
+    {
+        self.schedule_with(FixedInterval::new(initial, interval), scheduled_fn)
+    }
+
+    /// Schedules a function for running at fixed rate. See `CoreExecutor::schedule_fixed_rate`.
+    pub fn schedule_fixed_rate<F>(&self, initial: Duration, interval: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnMut(&Handle) + 'static
+    {
+        self.schedule_with(FixedRate::new(initial, interval), scheduled_fn)
+    }
+
+    /// Schedules a function for running once, after the specified `delay` of virtual time.
+    pub fn schedule_once<F>(&self, delay: Duration, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Handle) + 'static
+    {
+        self.schedule_at(self.now() + delay, scheduled_fn)
+    }
+
+    /// Schedules a function for running once, at the specified instant of virtual time.
+    pub fn schedule_at<F>(&self, instant: Instant, scheduled_fn: F) -> TaskHandle
+        where F: FnOnce(&Handle) + 'static
+    {
+        let mut scheduled_fn = Some(scheduled_fn);
+        self.schedule_with(
+            Once(Some(instant)),
+            move |handle| {
+                if let Some(scheduled_fn) = scheduled_fn.take() {
+                    scheduled_fn(handle);
+                }
+            }
+        )
+    }
+
+    /// Schedules a function according to a cron expression. See `CoreExecutor::schedule_cron`.
+    pub fn schedule_cron<F>(&self, expression: &str, scheduled_fn: F) -> Result<TaskHandle, ParseError>
+        where F: FnMut(&Handle) + 'static
+    {
+        let cron = CronSchedule::parse(expression)?;
+        Ok(self.schedule_with(cron, scheduled_fn))
+    }
+
+    /// Returns the instant of the next execution of any task, if any.
+    pub fn next_run(&self) -> Option<Instant> {
+        self.tasks.borrow().iter()
+            .filter(|test_task| !test_task.task.stopped())
+            .map(|test_task| test_task.next)
+            .min()
+    }
+
+    /// Moves the virtual clock forward to the next due execution, and runs it. Returns false if
+    /// there are no more executions.
+    pub fn step(&self) -> bool {
+        match self.next_run() {
+            Some(next) => {
+                let now = self.now();
+                self.advance(if next > now { next - now } else { Duration::from_secs(0) });
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Moves the virtual clock forward by `duration`, running all the executions that are due
+    /// in the meantime, in order.
+    pub fn advance(&self, duration: Duration) {
+        let target = self.now() + duration;
+        while let Some(mut test_task) = self.next_due(target) {
+            if test_task.next > self.now() {
+                self.set_now(test_task.next);
+            }
+            if self.run(&mut test_task) {
+                self.push(test_task);
+            }
+        }
+        self.set_now(target);
+    }
+
+    /// Moves the virtual clock, and polls the futures waiting for it.
+    fn set_now(&self, now: Instant) {
+        self.clock.set(now);
+        self.core.borrow_mut().turn(Some(Duration::from_secs(0)));
+    }
+
+    fn push(&self, mut test_task: TestTask) {
+        test_task.sequence = self.sequence.get();
+        self.sequence.set(test_task.sequence + 1);
+        self.tasks.borrow_mut().push(test_task);
+    }
+
+    /// Removes and returns the first task due before `target`, dropping the stopped tasks.
+    fn next_due(&self, target: Instant) -> Option<TestTask> {
+        let mut tasks = self.tasks.borrow_mut();
+        tasks.retain(|test_task| !test_task.task.stopped());
+        let index = tasks.iter()
+            .enumerate()
+            .filter(|&(_, test_task)| test_task.next <= target)
+            .min_by_key(|&(_, test_task)| (test_task.next, test_task.sequence))
+            .map(|(index, _)| index)?;
+        Some(tasks.remove(index))
+    }
+
+    /// Executes the task, returning true if it has to be executed again.
+    fn run(&self, test_task: &mut TestTask) -> bool {
+        let handle = self.core.borrow().handle();
+        let TestTask { ref task, ref mut schedule, ref mut scheduled_fn, ref mut next, .. } = *test_task;
+        if task.paused() {
+            debug!("{} paused, skipping execution", task.handle);
+        } else {
+            let start = task.started();
+            let result = task.call(|| scheduled_fn(&handle));
+            let duration = task.ended(start);
+            if result.is_some() {
+                task.completed(duration);
+            }
+            schedule.executed();
+        }
+        task.update_schedule(schedule);
+        match clock::with_clock(&self.settings.clock, || schedule.next_after(self.now())) {
+            Some(next_after) => {
+                *next = task.next_execution(schedule, next_after);
+                true
+            },
+            None => {
+                task.finished();
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use clock::Clock;
+    use schedule::{FixedRate, Schedule};
+    use super::TestExecutor;
+
+    fn s(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn advance_test() {
+        let executor = TestExecutor::new().unwrap();
+        let start = executor.now();
+        let executions = Rc::new(RefCell::new(Vec::new()));
+
+        let executions_clone = Rc::clone(&executions);
+        executor.schedule_fixed_rate(s(0), s(10), move |_handle| {
+            executions_clone.borrow_mut().push("rate");
+        });
+        let executions_clone = Rc::clone(&executions);
+        executor.schedule_once(s(15), move |_handle| {
+            executions_clone.borrow_mut().push("once");
+        });
+        let executions_clone = Rc::clone(&executions);
+        let interval = executor.schedule_fixed_interval(s(5), s(20), move |_handle| {
+            executions_clone.borrow_mut().push("interval");
+        });
+
+        executor.advance(s(40));
+        assert_eq!(executor.now(), start + s(40));
+        assert_eq!(*executions.borrow(), vec!["rate", "interval", "rate", "once", "rate", "interval", "rate", "rate"]);
+        assert_eq!(interval.runs(), 2);
+        assert_eq!(interval.next_run(), Some(start + s(45)));
+        assert_eq!(interval.stats().max_lag, Some(s(0)));
+        assert_eq!(interval.stats().total_duration, s(0));
+
+        interval.stop();
+        executions.borrow_mut().clear();
+        executor.advance(s(20));
+        assert_eq!(*executions.borrow(), vec!["rate", "rate"]);
+    }
+
+    #[test]
+    fn simulation_test() {
+        // Monday 2024-01-01 00:00:00 UTC
+        let monday = UNIX_EPOCH + s(1_704_067_200);
+        let executor = TestExecutor::starting_at(monday).unwrap();
+        let start = executor.now();
+        let executions = Rc::new(RefCell::new(Vec::new()));
+        let executions_clone = Rc::clone(&executions);
+        let clock = Arc::clone(&executor.clock);
+        executor.schedule_cron("0 */15 9-17 * * MON-FRI", move |_handle| {
+            executions_clone.borrow_mut().push(clock.system_time());
+        }).unwrap();
+
+        // Simulate a week of activity
+        let mut steps = 0;
+        while executor.next_run().unwrap() < start + s(7 * 24 * 3600) {
+            assert!(executor.step());
+            steps += 1;
+        }
+        assert_eq!(steps, 5 * 9 * 4);
+        let expected = (0..5)
+            .flat_map(|day| (9..18).flat_map(move |hour| (0..4).map(move |quarter| {
+                monday + s(day * 24 * 3600 + hour * 3600 + quarter * 15 * 60)
+            })))
+            .collect::<Vec<_>>();
+        assert_eq!(*executions.borrow(), expected);
+        assert_eq!(executions.borrow()[0], monday + s(9 * 3600));
+        assert_eq!(executions.borrow()[179], monday + s(4 * 24 * 3600 + 17 * 3600 + 45 * 60));
+        // Next Monday at 9:00
+        assert_eq!(executor.next_run(), Some(start + s(7 * 24 * 3600 + 9 * 3600)));
+
+        let executor = TestExecutor::new().unwrap();
+        executor.schedule_once(s(3600), |_handle| ());
+        assert!(executor.step());
+        assert!(!executor.step());
+        assert_eq!(executor.next_run(), None);
+    }
+
+    #[test]
+    fn panic_test() {
+        let executor = TestExecutor::new().unwrap();
+        let handle = executor.schedule_fixed_rate(s(0), s(1), |_handle| panic!("failure"));
+        executor.advance(s(9));
+        assert_eq!(handle.panics(), 10);
+        assert!(!handle.stopped());
+    }
+
+    #[test]
+    fn max_runs_pause_test() {
+        let executor = TestExecutor::new().unwrap();
+        let executions = Rc::new(RefCell::new(0));
+        let executions_clone = Rc::clone(&executions);
+        let task = executor.schedule_with(FixedRate::new(s(0), s(10)).max_runs(3), move |_handle| {
+            *executions_clone.borrow_mut() += 1;
+        });
+        task.pause();
+        executor.advance(s(60));
+        assert_eq!(*executions.borrow(), 0);
+        task.resume();
+        executor.advance(s(60));
+        assert_eq!(*executions.borrow(), 3);
+        assert!(task.stopped());
+    }
+}
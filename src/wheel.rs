@@ -0,0 +1,497 @@
+//! A hierarchical timing wheel, holding the timers of all the tasks of an executor. Instead of
+//! registering one timer in the event loop per task and per execution, the executors using the
+//! `SystemClock` add an entry to the wheel, which is driven by a single timer of the event loop
+//! set to its earliest expiration. See `ExecutorBuilder::timer_wheel`.
+//!
+//! The wheel has a resolution of one millisecond, and six levels of 64 slots: the slots of the
+//! first level last one millisecond, the ones of the second level 64 milliseconds, and so on.
+//! An entry is stored in the lowest level whose slots are longer than its remaining time, and
+//! it's moved to the lower levels as its slot expires, until it fires from the first level.
+//! Finding the earliest expiration only scans the occupied slots bitmap of each level.
+use futures::{Async, Future, Poll};
+use futures::task::{self as future_task, Task as FutureTask};
+use tokio_core::reactor::{Handle, Timeout};
+
+use error::ScheduleError;
+
+use std::cmp;
+use std::io;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const LEVELS: usize = 6;
+const SLOTS: usize = 64;
+const SLOT_BITS: u32 = 6;
+
+/// The longest delay of an entry, in milliseconds. Longer ones are moved back to the last level
+/// when their slot expires.
+const MAX_DELAY: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// A timer in the wheel.
+struct Entry {
+    /// The expiration, in milliseconds since the start of the wheel.
+    when: u64,
+    fired: AtomicBool,
+    cancelled: AtomicBool,
+    waiter: Mutex<Option<FutureTask>>,
+}
+
+impl Entry {
+    fn fire(&self) {
+        self.fired.store(true, Ordering::SeqCst);
+        if let Some(waiter) = self.waiter.lock().unwrap().take() {
+            waiter.notify();
+        }
+    }
+}
+
+struct Level {
+    /// The slots containing at least one entry.
+    occupied: u64,
+    slots: Vec<Vec<Arc<Entry>>>,
+}
+
+impl Level {
+    fn new() -> Level {
+        Level { occupied: 0, slots: (0..SLOTS).map(|_| Vec::new()).collect() }
+    }
+}
+
+/// Returns the duration in milliseconds of the slots of `level`.
+fn slot_range(level: usize) -> u64 {
+    1 << (SLOT_BITS * level as u32)
+}
+
+/// Returns the level of an entry expiring at `when`, when the wheel is at `elapsed`.
+fn level_for(elapsed: u64, when: u64) -> usize {
+    let masked = (elapsed ^ when) | (SLOTS as u64 - 1);
+    let significant = 63 - masked.leading_zeros();
+    cmp::min(significant as usize / SLOT_BITS as usize, LEVELS - 1)
+}
+
+struct State {
+    /// The milliseconds since the start of the wheel that have been processed.
+    elapsed: u64,
+    levels: Vec<Level>,
+    /// The number of entries in the wheel, including the cancelled ones.
+    entries: usize,
+    driver: Option<DriverState>,
+    /// Set when the driver failed to create its timer.
+    failed: bool,
+}
+
+/// The state of the future driving the wheel on the event loop.
+struct DriverState {
+    task: Option<FutureTask>,
+    /// The expiration the driver is waiting for.
+    wakeup: Option<u64>,
+}
+
+impl State {
+    fn insert(&mut self, entry: Arc<Entry>) {
+        let when = cmp::min(entry.when, self.elapsed + MAX_DELAY);
+        let level = level_for(self.elapsed, when);
+        let slot = ((when >> (SLOT_BITS * level as u32)) as usize) & (SLOTS - 1);
+        let level = &mut self.levels[level];
+        level.slots[slot].push(entry);
+        level.occupied |= 1 << slot;
+    }
+
+    /// Returns the level, slot and start of the earliest occupied slot.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        for (index, level) in self.levels.iter().enumerate() {
+            if level.occupied == 0 {
+                continue;
+            }
+            let slot_range = slot_range(index);
+            let level_range = slot_range * SLOTS as u64;
+            let now_slot = ((self.elapsed / slot_range) as usize) & (SLOTS - 1);
+            let slot = (level.occupied.rotate_right(now_slot as u32).trailing_zeros() as usize + now_slot) & (SLOTS - 1);
+            let level_start = self.elapsed - self.elapsed % level_range;
+            let mut deadline = level_start + slot as u64 * slot_range;
+            if slot < now_slot {
+                deadline += level_range;
+            }
+            return Some((index, slot, deadline));
+        }
+        None
+    }
+
+    /// Processes the slots expired at `now`, firing their entries or moving them to the lower
+    /// levels.
+    fn advance(&mut self, now: u64) {
+        while let Some((level, slot, deadline)) = self.next_expiration() {
+            if deadline > now {
+                break;
+            }
+            self.elapsed = cmp::max(self.elapsed, deadline);
+            self.levels[level].occupied &= !(1 << slot);
+            let entries = mem::take(&mut self.levels[level].slots[slot]);
+            for entry in entries {
+                if entry.cancelled.load(Ordering::Relaxed) {
+                    self.entries -= 1;
+                } else if entry.when <= self.elapsed {
+                    self.entries -= 1;
+                    entry.fire();
+                } else {
+                    self.insert(entry);
+                }
+            }
+        }
+        self.elapsed = cmp::max(self.elapsed, now);
+    }
+}
+
+/// The timers of an executor.
+pub(crate) struct TimerWheel {
+    start: Instant,
+    state: Mutex<State>,
+}
+
+impl TimerWheel {
+    pub(crate) fn new() -> TimerWheel {
+        TimerWheel {
+            start: Instant::now(),
+            state: Mutex::new(State {
+                elapsed: 0,
+                levels: (0..LEVELS).map(|_| Level::new()).collect(),
+                entries: 0,
+                driver: None,
+                failed: false,
+            }),
+        }
+    }
+
+    /// Returns the milliseconds elapsed between the start of the wheel and `instant`, rounded
+    /// up so that the entries never fire early.
+    fn tick(&self, instant: Instant) -> u64 {
+        let since_start = instant.saturating_duration_since(self.start);
+        let millis = since_start.as_millis() as u64;
+        if since_start > Duration::from_millis(millis) { millis + 1 } else { millis }
+    }
+
+    /// Returns the milliseconds elapsed since the start of the wheel.
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    /// Returns a future completing at `deadline`, driven by the event loop of `handle`.
+    pub(crate) fn sleep_until(wheel: &Arc<TimerWheel>, deadline: Instant, handle: &Handle) -> Box<dyn Future<Item=(), Error=ScheduleError>> {
+        Box::new(Sleep { wheel: Arc::clone(wheel), when: wheel.tick(deadline), handle: handle.clone(), entry: None })
+    }
+
+    /// Adds an entry, starting the driver or making it wake up earlier if needed.
+    fn register(wheel: &Arc<TimerWheel>, entry: Arc<Entry>, handle: &Handle) {
+        let mut state = wheel.state.lock().unwrap();
+        state.entries += 1;
+        state.failed = false;
+        let when = entry.when;
+        state.insert(entry);
+        match state.driver {
+            Some(ref mut driver) => {
+                if driver.wakeup.is_none_or(|wakeup| when < wakeup) {
+                    if let Some(task) = driver.task.take() {
+                        task.notify();
+                    }
+                }
+            },
+            None => {
+                state.driver = Some(DriverState { task: None, wakeup: None });
+                handle.spawn(Driver { wheel: Arc::clone(wheel), handle: handle.clone(), timeout: None, done: false });
+            },
+        }
+    }
+}
+
+/// A future waiting for an entry of the wheel to fire.
+struct Sleep {
+    wheel: Arc<TimerWheel>,
+    when: u64,
+    handle: Handle,
+    entry: Option<Arc<Entry>>,
+}
+
+impl Future for Sleep {
+    type Item = ();
+    type Error = ScheduleError;
+
+    fn poll(&mut self) -> Poll<(), ScheduleError> {
+        match self.entry {
+            Some(ref entry) => {
+                *entry.waiter.lock().unwrap() = Some(future_task::current());
+                if entry.fired.load(Ordering::SeqCst) {
+                    return Ok(Async::Ready(()));
+                }
+                if self.wheel.state.lock().unwrap().failed {
+                    return Err(ScheduleError::Timer(io::Error::other("the timer of the timing wheel failed")));
+                }
+                Ok(Async::NotReady)
+            },
+            None => {
+                if self.when <= self.wheel.now() {
+                    return Ok(Async::Ready(()));
+                }
+                let entry = Arc::new(Entry {
+                    when: self.when,
+                    fired: AtomicBool::new(false),
+                    cancelled: AtomicBool::new(false),
+                    waiter: Mutex::new(Some(future_task::current())),
+                });
+                TimerWheel::register(&self.wheel, Arc::clone(&entry), &self.handle);
+                self.entry = Some(entry);
+                Ok(Async::NotReady)
+            },
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        // Removed from the wheel when its slot expires
+        if let Some(ref entry) = self.entry {
+            entry.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The future driving the wheel, waiting with a single timer of the event loop for the
+/// earliest expiration. It terminates when the wheel is empty.
+struct Driver {
+    wheel: Arc<TimerWheel>,
+    handle: Handle,
+    timeout: Option<(u64, Timeout)>,
+    done: bool,
+}
+
+impl Future for Driver {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            let mut state = self.wheel.state.lock().unwrap();
+            state.advance(self.wheel.now());
+            let next = match state.next_expiration() {
+                Some((_, _, next)) if state.entries > 0 => next,
+                _ => {
+                    state.driver = None;
+                    self.done = true;
+                    return Ok(Async::Ready(()));
+                },
+            };
+            if self.timeout.as_ref().is_none_or(|&(wakeup, _)| wakeup != next) {
+                let at = self.wheel.start + Duration::from_millis(next);
+                match Timeout::new_at(at, &self.handle) {
+                    Ok(timeout) => self.timeout = Some((next, timeout)),
+                    Err(e) => {
+                        error!("Failed to create the timer of the timing wheel: {}", e);
+                        state.failed = true;
+                        state.driver = None;
+                        self.done = true;
+                        for level in &mut state.levels {
+                            for entry in level.slots.iter().flatten() {
+                                if let Some(waiter) = entry.waiter.lock().unwrap().take() {
+                                    waiter.notify();
+                                }
+                            }
+                        }
+                        return Ok(Async::Ready(()));
+                    },
+                }
+            }
+            if let Some(ref mut driver) = state.driver {
+                driver.task = Some(future_task::current());
+                driver.wakeup = Some(next);
+            }
+            drop(state);
+            match self.timeout.as_mut().map(|&mut (_, ref mut timeout)| timeout.poll()) {
+                Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+                // Expired, or failed: loop to process the expired slots, or to create a new timer
+                _ => self.timeout = None,
+            }
+        }
+    }
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        // For instance when the event loop is dropped: the next entry starts a new driver
+        if !self.done {
+            self.wheel.state.lock().unwrap().driver = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::{self, Future};
+    use tokio_core::reactor::Core;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use executor::ExecutorBuilder;
+    use super::{Entry, State, TimerWheel, level_for, Level, LEVELS};
+
+    fn state() -> State {
+        State { elapsed: 0, levels: (0..LEVELS).map(|_| Level::new()).collect(), entries: 0, driver: None, failed: false }
+    }
+
+    fn entry(when: u64) -> Arc<Entry> {
+        Arc::new(Entry { when, fired: Default::default(), cancelled: Default::default(), waiter: Default::default() })
+    }
+
+    #[test]
+    fn level_test() {
+        assert_eq!(level_for(0, 1), 0);
+        assert_eq!(level_for(0, 63), 0);
+        assert_eq!(level_for(0, 64), 1);
+        assert_eq!(level_for(60, 70), 1);
+        assert_eq!(level_for(64, 70), 0);
+        assert_eq!(level_for(0, 4096), 2);
+        assert_eq!(level_for(0, u64::MAX), LEVELS - 1);
+    }
+
+    #[test]
+    fn advance_test() {
+        let mut state = state();
+        let whens = [3, 63, 64, 100, 4000, 5000, 300_000, 1 << 40];
+        let entries = whens.iter().map(|&when| entry(when)).collect::<Vec<_>>();
+        for entry in &entries {
+            state.entries += 1;
+            state.insert(Arc::clone(entry));
+        }
+        entries[3].cancelled.store(true, Ordering::Relaxed);
+        let fired = |entries: &[Arc<Entry>]| entries.iter().filter(|e| e.fired.load(Ordering::SeqCst)).count();
+
+        assert_eq!(state.next_expiration().map(|(_, _, deadline)| deadline), Some(3));
+        state.advance(2);
+        assert_eq!(fired(&entries), 0);
+        state.advance(63);
+        assert_eq!(fired(&entries), 2);
+        state.advance(64);
+        assert_eq!(fired(&entries), 3);
+        state.advance(4999);
+        // The cancelled one is dropped without firing
+        assert_eq!(fired(&entries), 4);
+        assert!(!entries[3].fired.load(Ordering::SeqCst));
+        state.advance(5000);
+        assert_eq!(fired(&entries), 5);
+        // Moved to the lower levels until it expires
+        state.advance(299_999);
+        assert_eq!(fired(&entries), 5);
+        assert!(state.next_expiration().map(|(_, _, deadline)| deadline) <= Some(300_000));
+        state.advance(300_000);
+        assert_eq!(fired(&entries), 6);
+        assert_eq!(state.entries, 1);
+        state.advance(1 << 41);
+        assert_eq!(fired(&entries), 7);
+        assert_eq!(state.entries, 0);
+        assert_eq!(state.next_expiration(), None);
+    }
+
+    #[test]
+    fn cascade_test() {
+        let mut state = state();
+        state.advance(60);
+        let first = entry(130);
+        state.entries += 1;
+        state.insert(Arc::clone(&first));
+        // Crosses the boundary of a slot of the second level
+        assert_eq!(state.next_expiration(), Some((1, 2, 128)));
+        state.advance(128);
+        assert!(!first.fired.load(Ordering::SeqCst));
+        assert_eq!(state.levels[1].occupied, 0);
+        assert_eq!(state.next_expiration(), Some((0, 2, 130)));
+        state.advance(129);
+        assert!(!first.fired.load(Ordering::SeqCst));
+        state.advance(130);
+        assert!(first.fired.load(Ordering::SeqCst));
+
+        state.advance(4000);
+        let second = entry(4293);
+        state.entries += 1;
+        state.insert(Arc::clone(&second));
+        // From the third level to the second, and then to the first
+        assert_eq!(state.next_expiration(), Some((2, 1, 4096)));
+        state.advance(4096);
+        assert_eq!(state.next_expiration(), Some((1, 3, 4288)));
+        state.advance(4288);
+        assert_eq!(state.next_expiration(), Some((0, 5, 4293)));
+        state.advance(4292);
+        assert!(!second.fired.load(Ordering::SeqCst));
+        state.advance(4293);
+        assert!(second.fired.load(Ordering::SeqCst));
+        assert_eq!(state.entries, 0);
+        assert_eq!(state.next_expiration(), None);
+    }
+
+    #[test]
+    fn cancel_test() {
+        let mut state = state();
+        let entries = [10, 10, 130].iter().map(|&when| entry(when)).collect::<Vec<_>>();
+        for entry in &entries {
+            state.entries += 1;
+            state.insert(Arc::clone(entry));
+        }
+        // Already in the same slot as another entry
+        entries[0].cancelled.store(true, Ordering::Relaxed);
+        state.advance(10);
+        assert!(!entries[0].fired.load(Ordering::SeqCst));
+        assert!(entries[1].fired.load(Ordering::SeqCst));
+        assert_eq!(state.entries, 1);
+        // Cancelled after being moved to the first level
+        state.advance(128);
+        assert_eq!(state.next_expiration(), Some((0, 2, 130)));
+        entries[2].cancelled.store(true, Ordering::Relaxed);
+        state.advance(130);
+        assert!(!entries[2].fired.load(Ordering::SeqCst));
+        assert_eq!(state.entries, 0);
+        assert_eq!(state.next_expiration(), None);
+    }
+
+    #[test]
+    fn sleep_drop_test() {
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let wheel = Arc::new(TimerWheel::new());
+        let mut sleep = TimerWheel::sleep_until(&wheel, Instant::now() + Duration::from_millis(50), &handle);
+        core.run(future::lazy(|| {
+            assert!(sleep.poll().unwrap().is_not_ready());
+            Ok::<(), ()>(())
+        })).unwrap();
+        assert_eq!(wheel.state.lock().unwrap().entries, 1);
+        // Removed from the wheel once its slot expires, without firing
+        drop(sleep);
+        core.run(TimerWheel::sleep_until(&wheel, Instant::now() + Duration::from_millis(100), &handle)).unwrap();
+        let state = wheel.state.lock().unwrap();
+        assert_eq!(state.entries, 0);
+        assert!(state.driver.is_none());
+    }
+
+    #[test]
+    fn executor_test() {
+        let executor = ExecutorBuilder::new().timer_wheel(true).build().unwrap();
+        let intervals = [5, 13, 40, 100, 250];
+        let counters = (0..50).map(|_| Arc::new(AtomicUsize::new(0))).collect::<Vec<_>>();
+        for (i, counter) in counters.iter().enumerate() {
+            let counter = Arc::clone(counter);
+            let interval = Duration::from_millis(intervals[i % intervals.len()]);
+            executor.schedule_fixed_rate(Duration::from_millis(i as u64), interval, move |_handle| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        thread::sleep(Duration::from_millis(500));
+        drop(executor);
+        for (i, counter) in counters.iter().enumerate() {
+            let interval = intervals[i % intervals.len()];
+            let expected = (500 - i as u64) / interval + 1;
+            let runs = counter.load(Ordering::SeqCst) as u64;
+            assert!(runs >= expected / 2 && runs <= expected + 1, "task {} ran {} times, expected {}", i, runs, expected);
+        }
+    }
+}